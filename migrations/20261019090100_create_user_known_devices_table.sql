@@ -0,0 +1,10 @@
+-- Add migration script here
+CREATE TABLE user_known_devices(
+    user_id uuid NOT NULL
+        REFERENCES users (user_id),
+    ip_address TEXT NOT NULL,
+    user_agent TEXT NOT NULL,
+    first_seen_at timestamptz NOT NULL,
+    last_seen_at timestamptz NOT NULL,
+    PRIMARY KEY (user_id, ip_address, user_agent)
+);
@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TABLE users ADD COLUMN oidc_subject TEXT NULL UNIQUE;
@@ -0,0 +1,13 @@
+-- Add migration script here
+CREATE TABLE newsletter_issues(
+    newsletter_issue_id uuid NOT NULL,
+    PRIMARY KEY (newsletter_issue_id),
+    title TEXT NOT NULL,
+    text_content TEXT NOT NULL,
+    html_content TEXT NOT NULL,
+    status TEXT NOT NULL
+        CHECK (status IN ('draft', 'scheduled', 'sending', 'sent', 'cancelled')),
+    created_at timestamptz NOT NULL,
+    updated_at timestamptz NOT NULL,
+    published_at timestamptz NULL
+);
@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE newsletter_issues ADD COLUMN send_at timestamptz NULL;
+CREATE INDEX newsletter_issues_scheduled_idx ON newsletter_issues (send_at)
+    WHERE status = 'scheduled';
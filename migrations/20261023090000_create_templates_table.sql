@@ -0,0 +1,12 @@
+-- Add migration script here
+CREATE TABLE templates(
+    template_id uuid NOT NULL,
+    PRIMARY KEY (template_id),
+    name TEXT NOT NULL UNIQUE,
+    html_layout TEXT NOT NULL,
+    text_layout TEXT NOT NULL,
+    created_at timestamptz NOT NULL,
+    updated_at timestamptz NOT NULL
+);
+ALTER TABLE newsletter_issues
+    ADD COLUMN template_id uuid NULL REFERENCES templates (template_id);
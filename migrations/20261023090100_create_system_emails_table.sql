@@ -0,0 +1,18 @@
+-- Add migration script here
+CREATE TABLE system_emails(
+    kind TEXT NOT NULL,
+    PRIMARY KEY (kind),
+    subject TEXT NOT NULL,
+    html_body TEXT NOT NULL,
+    text_body TEXT NOT NULL,
+    template_id uuid NULL REFERENCES templates (template_id),
+    updated_at timestamptz NOT NULL
+);
+INSERT INTO system_emails (kind, subject, html_body, text_body, updated_at)
+VALUES (
+    'subscription_confirmation',
+    'Welcome!',
+    'Welcome to our newsletter!<br />Click <a href="{{ confirmation_link }}">here</a> to confirm your subscription.',
+    E'Welcome to our newsletter!\nVisit {{ confirmation_link }} to confirm your subscription.',
+    now()
+);
@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE newsletter_issues
+    ADD COLUMN html_warnings TEXT[] NOT NULL DEFAULT '{}';
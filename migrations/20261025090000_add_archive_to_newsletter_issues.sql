@@ -0,0 +1,18 @@
+-- Add migration script here
+-- The issues sent before the archive existed stay private, the new ones are
+-- public unless an admin hides them.
+ALTER TABLE newsletter_issues
+    ADD COLUMN slug TEXT,
+    ADD COLUMN is_public BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE newsletter_issues ALTER COLUMN is_public SET DEFAULT TRUE;
+UPDATE newsletter_issues
+SET slug = COALESCE(
+        NULLIF(trim(BOTH '-' FROM regexp_replace(lower(title), '[^a-z0-9]+', '-', 'g')), ''),
+        'issue'
+    ) || '-' || left(replace(newsletter_issue_id::text, '-', ''), 8);
+ALTER TABLE newsletter_issues
+    ALTER COLUMN slug SET NOT NULL,
+    ADD CONSTRAINT newsletter_issues_slug_key UNIQUE (slug);
+CREATE INDEX newsletter_issues_public_idx
+    ON newsletter_issues (published_at DESC)
+    WHERE is_public AND status IN ('sending', 'sent');
@@ -0,0 +1,21 @@
+-- Add migration script here
+CREATE TABLE rss_feeds(
+    rss_feed_id uuid NOT NULL,
+    PRIMARY KEY (rss_feed_id),
+    url TEXT NOT NULL UNIQUE,
+    poll_interval_minutes INTEGER NOT NULL CHECK (poll_interval_minutes > 0),
+    -- New items are sent right away instead of being saved as drafts.
+    auto_send BOOLEAN NOT NULL,
+    next_poll_at timestamptz NOT NULL,
+    last_polled_at timestamptz NULL,
+    last_error TEXT NULL,
+    created_at timestamptz NOT NULL
+);
+CREATE TABLE rss_feed_items(
+    rss_feed_id uuid NOT NULL REFERENCES rss_feeds (rss_feed_id) ON DELETE CASCADE,
+    guid TEXT NOT NULL,
+    PRIMARY KEY (rss_feed_id, guid),
+    newsletter_issue_id uuid NULL
+        REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE SET NULL,
+    seen_at timestamptz NOT NULL
+);
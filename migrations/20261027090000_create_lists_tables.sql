@@ -0,0 +1,47 @@
+-- Add migration script here
+CREATE TABLE lists(
+    list_id uuid NOT NULL,
+    PRIMARY KEY (list_id),
+    name TEXT NOT NULL UNIQUE,
+    -- Names the list in the public subscribe form.
+    slug TEXT NOT NULL UNIQUE,
+    description TEXT NOT NULL,
+    -- Subscriptions and issues that do not name a list go to the default one.
+    is_default BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at timestamptz NOT NULL
+);
+CREATE UNIQUE INDEX lists_default_idx ON lists (is_default) WHERE is_default;
+
+CREATE TABLE list_memberships(
+    list_id uuid NOT NULL REFERENCES lists (list_id) ON DELETE CASCADE,
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    PRIMARY KEY (list_id, subscriber_id),
+    status TEXT NOT NULL
+        CHECK (status IN ('pending_confirmation', 'confirmed', 'unsubscribed')),
+    subscribed_at timestamptz NOT NULL
+);
+
+CREATE TABLE newsletter_issue_lists(
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+    list_id uuid NOT NULL REFERENCES lists (list_id),
+    PRIMARY KEY (newsletter_issue_id, list_id)
+);
+
+-- The flat list of subscribers becomes the default list.
+INSERT INTO lists (list_id, name, slug, description, is_default, created_at)
+VALUES (gen_random_uuid(), 'Newsletter', 'newsletter', '', TRUE, now());
+INSERT INTO list_memberships (list_id, subscriber_id, status, subscribed_at)
+SELECT (SELECT list_id FROM lists WHERE is_default), id, status_subscription, subscribed_at
+FROM subscriptions;
+INSERT INTO newsletter_issue_lists (newsletter_issue_id, list_id)
+SELECT newsletter_issue_id, (SELECT list_id FROM lists WHERE is_default)
+FROM newsletter_issues;
+
+-- A confirmation link confirms the membership of one list.
+ALTER TABLE subscriptions_tokens
+    ADD COLUMN list_id uuid NULL REFERENCES lists (list_id) ON DELETE CASCADE;
+UPDATE subscriptions_tokens SET list_id = (SELECT list_id FROM lists WHERE is_default);
+ALTER TABLE subscriptions_tokens ALTER COLUMN list_id SET NOT NULL;
+
+ALTER TABLE subscriptions DROP COLUMN status_subscription;
@@ -0,0 +1,20 @@
+-- Add migration script here
+CREATE TABLE subscriber_tags(
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (subscriber_id, tag)
+);
+CREATE INDEX subscriber_tags_tag_idx ON subscriber_tags (tag);
+
+CREATE TABLE segments(
+    segment_id uuid NOT NULL,
+    PRIMARY KEY (segment_id),
+    name TEXT NOT NULL UNIQUE,
+    -- The conditions of the segment, as JSON.
+    definition TEXT NOT NULL,
+    created_at timestamptz NOT NULL
+);
+
+-- An issue without a segment goes to every confirmed member of its lists.
+ALTER TABLE newsletter_issues
+    ADD COLUMN segment_id uuid NULL REFERENCES segments (segment_id);
@@ -0,0 +1,25 @@
+-- Add migration script here
+CREATE TABLE attribute_definitions(
+    -- Names the field of the subscribe form and the merge tag.
+    key TEXT NOT NULL,
+    PRIMARY KEY (key),
+    label TEXT NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('text', 'number', 'date', 'boolean', 'choice')),
+    required BOOLEAN NOT NULL,
+    -- The options of a `choice`.
+    choices TEXT[] NOT NULL,
+    -- The bounds of a `number`.
+    min_value DOUBLE PRECISION NULL,
+    max_value DOUBLE PRECISION NULL,
+    -- The maximum number of characters of a `text`.
+    max_length INT NULL,
+    created_at timestamptz NOT NULL
+);
+
+-- Values are stored in their canonical text form: `42`, `2026-10-29`, `true`.
+CREATE TABLE subscriber_attributes(
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    key TEXT NOT NULL REFERENCES attribute_definitions (key) ON DELETE CASCADE,
+    PRIMARY KEY (subscriber_id, key),
+    value TEXT NOT NULL
+);
@@ -0,0 +1,19 @@
+-- Add migration script here
+-- When a confirmation link was sent and first used, unknown for older links.
+ALTER TABLE subscriptions_tokens
+    ADD COLUMN created_at timestamptz NULL,
+    ADD COLUMN used_at timestamptz NULL;
+
+-- The outcome of each delivery of an issue, kept once the task is done.
+CREATE TABLE issue_deliveries(
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+    subscriber_email TEXT NOT NULL,
+    -- NULL when the email belonged to no subscriber any more.
+    subscriber_id uuid NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    outcome TEXT NOT NULL CHECK (outcome IN ('sent', 'failed', 'skipped')),
+    n_attempts SMALLINT NOT NULL,
+    delivered_at timestamptz NOT NULL,
+    PRIMARY KEY (newsletter_issue_id, subscriber_email)
+);
+CREATE INDEX issue_deliveries_subscriber_id_idx ON issue_deliveries (subscriber_id);
@@ -0,0 +1,14 @@
+-- Add migration script here
+-- What the admins did to the subscribers. The subscriber is not a foreign key
+-- so the entries outlive a deleted subscriber.
+CREATE TABLE audit_log(
+    audit_log_id uuid NOT NULL,
+    PRIMARY KEY (audit_log_id),
+    user_id uuid NULL REFERENCES users (user_id) ON DELETE SET NULL,
+    action TEXT NOT NULL,
+    subscriber_id uuid NULL,
+    subscriber_email TEXT NULL,
+    details TEXT NOT NULL,
+    created_at timestamptz NOT NULL
+);
+CREATE INDEX audit_log_subscriber_id_idx ON audit_log (subscriber_id);
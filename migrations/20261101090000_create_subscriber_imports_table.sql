@@ -0,0 +1,33 @@
+-- Add migration script here
+-- A CSV file of subscribers, imported in the background.
+CREATE TABLE subscriber_imports(
+    import_id uuid NOT NULL,
+    PRIMARY KEY (import_id),
+    user_id uuid NOT NULL REFERENCES users (user_id),
+    file_name TEXT NOT NULL,
+    list_id uuid NOT NULL REFERENCES lists (list_id) ON DELETE CASCADE,
+    mode TEXT NOT NULL CHECK (mode IN ('confirmed', 'send_confirmation')),
+    -- How the subscribers consented, required to import them as confirmed.
+    consent_attestation TEXT NULL,
+    status TEXT NOT NULL CHECK (status IN ('pending', 'done', 'failed')),
+    -- Emptied once the import is done.
+    content TEXT NOT NULL,
+    n_rows INTEGER NOT NULL DEFAULT 0,
+    n_imported INTEGER NOT NULL DEFAULT 0,
+    n_duplicates INTEGER NOT NULL DEFAULT 0,
+    n_errors INTEGER NOT NULL DEFAULT 0,
+    created_at timestamptz NOT NULL,
+    finished_at timestamptz NULL
+);
+CREATE INDEX subscriber_imports_pending_idx ON subscriber_imports (created_at)
+    WHERE status = 'pending';
+
+-- The rows that were not imported, with the reason.
+CREATE TABLE subscriber_import_errors(
+    import_id uuid NOT NULL
+        REFERENCES subscriber_imports (import_id) ON DELETE CASCADE,
+    line INTEGER NOT NULL,
+    email TEXT NOT NULL,
+    error TEXT NOT NULL,
+    PRIMARY KEY (import_id, line)
+);
@@ -0,0 +1,23 @@
+-- Add migration script here
+-- The people who asked to be erased. Only a SHA-256 hash of their email is
+-- kept, so an import or an admin cannot add them back.
+CREATE TABLE erased_subscribers(
+    email_hash TEXT NOT NULL,
+    PRIMARY KEY (email_hash),
+    erased_at timestamptz NOT NULL
+);
+
+INSERT INTO system_emails (kind, subject, html_body, text_body, updated_at)
+VALUES (
+    'data_request',
+    'Your data',
+    'You asked for the data we hold about you.<br />Click <a href="{{ download_link }}">here</a> to download it, the link is valid for 24 hours.',
+    E'You asked for the data we hold about you.\nVisit {{ download_link }} to download it, the link is valid for 24 hours.',
+    now()
+), (
+    'erasure_request',
+    'Erase your data',
+    'You asked us to erase the data we hold about you.<br />Click <a href="{{ erasure_link }}">here</a> to confirm, the link is valid for 24 hours.',
+    E'You asked us to erase the data we hold about you.\nVisit {{ erasure_link }} to confirm, the link is valid for 24 hours.',
+    now()
+);
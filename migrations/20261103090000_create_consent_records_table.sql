@@ -0,0 +1,20 @@
+-- Add migration script here
+-- How and when a subscriber opted in to a list, one row per signup. The
+-- confirmation columns are filled when the link of the signup is used.
+CREATE TABLE consent_records(
+    consent_id uuid NOT NULL,
+    PRIMARY KEY (consent_id),
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    list_id uuid NOT NULL REFERENCES lists (list_id) ON DELETE CASCADE,
+    -- The version of the consent text shown by the form.
+    text_version TEXT NOT NULL,
+    -- The form or the site the signup came from.
+    source TEXT NOT NULL,
+    signup_ip TEXT NULL,
+    signup_user_agent TEXT NULL,
+    signed_up_at timestamptz NOT NULL,
+    confirmation_ip TEXT NULL,
+    confirmation_user_agent TEXT NULL,
+    confirmed_at timestamptz NULL
+);
+CREATE INDEX consent_records_subscriber_id_idx ON consent_records (subscriber_id, list_id);
@@ -0,0 +1,37 @@
+-- Add migration script here
+-- The email in lowercase, two subscribers cannot share it. The application
+-- also converts international domains to punycode, the existing addresses are
+-- only lowercased here.
+ALTER TABLE subscriptions ADD COLUMN normalized_email TEXT NULL;
+UPDATE subscriptions SET normalized_email = lower(trim(email));
+
+-- The subscribers whose address was already taken by an older subscriber,
+-- waiting for an admin to merge them. The rows are kept once merged.
+CREATE TABLE subscriber_duplicates(
+    duplicate_id uuid NOT NULL,
+    PRIMARY KEY (duplicate_id),
+    -- The oldest subscriber with the address, who keeps it.
+    kept_id uuid NOT NULL,
+    normalized_email TEXT NOT NULL,
+    -- The address of the duplicate, it is deleted by the merge.
+    email TEXT NOT NULL,
+    detected_at timestamptz NOT NULL,
+    merged_at timestamptz NULL
+);
+
+INSERT INTO subscriber_duplicates (duplicate_id, kept_id, normalized_email, email, detected_at)
+SELECT id, kept_id, normalized_email, email, now()
+FROM (
+    SELECT id, email, normalized_email,
+        first_value(id) OVER (
+            PARTITION BY normalized_email ORDER BY subscribed_at, id
+        ) AS kept_id
+    FROM subscriptions
+) ranked
+WHERE id <> kept_id;
+
+-- NULL until a duplicate is merged.
+UPDATE subscriptions SET normalized_email = NULL
+WHERE id IN (SELECT duplicate_id FROM subscriber_duplicates);
+
+CREATE UNIQUE INDEX subscriptions_normalized_email_idx ON subscriptions (normalized_email);
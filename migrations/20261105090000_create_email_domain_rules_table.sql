@@ -0,0 +1,10 @@
+-- Add migration script here
+-- The email domains the admins block or allow on signup, on top of the
+-- disposable domains bundled with the application.
+CREATE TABLE email_domain_rules(
+    -- In lowercase ASCII, it also covers its subdomains.
+    domain TEXT NOT NULL,
+    PRIMARY KEY (domain),
+    rule TEXT NOT NULL CHECK (rule IN ('block', 'allow')),
+    created_at timestamptz NOT NULL
+);
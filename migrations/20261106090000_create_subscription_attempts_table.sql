@@ -0,0 +1,13 @@
+-- Add migration script here
+-- The signups of the last hour, for the rate limits of the subscribe form.
+CREATE TABLE subscription_attempts(
+    -- NULL when the address of the client is unknown.
+    ip_address TEXT NULL,
+    email_domain TEXT NOT NULL,
+    attempted_at timestamptz NOT NULL
+);
+CREATE INDEX subscription_attempts_ip_address_idx
+    ON subscription_attempts (ip_address, attempted_at);
+CREATE INDEX subscription_attempts_email_domain_idx
+    ON subscription_attempts (email_domain, attempted_at);
+CREATE INDEX subscription_attempts_attempted_at_idx ON subscription_attempts (attempted_at);
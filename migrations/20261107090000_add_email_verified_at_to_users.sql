@@ -0,0 +1,12 @@
+-- Add migration script here
+ALTER TABLE users ADD COLUMN email_verified_at timestamptz NULL;
+-- An address shared by several users cannot tell them apart, it has to be set
+-- again by each of them.
+UPDATE users SET email = NULL
+WHERE lower(email) IN (
+    SELECT lower(email) FROM users
+    WHERE email IS NOT NULL
+    GROUP BY lower(email)
+    HAVING COUNT(*) > 1
+);
+CREATE UNIQUE INDEX users_lower_email_key ON users (lower(email));
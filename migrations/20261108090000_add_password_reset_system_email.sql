@@ -0,0 +1,9 @@
+-- Add migration script here
+INSERT INTO system_emails (kind, subject, html_body, text_body, updated_at)
+VALUES (
+    'password_reset',
+    'Reset your password',
+    'You asked to reset the password of your account.<br />Click <a href="{{ reset_link }}">here</a> to choose a new one, the link is valid for an hour.',
+    E'You asked to reset the password of your account.\nVisit {{ reset_link }} to choose a new one, the link is valid for an hour.',
+    now()
+);
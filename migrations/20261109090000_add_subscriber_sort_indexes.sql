@@ -0,0 +1,5 @@
+-- Add migration script here
+-- The admin list of the subscribers is paginated on each sort key, then the id.
+CREATE INDEX subscriptions_subscribed_at_idx ON subscriptions (subscribed_at, id);
+CREATE INDEX subscriptions_email_idx ON subscriptions (email, id);
+CREATE INDEX subscriptions_lower_name_idx ON subscriptions (lower(name), id);
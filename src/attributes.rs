@@ -0,0 +1,477 @@
+//! Extra fields of subscribers defined by the admins: they are asked in the
+//! subscribe form, can be used as merge tags in issues and in segments.
+
+use crate::bot_protection::{
+    CHALLENGE_RESPONSE_FIELD, FORM_RENDERED_AT_FIELD, FORM_SIGNATURE_FIELD, HONEYPOT_FIELD,
+};
+use crate::merge_tags::{
+    CONFIRMATION_VARIABLES, DATA_REQUEST_VARIABLES, ERASURE_REQUEST_VARIABLES, ISSUE_VARIABLES,
+    LAYOUT_VARIABLES,
+};
+use anyhow::Context;
+use chrono::{NaiveDate, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeKind {
+    Text,
+    Number,
+    Date,
+    Boolean,
+    /// One of a fixed set of options.
+    Choice,
+}
+
+impl AttributeKind {
+    pub const ALL: [AttributeKind; 5] = [
+        AttributeKind::Text,
+        AttributeKind::Number,
+        AttributeKind::Date,
+        AttributeKind::Boolean,
+        AttributeKind::Choice,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AttributeKind::Text => "text",
+            AttributeKind::Number => "number",
+            AttributeKind::Date => "date",
+            AttributeKind::Boolean => "boolean",
+            AttributeKind::Choice => "choice",
+        }
+    }
+
+    /// Whether values can be compared with `lt`, `gt` and the like.
+    pub fn is_ordered(&self) -> bool {
+        matches!(self, AttributeKind::Number | AttributeKind::Date)
+    }
+}
+
+impl TryFrom<String> for AttributeKind {
+    type Error = String;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        AttributeKind::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == s)
+            .ok_or_else(|| format!("{} is not a kind of attribute.", s))
+    }
+}
+
+impl std::fmt::Display for AttributeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeDefinition {
+    /// Names the field of the subscribe form and the merge tag.
+    pub key: String,
+    pub label: String,
+    pub kind: AttributeKind,
+    pub required: bool,
+    pub choices: Vec<String>,
+    pub min_value: Option<f64>,
+    pub max_value: Option<f64>,
+    pub max_length: Option<i32>,
+}
+
+impl AttributeDefinition {
+    pub fn validate(&self) -> Result<(), String> {
+        let mut chars = self.key.chars();
+        if !chars.next().is_some_and(|c| c.is_ascii_lowercase())
+            || !chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        {
+            return Err(
+                "The key must start with a lowercase letter and only contain lowercase letters, digits and underscores."
+                    .to_string(),
+            );
+        }
+        if is_reserved_key(&self.key) {
+            return Err(format!("The key `{}` is reserved.", self.key));
+        }
+        if self.label.trim().is_empty() {
+            return Err("The label of the attribute is missing.".to_string());
+        }
+        if self.kind == AttributeKind::Choice && self.choices.is_empty() {
+            return Err("A choice needs at least one option.".to_string());
+        }
+        if let (Some(min), Some(max)) = (self.min_value, self.max_value)
+            && min > max
+        {
+            return Err("The minimum cannot be greater than the maximum.".to_string());
+        }
+        if self.max_length.is_some_and(|max_length| max_length < 1) {
+            return Err("The maximum length must be at least 1.".to_string());
+        }
+        Ok(())
+    }
+
+    /// Returns the canonical form of a submitted value, `None` if the field
+    /// was left empty.
+    pub fn parse_value(&self, value: &str) -> Result<Option<String>, String> {
+        let value = value.trim();
+        let label = &self.label;
+        if self.kind == AttributeKind::Boolean {
+            // An unchecked checkbox is not submitted at all.
+            let checked = match value.to_lowercase().as_str() {
+                "true" | "on" | "yes" | "1" => true,
+                "" | "false" | "off" | "no" | "0" => false,
+                _ => return Err(format!("{} must be true or false.", label)),
+            };
+            if self.required && !checked {
+                return Err(format!("{} must be checked.", label));
+            }
+            return Ok(Some(checked.to_string()));
+        }
+        if value.is_empty() {
+            if self.required {
+                return Err(format!("{} is required.", label));
+            }
+            return Ok(None);
+        }
+        let value = match self.kind {
+            AttributeKind::Text => {
+                if let Some(max_length) = self.max_length
+                    && value.chars().count() > max_length as usize
+                {
+                    return Err(format!(
+                        "{} is at most {} characters long.",
+                        label, max_length
+                    ));
+                }
+                value.to_string()
+            }
+            AttributeKind::Number => {
+                let number = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|number| number.is_finite())
+                    .ok_or_else(|| format!("{} must be a number.", label))?;
+                if let Some(min) = self.min_value
+                    && number < min
+                {
+                    return Err(format!("{} must be at least {}.", label, min));
+                }
+                if let Some(max) = self.max_value
+                    && number > max
+                {
+                    return Err(format!("{} must be at most {}.", label, max));
+                }
+                number.to_string()
+            }
+            AttributeKind::Date => NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map_err(|_| format!("{} must be a date (YYYY-MM-DD).", label))?
+                .to_string(),
+            AttributeKind::Choice => {
+                if !self.choices.iter().any(|choice| choice == value) {
+                    return Err(format!(
+                        "{} must be one of: {}.",
+                        label,
+                        self.choices.join(", ")
+                    ));
+                }
+                value.to_string()
+            }
+            AttributeKind::Boolean => unreachable!("Booleans are parsed above"),
+        };
+        Ok(Some(value))
+    }
+}
+
+/// The fields of the subscribe form that are not attributes.
+const SUBSCRIBE_FORM_FIELDS: &[&str] = &[
+    "list",
+    "consent_version",
+    "source",
+    HONEYPOT_FIELD,
+    FORM_RENDERED_AT_FIELD,
+    FORM_SIGNATURE_FIELD,
+    CHALLENGE_RESPONSE_FIELD,
+];
+
+/// Attributes cannot shadow the variables of the emails, nor the other fields
+/// of the subscribe form.
+fn is_reserved_key(key: &str) -> bool {
+    ISSUE_VARIABLES
+        .iter()
+        .chain(CONFIRMATION_VARIABLES)
+        .chain(DATA_REQUEST_VARIABLES)
+        .chain(ERASURE_REQUEST_VARIABLES)
+        .chain(LAYOUT_VARIABLES)
+        .chain(SUBSCRIBE_FORM_FIELDS)
+        .any(|variable| *variable == key)
+}
+
+/// The canonical values of the fields submitted with a subscription, in the
+/// order of the definitions. Fields that are not attributes are ignored.
+pub fn parse_submitted_values(
+    definitions: &[AttributeDefinition],
+    submitted: &HashMap<String, String>,
+) -> Result<Vec<(String, String)>, String> {
+    let mut values = Vec::new();
+    for definition in definitions {
+        let submitted = submitted
+            .get(&definition.key)
+            .map(String::as_str)
+            .unwrap_or_default();
+        if let Some(value) = definition.parse_value(submitted)? {
+            values.push((definition.key.clone(), value));
+        }
+    }
+    Ok(values)
+}
+
+/// The variables of newsletter issues, including the attributes.
+pub fn issue_variables(definitions: &[AttributeDefinition]) -> Vec<&str> {
+    ISSUE_VARIABLES
+        .iter()
+        .copied()
+        .chain(definitions.iter().map(|definition| definition.key.as_str()))
+        .collect()
+}
+
+struct DefinitionRow {
+    key: String,
+    label: String,
+    kind: String,
+    required: bool,
+    choices: Vec<String>,
+    min_value: Option<f64>,
+    max_value: Option<f64>,
+    max_length: Option<i32>,
+}
+
+impl TryFrom<DefinitionRow> for AttributeDefinition {
+    type Error = anyhow::Error;
+    fn try_from(r: DefinitionRow) -> Result<Self, Self::Error> {
+        Ok(AttributeDefinition {
+            key: r.key,
+            label: r.label,
+            kind: AttributeKind::try_from(r.kind).map_err(|e| anyhow::anyhow!(e))?,
+            required: r.required,
+            choices: r.choices,
+            min_value: r.min_value,
+            max_value: r.max_value,
+            max_length: r.max_length,
+        })
+    }
+}
+
+/// In the order they were defined, which is the order of the subscribe form.
+pub async fn list_definitions(pool: &PgPool) -> Result<Vec<AttributeDefinition>, anyhow::Error> {
+    sqlx::query_as!(
+        DefinitionRow,
+        r#"SELECT key, label, kind, required, choices, min_value, max_value, max_length
+        FROM attribute_definitions
+        ORDER BY created_at, key"#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the attribute definitions")?
+    .into_iter()
+    .map(AttributeDefinition::try_from)
+    .collect()
+}
+
+/// Returns `false` if the key is taken by another attribute.
+#[tracing::instrument(name = "Insert an attribute definition", skip(pool, definition), fields(key = %definition.key))]
+pub async fn insert_definition(
+    pool: &PgPool,
+    definition: &AttributeDefinition,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"INSERT INTO attribute_definitions
+            (key, label, kind, required, choices, min_value, max_value, max_length, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT DO NOTHING"#,
+        definition.key,
+        definition.label,
+        definition.kind.as_str(),
+        definition.required,
+        &definition.choices,
+        definition.min_value,
+        definition.max_value,
+        definition.max_length,
+        Utc::now()
+    )
+    .execute(pool)
+    .await
+    .context("Failed to insert the attribute definition")?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// The values a subscriber already has are kept, like their name: anyone can
+/// submit the subscribe form with the email of someone else.
+#[tracing::instrument(
+    name = "Store the attributes of a subscriber",
+    skip(transaction, values)
+)]
+pub async fn store_attributes(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    values: &[(String, String)],
+) -> Result<(), anyhow::Error> {
+    let (keys, values): (Vec<String>, Vec<String>) = values.iter().cloned().unzip();
+    sqlx::query!(
+        r#"INSERT INTO subscriber_attributes (subscriber_id, key, value)
+        SELECT $1, key, value FROM UNNEST($2::text[], $3::text[]) AS a(key, value)
+        ON CONFLICT (subscriber_id, key) DO NOTHING"#,
+        subscriber_id,
+        &keys,
+        &values
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to store the attributes of the subscriber")?;
+    Ok(())
+}
+
+/// Every attribute with the value of the subscriber with this email, empty
+/// when they have none or are not a subscriber.
+#[tracing::instrument(name = "Get the attributes of a recipient", skip(pool))]
+pub async fn get_recipient_attributes(
+    pool: &PgPool,
+    email: &str,
+) -> Result<Vec<(String, String)>, anyhow::Error> {
+    let attributes = sqlx::query!(
+        r#"SELECT d.key, COALESCE(a.value, '') AS "value!"
+        FROM attribute_definitions d
+        LEFT JOIN subscriber_attributes a ON a.key = d.key
+            AND a.subscriber_id = (SELECT id FROM subscriptions WHERE email = $1)
+        ORDER BY d.key"#,
+        email
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the attributes of the recipient")?;
+    Ok(attributes.into_iter().map(|r| (r.key, r.value)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AttributeDefinition, AttributeKind, parse_submitted_values};
+    use claim::{assert_err, assert_ok, assert_ok_eq};
+    use std::collections::HashMap;
+
+    fn definition(kind: AttributeKind) -> AttributeDefinition {
+        AttributeDefinition {
+            key: "field".to_string(),
+            label: "Field".to_string(),
+            kind,
+            required: false,
+            choices: Vec::new(),
+            min_value: None,
+            max_value: None,
+            max_length: None,
+        }
+    }
+
+    #[test]
+    fn numbers_are_checked_against_their_bounds() {
+        let age = AttributeDefinition {
+            min_value: Some(18.0),
+            max_value: Some(130.0),
+            ..definition(AttributeKind::Number)
+        };
+        assert_ok_eq!(age.parse_value(" 42.0 "), Some("42".to_string()));
+        assert_ok_eq!(age.parse_value("18.5"), Some("18.5".to_string()));
+        assert_err!(age.parse_value("17"));
+        assert_err!(age.parse_value("200"));
+        assert_err!(age.parse_value("forty"));
+        assert_err!(age.parse_value("NaN"));
+    }
+
+    #[test]
+    fn dates_are_checked() {
+        let birthday = definition(AttributeKind::Date);
+        assert_ok_eq!(
+            birthday.parse_value("2000-02-29"),
+            Some("2000-02-29".to_string())
+        );
+        assert_err!(birthday.parse_value("2001-02-29"));
+        assert_err!(birthday.parse_value("29/02/2000"));
+    }
+
+    #[test]
+    fn an_unchecked_boolean_is_false() {
+        let beta = definition(AttributeKind::Boolean);
+        assert_ok_eq!(beta.parse_value("on"), Some("true".to_string()));
+        assert_ok_eq!(beta.parse_value(""), Some("false".to_string()));
+        let consent = AttributeDefinition {
+            required: true,
+            ..definition(AttributeKind::Boolean)
+        };
+        assert_err!(consent.parse_value(""));
+    }
+
+    #[test]
+    fn a_choice_must_be_one_of_the_options() {
+        let plan = AttributeDefinition {
+            choices: vec!["free".to_string(), "pro".to_string()],
+            ..definition(AttributeKind::Choice)
+        };
+        assert_ok_eq!(plan.parse_value("pro"), Some("pro".to_string()));
+        assert_err!(plan.parse_value("enterprise"));
+    }
+
+    #[test]
+    fn texts_are_checked_against_their_length() {
+        let company = AttributeDefinition {
+            max_length: Some(5),
+            ..definition(AttributeKind::Text)
+        };
+        assert_ok_eq!(company.parse_value("Acmé"), Some("Acmé".to_string()));
+        assert_err!(company.parse_value("Initech"));
+    }
+
+    #[test]
+    fn only_required_fields_can_be_left_empty() {
+        let optional = definition(AttributeKind::Text);
+        assert_ok_eq!(optional.parse_value("  "), None);
+        let required = AttributeDefinition {
+            required: true,
+            ..definition(AttributeKind::Text)
+        };
+        assert_err!(required.parse_value("  "));
+    }
+
+    #[test]
+    fn other_submitted_fields_are_ignored() {
+        let submitted = HashMap::from([
+            ("field".to_string(), "Acme".to_string()),
+            ("list".to_string(), "weekly".to_string()),
+        ]);
+        assert_ok_eq!(
+            parse_submitted_values(&[definition(AttributeKind::Text)], &submitted),
+            vec![("field".to_string(), "Acme".to_string())]
+        );
+    }
+
+    #[test]
+    fn keys_must_be_identifiers_and_not_reserved() {
+        assert_ok!(definition(AttributeKind::Text).validate());
+        for key in [
+            "",
+            "Company",
+            "1st",
+            "first-name",
+            "name",
+            "unsubscribe_url",
+            "source",
+        ] {
+            let invalid = AttributeDefinition {
+                key: key.to_string(),
+                ..definition(AttributeKind::Text)
+            };
+            assert_err!(invalid.validate(), "{}", key);
+        }
+    }
+
+    #[test]
+    fn a_choice_needs_options() {
+        assert_err!(definition(AttributeKind::Choice).validate());
+    }
+}
@@ -0,0 +1,154 @@
+//! The audit trail of what the admins did to the subscribers.
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+/// The number of entries shown on the audit trail page.
+pub const AUDIT_ENTRIES_PER_PAGE: i64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    AddSubscriber,
+    EditSubscriber,
+    ForceConfirm,
+    Unsubscribe,
+    ResendConfirmation,
+    DeleteSubscriber,
+    ImportSubscribers,
+    ExportSubscribers,
+    MergeSubscribers,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 9] = [
+        AuditAction::AddSubscriber,
+        AuditAction::EditSubscriber,
+        AuditAction::ForceConfirm,
+        AuditAction::Unsubscribe,
+        AuditAction::ResendConfirmation,
+        AuditAction::DeleteSubscriber,
+        AuditAction::ImportSubscribers,
+        AuditAction::ExportSubscribers,
+        AuditAction::MergeSubscribers,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::AddSubscriber => "add_subscriber",
+            AuditAction::EditSubscriber => "edit_subscriber",
+            AuditAction::ForceConfirm => "force_confirm",
+            AuditAction::Unsubscribe => "unsubscribe",
+            AuditAction::ResendConfirmation => "resend_confirmation",
+            AuditAction::DeleteSubscriber => "delete_subscriber",
+            AuditAction::ImportSubscribers => "import_subscribers",
+            AuditAction::ExportSubscribers => "export_subscribers",
+            AuditAction::MergeSubscribers => "merge_subscribers",
+        }
+    }
+}
+
+impl TryFrom<String> for AuditAction {
+    type Error = String;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        AuditAction::ALL
+            .into_iter()
+            .find(|action| action.as_str() == s)
+            .ok_or_else(|| format!("{} is not a valid audit action", s))
+    }
+}
+
+impl std::fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Recorded in the transaction of the action, so only done actions are kept.
+#[tracing::instrument(name = "Record an audit entry", skip(transaction, details))]
+pub async fn record_action(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    action: AuditAction,
+    subscriber_id: Option<Uuid>,
+    subscriber_email: Option<&str>,
+    details: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO audit_log
+            (audit_log_id, user_id, action, subscriber_id, subscriber_email, details, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+        Uuid::new_v4(),
+        user_id,
+        action.as_str(),
+        subscriber_id,
+        subscriber_email,
+        details,
+        Utc::now()
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+pub struct AuditEntry {
+    /// `None` once the admin has been removed.
+    pub username: Option<String>,
+    pub action: AuditAction,
+    pub subscriber_id: Option<Uuid>,
+    pub subscriber_email: Option<String>,
+    pub details: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The latest entries first, only those about `subscriber_id` if given.
+pub async fn list_entries(
+    pool: &PgPool,
+    subscriber_id: Option<Uuid>,
+) -> Result<Vec<AuditEntry>, anyhow::Error> {
+    sqlx::query!(
+        r#"SELECT u.username AS "username?", a.action, a.subscriber_id,
+            a.subscriber_email, a.details, a.created_at
+        FROM audit_log a
+        LEFT JOIN users u ON u.user_id = a.user_id
+        WHERE $1::uuid IS NULL OR a.subscriber_id = $1
+        ORDER BY a.created_at DESC
+        LIMIT $2"#,
+        subscriber_id,
+        AUDIT_ENTRIES_PER_PAGE
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the audit trail")?
+    .into_iter()
+    .map(|r| {
+        Ok(AuditEntry {
+            username: r.username,
+            action: AuditAction::try_from(r.action).map_err(|e| anyhow::anyhow!(e))?,
+            subscriber_id: r.subscriber_id,
+            subscriber_email: r.subscriber_email,
+            details: r.details,
+            created_at: r.created_at,
+        })
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AuditAction;
+    use claim::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn every_action_round_trips() {
+        for action in AuditAction::ALL {
+            assert_ok_eq!(AuditAction::try_from(action.as_str().to_string()), action);
+        }
+    }
+
+    #[test]
+    fn an_unknown_action_is_rejected() {
+        assert_err!(AuditAction::try_from("export".to_string()));
+    }
+}
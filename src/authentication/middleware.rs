@@ -3,13 +3,24 @@ use actix_web::HttpMessage;
 use actix_web::body::MessageBody;
 use actix_web::dev::{ServiceRequest, ServiceResponse};
 use actix_web::error::InternalError;
+use actix_web::http::ConnectionType;
+use actix_web::web;
 use actix_web_lab::middleware::Next;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use sqlx::PgPool;
 use std::ops::Deref;
 use uuid::Uuid;
 
 use crate::session_state::TypedSession;
 use crate::utils::{e500, see_other};
 
+/// How much of the body of a rejected request is read, so that the client can
+/// send its next request on the same connection. Past it, the connection is
+/// closed instead.
+const MAX_DRAINED_BODY_SIZE: usize = 1024 * 1024;
+
 pub async fn reject_anonymous_user(
     mut req: ServiceRequest,
     next: Next<impl MessageBody>,
@@ -21,16 +32,72 @@ pub async fn reject_anonymous_user(
 
     match session.get_user_id().map_err(e500)? {
         Some(userid) => {
+            let pool = req
+                .app_data::<web::Data<PgPool>>()
+                .ok_or_else(|| e500("The connection pool is not available"))?;
+            let logged_in_at = session.get_logged_in_at().map_err(e500)?;
+            if session_is_revoked(pool, userid, logged_in_at)
+                .await
+                .map_err(e500)?
+            {
+                session.log_out();
+                let e = anyhow::anyhow!("The session of the user has been revoked");
+                return Err(redirect_to_login(req, e).await);
+            }
             req.extensions_mut().insert(UserId(userid));
 
             next.call(req).await
         }
         None => {
-            let response = see_other("/login");
             let e = anyhow::anyhow!("The user is not logged in");
-            Err(InternalError::from_response(e, response).into())
+            Err(redirect_to_login(req, e).await)
+        }
+    }
+}
+
+/// The body the handler would have read is drained first: left unread, it
+/// would be taken for the start of the next request on the connection.
+async fn redirect_to_login(mut req: ServiceRequest, e: anyhow::Error) -> actix_web::Error {
+    let mut payload = req.take_payload();
+    let mut drained = 0;
+    let mut keep_alive = true;
+    while let Some(chunk) = payload.next().await {
+        match chunk {
+            Ok(chunk) if drained + chunk.len() <= MAX_DRAINED_BODY_SIZE => drained += chunk.len(),
+            _ => {
+                keep_alive = false;
+                break;
+            }
         }
     }
+    let mut response = see_other("/login");
+    if !keep_alive {
+        response
+            .head_mut()
+            .set_connection_type(ConnectionType::Close);
+    }
+    InternalError::from_response(e, response).into()
+}
+
+#[tracing::instrument(name = "Check if the session was revoked", skip(pool))]
+async fn session_is_revoked(
+    pool: &PgPool,
+    user_id: Uuid,
+    logged_in_at: Option<DateTime<Utc>>,
+) -> Result<bool, anyhow::Error> {
+    let revoked_at = sqlx::query!(
+        r#"SELECT sessions_revoked_at FROM users WHERE user_id = $1"#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the session revocation date")?
+    .and_then(|r| r.sessions_revoked_at);
+    Ok(match (revoked_at, logged_in_at) {
+        (None, _) => false,
+        (Some(_), None) => true,
+        (Some(revoked_at), Some(logged_in_at)) => logged_in_at < revoked_at,
+    })
 }
 
 impl Deref for UserId {
@@ -1,5 +1,8 @@
 pub mod middleware;
+pub mod notifications;
+pub mod oidc;
 pub mod password;
 
 pub use middleware::{UserId, reject_anonymous_user};
+pub use notifications::{LoginContext, on_failed_login, on_successful_login};
 pub use password::{AuthError, Credential, validate_credential};
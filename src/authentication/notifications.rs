@@ -0,0 +1,249 @@
+use crate::consent::client_ip;
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::signature::signed_query;
+use crate::startup::{ApplicationBaseUrl, HmacSecret};
+use crate::utils::escape_html;
+use actix_web::HttpRequest;
+use actix_web::http::header::USER_AGENT;
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// How long the link to revoke all the sessions of a user stays valid.
+const REVOKE_LINK_VALIDITY_DAYS: i64 = 7;
+/// How long the link verifying the email of a user stays valid.
+const VERIFY_EMAIL_LINK_VALIDITY_HOURS: i64 = 24;
+
+#[derive(Debug, Clone)]
+pub struct LoginContext {
+    pub ip_address: String,
+    pub user_agent: String,
+}
+
+impl LoginContext {
+    pub fn from_request(request: &HttpRequest) -> Self {
+        let ip_address = client_ip(request).unwrap_or_else(|| "unknown".to_string());
+        let user_agent = request
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("unknown")
+            .to_string();
+        Self {
+            ip_address,
+            user_agent,
+        }
+    }
+}
+
+#[tracing::instrument(
+    name = "Record a successful login",
+    skip(pool, email_client, base_url, hmac_secret)
+)]
+pub async fn on_successful_login(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &ApplicationBaseUrl,
+    hmac_secret: &HmacSecret,
+    user_id: Uuid,
+    context: &LoginContext,
+) -> Result<(), anyhow::Error> {
+    let user = sqlx::query!(
+        r#"UPDATE users SET failed_login_attempts = 0
+        WHERE user_id = $1
+        RETURNING email, email_verified_at IS NOT NULL AS "email_verified!", login_notifications"#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to reset the failed login attempts")?;
+
+    let known = sqlx::query!(
+        r#"SELECT
+            EXISTS(SELECT 1 FROM user_known_devices WHERE user_id = $1 AND ip_address = $2)
+                AS "known_ip!",
+            EXISTS(SELECT 1 FROM user_known_devices WHERE user_id = $1 AND user_agent = $3)
+                AS "known_user_agent!""#,
+        user_id,
+        context.ip_address,
+        context.user_agent
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to look for the known devices of the user")?;
+
+    sqlx::query!(
+        r#"INSERT INTO user_known_devices
+            (user_id, ip_address, user_agent, first_seen_at, last_seen_at)
+        VALUES ($1, $2, $3, $4, $4)
+        ON CONFLICT (user_id, ip_address, user_agent)
+        DO UPDATE SET last_seen_at = EXCLUDED.last_seen_at"#,
+        user_id,
+        context.ip_address,
+        context.user_agent,
+        Utc::now()
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the device of the user")?;
+
+    if !user.login_notifications || (known.known_ip && known.known_user_agent) {
+        return Ok(());
+    }
+    // The link revokes every session: it only goes to an address the user
+    // proved is theirs.
+    let Some(email) = user.email.filter(|_| user.email_verified) else {
+        return Ok(());
+    };
+    let email = SubscriberEmail::parse(email).map_err(|e| anyhow::anyhow!(e))?;
+    let revoke_link = revoke_sessions_link(base_url, hmac_secret, user_id);
+    email_client
+        .send_email(
+            &email,
+            "New login to your account",
+            &format!(
+                "Your account was used to log in from a new device.<br />\
+                IP address: {}<br />\
+                User agent: {}<br />\
+                If it was not you, click <a href=\"{}\">here</a> to revoke all your sessions.",
+                escape_html(&context.ip_address),
+                escape_html(&context.user_agent),
+                revoke_link
+            ),
+            &format!(
+                "Your account was used to log in from a new device.\n\
+                IP address: {}\n\
+                User agent: {}\n\
+                If it was not you, visit {} to revoke all your sessions.",
+                context.ip_address, context.user_agent, revoke_link
+            ),
+        )
+        .await
+        .context("Failed to send the login notification")?;
+    Ok(())
+}
+
+#[tracing::instrument(
+    name = "Record a failed login",
+    skip(pool, email_client, base_url, hmac_secret)
+)]
+pub async fn on_failed_login(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &ApplicationBaseUrl,
+    hmac_secret: &HmacSecret,
+    failed_attempts_threshold: u32,
+    username: &str,
+    context: &LoginContext,
+) -> Result<(), anyhow::Error> {
+    let Some(user) = sqlx::query!(
+        r#"UPDATE users SET failed_login_attempts = failed_login_attempts + 1
+        WHERE username = $1
+        RETURNING user_id, email, email_verified_at IS NOT NULL AS "email_verified!",
+            login_notifications, failed_login_attempts"#,
+        username
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to increment the failed login attempts")?
+    else {
+        return Ok(());
+    };
+
+    if !user.login_notifications
+        || failed_attempts_threshold == 0
+        || !(user.failed_login_attempts as u32).is_multiple_of(failed_attempts_threshold)
+    {
+        return Ok(());
+    }
+    let Some(email) = user.email.filter(|_| user.email_verified) else {
+        return Ok(());
+    };
+    let email = SubscriberEmail::parse(email).map_err(|e| anyhow::anyhow!(e))?;
+    let revoke_link = revoke_sessions_link(base_url, hmac_secret, user.user_id);
+    email_client
+        .send_email(
+            &email,
+            "Failed login attempts on your account",
+            &format!(
+                "There were {} failed attempts to log in to your account.<br />\
+                Last attempt from IP address: {}<br />\
+                If it was not you, click <a href=\"{}\">here</a> to revoke all your sessions.",
+                user.failed_login_attempts,
+                escape_html(&context.ip_address),
+                revoke_link
+            ),
+            &format!(
+                "There were {} failed attempts to log in to your account.\n\
+                Last attempt from IP address: {}\n\
+                If it was not you, visit {} to revoke all your sessions.",
+                user.failed_login_attempts, context.ip_address, revoke_link
+            ),
+        )
+        .await
+        .context("Failed to send the failed login notification")?;
+    Ok(())
+}
+
+pub fn revoke_sessions_link(
+    base_url: &ApplicationBaseUrl,
+    hmac_secret: &HmacSecret,
+    user_id: Uuid,
+) -> String {
+    let expires_at = Utc::now() + chrono::Duration::days(REVOKE_LINK_VALIDITY_DAYS);
+    format!(
+        "{}/sessions/revoke?user_id={}&{}",
+        base_url.0,
+        user_id,
+        signed_query(hmac_secret, &revoke_sessions_payload(user_id), expires_at)
+    )
+}
+
+pub fn revoke_sessions_payload(user_id: Uuid) -> String {
+    format!("revoke_sessions:{user_id}")
+}
+
+/// The user has to follow the link, while logged in, for the address to be
+/// verified.
+#[tracing::instrument(
+    name = "Send the email verification",
+    skip(email_client, base_url, hmac_secret)
+)]
+pub async fn send_email_verification(
+    email_client: &EmailClient,
+    base_url: &ApplicationBaseUrl,
+    hmac_secret: &HmacSecret,
+    user_id: Uuid,
+    email: &SubscriberEmail,
+) -> Result<(), anyhow::Error> {
+    let expires_at = Utc::now() + chrono::Duration::hours(VERIFY_EMAIL_LINK_VALIDITY_HOURS);
+    let verify_link = format!(
+        "{}/admin/settings/notifications/verify?{}",
+        base_url.0,
+        signed_query(
+            hmac_secret,
+            &verify_email_payload(user_id, email.as_ref()),
+            expires_at
+        )
+    );
+    email_client
+        .send_email(
+            email,
+            "Verify your email",
+            &format!(
+                "Click <a href=\"{}\">here</a> to verify the email of your account.",
+                verify_link
+            ),
+            &format!("Visit {} to verify the email of your account.", verify_link),
+        )
+        .await
+        .context("Failed to send the email verification")?;
+    Ok(())
+}
+
+/// Bound to the address, the link does not verify another address set since.
+pub fn verify_email_payload(user_id: Uuid, email: &str) -> String {
+    format!("verify_email:{user_id}:{}", email.to_lowercase())
+}
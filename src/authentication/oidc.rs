@@ -0,0 +1,184 @@
+use crate::configuration::OidcSettings;
+use anyhow::Context;
+use base64::prelude::*;
+use rand::distr::Alphanumeric;
+use rand::{Rng, rng};
+use reqwest::{Client, Url};
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Client for the authorization code flow with PKCE of an OpenID Connect
+/// identity provider.
+pub struct OidcClient {
+    pub settings: OidcSettings,
+    pub redirect_url: String,
+    pub http_client: Client,
+}
+
+/// What has to be kept in the session between the redirection to the identity
+/// provider and the callback.
+pub struct AuthorizationRequest {
+    pub url: Url,
+    pub state: String,
+    pub code_verifier: Secret<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: Secret<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UserInfo {
+    pub sub: String,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+}
+
+impl OidcClient {
+    pub fn new(settings: OidcSettings, base_url: &str) -> OidcClient {
+        let http_client = Client::builder()
+            .timeout(settings.timeout())
+            .build()
+            .unwrap();
+        OidcClient {
+            redirect_url: format!("{}/login/oidc/callback", base_url),
+            settings,
+            http_client,
+        }
+    }
+
+    pub fn authorization_request(&self) -> Result<AuthorizationRequest, anyhow::Error> {
+        let state = generate_random_string(32);
+        let code_verifier = generate_random_string(64);
+        let url = Url::parse_with_params(
+            &self.settings.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.settings.client_id.as_str()),
+                ("redirect_uri", self.redirect_url.as_str()),
+                ("scope", self.settings.scopes.as_str()),
+                ("state", state.as_str()),
+                ("code_challenge", code_challenge(&code_verifier).as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .context("The authorization endpoint is not a valid url")?;
+        Ok(AuthorizationRequest {
+            url,
+            state,
+            code_verifier: Secret::new(code_verifier),
+        })
+    }
+
+    #[tracing::instrument(name = "Exchange the authorization code", skip_all)]
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &Secret<String>,
+    ) -> Result<UserInfo, anyhow::Error> {
+        let token: TokenResponse = self
+            .http_client
+            .post(&self.settings.token_endpoint)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", self.redirect_url.as_str()),
+                ("client_id", self.settings.client_id.as_str()),
+                (
+                    "client_secret",
+                    self.settings.client_secret.expose_secret().as_str(),
+                ),
+                ("code_verifier", code_verifier.expose_secret().as_str()),
+            ])
+            .send()
+            .await
+            .context("Failed to reach the token endpoint")?
+            .error_for_status()
+            .context("The token endpoint rejected the authorization code")?
+            .json()
+            .await
+            .context("Failed to parse the token response")?;
+
+        self.http_client
+            .get(&self.settings.userinfo_endpoint)
+            .bearer_auth(token.access_token.expose_secret())
+            .send()
+            .await
+            .context("Failed to reach the userinfo endpoint")?
+            .error_for_status()
+            .context("The userinfo endpoint rejected the access token")?
+            .json()
+            .await
+            .context("Failed to parse the userinfo response")
+    }
+}
+
+/// Find the user matching the identity returned by the provider. A user is
+/// first looked up by subject, then by an email verified both by the provider
+/// and by the user, in which case the subject is linked to the user for the
+/// next logins.
+#[tracing::instrument(name = "Find the user of an OIDC identity", skip(pool))]
+pub async fn find_user(pool: &PgPool, user_info: &UserInfo) -> Result<Option<Uuid>, anyhow::Error> {
+    let user = sqlx::query!(
+        r#"SELECT user_id FROM users WHERE oidc_subject = $1"#,
+        user_info.sub
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look for the user by subject")?;
+    if let Some(user) = user {
+        return Ok(Some(user.user_id));
+    }
+
+    let Some(email) = user_info
+        .email
+        .as_ref()
+        .filter(|_| user_info.email_verified)
+    else {
+        return Ok(None);
+    };
+    let user = sqlx::query!(
+        r#"UPDATE users SET oidc_subject = $1
+        WHERE oidc_subject IS NULL
+            AND email_verified_at IS NOT NULL
+            AND lower(email) = lower($2)
+        RETURNING user_id"#,
+        user_info.sub,
+        email
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look for the user by email")?;
+    Ok(user.map(|u| u.user_id))
+}
+
+fn generate_random_string(length: usize) -> String {
+    let mut rng = rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(length)
+        .collect()
+}
+
+fn code_challenge(code_verifier: &str) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::code_challenge;
+
+    #[test]
+    fn code_challenge_follows_the_rfc_example() {
+        // Example from appendix B of RFC 7636
+        assert_eq!(
+            code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+}
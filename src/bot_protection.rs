@@ -0,0 +1,307 @@
+//! Keeps bots from using the subscribe form to send confirmation emails to
+//! people who did not ask for them: a honeypot field, a minimum time to fill
+//! the form, rate limits and an optional challenge.
+
+use crate::configuration::{ChallengeSettings, SignupSettings};
+use crate::signature::{sign, verify};
+use crate::startup::HmacSecret;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Hidden from people, a bot filling every field fills it.
+pub const HONEYPOT_FIELD: &str = "website";
+/// The form fields of the signed timestamp.
+pub const FORM_RENDERED_AT_FIELD: &str = "form_rendered_at";
+pub const FORM_SIGNATURE_FIELD: &str = "form_signature";
+/// The response of the challenge widget.
+pub const CHALLENGE_RESPONSE_FIELD: &str = "challenge_response";
+/// A form older than this has to be reloaded.
+const FORM_VALIDITY_HOURS: i64 = 24;
+
+fn form_payload(rendered_at: i64) -> String {
+    format!("subscribe_form:{rendered_at}")
+}
+
+/// The values of the `form_rendered_at` and `form_signature` fields.
+pub fn form_timestamp(secret: &HmacSecret, rendered_at: DateTime<Utc>) -> (i64, String) {
+    let rendered_at = rendered_at.timestamp();
+    (rendered_at, sign(secret, &form_payload(rendered_at)))
+}
+
+/// The hidden fields of the subscribe form, with the time it was shown.
+pub fn form_timestamp_fields(secret: &HmacSecret, rendered_at: DateTime<Utc>) -> String {
+    let (rendered_at, signature) = form_timestamp(secret, rendered_at);
+    format!(
+        r#"<input hidden type="text" name="{FORM_RENDERED_AT_FIELD}" value="{rendered_at}">
+    <input hidden type="text" name="{FORM_SIGNATURE_FIELD}" value="{signature}">"#
+    )
+}
+
+/// The form must have been shown by us, between `min_fill_seconds` and a day
+/// ago.
+pub fn check_fill_time(
+    secret: &HmacSecret,
+    rendered_at: &str,
+    signature: &str,
+    min_fill_seconds: u64,
+    now: DateTime<Utc>,
+) -> Result<(), String> {
+    if min_fill_seconds == 0 {
+        return Ok(());
+    }
+    let expired = || "The form has expired, please reload the page and try again.".to_string();
+    let rendered_at: i64 = rendered_at.trim().parse().map_err(|_| expired())?;
+    if !verify(secret, &form_payload(rendered_at), signature) {
+        return Err(expired());
+    }
+    let elapsed = now.timestamp() - rendered_at;
+    if elapsed > FORM_VALIDITY_HOURS * 3600 {
+        Err(expired())
+    } else if elapsed < min_fill_seconds as i64 {
+        Err("The form was sent too quickly, please try again.".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks the response of the challenge widget of the subscribe form.
+pub trait ChallengeVerifier: Send + Sync {
+    /// Added to the subscribe form, it must send its response in the
+    /// `challenge_response` field.
+    fn widget_html(&self) -> &str;
+
+    fn verify<'a>(
+        &'a self,
+        response: &'a str,
+        ip_address: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<bool, anyhow::Error>>;
+}
+
+impl ChallengeSettings {
+    pub fn verifier(&self) -> Arc<dyn ChallengeVerifier> {
+        match self {
+            ChallengeSettings::Siteverify {
+                verify_url,
+                secret,
+                widget_html,
+                timeout_millisecond,
+            } => Arc::new(SiteverifyChallengeVerifier {
+                verify_url: verify_url.clone(),
+                secret: secret.clone(),
+                widget_html: widget_html.clone(),
+                http_client: reqwest::Client::builder()
+                    .timeout(std::time::Duration::from_millis(*timeout_millisecond))
+                    .build()
+                    .unwrap(),
+            }),
+            ChallengeSettings::Stub { accepted_response } => Arc::new(StubChallengeVerifier {
+                accepted_response: accepted_response.clone(),
+            }),
+        }
+    }
+}
+
+/// The `siteverify` protocol shared by hCaptcha, reCAPTCHA and Turnstile.
+pub struct SiteverifyChallengeVerifier {
+    verify_url: String,
+    secret: Secret<String>,
+    widget_html: String,
+    http_client: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct SiteverifyResponse {
+    success: bool,
+}
+
+impl ChallengeVerifier for SiteverifyChallengeVerifier {
+    fn widget_html(&self) -> &str {
+        &self.widget_html
+    }
+
+    fn verify<'a>(
+        &'a self,
+        response: &'a str,
+        ip_address: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<bool, anyhow::Error>> {
+        Box::pin(async move {
+            let mut form = vec![
+                ("secret", self.secret.expose_secret().as_str()),
+                ("response", response),
+            ];
+            if let Some(ip_address) = ip_address {
+                form.push(("remoteip", ip_address));
+            }
+            let verification: SiteverifyResponse = self
+                .http_client
+                .post(&self.verify_url)
+                .form(&form)
+                .send()
+                .await
+                .context("Failed to reach the challenge provider")?
+                .error_for_status()
+                .context("The challenge provider failed")?
+                .json()
+                .await
+                .context("Failed to parse the challenge verification")?;
+            Ok(verification.success)
+        })
+    }
+}
+
+/// Accepts a single response without calling anyone.
+pub struct StubChallengeVerifier {
+    accepted_response: String,
+}
+
+impl ChallengeVerifier for StubChallengeVerifier {
+    fn widget_html(&self) -> &str {
+        ""
+    }
+
+    fn verify<'a>(
+        &'a self,
+        response: &'a str,
+        _ip_address: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<bool, anyhow::Error>> {
+        Box::pin(async move { Ok(response == self.accepted_response) })
+    }
+}
+
+/// Records the signup attempt. Returns why it is rejected if the address or
+/// the email domain made too many in the last hour.
+#[tracing::instrument(name = "Check the signup rate limits", skip(pool, settings))]
+pub async fn check_rate_limits(
+    pool: &PgPool,
+    settings: &SignupSettings,
+    ip_address: Option<&str>,
+    email_domain: &str,
+) -> Result<Option<String>, anyhow::Error> {
+    let now = Utc::now();
+    let since = now - chrono::Duration::hours(1);
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a connection from the pool")?;
+    sqlx::query!(
+        r#"DELETE FROM subscription_attempts WHERE attempted_at < $1"#,
+        since
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to forget the old signup attempts")?;
+    sqlx::query!(
+        r#"INSERT INTO subscription_attempts (ip_address, email_domain, attempted_at)
+        VALUES ($1, $2, $3)"#,
+        ip_address,
+        email_domain,
+        now
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to record the signup attempt")?;
+    let counts = sqlx::query!(
+        r#"SELECT
+            COUNT(*) FILTER (WHERE ip_address = $1) AS "by_ip!",
+            COUNT(*) FILTER (WHERE email_domain = $2) AS "by_domain!"
+        FROM subscription_attempts
+        WHERE attempted_at >= $3"#,
+        ip_address,
+        email_domain,
+        since
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .context("Failed to count the signup attempts")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the signup attempt")?;
+    let exceeds = |count: i64, limit: u32| limit > 0 && count > i64::from(limit);
+    if exceeds(counts.by_ip, settings.max_signups_per_ip_per_hour) {
+        Ok(Some(
+            "Too many signups from your address, please try again later.".to_string(),
+        ))
+    } else if exceeds(counts.by_domain, settings.max_signups_per_domain_per_hour) {
+        Ok(Some(format!(
+            "Too many signups for addresses at {}, please try again later.",
+            email_domain
+        )))
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_fill_time, form_timestamp};
+    use crate::startup::HmacSecret;
+    use chrono::{Duration, Utc};
+    use claim::{assert_err, assert_ok};
+    use secrecy::Secret;
+
+    fn secret() -> HmacSecret {
+        HmacSecret(Secret::new("a very long secret for the tests".to_string()))
+    }
+
+    fn signed(seconds_ago: i64) -> (String, String) {
+        let (rendered_at, signature) =
+            form_timestamp(&secret(), Utc::now() - Duration::seconds(seconds_ago));
+        (rendered_at.to_string(), signature)
+    }
+
+    #[test]
+    fn a_form_filled_in_time_is_accepted() {
+        let (rendered_at, signature) = signed(10);
+        assert_ok!(check_fill_time(
+            &secret(),
+            &rendered_at,
+            &signature,
+            3,
+            Utc::now()
+        ));
+    }
+
+    #[test]
+    fn a_form_sent_too_quickly_is_rejected() {
+        let (rendered_at, signature) = signed(1);
+        assert_err!(check_fill_time(
+            &secret(),
+            &rendered_at,
+            &signature,
+            3,
+            Utc::now()
+        ));
+    }
+
+    #[test]
+    fn a_forged_or_old_timestamp_is_rejected() {
+        let (rendered_at, _) = signed(10);
+        assert_err!(check_fill_time(
+            &secret(),
+            &rendered_at,
+            "00",
+            3,
+            Utc::now()
+        ));
+        assert_err!(check_fill_time(&secret(), "", "", 3, Utc::now()));
+        let (rendered_at, signature) = signed(2 * 24 * 3600);
+        assert_err!(check_fill_time(
+            &secret(),
+            &rendered_at,
+            &signature,
+            3,
+            Utc::now()
+        ));
+    }
+
+    #[test]
+    fn the_check_can_be_turned_off() {
+        assert_ok!(check_fill_time(&secret(), "", "", 0, Utc::now()));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
 use config;
 use secrecy::{ExposeSecret, Secret};
 use serde::Deserialize;
@@ -6,6 +7,7 @@ use serde_aux::field_attributes::deserialize_number_from_string;
 use sqlx::ConnectOptions;
 use sqlx::postgres::PgConnectOptions;
 use sqlx::postgres::PgSslMode;
+use std::net::IpAddr;
 #[derive(Deserialize, Debug, Clone)]
 pub struct ApplicationSettings {
     #[serde(deserialize_with = "deserialize_number_from_string")]
@@ -13,6 +15,10 @@ pub struct ApplicationSettings {
     pub host: String,
     pub base_url: String,
     pub hmac_secret: Secret<String>,
+    /// The reverse proxies in front of the application, the address of the
+    /// clients is read from their `X-Forwarded-For` header.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -21,6 +27,120 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
     pub redis_uri: Secret<String>,
+    pub login_notifications: LoginNotificationSettings,
+    pub newsletter: NewsletterSettings,
+    pub oidc: Option<OidcSettings>,
+    #[serde(default)]
+    pub signup: SignupSettings,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SignupSettings {
+    /// Only the email domains allowed by the admins can subscribe.
+    #[serde(default)]
+    pub allowlist_only: bool,
+    /// The time a person takes at least to fill the subscribe form, 0 turns
+    /// the check off for the forms embedded on other sites.
+    #[serde(
+        default = "default_min_fill_seconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub min_fill_seconds: u64,
+    /// 0 turns the limit off.
+    #[serde(
+        default = "default_max_signups_per_ip_per_hour",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_signups_per_ip_per_hour: u32,
+    /// Protects the inboxes of a domain from a flood of confirmation emails,
+    /// 0 turns the limit off.
+    #[serde(
+        default = "default_max_signups_per_domain_per_hour",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_signups_per_domain_per_hour: u32,
+    pub challenge: Option<ChallengeSettings>,
+}
+
+fn default_min_fill_seconds() -> u64 {
+    3
+}
+
+fn default_max_signups_per_ip_per_hour() -> u32 {
+    10
+}
+
+fn default_max_signups_per_domain_per_hour() -> u32 {
+    100
+}
+
+impl Default for SignupSettings {
+    fn default() -> Self {
+        Self {
+            allowlist_only: false,
+            min_fill_seconds: default_min_fill_seconds(),
+            max_signups_per_ip_per_hour: default_max_signups_per_ip_per_hour(),
+            max_signups_per_domain_per_hour: default_max_signups_per_domain_per_hour(),
+            challenge: None,
+        }
+    }
+}
+
+/// A challenge the subscribe form must pass, as a CAPTCHA.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ChallengeSettings {
+    /// A provider with a `siteverify` endpoint, as hCaptcha, reCAPTCHA or
+    /// Turnstile.
+    Siteverify {
+        verify_url: String,
+        secret: Secret<String>,
+        /// The widget added to the subscribe form, with its script. It must
+        /// send its response in a `challenge_response` field.
+        widget_html: String,
+        #[serde(deserialize_with = "deserialize_number_from_string")]
+        timeout_millisecond: u64,
+    },
+    /// Accepts a single response, for the tests.
+    Stub { accepted_response: String },
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct NewsletterSettings {
+    pub markdown_layout: String,
+    /// The name of the newsletter, as shown in the feeds.
+    pub title: String,
+    pub description: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct LoginNotificationSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub failed_attempts_threshold: u32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OidcSettings {
+    pub provider_name: String,
+    pub client_id: String,
+    pub client_secret: Secret<String>,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    #[serde(default = "default_oidc_scopes")]
+    pub scopes: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub timeout_millisecond: u64,
+}
+
+fn default_oidc_scopes() -> String {
+    "openid email".to_string()
+}
+
+impl OidcSettings {
+    pub fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.timeout_millisecond)
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -32,6 +152,17 @@ pub struct EmailClientSettings {
 }
 
 impl EmailClientSettings {
+    pub fn client(self) -> EmailClient {
+        let sender_email = self.sender().expect("Invalid sender email address.");
+        let timeout = self.timeout();
+        EmailClient::new(
+            self.base_url,
+            sender_email,
+            self.authorization_token,
+            timeout,
+        )
+    }
+
     pub fn sender(&self) -> Result<SubscriberEmail, String> {
         SubscriberEmail::parse(self.sender_email.clone())
     }
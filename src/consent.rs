@@ -0,0 +1,251 @@
+//! The proof of how and when each subscriber opted in to a list.
+
+use actix_web::http::header::{USER_AGENT, X_FORWARDED_FOR};
+use actix_web::{HttpRequest, web};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::net::IpAddr;
+use uuid::Uuid;
+
+/// The consent text of the subscribe form of the home page.
+pub const CONSENT_TEXT: &str = "By subscribing, you agree to receive our newsletter by email. \
+    You can unsubscribe at any time with the link at the bottom of each email.";
+/// Change it with `CONSENT_TEXT`, the records keep the version that was shown.
+pub const CONSENT_TEXT_VERSION: &str = "2026-11-03";
+/// The source of the signups whose form does not name one.
+pub const DEFAULT_CONSENT_SOURCE: &str = "subscribe_form";
+const MAX_CONSENT_FIELD_LENGTH: usize = 100;
+
+/// The reverse proxies whose `X-Forwarded-For` header is believed.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies(pub Vec<IpAddr>);
+
+/// The address of the client. Behind the trusted proxies, it is the last
+/// address of `X-Forwarded-For` they did not add themselves: anyone else can
+/// write what they want in the header, and it would get around the rate
+/// limits.
+pub fn client_ip(request: &HttpRequest) -> Option<String> {
+    let mut client = request.peer_addr()?.ip();
+    let trusted = request
+        .app_data::<web::Data<TrustedProxies>>()
+        .map(|proxies| proxies.0.as_slice())
+        .unwrap_or_default();
+    let forwarded: Vec<&str> = request
+        .headers()
+        .get_all(X_FORWARDED_FOR)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+    for address in forwarded.into_iter().rev() {
+        if !trusted.contains(&client) {
+            break;
+        }
+        match address.trim().parse() {
+            Ok(address) => client = address,
+            Err(_) => break,
+        }
+    }
+    Some(client.to_string())
+}
+
+/// Where a signup or a confirmation came from.
+#[derive(Debug, Clone, Default)]
+pub struct ConsentContext {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl ConsentContext {
+    pub fn from_request(request: &HttpRequest) -> Self {
+        Self {
+            ip_address: client_ip(request),
+            user_agent: request
+                .headers()
+                .get(USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+        }
+    }
+}
+
+/// The text version and the source of a signup, the defaults if the form
+/// sent none.
+pub fn parse_consent(text_version: &str, source: &str) -> Result<(String, String), String> {
+    let text_version = match text_version.trim() {
+        "" => CONSENT_TEXT_VERSION,
+        text_version => text_version,
+    };
+    let source = match source.trim() {
+        "" => DEFAULT_CONSENT_SOURCE,
+        source => source,
+    };
+    if text_version.chars().count() > MAX_CONSENT_FIELD_LENGTH
+        || source.chars().count() > MAX_CONSENT_FIELD_LENGTH
+    {
+        return Err(format!(
+            "The consent version and source are limited to {} characters.",
+            MAX_CONSENT_FIELD_LENGTH
+        ));
+    }
+    Ok((text_version.to_string(), source.to_string()))
+}
+
+pub struct ConsentRecord {
+    pub list_name: String,
+    pub text_version: String,
+    pub source: String,
+    pub signup_ip: Option<String>,
+    pub signup_user_agent: Option<String>,
+    pub signed_up_at: DateTime<Utc>,
+    pub confirmation_ip: Option<String>,
+    pub confirmation_user_agent: Option<String>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+}
+
+#[tracing::instrument(name = "Record a signup consent", skip(transaction, context))]
+pub async fn record_signup(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    list_id: Uuid,
+    text_version: &str,
+    source: &str,
+    context: &ConsentContext,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"INSERT INTO consent_records (consent_id, subscriber_id, list_id, text_version, source,
+            signup_ip, signup_user_agent, signed_up_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+        Uuid::new_v4(),
+        subscriber_id,
+        list_id,
+        text_version,
+        source,
+        context.ip_address,
+        context.user_agent,
+        Utc::now()
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to record the consent")?;
+    Ok(())
+}
+
+/// Completes the latest unconfirmed signup to the list, if there is one.
+#[tracing::instrument(name = "Record a confirmation consent", skip(transaction, context))]
+pub async fn record_confirmation(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    list_id: Uuid,
+    context: &ConsentContext,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"UPDATE consent_records
+        SET confirmation_ip = $3, confirmation_user_agent = $4, confirmed_at = $5
+        WHERE consent_id = (
+            SELECT consent_id FROM consent_records
+            WHERE subscriber_id = $1 AND list_id = $2 AND confirmed_at IS NULL
+            ORDER BY signed_up_at DESC
+            LIMIT 1
+        )"#,
+        subscriber_id,
+        list_id,
+        context.ip_address,
+        context.user_agent,
+        Utc::now()
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to record the confirmation")?;
+    Ok(())
+}
+
+/// The latest signups first.
+pub async fn get_consent_history(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<ConsentRecord>, anyhow::Error> {
+    sqlx::query_as!(
+        ConsentRecord,
+        r#"SELECT l.name AS list_name, c.text_version, c.source, c.signup_ip,
+            c.signup_user_agent, c.signed_up_at, c.confirmation_ip, c.confirmation_user_agent,
+            c.confirmed_at
+        FROM consent_records c
+        JOIN lists l ON l.list_id = c.list_id
+        WHERE c.subscriber_id = $1
+        ORDER BY c.signed_up_at DESC"#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the consent history")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_consent;
+    use super::{CONSENT_TEXT_VERSION, DEFAULT_CONSENT_SOURCE, TrustedProxies, client_ip};
+    use actix_web::test::TestRequest;
+    use actix_web::web;
+    use claim::{assert_err, assert_ok_eq};
+
+    fn client_ip_behind(trusted: &[&str], forwarded: &str) -> Option<String> {
+        let request = TestRequest::default()
+            .peer_addr("10.0.0.1:443".parse().unwrap())
+            .insert_header(("X-Forwarded-For", forwarded))
+            .app_data(web::Data::new(TrustedProxies(
+                trusted.iter().map(|ip| ip.parse().unwrap()).collect(),
+            )))
+            .to_http_request();
+        client_ip(&request)
+    }
+
+    #[test]
+    fn the_forwarded_header_of_a_client_is_ignored() {
+        assert_eq!(
+            client_ip_behind(&[], "203.0.113.7"),
+            Some("10.0.0.1".to_string())
+        );
+    }
+
+    #[test]
+    fn the_address_added_by_a_trusted_proxy_is_used() {
+        // The first address was written by the client itself.
+        assert_eq!(
+            client_ip_behind(&["10.0.0.1"], "198.51.100.1, 203.0.113.7"),
+            Some("203.0.113.7".to_string())
+        );
+        assert_eq!(
+            client_ip_behind(&["10.0.0.1", "10.0.0.2"], "203.0.113.7, 10.0.0.2"),
+            Some("203.0.113.7".to_string())
+        );
+        assert_eq!(
+            client_ip_behind(&["10.0.0.1"], "not an address"),
+            Some("10.0.0.1".to_string())
+        );
+    }
+
+    #[test]
+    fn a_form_without_consent_fields_gets_the_defaults() {
+        assert_ok_eq!(
+            parse_consent(" ", ""),
+            (
+                CONSENT_TEXT_VERSION.to_string(),
+                DEFAULT_CONSENT_SOURCE.to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn the_fields_of_the_form_are_kept() {
+        assert_ok_eq!(
+            parse_consent("v2", " footer "),
+            ("v2".to_string(), "footer".to_string())
+        );
+    }
+
+    #[test]
+    fn a_long_source_is_rejected() {
+        assert_err!(parse_consent("", &"a".repeat(101)));
+    }
+}
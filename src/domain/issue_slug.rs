@@ -0,0 +1,74 @@
+use uuid::Uuid;
+
+/// Titles are cut to keep the URLs of the archive readable.
+const MAX_TITLE_LENGTH: usize = 60;
+
+/// The part of the URL of an issue in the public archive, made of its title
+/// and of the beginning of its id so that titles can be reused. The title is
+/// transliterated to ASCII, `Été` becomes `ete`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IssueSlug(String);
+
+impl IssueSlug {
+    pub fn new(title: &str, id: Uuid) -> IssueSlug {
+        let mut slug = String::new();
+        for c in deunicode::deunicode(title)
+            .chars()
+            .flat_map(|c| c.to_lowercase())
+        {
+            if c.is_ascii_alphanumeric() {
+                slug.push(c);
+            } else if !slug.is_empty() && !slug.ends_with('-') {
+                slug.push('-');
+            }
+            if slug.len() >= MAX_TITLE_LENGTH {
+                break;
+            }
+        }
+        let slug = slug.trim_end_matches('-');
+        let slug = if slug.is_empty() { "issue" } else { slug };
+        IssueSlug(format!("{}-{}", slug, &id.simple().to_string()[..8]))
+    }
+}
+
+impl AsRef<str> for IssueSlug {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IssueSlug;
+    use uuid::Uuid;
+
+    fn id() -> Uuid {
+        Uuid::parse_str("3f2b8c1e-0000-4000-8000-000000000000").unwrap()
+    }
+
+    #[test]
+    fn the_title_is_lowercased_and_dashed() {
+        let slug = IssueSlug::new("  Hello, World! Issue #3 ", id());
+        assert_eq!(slug.as_ref(), "hello-world-issue-3-3f2b8c1e");
+    }
+
+    #[test]
+    fn non_ascii_characters_are_transliterated() {
+        let slug = IssueSlug::new("Été à Paris", id());
+        assert_eq!(slug.as_ref(), "ete-a-paris-3f2b8c1e");
+        let slug = IssueSlug::new("Straße über Zürich", id());
+        assert_eq!(slug.as_ref(), "strasse-uber-zurich-3f2b8c1e");
+    }
+
+    #[test]
+    fn a_title_without_letters_gets_a_generic_slug() {
+        let slug = IssueSlug::new("!!!", id());
+        assert_eq!(slug.as_ref(), "issue-3f2b8c1e");
+    }
+
+    #[test]
+    fn long_titles_are_cut() {
+        let slug = IssueSlug::new(&"a".repeat(200), id());
+        assert_eq!(slug.as_ref().len(), 60 + 9);
+    }
+}
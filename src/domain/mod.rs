@@ -1,7 +1,15 @@
+mod issue_slug;
+mod issue_status;
 mod new_subscriber;
+mod send_at;
 mod subscriber_email;
 mod subscriber_name;
+mod subscription_status;
 
+pub use issue_slug::IssueSlug;
+pub use issue_status::IssueStatus;
 pub use new_subscriber::NewSubscriber;
+pub use send_at::SendAt;
 pub use subscriber_email::SubscriberEmail;
 pub use subscriber_name::SuscriberName;
+pub use subscription_status::SubscriptionStatus;
@@ -0,0 +1,90 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+
+/// The moment a scheduled issue goes out, entered as a local date and time
+/// in an IANA timezone (e.g. `Europe/Paris`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendAt(DateTime<Utc>);
+
+impl SendAt {
+    pub fn parse(local: &str, timezone: &str) -> Result<SendAt, String> {
+        let timezone: Tz = timezone
+            .trim()
+            .parse()
+            .map_err(|_| format!("{} is not a valid timezone.", timezone))?;
+        let local = local.trim();
+        let naive = NaiveDateTime::parse_from_str(local, "%Y-%m-%dT%H:%M")
+            .or_else(|_| NaiveDateTime::parse_from_str(local, "%Y-%m-%dT%H:%M:%S"))
+            .map_err(|_| format!("{} is not a valid date and time.", local))?;
+        // When the clock goes back the time happens twice, we pick the first one.
+        let send_at = naive
+            .and_local_timezone(timezone)
+            .earliest()
+            .ok_or_else(|| format!("{} does not exist in {}.", local, timezone))?;
+        Ok(SendAt(send_at.with_timezone(&Utc)))
+    }
+
+    pub fn into_inner(self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+impl AsRef<DateTime<Utc>> for SendAt {
+    fn as_ref(&self) -> &DateTime<Utc> {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SendAt;
+    use chrono::{TimeZone, Utc};
+    use claim::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn a_local_time_is_converted_to_utc() {
+        assert_ok_eq!(
+            SendAt::parse("2026-10-26T09:00", "Europe/Paris").map(SendAt::into_inner),
+            Utc.with_ymd_and_hms(2026, 10, 26, 8, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn seconds_are_accepted() {
+        assert_ok_eq!(
+            SendAt::parse("2026-07-06T09:00:30", "UTC").map(SendAt::into_inner),
+            Utc.with_ymd_and_hms(2026, 7, 6, 9, 0, 30).unwrap()
+        );
+    }
+
+    #[test]
+    fn daylight_saving_time_is_taken_into_account() {
+        assert_ok_eq!(
+            SendAt::parse("2026-07-06T09:00", "Europe/Paris").map(SendAt::into_inner),
+            Utc.with_ymd_and_hms(2026, 7, 6, 7, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn an_ambiguous_time_picks_the_first_occurrence() {
+        assert_ok_eq!(
+            SendAt::parse("2026-10-25T02:30", "Europe/Paris").map(SendAt::into_inner),
+            Utc.with_ymd_and_hms(2026, 10, 25, 0, 30, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn a_skipped_time_is_rejected() {
+        assert_err!(SendAt::parse("2026-03-29T02:30", "Europe/Paris"));
+    }
+
+    #[test]
+    fn an_unknown_timezone_is_rejected() {
+        assert_err!(SendAt::parse("2026-10-26T09:00", "Mars/Olympus_Mons"));
+    }
+
+    #[test]
+    fn an_invalid_date_is_rejected() {
+        assert_err!(SendAt::parse("next monday", "UTC"));
+    }
+}
@@ -1,16 +1,34 @@
 use serde::Deserialize;
 use validator::ValidateEmail;
+/// The address is trimmed and its domain is in lowercase ASCII, international
+/// domains are converted to punycode. The local part is kept as typed since
+/// the mail server of the domain may tell its case apart.
 #[derive(Debug, Clone, Deserialize)]
 pub struct SubscriberEmail(String);
 
 impl SubscriberEmail {
     pub fn parse(s: String) -> Result<SubscriberEmail, String> {
-        if s.validate_email() {
-            Ok(Self(s))
+        let invalid = || format!("{} is not a valid subscriber email.", s);
+        let trimmed = s.trim();
+        let (local_part, domain) = trimmed.rsplit_once('@').ok_or_else(invalid)?;
+        let domain = idna::domain_to_ascii(domain).map_err(|_| invalid())?;
+        let email = format!("{}@{}", local_part, domain);
+        if email.validate_email() {
+            Ok(Self(email))
         } else {
-            Err(format!("{} is not a valid subscriber email.", s))
+            Err(invalid())
         }
     }
+
+    /// In lowercase ASCII.
+    pub fn domain(&self) -> &str {
+        self.0.rsplit_once('@').map_or("", |(_, domain)| domain)
+    }
+
+    /// Two addresses that only differ by case belong to the same subscriber.
+    pub fn normalized(&self) -> String {
+        self.0.to_lowercase()
+    }
 }
 
 impl AsRef<str> for SubscriberEmail {
@@ -44,6 +62,25 @@ mod tests {
         assert_err!(SubscriberEmail::parse(email));
     }
 
+    #[test]
+    fn the_email_is_trimmed_and_its_domain_lowercased() {
+        let email = SubscriberEmail::parse("  Ursula@Example.COM ".to_string()).unwrap();
+        assert_eq!(email.as_ref(), "Ursula@example.com");
+        assert_eq!(email.normalized(), "ursula@example.com");
+    }
+
+    #[test]
+    fn an_international_domain_is_converted_to_punycode() {
+        let email = SubscriberEmail::parse("ursula@Bücher.example".to_string()).unwrap();
+        assert_eq!(email.as_ref(), "ursula@xn--bcher-kva.example");
+        assert_eq!(email.domain(), "xn--bcher-kva.example");
+    }
+
+    #[test]
+    fn an_invalid_international_domain_is_rejected() {
+        assert_err!(SubscriberEmail::parse("ursula@xn--.example".to_string()));
+    }
+
     #[quickcheck]
     fn email_is_valid(s: ValidEmailFixture) -> bool {
         (SubscriberEmail::parse(s.0)).is_ok()
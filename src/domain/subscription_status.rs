@@ -0,0 +1,60 @@
+/// The status of a subscriber on a list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscriptionStatus {
+    PendingConfirmation,
+    Confirmed,
+    Unsubscribed,
+}
+
+impl SubscriptionStatus {
+    pub const ALL: [SubscriptionStatus; 3] = [
+        SubscriptionStatus::PendingConfirmation,
+        SubscriptionStatus::Confirmed,
+        SubscriptionStatus::Unsubscribed,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubscriptionStatus::PendingConfirmation => "pending_confirmation",
+            SubscriptionStatus::Confirmed => "confirmed",
+            SubscriptionStatus::Unsubscribed => "unsubscribed",
+        }
+    }
+}
+
+impl TryFrom<String> for SubscriptionStatus {
+    type Error = String;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        SubscriptionStatus::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .ok_or_else(|| format!("{} is not a valid subscription status", s))
+    }
+}
+
+impl std::fmt::Display for SubscriptionStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SubscriptionStatus;
+    use claim::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn every_status_round_trips() {
+        for status in SubscriptionStatus::ALL {
+            assert_ok_eq!(
+                SubscriptionStatus::try_from(status.as_str().to_string()),
+                status
+            );
+        }
+    }
+
+    #[test]
+    fn an_unknown_status_is_rejected() {
+        assert_err!(SubscriptionStatus::try_from("pending".to_string()));
+    }
+}
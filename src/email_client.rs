@@ -3,6 +3,7 @@ use reqwest::Client;
 use secrecy::{ExposeSecret, Secret};
 use serde::Serialize;
 
+#[derive(Clone)]
 pub struct EmailClient {
     pub sender: SubscriberEmail,
     pub http_client: Client,
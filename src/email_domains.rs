@@ -0,0 +1,247 @@
+//! The email domains that cannot subscribe: the bundled disposable domains and
+//! the domains blocked by the admins. In allowlist mode, only the domains the
+//! admins allowed can subscribe.
+
+use crate::domain::SubscriberEmail;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::collections::HashSet;
+
+const DISPOSABLE_DOMAINS: &str = include_str!("disposable_domains.txt");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DomainRule {
+    Block,
+    /// Lets a bundled disposable domain subscribe, and is required in
+    /// allowlist mode.
+    Allow,
+}
+
+impl DomainRule {
+    pub const ALL: [DomainRule; 2] = [DomainRule::Block, DomainRule::Allow];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DomainRule::Block => "block",
+            DomainRule::Allow => "allow",
+        }
+    }
+}
+
+impl TryFrom<String> for DomainRule {
+    type Error = String;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        DomainRule::ALL
+            .into_iter()
+            .find(|rule| rule.as_str() == s)
+            .ok_or_else(|| format!("{} is not a domain rule, use block or allow.", s))
+    }
+}
+
+impl std::fmt::Display for DomainRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A domain as the admins type it, `@` and case allowed, converted to the
+/// form of the domains of `SubscriberEmail`.
+pub fn parse_domain(s: &str) -> Result<String, String> {
+    let domain = s.trim().trim_start_matches('@');
+    let invalid = || format!("{} is not a valid domain.", s.trim());
+    let domain = idna::domain_to_ascii(domain).map_err(|_| invalid())?;
+    if domain.contains('.') && !domain.starts_with('.') && !domain.ends_with('.') {
+        Ok(domain)
+    } else {
+        Err(invalid())
+    }
+}
+
+/// The domains bundled with the application, comments and blank lines
+/// skipped.
+pub fn disposable_domains() -> impl Iterator<Item = &'static str> {
+    DISPOSABLE_DOMAINS
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// The domain and its parent domains, `mail.example.com` then `example.com`.
+fn domain_and_parents(domain: &str) -> impl Iterator<Item = &str> {
+    std::iter::successors(Some(domain), |domain| {
+        domain.split_once('.').map(|(_, parent)| parent)
+    })
+    .filter(|domain| domain.contains('.'))
+}
+
+/// Which email domains can subscribe.
+#[derive(Debug, Clone, Default)]
+pub struct DomainPolicy {
+    pub allowlist_only: bool,
+    pub blocked: HashSet<String>,
+    pub allowed: HashSet<String>,
+}
+
+impl DomainPolicy {
+    /// An allowed domain wins over a blocked one, a rule on a domain covers its
+    /// subdomains.
+    pub fn check(&self, email: &SubscriberEmail) -> Result<(), String> {
+        let domain = email.domain();
+        let matches = |domains: &HashSet<String>| {
+            domain_and_parents(domain).any(|domain| domains.contains(domain))
+        };
+        if matches(&self.allowed) {
+            Ok(())
+        } else if self.allowlist_only {
+            Err(format!(
+                "Addresses at {} cannot subscribe, only those of approved domains can.",
+                domain
+            ))
+        } else if matches(&self.blocked) {
+            Err(format!(
+                "Addresses at {} cannot subscribe, please use another address.",
+                domain
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The bundled disposable domains and the rules of the admins.
+pub async fn load_domain_policy(
+    pool: &PgPool,
+    allowlist_only: bool,
+) -> Result<DomainPolicy, anyhow::Error> {
+    let mut policy = DomainPolicy {
+        allowlist_only,
+        blocked: disposable_domains().map(str::to_string).collect(),
+        allowed: HashSet::new(),
+    };
+    for rule in list_domain_rules(pool).await? {
+        match rule.rule {
+            DomainRule::Block => policy.blocked.insert(rule.domain),
+            DomainRule::Allow => policy.allowed.insert(rule.domain),
+        };
+    }
+    Ok(policy)
+}
+
+pub struct DomainRuleEntry {
+    pub domain: String,
+    pub rule: DomainRule,
+    pub created_at: DateTime<Utc>,
+}
+
+pub async fn list_domain_rules(pool: &PgPool) -> Result<Vec<DomainRuleEntry>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT domain, rule, created_at FROM email_domain_rules ORDER BY rule, domain"#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the email domain rules")?;
+    rows.into_iter()
+        .map(|r| {
+            Ok(DomainRuleEntry {
+                domain: r.domain,
+                rule: DomainRule::try_from(r.rule).map_err(anyhow::Error::msg)?,
+                created_at: r.created_at,
+            })
+        })
+        .collect()
+}
+
+/// Replaces the rule of the domain, if it had one.
+#[tracing::instrument(name = "Save an email domain rule", skip(pool))]
+pub async fn upsert_domain_rule(
+    pool: &PgPool,
+    domain: &str,
+    rule: DomainRule,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"INSERT INTO email_domain_rules (domain, rule, created_at) VALUES ($1, $2, $3)
+        ON CONFLICT (domain) DO UPDATE SET rule = EXCLUDED.rule, created_at = EXCLUDED.created_at"#,
+        domain,
+        rule.as_str(),
+        Utc::now()
+    )
+    .execute(pool)
+    .await
+    .context("Failed to save the email domain rule")?;
+    Ok(())
+}
+
+/// Returns `false` if the domain had no rule.
+#[tracing::instrument(name = "Delete an email domain rule", skip(pool))]
+pub async fn delete_domain_rule(pool: &PgPool, domain: &str) -> Result<bool, anyhow::Error> {
+    let deleted = sqlx::query!(
+        r#"DELETE FROM email_domain_rules WHERE domain = $1"#,
+        domain
+    )
+    .execute(pool)
+    .await
+    .context("Failed to delete the email domain rule")?;
+    Ok(deleted.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DomainPolicy, DomainRule, disposable_domains, parse_domain};
+    use crate::domain::SubscriberEmail;
+    use claim::{assert_err, assert_ok, assert_ok_eq};
+
+    fn email(s: &str) -> SubscriberEmail {
+        SubscriberEmail::parse(s.to_string()).unwrap()
+    }
+
+    fn policy(allowlist_only: bool, blocked: &[&str], allowed: &[&str]) -> DomainPolicy {
+        DomainPolicy {
+            allowlist_only,
+            blocked: blocked.iter().map(|domain| domain.to_string()).collect(),
+            allowed: allowed.iter().map(|domain| domain.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn domain_rules_round_trip() {
+        for rule in DomainRule::ALL {
+            assert_ok_eq!(DomainRule::try_from(rule.as_str().to_string()), rule);
+        }
+    }
+
+    #[test]
+    fn the_bundled_domains_are_valid_domains() {
+        assert!(disposable_domains().any(|domain| domain == "mailinator.com"));
+        assert!(disposable_domains().all(|domain| parse_domain(domain) == Ok(domain.into())));
+    }
+
+    #[test]
+    fn domains_are_parsed_like_the_emails() {
+        assert_ok_eq!(parse_domain(" @Bücher.Example "), "xn--bcher-kva.example");
+        assert_err!(parse_domain("localhost"));
+        assert_err!(parse_domain(".example.com"));
+    }
+
+    #[test]
+    fn a_blocked_domain_covers_its_subdomains() {
+        let policy = policy(false, &["mailinator.com"], &[]);
+        assert_err!(policy.check(&email("ursula@mailinator.com")));
+        assert_err!(policy.check(&email("ursula@eu.Mailinator.com")));
+        assert_ok!(policy.check(&email("ursula@notmailinator.com")));
+    }
+
+    #[test]
+    fn an_allowed_domain_wins_over_a_blocked_one() {
+        let policy = policy(false, &["example.com"], &["staff.example.com"]);
+        assert_ok!(policy.check(&email("ursula@staff.example.com")));
+        assert_err!(policy.check(&email("ursula@example.com")));
+    }
+
+    #[test]
+    fn the_allowlist_mode_rejects_the_other_domains() {
+        let policy = policy(true, &[], &["example.com"]);
+        assert_ok!(policy.check(&email("ursula@example.com")));
+        assert_err!(policy.check(&email("ursula@example.org")));
+    }
+}
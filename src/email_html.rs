@@ -0,0 +1,437 @@
+//! Mail clients ignore most of HTML and CSS: the HTML of an issue is
+//! restricted to an allowlist of tags and attributes, and the rules of its
+//! `<style>` elements are inlined onto the elements they apply to, since
+//! Gmail and Outlook drop `<style>` elements.
+
+use crate::merge_tags::{protect_merge_tags, restore_merge_tags};
+use html5ever::{QualName, local_name, ns};
+use reqwest::Url;
+use scraper::{Html, Node, Selector};
+use std::collections::{HashMap, HashSet};
+
+const ALLOWED_TAGS: &[&str] = &[
+    "a",
+    "abbr",
+    "b",
+    "blockquote",
+    "br",
+    "caption",
+    "center",
+    "code",
+    "col",
+    "colgroup",
+    "dd",
+    "del",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "figcaption",
+    "figure",
+    "font",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "i",
+    "img",
+    "ins",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "s",
+    "small",
+    "span",
+    "strike",
+    "strong",
+    "sub",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+    "u",
+    "ul",
+];
+
+const ALLOWED_ATTRIBUTES: &[&str] = &[
+    "align",
+    "bgcolor",
+    "border",
+    "cellpadding",
+    "cellspacing",
+    "colspan",
+    "dir",
+    "height",
+    "lang",
+    "rowspan",
+    "style",
+    "title",
+    "valign",
+    "width",
+];
+
+const ALLOWED_TAG_ATTRIBUTES: &[(&str, &[&str])] = &[
+    ("a", &["href", "name", "target"]),
+    ("img", &["alt", "src"]),
+    ("font", &["color", "face", "size"]),
+];
+
+const ALLOWED_URL_SCHEMES: &[&str] = &["http", "https", "mailto", "tel"];
+
+/// Only meant for the `<style>` rules, which are inlined: they are dropped
+/// without a warning.
+const SILENTLY_REMOVED_ATTRIBUTES: &[&str] = &["class", "id"];
+
+pub struct PreparedHtml {
+    pub html: String,
+    /// What was removed from the HTML, in plain words for the admin.
+    pub warnings: Vec<String>,
+}
+
+/// Inline the `<style>` rules of `html`, then sanitize it. Merge tags are
+/// kept as they are.
+pub fn prepare_email_html(html: &str) -> PreparedHtml {
+    let (html, merge_tags) = protect_merge_tags(html);
+    let mut warnings = Vec::new();
+    let mut document = Html::parse_fragment(&html);
+    inline_styles(&mut document, &mut warnings);
+    check_allowlist(&document, &mut warnings);
+    let html = sanitizer()
+        .clean(&document.root_element().inner_html())
+        .to_string();
+    let mut seen = HashSet::new();
+    warnings.retain(|warning| seen.insert(warning.clone()));
+    PreparedHtml {
+        html: restore_merge_tags(&html, &merge_tags),
+        warnings: warnings
+            .iter()
+            .map(|warning| restore_merge_tags(warning, &merge_tags))
+            .collect(),
+    }
+}
+
+fn sanitizer() -> ammonia::Builder<'static> {
+    let mut builder = ammonia::Builder::default();
+    builder
+        .tags(ALLOWED_TAGS.iter().copied().collect())
+        .generic_attributes(ALLOWED_ATTRIBUTES.iter().copied().collect())
+        .tag_attributes(
+            ALLOWED_TAG_ATTRIBUTES
+                .iter()
+                .map(|(tag, attributes)| (*tag, attributes.iter().copied().collect()))
+                .collect(),
+        )
+        .url_schemes(ALLOWED_URL_SCHEMES.iter().copied().collect());
+    builder
+}
+
+fn check_allowlist(document: &Html, warnings: &mut Vec<String>) {
+    for element in document.root_element().descendent_elements().skip(1) {
+        let tag = element.value().name();
+        if tag == "style" {
+            continue;
+        }
+        if !ALLOWED_TAGS.contains(&tag) {
+            warnings.push(format!("The <{}> element has been removed.", tag));
+            continue;
+        }
+        let tag_attributes = ALLOWED_TAG_ATTRIBUTES
+            .iter()
+            .find(|(t, _)| *t == tag)
+            .map(|(_, attributes)| *attributes)
+            .unwrap_or_default();
+        for (attribute, value) in element.value().attrs() {
+            if SILENTLY_REMOVED_ATTRIBUTES.contains(&attribute) {
+                continue;
+            }
+            if !ALLOWED_ATTRIBUTES.contains(&attribute) && !tag_attributes.contains(&attribute) {
+                warnings.push(format!(
+                    "The {} attribute of <{}> has been removed.",
+                    attribute, tag
+                ));
+            } else if matches!(attribute, "href" | "src")
+                && let Ok(url) = Url::parse(value)
+                && !ALLOWED_URL_SCHEMES.contains(&url.scheme())
+            {
+                warnings.push(format!(
+                    "The {}: URL of <{}> has been removed.",
+                    url.scheme(),
+                    tag
+                ));
+            }
+        }
+    }
+}
+
+struct Rule {
+    selector: Selector,
+    specificity: (usize, usize, usize),
+    declarations: Vec<(String, String)>,
+}
+
+fn inline_styles(document: &mut Html, warnings: &mut Vec<String>) {
+    let style_selector = Selector::parse("style").expect("The selector is valid");
+    let stylesheet: String = document
+        .select(&style_selector)
+        .map(|style| style.text().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut rules = parse_stylesheet(&stylesheet, warnings);
+    // Later rules win over earlier rules of the same specificity.
+    rules.sort_by_key(|rule| rule.specificity);
+
+    let mut styles: HashMap<_, Vec<(String, String)>> = HashMap::new();
+    for rule in &rules {
+        for element in document.select(&rule.selector) {
+            styles
+                .entry(element.id())
+                .or_default()
+                .extend(rule.declarations.iter().cloned());
+        }
+    }
+    for (id, mut declarations) in styles {
+        let Some(mut node) = document.tree.get_mut(id) else {
+            continue;
+        };
+        let Node::Element(element) = node.value() else {
+            continue;
+        };
+        // The style attribute of the element wins over the stylesheet.
+        if let Some(inline) = element.attr("style") {
+            declarations.extend(parse_declarations(inline));
+        }
+        element
+            .attrs
+            .retain(|(name, _)| name.local != local_name!("style"));
+        element.attrs.push((
+            QualName::new(None, ns!(), local_name!("style")),
+            format_declarations(declarations).into(),
+        ));
+    }
+}
+
+fn parse_stylesheet(stylesheet: &str, warnings: &mut Vec<String>) -> Vec<Rule> {
+    let stylesheet = strip_comments(stylesheet);
+    let mut rules = Vec::new();
+    let mut rest = stylesheet.as_str();
+    while let Some(open) = rest.find('{') {
+        let prelude = rest[..open].trim();
+        let block_end = match matching_brace(&rest[open..]) {
+            Some(end) => open + end,
+            None => rest.len(),
+        };
+        let block = &rest[open + 1..block_end];
+        rest = rest.get(block_end + 1..).unwrap_or_default();
+
+        if let Some(at_rule) = prelude.strip_prefix('@') {
+            let name = at_rule.split_whitespace().next().unwrap_or_default();
+            warnings.push(format!(
+                "The @{} rule cannot be inlined and has been removed.",
+                name
+            ));
+            continue;
+        }
+        let declarations = parse_declarations(block);
+        for selector in prelude.split(',').map(str::trim) {
+            if has_pseudo_class(selector) {
+                warnings.push(format!(
+                    "The `{}` selector cannot be inlined and has been removed.",
+                    selector
+                ));
+                continue;
+            }
+            match Selector::parse(selector) {
+                Ok(parsed) => rules.push(Rule {
+                    selector: parsed,
+                    specificity: specificity(selector),
+                    declarations: declarations.clone(),
+                }),
+                Err(_) => warnings.push(format!(
+                    "The `{}` selector is not valid and has been removed.",
+                    selector
+                )),
+            }
+        }
+    }
+    rules
+}
+
+fn strip_comments(css: &str) -> String {
+    let mut stripped = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        stripped.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+/// The position of the brace closing the block `s` starts with.
+fn matching_brace(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_declarations(block: &str) -> Vec<(String, String)> {
+    block
+        .split(';')
+        .filter_map(|declaration| declaration.split_once(':'))
+        .map(|(property, value)| (property.trim().to_lowercase(), value.trim().to_string()))
+        .filter(|(property, value)| !property.is_empty() && !value.is_empty())
+        .collect()
+}
+
+/// The last value of a property wins, at the position of its first
+/// occurrence.
+fn format_declarations(declarations: Vec<(String, String)>) -> String {
+    let mut merged: Vec<(String, String)> = Vec::new();
+    for (property, value) in declarations {
+        match merged.iter_mut().find(|(p, _)| *p == property) {
+            Some(existing) => existing.1 = value,
+            None => merged.push((property, value)),
+        }
+    }
+    merged
+        .iter()
+        .map(|(property, value)| format!("{}: {}", property, value))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Pseudo-classes and pseudo-elements depend on the state of the page,
+/// they cannot be written as a style attribute.
+fn has_pseudo_class(selector: &str) -> bool {
+    outside_brackets(selector).any(|c| c == ':')
+}
+
+/// The characters of a selector that are not in an attribute selector.
+fn outside_brackets(selector: &str) -> impl Iterator<Item = char> + '_ {
+    let mut depth = 0;
+    selector.chars().filter(move |c| {
+        match *c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            _ => return depth == 0,
+        }
+        false
+    })
+}
+
+/// The number of ids, of classes and attributes, and of elements of a
+/// selector without pseudo-classes.
+fn specificity(selector: &str) -> (usize, usize, usize) {
+    let ids = outside_brackets(selector).filter(|c| *c == '#').count();
+    let classes =
+        outside_brackets(selector).filter(|c| *c == '.').count() + selector.matches('[').count();
+    let elements = selector
+        .split([' ', '>', '+', '~'])
+        .filter(|compound| compound.starts_with(|c: char| c.is_ascii_alphabetic()))
+        .count();
+    (ids, classes, elements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::prepare_email_html;
+
+    #[test]
+    fn allowed_html_is_kept() {
+        let prepared = prepare_email_html(
+            r#"<p align="center">Hello <a href="https://example.com">world</a></p>"#,
+        );
+        assert_eq!(
+            prepared.html,
+            r#"<p align="center">Hello <a href="https://example.com" rel="noopener noreferrer">world</a></p>"#
+        );
+        assert!(prepared.warnings.is_empty());
+    }
+
+    #[test]
+    fn scripts_and_event_handlers_are_removed_with_a_warning() {
+        let prepared = prepare_email_html(
+            r#"<p onclick="alert(1)">Hi</p><script>alert(1)</script><a href="javascript:alert(1)">x</a>"#,
+        );
+        assert_eq!(
+            prepared.html,
+            r#"<p>Hi</p><a rel="noopener noreferrer">x</a>"#
+        );
+        assert_eq!(
+            prepared.warnings,
+            vec![
+                "The onclick attribute of <p> has been removed.",
+                "The <script> element has been removed.",
+                "The javascript: URL of <a> has been removed.",
+            ]
+        );
+    }
+
+    #[test]
+    fn style_rules_are_inlined() {
+        let prepared = prepare_email_html(
+            r#"<style>p { color: red; margin: 0 } .lead { color: blue }</style><p class="lead">Hi</p><p>there</p>"#,
+        );
+        assert_eq!(
+            prepared.html,
+            r#"<p style="color: blue; margin: 0">Hi</p><p style="color: red; margin: 0">there</p>"#
+        );
+        assert!(prepared.warnings.is_empty());
+    }
+
+    #[test]
+    fn the_style_attribute_wins_over_the_rules() {
+        let prepared = prepare_email_html(
+            r#"<style>#title { color: red }</style><h1 id="title" style="color: green">Hi</h1>"#,
+        );
+        assert_eq!(prepared.html, r#"<h1 style="color: green">Hi</h1>"#);
+    }
+
+    #[test]
+    fn rules_that_cannot_be_inlined_are_reported() {
+        let prepared = prepare_email_html(
+            r#"<style>a:hover { color: red } @media (max-width: 600px) { p { margin: 0 } }</style><p>Hi</p>"#,
+        );
+        assert_eq!(prepared.html, "<p>Hi</p>");
+        assert_eq!(
+            prepared.warnings,
+            vec![
+                "The `a:hover` selector cannot be inlined and has been removed.",
+                "The @media rule cannot be inlined and has been removed.",
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_tags_are_preserved() {
+        let prepared = prepare_email_html(
+            r#"<p>Hi {{ name | default: "friend" }}, <a href="{{ unsubscribe_url }}">unsubscribe</a></p>"#,
+        );
+        assert_eq!(
+            prepared.html,
+            r#"<p>Hi {{ name | default: "friend" }}, <a href="{{ unsubscribe_url }}" rel="noopener noreferrer">unsubscribe</a></p>"#
+        );
+    }
+}
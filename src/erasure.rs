@@ -0,0 +1,170 @@
+//! Erasing a subscriber at their request. What identifies them is deleted or
+//! anonymized, and a hash of their email is kept so they are not added back.
+
+use crate::domain::SubscriberEmail;
+use anyhow::Context;
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use sqlx::{PgExecutor, Postgres, Transaction};
+use uuid::Uuid;
+
+/// The hash kept for an erased email. It is not keyed, so the tombstones
+/// outlive a rotation of the HMAC secret.
+pub fn email_hash(email: &str) -> String {
+    hex::encode(Sha256::digest(email.trim().to_lowercase().as_bytes()))
+}
+
+/// Every subscriber with this email, whatever its case, is erased, the
+/// duplicates waiting for a merge included. Returns `false` if there was
+/// none, the tombstone is kept all the same.
+#[tracing::instrument(name = "Erase a subscriber", skip(transaction, email))]
+pub async fn erase_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &SubscriberEmail,
+) -> Result<bool, anyhow::Error> {
+    let email = email.normalized();
+    // The duplicates waiting for a merge have no normalized email yet.
+    let subscriber_ids: Vec<Uuid> = sqlx::query!(
+        r#"SELECT id FROM subscriptions
+        WHERE normalized_email = $1
+            OR (normalized_email IS NULL AND (
+                lower(email) = $1
+                OR id IN (SELECT duplicate_id FROM subscriber_duplicates WHERE normalized_email = $1)
+            ))
+        FOR UPDATE"#,
+        email
+    )
+    .fetch_all(&mut **transaction)
+    .await
+    .context("Failed to fetch the subscriber")?
+    .into_iter()
+    .map(|r| r.id)
+    .collect();
+    // The deliveries are kept for the statistics of the issues, under an
+    // address that cannot be traced back.
+    sqlx::query!(
+        r#"UPDATE issue_deliveries
+        SET subscriber_email = 'erased:' || gen_random_uuid(), subscriber_id = NULL
+        WHERE subscriber_id = ANY($1) OR lower(subscriber_email) = $2"#,
+        &subscriber_ids,
+        email
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to anonymize the deliveries")?;
+    sqlx::query!(
+        r#"DELETE FROM issue_delivery_queue WHERE lower(subscriber_email) = $1"#,
+        email
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to remove the queued deliveries")?;
+    // The admins' actions stay in the audit trail, without their details.
+    sqlx::query!(
+        r#"UPDATE audit_log
+        SET subscriber_email = NULL, details = 'Erased at the request of the subscriber.'
+        WHERE subscriber_id = ANY($1) OR lower(subscriber_email) = $2"#,
+        &subscriber_ids,
+        email
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to redact the audit trail")?;
+    sqlx::query!(
+        r#"DELETE FROM subscriber_import_errors WHERE lower(email) = $1"#,
+        email
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to remove the import errors")?;
+    // The merged duplicates are kept with the address they had.
+    sqlx::query!(
+        r#"DELETE FROM subscriber_duplicates
+        WHERE normalized_email = $1 OR duplicate_id = ANY($2) OR kept_id = ANY($2)"#,
+        email,
+        &subscriber_ids
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to remove the duplicates")?;
+    sqlx::query!(
+        r#"DELETE FROM subscriptions_tokens WHERE subscriptions_id = ANY($1)"#,
+        &subscriber_ids
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to delete the confirmation links")?;
+    // The lists, tags and attributes go with them.
+    sqlx::query!(
+        r#"DELETE FROM subscriptions WHERE id = ANY($1)"#,
+        &subscriber_ids
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to delete the subscriber")?;
+    sqlx::query!(
+        r#"INSERT INTO erased_subscribers (email_hash, erased_at) VALUES ($1, $2)
+        ON CONFLICT (email_hash) DO UPDATE SET erased_at = EXCLUDED.erased_at"#,
+        email_hash(&email),
+        Utc::now()
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to keep the tombstone")?;
+    Ok(!subscriber_ids.is_empty())
+}
+
+pub async fn is_erased(executor: impl PgExecutor<'_>, email: &str) -> Result<bool, anyhow::Error> {
+    let erased = sqlx::query!(
+        r#"SELECT EXISTS (SELECT 1 FROM erased_subscribers WHERE email_hash = $1) AS "erased!""#,
+        email_hash(email)
+    )
+    .fetch_one(executor)
+    .await
+    .context("Failed to look for a tombstone")?;
+    Ok(erased.erased)
+}
+
+/// The emails among `emails` that were erased.
+pub async fn erased_emails(
+    transaction: &mut Transaction<'_, Postgres>,
+    emails: &[String],
+) -> Result<Vec<String>, anyhow::Error> {
+    let hashes: Vec<String> = emails.iter().map(|email| email_hash(email)).collect();
+    let erased: Vec<String> = sqlx::query!(
+        r#"SELECT email_hash FROM erased_subscribers WHERE email_hash = ANY($1)"#,
+        &hashes
+    )
+    .fetch_all(&mut **transaction)
+    .await
+    .context("Failed to look for the tombstones")?
+    .into_iter()
+    .map(|r| r.email_hash)
+    .collect();
+    Ok(emails
+        .iter()
+        .zip(hashes)
+        .filter(|(_, hash)| erased.contains(hash))
+        .map(|(email, _)| email.clone())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::email_hash;
+
+    #[test]
+    fn the_hash_ignores_case_and_surrounding_spaces() {
+        assert_eq!(
+            email_hash(" Ursula@Example.com "),
+            email_hash("ursula@example.com")
+        );
+    }
+
+    #[test]
+    fn the_hash_does_not_contain_the_email() {
+        let hash = email_hash("ursula@example.com");
+        assert_eq!(hash.len(), 64);
+        assert!(!hash.contains("ursula"));
+    }
+}
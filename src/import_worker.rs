@@ -0,0 +1,282 @@
+use crate::domain::{NewSubscriber, SubscriberEmail, SuscriberName};
+use crate::email_client::EmailClient;
+use crate::erasure::erased_emails;
+use crate::issue_delivery_worker::ExecutionOutcome;
+use crate::routes::subscriptions::{
+    generate_random_token, insert_membership, insert_suscriber, send_email, store_token,
+};
+use crate::startup::ApplicationBaseUrl;
+use crate::subscriber_imports::{
+    ImportCounts, ImportError, ImportMode, PendingImport, dequeue_import, existing_emails,
+    fail_import, finish_import, insert_import_error,
+};
+use crate::subscribers::insert_confirmed_membership;
+use actix_web::web;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tracing::{Span, field::display};
+
+pub async fn run_import_worker_until_stopped(
+    pool: PgPool,
+    email_client: EmailClient,
+    base_url: String,
+) {
+    let base_url = web::Data::new(ApplicationBaseUrl(base_url));
+    loop {
+        match try_execute_import(&pool, &email_client, &base_url).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                actix_web::rt::time::sleep(Duration::from_secs(10)).await;
+            }
+            Err(_) => {
+                actix_web::rt::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+}
+
+/// Import one pending file. The confirmation emails are sent once the
+/// subscribers are committed.
+#[tracing::instrument(skip_all, fields(import_id=tracing::field::Empty), err)]
+pub async fn try_execute_import(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &web::Data<ApplicationBaseUrl>,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let Some((transaction, import)) = dequeue_import(pool).await? else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    Span::current().record("import_id", display(import.id));
+    let confirmations = match import_rows(transaction, &import).await {
+        Ok(confirmations) => confirmations,
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, "Failed to import the subscribers");
+            fail_import(pool, import.id).await?;
+            return Ok(ExecutionOutcome::TaskCompleted);
+        }
+    };
+    for (line, subscriber, token) in confirmations {
+        let email = subscriber.email.as_ref().to_string();
+        if let Err(e) = send_email(
+            pool,
+            email_client,
+            subscriber,
+            &import.list,
+            base_url.clone(),
+            &token,
+        )
+        .await
+        {
+            // The subscriber is imported, the link can be sent again later.
+            tracing::warn!(error.cause_chain = ?e, "Failed to send the confirmation email");
+            let error = ImportError {
+                line,
+                email,
+                error: "Imported, but the confirmation email could not be sent.".to_string(),
+            };
+            insert_import_error(pool, import.id, &error).await?;
+        }
+    }
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+/// Returns the confirmation emails to send, with the line of their row.
+async fn import_rows(
+    mut transaction: Transaction<'static, Postgres>,
+    import: &PendingImport,
+) -> Result<Vec<(i32, NewSubscriber, String)>, anyhow::Error> {
+    let mut rows = parse_rows(&import.content);
+    let emails: Vec<String> = rows
+        .valid
+        .iter()
+        .map(|(_, subscriber)| subscriber.email.normalized())
+        .collect();
+    let existing: HashSet<String> = existing_emails(&mut transaction, &emails)
+        .await?
+        .into_iter()
+        .collect();
+    let erased: HashSet<String> = erased_emails(&mut transaction, &emails)
+        .await?
+        .into_iter()
+        .collect();
+    let mut duplicates = rows.duplicates;
+    let mut confirmations = Vec::new();
+    let mut n_imported = 0;
+    for (line, subscriber) in rows.valid {
+        if existing.contains(&subscriber.email.normalized()) {
+            duplicates.push(ImportError {
+                line,
+                email: subscriber.email.as_ref().to_string(),
+                error: "Already a subscriber.".to_string(),
+            });
+            continue;
+        }
+        if erased.contains(&subscriber.email.normalized()) {
+            rows.errors.push(ImportError {
+                line,
+                email: subscriber.email.as_ref().to_string(),
+                error: "Erased at their request.".to_string(),
+            });
+            continue;
+        }
+        let (subscriber_id, _) = insert_suscriber(&mut transaction, &subscriber).await?;
+        match import.mode {
+            ImportMode::Confirmed => {
+                insert_confirmed_membership(&mut transaction, subscriber_id, import.list.id)
+                    .await?;
+            }
+            ImportMode::SendConfirmation => {
+                insert_membership(&mut transaction, subscriber_id, import.list.id).await?;
+                let token = generate_random_token();
+                store_token(&mut transaction, subscriber_id, import.list.id, &token).await?;
+                confirmations.push((line, subscriber, token));
+            }
+        }
+        n_imported += 1;
+    }
+    for error in duplicates.iter().chain(&rows.errors) {
+        insert_import_error(&mut *transaction, import.id, error).await?;
+    }
+    let counts = ImportCounts {
+        n_rows: rows.n_rows,
+        n_imported,
+        n_duplicates: duplicates.len() as i32,
+        n_errors: rows.errors.len() as i32,
+    };
+    finish_import(transaction, import.id, &counts).await?;
+    Ok(confirmations)
+}
+
+/// The rows of a file, before looking at the existing subscribers.
+struct ParsedRows {
+    n_rows: i32,
+    valid: Vec<(i32, NewSubscriber)>,
+    /// The rows whose email is on an earlier row.
+    duplicates: Vec<ImportError>,
+    errors: Vec<ImportError>,
+}
+
+/// The first line names the columns, `email` and `name` are required and the
+/// other columns are ignored.
+fn parse_rows(content: &str) -> ParsedRows {
+    let mut rows = ParsedRows {
+        n_rows: 0,
+        valid: Vec::new(),
+        duplicates: Vec::new(),
+        errors: Vec::new(),
+    };
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(content.trim_start_matches('\u{feff}').as_bytes());
+    let columns = reader.headers().map(|headers| {
+        let find = |name: &str| {
+            headers
+                .iter()
+                .position(|header| header.eq_ignore_ascii_case(name))
+        };
+        (find("email"), find("name"))
+    });
+    let (email_column, name_column) = match columns {
+        Ok((Some(email_column), Some(name_column))) => (email_column, name_column),
+        _ => {
+            rows.errors.push(ImportError {
+                line: 1,
+                email: String::new(),
+                error: "The first line must name an email and a name column.".to_string(),
+            });
+            return rows;
+        }
+    };
+    let mut first_lines: HashMap<String, i32> = HashMap::new();
+    for record in reader.records() {
+        rows.n_rows += 1;
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e
+                    .position()
+                    .map(|position| position.line() as i32)
+                    .unwrap_or(rows.n_rows + 1);
+                rows.errors.push(ImportError {
+                    line,
+                    email: String::new(),
+                    error: format!("The row cannot be read: {}", e),
+                });
+                continue;
+            }
+        };
+        let line = record
+            .position()
+            .map(|position| position.line() as i32)
+            .unwrap_or(rows.n_rows + 1);
+        let email = record.get(email_column).unwrap_or_default().to_string();
+        let name = record.get(name_column).unwrap_or_default().to_string();
+        let subscriber = SubscriberEmail::parse(email.clone()).and_then(|parsed_email| {
+            Ok(NewSubscriber {
+                email: parsed_email,
+                name: SuscriberName::parse(name)?,
+            })
+        });
+        match subscriber {
+            Err(e) => rows.errors.push(ImportError {
+                line,
+                email,
+                error: e,
+            }),
+            Ok(subscriber) => match first_lines.get(&subscriber.email.normalized()) {
+                Some(first_line) => rows.duplicates.push(ImportError {
+                    line,
+                    email,
+                    error: format!("Duplicate of line {}.", first_line),
+                }),
+                None => {
+                    first_lines.insert(subscriber.email.normalized(), line);
+                    rows.valid.push((line, subscriber));
+                }
+            },
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_rows;
+
+    #[test]
+    fn rows_are_validated_and_deduplicated() {
+        let content = "Name,Email,Company\n\
+            Ursula Le Guin,ursula@example.com,Earthsea\n\
+            ,octavia@example.com,\n\
+            Ursula again,Ursula@Example.com,\n\
+            Iain Banks,not-an-email\n";
+        let rows = parse_rows(content);
+        assert_eq!(rows.n_rows, 4);
+        let valid: Vec<(i32, &str)> = rows
+            .valid
+            .iter()
+            .map(|(line, subscriber)| (*line, subscriber.email.as_ref()))
+            .collect();
+        assert_eq!(valid, [(2, "ursula@example.com")]);
+        assert_eq!(rows.duplicates.len(), 1);
+        assert_eq!(rows.duplicates[0].line, 4);
+        assert_eq!(rows.duplicates[0].error, "Duplicate of line 2.");
+        let errors: Vec<i32> = rows.errors.iter().map(|error| error.line).collect();
+        assert_eq!(errors, [3, 5]);
+    }
+
+    #[test]
+    fn a_file_without_the_required_columns_is_rejected() {
+        let rows = parse_rows("email,company\nursula@example.com,Earthsea\n");
+        assert_eq!(rows.n_rows, 0);
+        assert_eq!(rows.errors[0].line, 1);
+    }
+
+    #[test]
+    fn a_byte_order_mark_is_ignored() {
+        let rows = parse_rows("\u{feff}email,name\nursula@example.com,Ursula\n");
+        assert_eq!(rows.valid.len(), 1);
+    }
+}
@@ -0,0 +1,271 @@
+use crate::attributes::get_recipient_attributes;
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::issue_rendering::{IssueRenderer, Recipient};
+use crate::mailing_lists::get_recipient_list;
+use crate::newsletter_issues::get_issue;
+use crate::templates::get_email_layout;
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::time::Duration;
+use tracing::{Span, field::display};
+use uuid::Uuid;
+
+/// A delivery task is given up after this number of failed attempts.
+const MAX_RETRIES: i16 = 5;
+
+pub enum ExecutionOutcome {
+    TaskCompleted,
+    EmptyQueue,
+}
+
+pub async fn run_worker_until_stopped(
+    pool: PgPool,
+    email_client: EmailClient,
+    renderer: IssueRenderer,
+) {
+    loop {
+        match try_execute_task(&pool, &email_client, &renderer).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                actix_web::rt::time::sleep(Duration::from_secs(10)).await;
+            }
+            Err(_) => {
+                actix_web::rt::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(newsletter_issue_id=tracing::field::Empty, subscriber_email=tracing::field::Empty),
+    err
+)]
+pub async fn try_execute_task(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    renderer: &IssueRenderer,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let Some((mut transaction, task)) = dequeue_task(pool).await? else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    Span::current()
+        .record("newsletter_issue_id", display(task.issue_id))
+        .record("subscriber_email", display(&task.subscriber_email));
+
+    let outcome = match SubscriberEmail::parse(task.subscriber_email.clone()) {
+        Ok(email) => {
+            let issue = get_issue(pool, task.issue_id)
+                .await?
+                .context("The newsletter issue of the task does not exist")?;
+            let layout = get_email_layout(pool, issue.template_id).await?;
+            let name = get_subscriber_name(pool, email.as_ref()).await?;
+            let attributes = get_recipient_attributes(pool, email.as_ref()).await?;
+            let list_id = get_recipient_list(pool, task.issue_id, email.as_ref()).await?;
+            let recipient = Recipient {
+                email,
+                name,
+                attributes,
+                list_id,
+            };
+            match renderer.render(&issue, layout.as_ref(), &recipient) {
+                Ok(rendered) => {
+                    if let Err(e) = email_client
+                        .send_email(
+                            &recipient.email,
+                            &rendered.subject,
+                            &rendered.html,
+                            &rendered.text,
+                        )
+                        .await
+                    {
+                        if task.n_retries + 1 < MAX_RETRIES {
+                            tracing::warn!(
+                                error.cause_chain = ?e,
+                                "Failed to deliver issue to a confirmed subscriber. Retrying later.",
+                            );
+                            postpone_task(transaction, &task).await?;
+                            return Ok(ExecutionOutcome::TaskCompleted);
+                        }
+                        tracing::error!(
+                            error.cause_chain = ?e,
+                            "Failed to deliver issue to a confirmed subscriber. Giving up.",
+                        );
+                        DeliveryOutcome::Failed
+                    } else {
+                        DeliveryOutcome::Sent
+                    }
+                }
+                Err(error) => {
+                    tracing::error!(
+                        error.cause_chain = ?error,
+                        "Skipping a confirmed subscriber. The issue cannot be rendered",
+                    );
+                    DeliveryOutcome::Skipped
+                }
+            }
+        }
+        Err(error) => {
+            tracing::error!(
+                error.cause_chain = ?error,
+                "Skipping a confirmed subscriber. \
+                Their stored contact details are invalid",
+            );
+            DeliveryOutcome::Skipped
+        }
+    };
+    record_delivery(&mut transaction, &task, outcome).await?;
+    delete_task(&mut transaction, &task).await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the transaction into the database")?;
+    mark_issue_as_sent_if_done(pool, task.issue_id).await?;
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+/// What became of a delivery task, kept in the delivery history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryOutcome {
+    Sent,
+    /// Given up after `MAX_RETRIES` attempts.
+    Failed,
+    /// The email could not be rendered or the address is invalid.
+    Skipped,
+}
+
+impl DeliveryOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryOutcome::Sent => "sent",
+            DeliveryOutcome::Failed => "failed",
+            DeliveryOutcome::Skipped => "skipped",
+        }
+    }
+}
+
+struct DeliveryTask {
+    issue_id: Uuid,
+    subscriber_email: String,
+    n_retries: i16,
+}
+
+#[tracing::instrument(skip_all)]
+async fn dequeue_task(
+    pool: &PgPool,
+) -> Result<Option<(Transaction<'static, Postgres>, DeliveryTask)>, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a connection from the pool")?;
+    let task = sqlx::query_as!(
+        DeliveryTask,
+        r#"SELECT newsletter_issue_id AS issue_id, subscriber_email, n_retries
+        FROM issue_delivery_queue
+        WHERE execute_after <= now()
+        FOR UPDATE
+        SKIP LOCKED
+        LIMIT 1"#,
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to dequeue a delivery task")?;
+    Ok(task.map(|task| (transaction, task)))
+}
+
+#[tracing::instrument(skip_all)]
+async fn delete_task(
+    transaction: &mut Transaction<'static, Postgres>,
+    task: &DeliveryTask,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"DELETE FROM issue_delivery_queue
+        WHERE newsletter_issue_id = $1 AND subscriber_email = $2"#,
+        task.issue_id,
+        task.subscriber_email
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to delete the delivery task")?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn record_delivery(
+    transaction: &mut Transaction<'static, Postgres>,
+    task: &DeliveryTask,
+    outcome: DeliveryOutcome,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"INSERT INTO issue_deliveries
+            (newsletter_issue_id, subscriber_email, subscriber_id, outcome, n_attempts, delivered_at)
+        VALUES ($1, $2, (SELECT id FROM subscriptions WHERE email = $2), $3, $4, $5)
+        ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+        SET outcome = EXCLUDED.outcome,
+            n_attempts = EXCLUDED.n_attempts,
+            delivered_at = EXCLUDED.delivered_at"#,
+        task.issue_id,
+        task.subscriber_email,
+        outcome.as_str(),
+        task.n_retries + 1,
+        Utc::now()
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to record the delivery")?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn postpone_task(
+    mut transaction: Transaction<'static, Postgres>,
+    task: &DeliveryTask,
+) -> Result<(), anyhow::Error> {
+    // Exponential backoff, starting at 30 seconds.
+    let delay = chrono::Duration::seconds(30 * 2_i64.pow(task.n_retries as u32));
+    sqlx::query!(
+        r#"UPDATE issue_delivery_queue
+        SET n_retries = n_retries + 1, execute_after = $1
+        WHERE newsletter_issue_id = $2 AND subscriber_email = $3"#,
+        Utc::now() + delay,
+        task.issue_id,
+        task.subscriber_email
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to postpone the delivery task")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the transaction into the database")?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_subscriber_name(pool: &PgPool, email: &str) -> Result<String, anyhow::Error> {
+    let subscriber = sqlx::query!(r#"SELECT name FROM subscriptions WHERE email = $1"#, email)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch the name of the subscriber")?;
+    Ok(subscriber.map(|s| s.name).unwrap_or_default())
+}
+
+#[tracing::instrument(skip(pool))]
+async fn mark_issue_as_sent_if_done(pool: &PgPool, issue_id: Uuid) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"UPDATE newsletter_issues SET status = 'sent', updated_at = $1
+        WHERE newsletter_issue_id = $2
+            AND status = 'sending'
+            AND NOT EXISTS (
+                SELECT 1 FROM issue_delivery_queue WHERE newsletter_issue_id = $2
+            )"#,
+        Utc::now(),
+        issue_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to mark the newsletter issue as sent")?;
+    Ok(())
+}
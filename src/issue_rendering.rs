@@ -0,0 +1,158 @@
+use crate::attributes::{AttributeDefinition, issue_variables};
+use crate::domain::SubscriberEmail;
+use crate::merge_tags::{ISSUE_VARIABLES, MergeValues, Template, TemplateError};
+use crate::newsletter_issues::NewsletterIssue;
+use crate::signature::{sign, verify};
+use crate::startup::HmacSecret;
+use crate::templates::{Layout, render_in_layout};
+use crate::utils::escape_html;
+use reqwest::Url;
+use uuid::Uuid;
+
+/// Who an issue is rendered for.
+pub struct Recipient {
+    pub email: SubscriberEmail,
+    pub name: String,
+    /// Every attribute with the value of the recipient, empty if they have
+    /// none.
+    pub attributes: Vec<(String, String)>,
+    /// The list the issue reaches them through, their unsubscribe link leaves
+    /// this one only. `None` if they are not a member of any of its lists.
+    pub list_id: Option<Uuid>,
+}
+
+/// An issue as it is handed over to the `EmailClient`.
+pub struct RenderedIssue {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+/// Turns an issue into the email a given recipient receives. Both the delivery
+/// worker and the test sends go through it, so they cannot drift apart.
+#[derive(Clone)]
+pub struct IssueRenderer {
+    base_url: String,
+    hmac_secret: HmacSecret,
+}
+
+impl IssueRenderer {
+    pub fn new(base_url: String, hmac_secret: HmacSecret) -> IssueRenderer {
+        IssueRenderer {
+            base_url,
+            hmac_secret,
+        }
+    }
+
+    /// `layout` is the layout chosen for the issue, if any.
+    pub fn render(
+        &self,
+        issue: &NewsletterIssue,
+        layout: Option<&Layout>,
+        recipient: &Recipient,
+    ) -> Result<RenderedIssue, TemplateError> {
+        let unsubscribe_url = self.unsubscribe_url(&recipient.email, recipient.list_id);
+        let mut values = MergeValues(vec![
+            ("name", &recipient.name),
+            ("email", recipient.email.as_ref()),
+            ("unsubscribe_url", &unsubscribe_url),
+        ]);
+        let mut variables = ISSUE_VARIABLES.to_vec();
+        for (key, value) in &recipient.attributes {
+            values.0.push((key, value));
+            variables.push(key);
+        }
+        let archive_url = self.archive_url(issue);
+        let html = format!(
+            "{}\n<hr>\n<p>This email was sent to {} ({}). \
+            <a href=\"{}\">Unsubscribe</a>. <a href=\"{}\">View in browser</a>.</p>",
+            Template::parse(&issue.html_content, &variables)?.render(&values, true),
+            escape_html(&recipient.name),
+            escape_html(recipient.email.as_ref()),
+            escape_html(&unsubscribe_url),
+            escape_html(&archive_url),
+        );
+        let text = format!(
+            "{}\n\n--\nThis email was sent to {} ({}).\nUnsubscribe: {}\nView in browser: {}\n",
+            Template::parse(&issue.text_content, &variables)?.render(&values, false),
+            recipient.name,
+            recipient.email.as_ref(),
+            unsubscribe_url,
+            archive_url,
+        );
+        let (html, text) = render_in_layout(layout, html, text, &values)?;
+        Ok(RenderedIssue {
+            subject: issue.title.clone(),
+            html,
+            text,
+        })
+    }
+
+    /// Without a list, the link leaves every list.
+    pub fn unsubscribe_url(&self, email: &SubscriberEmail, list_id: Option<Uuid>) -> String {
+        let signature = sign(
+            &self.hmac_secret,
+            &unsubscribe_payload(email.as_ref(), list_id),
+        );
+        let list_id = list_id.map(|id| id.to_string());
+        let mut parameters = vec![("email", email.as_ref())];
+        if let Some(list_id) = &list_id {
+            parameters.push(("list", list_id));
+        }
+        parameters.push(("signature", &signature));
+        Url::parse_with_params(&format!("{}/unsubscribe", self.base_url), &parameters)
+            .expect("The base url is not a valid url")
+            .to_string()
+    }
+
+    /// The link is signed so that it also works for private issues, and for
+    /// the tests of issues that are not sent yet.
+    pub fn archive_url(&self, issue: &NewsletterIssue) -> String {
+        let signature = sign(&self.hmac_secret, &archive_payload(&issue.slug));
+        Url::parse_with_params(
+            &format!("{}/archive/{}", self.base_url, issue.slug),
+            &[("signature", signature.as_str())],
+        )
+        .expect("The base url is not a valid url")
+        .to_string()
+    }
+}
+
+/// The HTML content of an issue as it is shown in the public archive, without
+/// the details of any recipient.
+pub fn render_for_archive(
+    issue: &NewsletterIssue,
+    attributes: &[AttributeDefinition],
+) -> Result<String, TemplateError> {
+    Ok(
+        Template::parse(&issue.html_content, &issue_variables(attributes))?
+            .render(&MergeValues(vec![]), true),
+    )
+}
+
+pub fn verify_archive_signature(hmac_secret: &HmacSecret, slug: &str, signature: &str) -> bool {
+    verify(hmac_secret, &archive_payload(slug), signature)
+}
+
+fn archive_payload(slug: &str) -> String {
+    format!("archive:{slug}")
+}
+
+/// Unsubscribe links do not expire, they have to keep working in old issues.
+/// Following one only shows a confirmation.
+pub fn verify_unsubscribe_signature(
+    hmac_secret: &HmacSecret,
+    email: &str,
+    list_id: Option<Uuid>,
+    signature: &str,
+) -> bool {
+    verify(hmac_secret, &unsubscribe_payload(email, list_id), signature)
+}
+
+/// The links sent before the lists existed are signed for the address only.
+fn unsubscribe_payload(email: &str, list_id: Option<Uuid>) -> String {
+    match list_id {
+        Some(list_id) => format!("unsubscribe:{email}:{list_id}"),
+        None => format!("unsubscribe:{email}"),
+    }
+}
@@ -0,0 +1,30 @@
+use crate::issue_delivery_worker::ExecutionOutcome;
+use crate::newsletter_issues::start_due_issue;
+use sqlx::PgPool;
+use std::time::Duration;
+
+pub async fn run_scheduler_until_stopped(pool: PgPool) {
+    loop {
+        match try_start_due_issue(&pool).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                actix_web::rt::time::sleep(Duration::from_secs(10)).await;
+            }
+            Err(_) => {
+                actix_web::rt::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+}
+
+/// Start the fan-out of one scheduled issue whose `send_at` has passed.
+#[tracing::instrument(skip_all, err)]
+pub async fn try_start_due_issue(pool: &PgPool) -> Result<ExecutionOutcome, anyhow::Error> {
+    match start_due_issue(pool).await? {
+        Some(issue_id) => {
+            tracing::info!(%issue_id, "Started sending a scheduled newsletter issue");
+            Ok(ExecutionOutcome::TaskCompleted)
+        }
+        None => Ok(ExecutionOutcome::EmptyQueue),
+    }
+}
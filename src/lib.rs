@@ -1,9 +1,33 @@
+pub mod attributes;
+pub mod audit;
 pub mod authentication;
+pub mod bot_protection;
 pub mod configuration;
+pub mod consent;
 pub mod domain;
 pub mod email_client;
+pub mod email_domains;
+pub mod email_html;
+pub mod erasure;
+pub mod import_worker;
+pub mod issue_delivery_worker;
+pub mod issue_rendering;
+pub mod issue_scheduler;
+pub mod mailing_lists;
+pub mod markdown;
+pub mod merge_tags;
+pub mod newsletter_issues;
 pub mod routes;
+pub mod rss_feeds;
+pub mod rss_poller;
+pub mod segments;
 pub mod session_state;
+pub mod signature;
 pub mod startup;
+pub mod subscriber_duplicates;
+pub mod subscriber_imports;
+pub mod subscribers;
+pub mod tags;
 pub mod telemetry;
+pub mod templates;
 pub mod utils;
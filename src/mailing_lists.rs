@@ -0,0 +1,147 @@
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// One of the publications people subscribe to.
+pub struct MailingList {
+    pub id: Uuid,
+    pub name: String,
+    /// Names the list in the public subscribe form.
+    pub slug: String,
+    pub description: String,
+    /// Subscriptions and issues that do not name a list go to this one.
+    pub is_default: bool,
+}
+
+/// The default list comes first.
+pub async fn list_lists(pool: &PgPool) -> Result<Vec<MailingList>, anyhow::Error> {
+    let lists = sqlx::query_as!(
+        MailingList,
+        r#"SELECT list_id AS id, name, slug, description, is_default
+        FROM lists
+        ORDER BY is_default DESC, name"#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the lists")?;
+    Ok(lists)
+}
+
+/// An empty slug stands for the default list.
+#[tracing::instrument(name = "Get a list", skip(pool))]
+pub async fn get_list_by_slug(
+    pool: &PgPool,
+    slug: &str,
+) -> Result<Option<MailingList>, anyhow::Error> {
+    let list = sqlx::query_as!(
+        MailingList,
+        r#"SELECT list_id AS id, name, slug, description, is_default
+        FROM lists
+        WHERE CASE WHEN $1 = '' THEN is_default ELSE slug = $1 END"#,
+        slug
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the list")?;
+    Ok(list)
+}
+
+pub async fn get_list(pool: &PgPool, list_id: Uuid) -> Result<Option<MailingList>, anyhow::Error> {
+    let list = sqlx::query_as!(
+        MailingList,
+        r#"SELECT list_id AS id, name, slug, description, is_default
+        FROM lists
+        WHERE list_id = $1"#,
+        list_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the list")?;
+    Ok(list)
+}
+
+/// Returns `None` if the name or the slug is taken by another list.
+#[tracing::instrument(name = "Insert a list", skip(pool, description))]
+pub async fn insert_list(
+    pool: &PgPool,
+    name: &str,
+    slug: &str,
+    description: &str,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let list = sqlx::query!(
+        r#"INSERT INTO lists (list_id, name, slug, description, created_at)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT DO NOTHING
+        RETURNING list_id"#,
+        Uuid::new_v4(),
+        name,
+        slug,
+        description,
+        Utc::now()
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to insert the list")?;
+    Ok(list.map(|l| l.list_id))
+}
+
+/// The number of confirmed members of each list.
+pub async fn count_confirmed_members(pool: &PgPool) -> Result<Vec<(Uuid, i64)>, anyhow::Error> {
+    let counts = sqlx::query!(
+        r#"SELECT list_id, COUNT(*) AS "count!"
+        FROM list_memberships
+        WHERE status = 'confirmed'
+        GROUP BY list_id"#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to count the members of the lists")?;
+    Ok(counts.into_iter().map(|r| (r.list_id, r.count)).collect())
+}
+
+/// The lists an issue is sent to.
+pub async fn get_issue_lists(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<Vec<MailingList>, anyhow::Error> {
+    let lists = sqlx::query_as!(
+        MailingList,
+        r#"SELECT l.list_id AS id, l.name, l.slug, l.description, l.is_default
+        FROM lists l
+        JOIN newsletter_issue_lists i ON i.list_id = l.list_id
+        WHERE i.newsletter_issue_id = $1
+        ORDER BY l.is_default DESC, l.name"#,
+        issue_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the lists of the newsletter issue")?;
+    Ok(lists)
+}
+
+/// The list of the issue a subscriber receives it through. A member of several
+/// of them gets it once, through the default list or the oldest one. The
+/// email is the one stored, an unmerged duplicate has its own lists.
+pub async fn get_recipient_list(
+    pool: &PgPool,
+    issue_id: Uuid,
+    email: &str,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let list = sqlx::query!(
+        r#"SELECT l.list_id
+        FROM lists l
+        JOIN newsletter_issue_lists i ON i.list_id = l.list_id
+        JOIN list_memberships m ON m.list_id = l.list_id
+        JOIN subscriptions s ON s.id = m.subscriber_id
+        WHERE i.newsletter_issue_id = $1 AND s.email = $2 AND m.status = 'confirmed'
+        ORDER BY l.is_default DESC, l.created_at
+        LIMIT 1"#,
+        issue_id,
+        email
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the list of the recipient")?;
+    Ok(list.map(|l| l.list_id))
+}
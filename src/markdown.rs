@@ -0,0 +1,149 @@
+use crate::merge_tags::{protect_merge_tags, restore_merge_tags};
+use crate::templates::CONTENT_SLOT;
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+pub struct RenderedMarkdown {
+    pub html: String,
+    pub text: String,
+}
+
+/// Render markdown to sanitized HTML placed inside `layout`, along with a
+/// plain-text version for the mail clients that do not display HTML. Merge
+/// tags are kept as they are, to be rendered for each recipient.
+pub fn render_markdown(markdown: &str, layout: &str) -> RenderedMarkdown {
+    let (markdown, merge_tags) = protect_merge_tags(markdown);
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, parser(&markdown));
+    let html = ammonia::clean(&html);
+    let text = to_plain_text(&markdown);
+    RenderedMarkdown {
+        html: layout.replace(CONTENT_SLOT, &restore_merge_tags(&html, &merge_tags)),
+        text: restore_merge_tags(&text, &merge_tags),
+    }
+}
+
+fn parser(markdown: &str) -> Parser<'_> {
+    Parser::new_ext(
+        markdown,
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH,
+    )
+}
+
+fn to_plain_text(markdown: &str) -> String {
+    let mut text = String::new();
+    // The next number of each enclosing list, `None` for bullet lists.
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    let mut links: Vec<String> = Vec::new();
+    for event in parser(markdown) {
+        match event {
+            Event::Text(t) | Event::Code(t) => text.push_str(&t),
+            Event::SoftBreak | Event::HardBreak => text.push('\n'),
+            Event::Rule => text.push_str("---\n\n"),
+            Event::Start(Tag::List(start)) => {
+                if !lists.is_empty() && !text.ends_with('\n') {
+                    text.push('\n');
+                }
+                lists.push(start);
+            }
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+                if lists.is_empty() {
+                    text.push('\n');
+                }
+            }
+            Event::Start(Tag::Item) => {
+                text.push_str(&"  ".repeat(lists.len().saturating_sub(1)));
+                match lists.last_mut() {
+                    Some(Some(n)) => {
+                        text.push_str(&format!("{}. ", n));
+                        *n += 1;
+                    }
+                    _ => text.push_str("- "),
+                }
+            }
+            Event::End(TagEnd::Item) if !text.ends_with('\n') => text.push('\n'),
+            Event::Start(Tag::Link { dest_url, .. })
+            | Event::Start(Tag::Image { dest_url, .. }) => {
+                links.push(dest_url.to_string());
+            }
+            Event::End(TagEnd::Link) | Event::End(TagEnd::Image) => {
+                if let Some(url) = links.pop()
+                    && !text.ends_with(&url)
+                {
+                    text.push_str(&format!(" ({})", url));
+                }
+            }
+            Event::End(TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::CodeBlock) => {
+                // Paragraphs of list items stay tight.
+                if lists.is_empty() {
+                    text.push_str("\n\n");
+                } else if !text.ends_with('\n') {
+                    text.push('\n');
+                }
+            }
+            Event::End(TagEnd::TableCell) => text.push('\t'),
+            Event::End(TagEnd::TableHead | TagEnd::TableRow) => text.push('\n'),
+            Event::End(TagEnd::Table) => text.push('\n'),
+            _ => {}
+        }
+    }
+    let mut text = text.trim_end().to_string();
+    text.push('\n');
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_markdown;
+
+    const LAYOUT: &str = "<div>{{ content }}</div>";
+
+    #[test]
+    fn markdown_is_rendered_inside_the_layout() {
+        let rendered = render_markdown("# Title\n\nSome *text*.", LAYOUT);
+        assert_eq!(
+            rendered.html,
+            "<div><h1>Title</h1>\n<p>Some <em>text</em>.</p>\n</div>"
+        );
+    }
+
+    #[test]
+    fn dangerous_html_is_removed() {
+        let rendered = render_markdown(
+            "Hello <script>alert(1)</script><img src=x onerror=alert(1)>",
+            LAYOUT,
+        );
+        assert!(!rendered.html.contains("script"));
+        assert!(!rendered.html.contains("onerror"));
+    }
+
+    #[test]
+    fn the_plain_text_version_is_readable() {
+        let rendered = render_markdown(
+            "# Title\n\nRead [the post](https://example.com).\n\n- one\n- two\n\n1. first\n2. second",
+            LAYOUT,
+        );
+        assert_eq!(
+            rendered.text,
+            "Title\n\nRead the post (https://example.com).\n\n- one\n- two\n\n1. first\n2. second\n"
+        );
+    }
+
+    #[test]
+    fn merge_tags_are_preserved() {
+        let rendered = render_markdown(
+            r#"Hi {{ name | default: "friend" }}, [unsubscribe]({{ unsubscribe_url }})"#,
+            LAYOUT,
+        );
+        assert!(
+            rendered
+                .html
+                .contains(r#"Hi {{ name | default: "friend" }}"#)
+        );
+        assert!(rendered.html.contains(r#"href="{{ unsubscribe_url }}""#));
+        assert_eq!(
+            rendered.text,
+            "Hi {{ name | default: \"friend\" }}, unsubscribe ({{ unsubscribe_url }})\n"
+        );
+    }
+}
@@ -0,0 +1,273 @@
+//! A tiny template language for emails: `{{ name }}` is replaced with the
+//! value of the `name` variable for each recipient, and
+//! `{{ name | default: "friend" }}` is used when the value is empty. Each kind
+//! of email has its own set of variables.
+
+use crate::utils::escape_html;
+
+/// The variables of newsletter issues.
+pub const ISSUE_VARIABLES: &[&str] = &["name", "email", "unsubscribe_url"];
+/// The variables of the subscription confirmation email.
+pub const CONFIRMATION_VARIABLES: &[&str] = &["name", "email", "confirmation_link", "list_name"];
+/// The variables of the email with the link to download one's data.
+pub const DATA_REQUEST_VARIABLES: &[&str] = &["name", "email", "download_link"];
+/// The variables of the email with the link to confirm an erasure.
+pub const ERASURE_REQUEST_VARIABLES: &[&str] = &["name", "email", "erasure_link"];
+/// The variables of the email with the link to reset the password of an
+/// admin, `name` is their username.
+pub const PASSWORD_RESET_VARIABLES: &[&str] = &["name", "email", "reset_link"];
+/// Layouts are shared by every kind of email, they only get what all of them
+/// have in common.
+pub const LAYOUT_VARIABLES: &[&str] = &["name", "email"];
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum TemplateError {
+    #[error("Unknown merge tag variable `{0}`.")]
+    UnknownVariable(String),
+    #[error("A merge tag is not closed with `}}}}`.")]
+    UnclosedTag,
+    #[error("Invalid merge tag `{{{{{0}}}}}`.")]
+    InvalidTag(String),
+}
+
+/// The values of the variables for one recipient, a missing variable renders
+/// as an empty value.
+pub struct MergeValues<'a>(pub Vec<(&'a str, &'a str)>);
+
+impl MergeValues<'_> {
+    fn get(&self, variable: &str) -> &str {
+        self.0
+            .iter()
+            .find(|(name, _)| *name == variable)
+            .map(|(_, value)| *value)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Tag {
+        variable: String,
+        default: Option<String>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Template(Vec<Part>);
+
+impl Template {
+    /// Parse a template in which only `variables` can be used.
+    pub fn parse(s: &str, variables: &[&str]) -> Result<Template, TemplateError> {
+        let mut parts = Vec::new();
+        let mut rest = s;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            let after_open = &rest[start + 2..];
+            let end = after_open.find("}}").ok_or(TemplateError::UnclosedTag)?;
+            parts.push(parse_tag(&after_open[..end], variables)?);
+            rest = &after_open[end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        Ok(Template(parts))
+    }
+
+    /// Render the template, values are escaped when `html` is set.
+    pub fn render(&self, values: &MergeValues, html: bool) -> String {
+        let mut rendered = String::new();
+        for part in &self.0 {
+            match part {
+                Part::Literal(literal) => rendered.push_str(literal),
+                Part::Tag { variable, default } => {
+                    let value = match (values.get(variable), default) {
+                        ("", Some(default)) => default.as_str(),
+                        (value, _) => value,
+                    };
+                    if html {
+                        rendered.push_str(&escape_html(value));
+                    } else {
+                        rendered.push_str(value);
+                    }
+                }
+            }
+        }
+        rendered
+    }
+}
+
+/// Check the merge tags of both versions of an email before it is stored.
+pub fn validate_merge_tags(
+    html: &str,
+    text: &str,
+    variables: &[&str],
+) -> Result<(), TemplateError> {
+    Template::parse(html, variables)?;
+    Template::parse(text, variables)?;
+    Ok(())
+}
+
+/// Merge tags would not survive HTML processing (they are percent-encoded in
+/// links, their quotes are escaped in attributes), they are swapped with
+/// placeholders in the meantime.
+pub fn protect_merge_tags(s: &str) -> (String, Vec<String>) {
+    let mut protected = String::with_capacity(s.len());
+    let mut merge_tags = Vec::new();
+    let mut rest = s;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        protected.push_str(&rest[..start]);
+        protected.push_str(&placeholder(merge_tags.len()));
+        merge_tags.push(rest[start..start + end + 2].to_string());
+        rest = &rest[start + end + 2..];
+    }
+    protected.push_str(rest);
+    (protected, merge_tags)
+}
+
+pub fn restore_merge_tags(s: &str, merge_tags: &[String]) -> String {
+    let mut restored = s.to_string();
+    for (i, tag) in merge_tags.iter().enumerate() {
+        restored = restored.replace(&placeholder(i), tag);
+    }
+    restored
+}
+
+fn placeholder(i: usize) -> String {
+    format!("zzmergetag{}zz", i)
+}
+
+fn parse_tag(tag: &str, variables: &[&str]) -> Result<Part, TemplateError> {
+    let invalid = || TemplateError::InvalidTag(tag.to_string());
+    let (variable, filter) = match tag.split_once('|') {
+        Some((variable, filter)) => (variable, Some(filter)),
+        None => (tag, None),
+    };
+    let variable = variable.trim();
+    if !variables.contains(&variable) {
+        return Err(TemplateError::UnknownVariable(variable.to_string()));
+    }
+    let variable = variable.to_string();
+    let default = match filter {
+        None => None,
+        Some(filter) => {
+            let argument = filter
+                .trim()
+                .strip_prefix("default")
+                .and_then(|f| f.trim_start().strip_prefix(':'))
+                .ok_or_else(invalid)?
+                .trim();
+            let quoted = argument
+                .strip_prefix('"')
+                .and_then(|a| a.strip_suffix('"'))
+                .or_else(|| {
+                    argument
+                        .strip_prefix('\'')
+                        .and_then(|a| a.strip_suffix('\''))
+                })
+                .ok_or_else(invalid)?;
+            Some(quoted.to_string())
+        }
+    };
+    Ok(Part::Tag { variable, default })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ISSUE_VARIABLES, MergeValues, Template, TemplateError};
+    use claim::{assert_err, assert_ok};
+
+    fn values(name: &str) -> MergeValues<'_> {
+        MergeValues(vec![
+            ("name", name),
+            ("email", "ursula@example.com"),
+            (
+                "unsubscribe_url",
+                "https://example.com/unsubscribe?email=ursula",
+            ),
+        ])
+    }
+
+    fn parse(template: &str) -> Result<Template, TemplateError> {
+        Template::parse(template, ISSUE_VARIABLES)
+    }
+
+    fn render(template: &str, name: &str, html: bool) -> String {
+        parse(template).unwrap().render(&values(name), html)
+    }
+
+    #[test]
+    fn text_without_tags_is_left_untouched() {
+        assert_eq!(
+            render("Hello { world }", "Ursula", false),
+            "Hello { world }"
+        );
+    }
+
+    #[test]
+    fn variables_are_replaced() {
+        assert_eq!(
+            render(
+                "Hi {{name}}, {{ email }}: {{ unsubscribe_url }}",
+                "Ursula",
+                false
+            ),
+            "Hi Ursula, ursula@example.com: https://example.com/unsubscribe?email=ursula"
+        );
+    }
+
+    #[test]
+    fn the_default_is_used_for_an_empty_value() {
+        let template = r#"Hi {{ name | default: "friend" }}!"#;
+        assert_eq!(render(template, "", false), "Hi friend!");
+        assert_eq!(render(template, "Ursula", false), "Hi Ursula!");
+        assert_eq!(render("Hi {{ name|default:'you' }}!", "", false), "Hi you!");
+    }
+
+    #[test]
+    fn values_are_escaped_in_html() {
+        assert_eq!(render("<p>{{ name }}</p>", "<b>", true), "<p>&lt;b&gt;</p>");
+        assert_eq!(render("{{ name }}", "<b>", false), "<b>");
+    }
+
+    #[test]
+    fn an_unknown_variable_is_rejected() {
+        assert_eq!(
+            parse("Hi {{ surname }}"),
+            Err(TemplateError::UnknownVariable("surname".to_string()))
+        );
+    }
+
+    #[test]
+    fn an_unclosed_tag_is_rejected() {
+        assert_eq!(parse("Hi {{ name"), Err(TemplateError::UnclosedTag));
+    }
+
+    #[test]
+    fn invalid_filters_are_rejected() {
+        assert_err!(parse("{{ name | upcase }}"));
+        assert_err!(parse("{{ name | default: friend }}"));
+        assert_err!(parse(r#"{{ name | default: "friend }}"#));
+    }
+
+    #[test]
+    fn a_variable_of_another_kind_of_email_is_rejected() {
+        assert_err!(parse("{{ confirmation_link }}"));
+    }
+
+    #[test]
+    fn a_missing_value_renders_as_empty() {
+        let template = Template::parse("[{{ confirmation_link }}]", &["confirmation_link"]);
+        assert_eq!(template.unwrap().render(&MergeValues(vec![]), false), "[]");
+    }
+
+    #[test]
+    fn an_empty_template_is_valid() {
+        assert_ok!(parse(""));
+    }
+}
@@ -0,0 +1,506 @@
+use crate::domain::{IssueSlug, IssueStatus};
+use crate::email_html::PreparedHtml;
+use crate::segments::get_issue_recipients;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{Acquire, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+pub struct NewsletterIssue {
+    pub id: Uuid,
+    pub title: String,
+    pub text_content: String,
+    pub html_content: String,
+    pub status: IssueStatus,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub published_at: Option<DateTime<Utc>>,
+    pub send_at: Option<DateTime<Utc>>,
+    pub template_id: Option<Uuid>,
+    /// What was removed from the HTML content when it was sanitized.
+    pub html_warnings: Vec<String>,
+    pub slug: String,
+    /// Whether the issue is listed in the public archive once sent.
+    pub is_public: bool,
+    pub segment_id: Option<Uuid>,
+}
+
+struct IssueRow {
+    newsletter_issue_id: Uuid,
+    title: String,
+    text_content: String,
+    html_content: String,
+    status: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    published_at: Option<DateTime<Utc>>,
+    send_at: Option<DateTime<Utc>>,
+    template_id: Option<Uuid>,
+    html_warnings: Vec<String>,
+    slug: String,
+    is_public: bool,
+    segment_id: Option<Uuid>,
+}
+
+impl TryFrom<IssueRow> for NewsletterIssue {
+    type Error = anyhow::Error;
+    fn try_from(r: IssueRow) -> Result<Self, Self::Error> {
+        Ok(NewsletterIssue {
+            id: r.newsletter_issue_id,
+            title: r.title,
+            text_content: r.text_content,
+            html_content: r.html_content,
+            status: IssueStatus::try_from(r.status).map_err(|e| anyhow::anyhow!(e))?,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+            published_at: r.published_at,
+            send_at: r.send_at,
+            template_id: r.template_id,
+            html_warnings: r.html_warnings,
+            slug: r.slug,
+            is_public: r.is_public,
+            segment_id: r.segment_id,
+        })
+    }
+}
+
+/// Who an issue is sent to.
+#[derive(Debug, Default)]
+pub struct Audience {
+    /// The default list if there are none.
+    pub list_ids: Vec<Uuid>,
+    /// Narrows the members of the lists down, if any.
+    pub segment_id: Option<Uuid>,
+}
+
+pub enum PublishOutcome {
+    Published,
+    NotPublishable,
+}
+
+#[tracing::instrument(name = "Insert a newsletter issue draft", skip_all)]
+pub async fn insert_draft(
+    db: impl Acquire<'_, Database = Postgres>,
+    title: &str,
+    text_content: &str,
+    html: &PreparedHtml,
+    template_id: Option<Uuid>,
+    audience: &Audience,
+) -> Result<Uuid, anyhow::Error> {
+    let mut transaction = db
+        .begin()
+        .await
+        .context("Failed to acquire a connection from the pool")?;
+    let id = Uuid::new_v4();
+    let slug = IssueSlug::new(title, id);
+    sqlx::query!(
+        r#"INSERT INTO newsletter_issues
+            (newsletter_issue_id, title, slug, text_content, html_content, html_warnings,
+            template_id, segment_id, status, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, 'draft', $9, $9)"#,
+        id,
+        title,
+        slug.as_ref(),
+        text_content,
+        html.html,
+        &html.warnings,
+        template_id,
+        audience.segment_id,
+        Utc::now()
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to insert the newsletter issue")?;
+    set_issue_lists(&mut transaction, id, &audience.list_ids).await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the transaction into the database")?;
+    Ok(id)
+}
+
+#[tracing::instrument(name = "Get a newsletter issue", skip(pool))]
+pub async fn get_issue(pool: &PgPool, id: Uuid) -> Result<Option<NewsletterIssue>, anyhow::Error> {
+    let issue = sqlx::query_as!(
+        IssueRow,
+        r#"SELECT newsletter_issue_id, title, text_content, html_content, status,
+            created_at, updated_at, published_at, send_at, template_id, html_warnings, slug,
+            is_public, segment_id
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1"#,
+        id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the newsletter issue")?;
+    issue.map(NewsletterIssue::try_from).transpose()
+}
+
+#[tracing::instrument(name = "List the newsletter issues", skip(pool))]
+pub async fn list_issues(pool: &PgPool) -> Result<Vec<NewsletterIssue>, anyhow::Error> {
+    sqlx::query_as!(
+        IssueRow,
+        r#"SELECT newsletter_issue_id, title, text_content, html_content, status,
+            created_at, updated_at, published_at, send_at, template_id, html_warnings, slug,
+            is_public, segment_id
+        FROM newsletter_issues
+        ORDER BY created_at DESC"#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the newsletter issues")?
+    .into_iter()
+    .map(NewsletterIssue::try_from)
+    .collect()
+}
+
+#[tracing::instrument(name = "List the scheduled newsletter issues", skip(pool))]
+pub async fn list_scheduled_issues(pool: &PgPool) -> Result<Vec<NewsletterIssue>, anyhow::Error> {
+    sqlx::query_as!(
+        IssueRow,
+        r#"SELECT newsletter_issue_id, title, text_content, html_content, status,
+            created_at, updated_at, published_at, send_at, template_id, html_warnings, slug,
+            is_public, segment_id
+        FROM newsletter_issues
+        WHERE status = 'scheduled'
+        ORDER BY send_at"#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the scheduled newsletter issues")?
+    .into_iter()
+    .map(NewsletterIssue::try_from)
+    .collect()
+}
+
+#[tracing::instrument(name = "Get a newsletter issue by slug", skip(pool))]
+pub async fn get_issue_by_slug(
+    pool: &PgPool,
+    slug: &str,
+) -> Result<Option<NewsletterIssue>, anyhow::Error> {
+    let issue = sqlx::query_as!(
+        IssueRow,
+        r#"SELECT newsletter_issue_id, title, text_content, html_content, status,
+            created_at, updated_at, published_at, send_at, template_id, html_warnings, slug,
+            is_public, segment_id
+        FROM newsletter_issues
+        WHERE slug = $1"#,
+        slug
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the newsletter issue")?;
+    issue.map(NewsletterIssue::try_from).transpose()
+}
+
+/// The sent issues that are not private, the most recent first.
+#[tracing::instrument(name = "List the public newsletter issues", skip(pool))]
+pub async fn list_public_issues(
+    pool: &PgPool,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<NewsletterIssue>, anyhow::Error> {
+    sqlx::query_as!(
+        IssueRow,
+        r#"SELECT newsletter_issue_id, title, text_content, html_content, status,
+            created_at, updated_at, published_at, send_at, template_id, html_warnings, slug,
+            is_public, segment_id
+        FROM newsletter_issues
+        WHERE is_public AND status IN ('sending', 'sent')
+        ORDER BY published_at DESC
+        LIMIT $1
+        OFFSET $2"#,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the public newsletter issues")?
+    .into_iter()
+    .map(NewsletterIssue::try_from)
+    .collect()
+}
+
+#[tracing::instrument(name = "Count the public newsletter issues", skip(pool))]
+pub async fn count_public_issues(pool: &PgPool) -> Result<i64, anyhow::Error> {
+    let count = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM newsletter_issues
+        WHERE is_public AND status IN ('sending', 'sent')"#
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count the public newsletter issues")?
+    .count;
+    Ok(count)
+}
+
+/// When the sent issues last changed, including their visibility: a change
+/// of the public issues cannot happen later than that.
+#[tracing::instrument(name = "Get the last update of the sent issues", skip(pool))]
+pub async fn last_sent_issue_update(pool: &PgPool) -> Result<Option<DateTime<Utc>>, anyhow::Error> {
+    let last_update = sqlx::query!(
+        r#"SELECT MAX(updated_at) AS last_update FROM newsletter_issues
+        WHERE status IN ('sending', 'sent')"#
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to fetch the last update of the sent issues")?
+    .last_update;
+    Ok(last_update)
+}
+
+/// Returns `false` if the issue does not exist.
+#[tracing::instrument(name = "Set the visibility of a newsletter issue", skip(pool))]
+pub async fn set_issue_visibility(
+    pool: &PgPool,
+    id: Uuid,
+    is_public: bool,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE newsletter_issues SET is_public = $1, updated_at = $2
+        WHERE newsletter_issue_id = $3"#,
+        is_public,
+        Utc::now(),
+        id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to update the visibility of the newsletter issue")?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Only drafts can be edited, returns `false` if the issue is not a draft.
+#[tracing::instrument(
+    name = "Update a newsletter issue draft",
+    skip(pool, title, text_content, html)
+)]
+pub async fn update_draft(
+    pool: &PgPool,
+    id: Uuid,
+    title: &str,
+    text_content: &str,
+    html: &PreparedHtml,
+    template_id: Option<Uuid>,
+    audience: &Audience,
+) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a connection from the pool")?;
+    let slug = IssueSlug::new(title, id);
+    let result = sqlx::query!(
+        r#"UPDATE newsletter_issues
+        SET title = $1, slug = $2, text_content = $3, html_content = $4, html_warnings = $5,
+            template_id = $6, segment_id = $7, updated_at = $8
+        WHERE newsletter_issue_id = $9 AND status = 'draft'"#,
+        title,
+        slug.as_ref(),
+        text_content,
+        html.html,
+        &html.warnings,
+        template_id,
+        audience.segment_id,
+        Utc::now(),
+        id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to update the newsletter issue")?;
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+    set_issue_lists(&mut transaction, id, &audience.list_ids).await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the transaction into the database")?;
+    Ok(true)
+}
+
+/// Unknown list ids are ignored, no list at all stands for the default one.
+async fn set_issue_lists(
+    transaction: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+    list_ids: &[Uuid],
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"DELETE FROM newsletter_issue_lists WHERE newsletter_issue_id = $1"#,
+        id
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to remove the lists of the newsletter issue")?;
+    sqlx::query!(
+        r#"INSERT INTO newsletter_issue_lists (newsletter_issue_id, list_id)
+        SELECT $1, list_id
+        FROM lists
+        WHERE CASE WHEN cardinality($2::uuid[]) = 0 THEN is_default ELSE list_id = ANY($2) END"#,
+        id,
+        list_ids
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to set the lists of the newsletter issue")?;
+    Ok(())
+}
+
+/// Start the fan-out of a draft: a delivery task is enqueued for every
+/// recipient of the issue and the issue goes to `sending`.
+#[tracing::instrument(name = "Publish a newsletter issue", skip(pool))]
+pub async fn publish_issue(pool: &PgPool, id: Uuid) -> Result<PublishOutcome, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a connection from the pool")?;
+    let outcome = publish_draft(&mut transaction, id).await?;
+    if let PublishOutcome::Published = outcome {
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit the transaction into the database")?;
+    }
+    Ok(outcome)
+}
+
+/// [`publish_issue`] within an existing transaction.
+pub async fn publish_draft(
+    transaction: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+) -> Result<PublishOutcome, anyhow::Error> {
+    let updated = sqlx::query!(
+        r#"UPDATE newsletter_issues
+        SET status = 'sending', published_at = $1, updated_at = $1
+        WHERE newsletter_issue_id = $2 AND status = 'draft'"#,
+        Utc::now(),
+        id
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to update the status of the newsletter issue")?;
+    if updated.rows_affected() == 0 {
+        return Ok(PublishOutcome::NotPublishable);
+    }
+    enqueue_delivery_tasks(transaction, id).await?;
+    Ok(PublishOutcome::Published)
+}
+
+#[tracing::instrument(name = "Enqueue delivery tasks", skip(transaction))]
+pub async fn enqueue_delivery_tasks(
+    transaction: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+) -> Result<(), anyhow::Error> {
+    let recipients = get_issue_recipients(transaction, id).await?;
+    let enqueued = sqlx::query!(
+        r#"INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+        SELECT $1, email FROM UNNEST($2::text[]) AS email"#,
+        id,
+        &recipients
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to enqueue the delivery tasks")?;
+    if enqueued.rows_affected() == 0 {
+        // Nobody to send the issue to, it will never be picked up by a worker.
+        sqlx::query!(
+            r#"UPDATE newsletter_issues SET status = 'sent', updated_at = $1
+            WHERE newsletter_issue_id = $2"#,
+            Utc::now(),
+            id
+        )
+        .execute(&mut **transaction)
+        .await
+        .context("Failed to mark the newsletter issue as sent")?;
+    }
+    Ok(())
+}
+
+/// Schedule a draft, or reschedule an issue that did not start sending yet.
+/// Returns `false` if the issue is in any other state.
+#[tracing::instrument(name = "Schedule a newsletter issue", skip(pool))]
+pub async fn schedule_issue(
+    pool: &PgPool,
+    id: Uuid,
+    send_at: DateTime<Utc>,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE newsletter_issues SET status = 'scheduled', send_at = $1, updated_at = $2
+        WHERE newsletter_issue_id = $3 AND status IN ('draft', 'scheduled')"#,
+        send_at,
+        Utc::now(),
+        id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to schedule the newsletter issue")?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Start the fan-out of the scheduled issue that is the most overdue, if any.
+/// Concurrent schedulers skip the issues that are being started.
+#[tracing::instrument(name = "Start a due newsletter issue", skip(pool))]
+pub async fn start_due_issue(pool: &PgPool) -> Result<Option<Uuid>, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a connection from the pool")?;
+    let Some(issue) = sqlx::query!(
+        r#"SELECT newsletter_issue_id FROM newsletter_issues
+        WHERE status = 'scheduled' AND send_at <= now()
+        ORDER BY send_at
+        FOR UPDATE
+        SKIP LOCKED
+        LIMIT 1"#
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to look for a due newsletter issue")?
+    else {
+        return Ok(None);
+    };
+    let id = issue.newsletter_issue_id;
+    sqlx::query!(
+        r#"UPDATE newsletter_issues
+        SET status = 'sending', published_at = $1, updated_at = $1
+        WHERE newsletter_issue_id = $2"#,
+        Utc::now(),
+        id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to update the status of the newsletter issue")?;
+    enqueue_delivery_tasks(&mut transaction, id).await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the transaction into the database")?;
+    Ok(Some(id))
+}
+
+/// Drafts and scheduled issues can be cancelled, returns `false` otherwise.
+#[tracing::instrument(name = "Cancel a newsletter issue", skip(pool))]
+pub async fn cancel_issue(pool: &PgPool, id: Uuid) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE newsletter_issues SET status = 'cancelled', updated_at = $1
+        WHERE newsletter_issue_id = $2 AND status IN ('draft', 'scheduled')"#,
+        Utc::now(),
+        id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to cancel the newsletter issue")?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Issues that were never sent can be deleted, returns `false` otherwise.
+#[tracing::instrument(name = "Delete a newsletter issue", skip(pool))]
+pub async fn delete_issue(pool: &PgPool, id: Uuid) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"DELETE FROM newsletter_issues
+        WHERE newsletter_issue_id = $1 AND status IN ('draft', 'cancelled')"#,
+        id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to delete the newsletter issue")?;
+    Ok(result.rows_affected() == 1)
+}
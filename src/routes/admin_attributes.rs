@@ -0,0 +1,191 @@
+use crate::attributes::{AttributeDefinition, AttributeKind, insert_definition, list_definitions};
+use crate::routes::admin_newsletters::new::flash_messages_html;
+use crate::utils::{e500, escape_html, see_other};
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn list_attributes_page(
+    pool: web::Data<PgPool>,
+    flash_message: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let message_html = flash_messages_html(&flash_message)?;
+    let mut attributes_html = String::new();
+    for attribute in list_definitions(&pool).await.map_err(e500)? {
+        writeln!(
+            attributes_html,
+            "<tr><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape_html(&attribute.key),
+            escape_html(&attribute.label),
+            attribute.kind,
+            if attribute.required { "Yes" } else { "No" },
+            escape_html(&rules(&attribute))
+        )
+        .map_err(e500)?;
+    }
+    let kind_options: String = AttributeKind::ALL
+        .iter()
+        .map(|kind| format!(r#"<option value="{0}">{0}</option>"#, kind))
+        .collect();
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>Subscriber attributes</title>
+</head>
+<body>
+{message_html}
+<h2>Subscriber attributes</h2>
+<p>Attributes are asked in the subscribe form. Their key can be used as a merge tag in issues, like <code>{{{{ company | default: "there" }}}}</code>, and in segments.</p>
+<table>
+<tr><th>Key</th><th>Label</th><th>Kind</th><th>Required</th><th>Rules</th></tr>
+{attributes_html}
+</table>
+<h2>Define an attribute</h2>
+<form action="/admin/attributes" method="post">
+<label>Key
+<input type="text" placeholder="company" name="key">
+</label>
+<br>
+<label>Label
+<input type="text" placeholder="Company" name="label">
+</label>
+<br>
+<label>Kind
+<select name="kind">{kind_options}</select>
+</label>
+<br>
+<label><input type="checkbox" name="required" value="true"> Required</label>
+<br>
+<label>Choices
+<input type="text" placeholder="Options of a choice, separated by commas" name="choices">
+</label>
+<br>
+<label>Minimum
+<input type="number" step="any" name="min_value">
+</label>
+<label>Maximum
+<input type="number" step="any" name="max_value">
+</label>
+<br>
+<label>Maximum length
+<input type="number" min="1" name="max_length">
+</label>
+<br>
+<button type="submit">Create</button>
+</form>
+<p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+/// The validation rules of an attribute, for the table.
+fn rules(attribute: &AttributeDefinition) -> String {
+    let mut rules = Vec::new();
+    if !attribute.choices.is_empty() {
+        rules.push(format!("one of {}", attribute.choices.join(", ")));
+    }
+    if let Some(min) = attribute.min_value {
+        rules.push(format!("at least {}", min));
+    }
+    if let Some(max) = attribute.max_value {
+        rules.push(format!("at most {}", max));
+    }
+    if let Some(max_length) = attribute.max_length {
+        rules.push(format!("at most {} characters", max_length));
+    }
+    rules.join(", ")
+}
+
+#[derive(Deserialize)]
+pub struct AttributeForm {
+    key: String,
+    label: String,
+    kind: String,
+    #[serde(default)]
+    required: Option<String>,
+    #[serde(default)]
+    choices: String,
+    #[serde(default)]
+    min_value: String,
+    #[serde(default)]
+    max_value: String,
+    #[serde(default)]
+    max_length: String,
+}
+
+impl TryFrom<AttributeForm> for AttributeDefinition {
+    type Error = String;
+    fn try_from(form: AttributeForm) -> Result<Self, Self::Error> {
+        let kind = AttributeKind::try_from(form.kind)?;
+        let number = |field: &str, value: &str| -> Result<Option<f64>, String> {
+            let value = value.trim();
+            if value.is_empty() {
+                return Ok(None);
+            }
+            value
+                .parse::<f64>()
+                .ok()
+                .filter(|number| number.is_finite())
+                .map(Some)
+                .ok_or_else(|| format!("The {} must be a number.", field))
+        };
+        let max_length = match form.max_length.trim() {
+            "" => None,
+            max_length => Some(
+                max_length
+                    .parse::<i32>()
+                    .map_err(|_| "The maximum length must be a whole number.".to_string())?,
+            ),
+        };
+        // The rules that do not apply to the kind are dropped.
+        let definition = AttributeDefinition {
+            key: form.key.trim().to_string(),
+            label: form.label.trim().to_string(),
+            kind,
+            required: form.required.is_some(),
+            choices: if kind == AttributeKind::Choice {
+                form.choices
+                    .split(',')
+                    .map(|choice| choice.trim().to_string())
+                    .filter(|choice| !choice.is_empty())
+                    .collect()
+            } else {
+                Vec::new()
+            },
+            min_value: number("minimum", &form.min_value)?
+                .filter(|_| kind == AttributeKind::Number),
+            max_value: number("maximum", &form.max_value)?
+                .filter(|_| kind == AttributeKind::Number),
+            max_length: max_length.filter(|_| kind == AttributeKind::Text),
+        };
+        definition.validate()?;
+        Ok(definition)
+    }
+}
+
+#[tracing::instrument(name = "Define a subscriber attribute", skip(form, pool))]
+pub async fn create_attribute(
+    form: web::Form<AttributeForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let definition = match AttributeDefinition::try_from(form.into_inner()) {
+        Ok(definition) => definition,
+        Err(e) => {
+            FlashMessage::error(escape_html(&e)).send();
+            return Ok(see_other("/admin/attributes"));
+        }
+    };
+    if insert_definition(&pool, &definition).await.map_err(e500)? {
+        FlashMessage::info("The attribute has been created.").send();
+    } else {
+        FlashMessage::error("An attribute with this key already exists.").send();
+    }
+    Ok(see_other("/admin/attributes"))
+}
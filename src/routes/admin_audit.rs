@@ -0,0 +1,62 @@
+use crate::audit::{AuditEntry, list_entries};
+use crate::utils::{e500, escape_html};
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+/// The rows of a table of audit entries, the subscriber column is left out
+/// on the page of a subscriber.
+pub fn audit_entries_html(
+    entries: &[AuditEntry],
+    with_subscriber: bool,
+) -> Result<String, actix_web::Error> {
+    let mut entries_html = String::new();
+    for entry in entries {
+        let subscriber = if with_subscriber {
+            format!(
+                "<td>{}</td>",
+                escape_html(entry.subscriber_email.as_deref().unwrap_or("-"))
+            )
+        } else {
+            String::new()
+        };
+        writeln!(
+            entries_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td>{}<td>{}</td></tr>",
+            entry.created_at.format("%Y-%m-%d %H:%M UTC"),
+            escape_html(entry.username.as_deref().unwrap_or("-")),
+            entry.action,
+            subscriber,
+            escape_html(&entry.details),
+        )
+        .map_err(e500)?;
+    }
+    if entries.is_empty() {
+        entries_html.push_str("<tr><td>Nothing has been recorded yet.</td></tr>\n");
+    }
+    Ok(entries_html)
+}
+
+pub async fn audit_trail_page(pool: web::Data<PgPool>) -> Result<HttpResponse, actix_web::Error> {
+    let entries = list_entries(&pool, None).await.map_err(e500)?;
+    let entries_html = audit_entries_html(&entries, true)?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>Audit trail</title>
+</head>
+<body>
+<h2>Audit trail</h2>
+<p>The latest actions of the admins on the subscribers.</p>
+<table>
+<tr><th>At</th><th>Admin</th><th>Action</th><th>Subscriber</th><th>Details</th></tr>
+{entries_html}</table>
+<p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
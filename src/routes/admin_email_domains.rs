@@ -0,0 +1,132 @@
+use crate::configuration::SignupSettings;
+use crate::email_domains::{
+    DomainRule, delete_domain_rule, disposable_domains, list_domain_rules, parse_domain,
+    upsert_domain_rule,
+};
+use crate::routes::admin_newsletters::new::flash_messages_html;
+use crate::utils::{e500, escape_html, see_other};
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn email_domains_page(
+    pool: web::Data<PgPool>,
+    signup_settings: web::Data<SignupSettings>,
+    flash_message: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let message_html = flash_messages_html(&flash_message)?;
+    let mut rules_html = String::new();
+    for rule in list_domain_rules(&pool).await.map_err(e500)? {
+        writeln!(
+            rules_html,
+            r#"<tr><td>{domain}</td><td>{rule}</td><td>{created_at}</td><td><form action="/admin/email-domains/delete" method="post"><input hidden type="text" name="domain" value="{domain}"><button type="submit">Remove</button></form></td></tr>"#,
+            domain = escape_html(&rule.domain),
+            rule = rule.rule,
+            created_at = rule.created_at.format("%Y-%m-%d %H:%M UTC"),
+        )
+        .map_err(e500)?;
+    }
+    if rules_html.is_empty() {
+        rules_html.push_str("<tr><td>No domain has been blocked or allowed yet.</td></tr>\n");
+    }
+    let mode = if signup_settings.allowlist_only {
+        "Only the allowed domains can subscribe."
+    } else {
+        "Every domain can subscribe, except the blocked ones."
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>Email domains</title>
+</head>
+<body>
+{message_html}
+<h2>Email domains</h2>
+<p>{mode} A rule on a domain also covers its subdomains, and an allowed domain wins over a blocked one.</p>
+<p>{n_disposable} disposable email domains are blocked by default.</p>
+<table>
+<tr><th>Domain</th><th>Rule</th><th>Added at</th><th></th></tr>
+{rules_html}</table>
+<h2>Block or allow a domain</h2>
+<form action="/admin/email-domains" method="post">
+<label>Domain
+<input type="text" placeholder="example.com" name="domain">
+</label>
+<label>
+<input type="radio" name="rule" value="block" checked>
+Block
+</label>
+<label>
+<input type="radio" name="rule" value="allow">
+Allow
+</label>
+<button type="submit">Save</button>
+</form>
+<p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+            n_disposable = disposable_domains().count(),
+        )))
+}
+
+#[derive(Deserialize)]
+pub struct DomainRuleForm {
+    domain: String,
+    rule: String,
+}
+
+#[tracing::instrument(name = "Save an email domain rule", skip(form, pool))]
+pub async fn save_email_domain_rule(
+    form: web::Form<DomainRuleForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let parsed = parse_domain(&form.domain)
+        .and_then(|domain| Ok((domain, DomainRule::try_from(form.0.rule)?)));
+    let (domain, rule) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            FlashMessage::error(escape_html(&e)).send();
+            return Ok(see_other("/admin/email-domains"));
+        }
+    };
+    upsert_domain_rule(&pool, &domain, rule)
+        .await
+        .map_err(e500)?;
+    let message = match rule {
+        DomainRule::Block => format!("{} is blocked.", domain),
+        DomainRule::Allow => format!("{} is allowed.", domain),
+    };
+    FlashMessage::info(escape_html(&message)).send();
+    Ok(see_other("/admin/email-domains"))
+}
+
+#[derive(Deserialize)]
+pub struct DeleteDomainRuleForm {
+    domain: String,
+}
+
+#[tracing::instrument(name = "Delete an email domain rule", skip(form, pool))]
+pub async fn delete_email_domain_rule(
+    form: web::Form<DeleteDomainRuleForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if delete_domain_rule(&pool, &form.domain)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::info(format!(
+            "The rule of {} is removed.",
+            escape_html(&form.domain)
+        ))
+        .send();
+    } else {
+        FlashMessage::error("This domain has no rule.").send();
+    }
+    Ok(see_other("/admin/email-domains"))
+}
@@ -0,0 +1,118 @@
+use crate::mailing_lists::{count_confirmed_members, insert_list, list_lists};
+use crate::routes::admin_newsletters::new::flash_messages_html;
+use crate::utils::{e500, escape_html, see_other};
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn list_mailing_lists(
+    pool: web::Data<PgPool>,
+    flash_message: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let message_html = flash_messages_html(&flash_message)?;
+    let lists = list_lists(&pool).await.map_err(e500)?;
+    let counts = count_confirmed_members(&pool).await.map_err(e500)?;
+    let mut lists_html = String::new();
+    for list in lists {
+        let members = counts
+            .iter()
+            .find(|(list_id, _)| *list_id == list.id)
+            .map(|(_, count)| *count)
+            .unwrap_or(0);
+        writeln!(
+            lists_html,
+            r#"<tr><td>{name}{default}</td><td>{description}</td><td>{members}</td><td><code>&lt;input type="hidden" name="list" value="{slug}"&gt;</code></td></tr>"#,
+            name = escape_html(&list.name),
+            default = if list.is_default { " (default)" } else { "" },
+            description = escape_html(&list.description),
+            slug = escape_html(&list.slug),
+        )
+        .map_err(e500)?;
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>Lists</title>
+</head>
+<body>
+{message_html}
+<h2>Lists</h2>
+<p>A subscribe form names its list with a hidden field, the default list is used without one. A form on another site has no signed timestamp, it needs the <code>signup.min_fill_seconds</code> setting at 0.</p>
+<table>
+<tr><th>Name</th><th>Description</th><th>Confirmed subscribers</th><th>Subscribe form field</th></tr>
+{lists_html}
+</table>
+<h2>Create a list</h2>
+<form action="/admin/lists" method="post">
+<label>Name
+<input type="text" placeholder="Enter the name of the list" name="name">
+</label>
+<br>
+<label>Slug
+<input type="text" placeholder="weekly-digest" name="slug">
+</label>
+<br>
+<label>Description
+<input type="text" name="description">
+</label>
+<br>
+<button type="submit">Create</button>
+</form>
+<p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[derive(Deserialize)]
+pub struct ListForm {
+    name: String,
+    slug: String,
+    #[serde(default)]
+    description: String,
+}
+
+impl ListForm {
+    fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("The name of the list is missing.".to_string());
+        }
+        let slug = &self.slug;
+        if slug.is_empty()
+            || !slug
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            return Err(
+                "The slug can only contain lowercase letters, digits and dashes.".to_string(),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[tracing::instrument(name = "Create a list", skip(form, pool))]
+pub async fn create_mailing_list(
+    form: web::Form<ListForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if let Err(e) = form.validate() {
+        FlashMessage::error(e).send();
+        return Ok(see_other("/admin/lists"));
+    }
+    let inserted = insert_list(&pool, form.name.trim(), &form.slug, form.description.trim())
+        .await
+        .map_err(e500)?;
+    if inserted.is_some() {
+        FlashMessage::info("The list has been created.").send();
+    } else {
+        FlashMessage::error("A list with this name or slug already exists.").send();
+    }
+    Ok(see_other("/admin/lists"))
+}
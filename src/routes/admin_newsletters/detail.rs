@@ -0,0 +1,180 @@
+use crate::domain::IssueStatus;
+use crate::mailing_lists::get_issue_lists;
+use crate::newsletter_issues::get_issue;
+use crate::routes::admin_newsletters::new::flash_messages_html;
+use crate::segments::{count_issue_recipients, get_issue_segment};
+use crate::templates::{CONTENT_SLOT, get_email_layout};
+use crate::utils::{e500, escape_html};
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub async fn newsletter_issue(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    flash_message: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let message_html = flash_messages_html(&flash_message)?;
+    let Some(issue) = get_issue(&pool, *issue_id).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let id = issue.id;
+    let actions_html = match issue.status {
+        IssueStatus::Draft => {
+            let subscribers = count_issue_recipients(&pool, id).await.map_err(e500)?;
+            let schedule_form = schedule_form_html(id, "Schedule");
+            format!(
+                r#"<p><a href="/admin/newsletters/{id}/edit">Edit the draft</a></p>
+<p>This issue will be sent to {subscribers} confirmed subscribers.</p>
+<form action="/admin/newsletters/{id}/publish" method="post">
+<button type="submit">Publish now</button>
+</form>
+{schedule_form}
+<form action="/admin/newsletters/{id}/cancel" method="post">
+<button type="submit">Cancel</button>
+</form>
+<form action="/admin/newsletters/{id}/delete" method="post">
+<button type="submit">Delete</button>
+</form>"#
+            )
+        }
+        IssueStatus::Scheduled => {
+            let subscribers = count_issue_recipients(&pool, id).await.map_err(e500)?;
+            let send_at = issue
+                .send_at
+                .map(|send_at| send_at.format("%Y-%m-%d %H:%M UTC").to_string())
+                .unwrap_or_default();
+            let schedule_form = schedule_form_html(id, "Reschedule");
+            format!(
+                r#"<p>Scheduled for {send_at}, to {subscribers} confirmed subscribers.</p>
+{schedule_form}
+<form action="/admin/newsletters/{id}/cancel" method="post">
+<button type="submit">Cancel</button>
+</form>"#
+            )
+        }
+        IssueStatus::Cancelled => format!(
+            r#"<form action="/admin/newsletters/{id}/delete" method="post">
+<button type="submit">Delete</button>
+</form>"#
+        ),
+        IssueStatus::Sending | IssueStatus::Sent => String::new(),
+    };
+    let warnings_html = if issue.html_warnings.is_empty() {
+        String::new()
+    } else {
+        let items: String = issue
+            .html_warnings
+            .iter()
+            .map(|warning| format!("<li>{}</li>\n", escape_html(warning)))
+            .collect();
+        format!(
+            "<p>Some of the HTML content is not supported by mail clients:</p>\n<ul>\n{items}</ul>\n"
+        )
+    };
+    let visibility_html = if issue.is_public {
+        format!(
+            r#"<p>This issue is listed in the <a href="/archive/{slug}">public archive</a> once sent.</p>
+<form action="/admin/newsletters/{id}/visibility" method="post">
+<input type="hidden" name="is_public" value="false">
+<button type="submit">Make private</button>
+</form>"#,
+            slug = issue.slug
+        )
+    } else {
+        format!(
+            r#"<p>This issue is private, it is left out of the public archive.</p>
+<form action="/admin/newsletters/{id}/visibility" method="post">
+<input type="hidden" name="is_public" value="true">
+<button type="submit">Make public</button>
+</form>"#
+        )
+    };
+    let lists = get_issue_lists(&pool, id)
+        .await
+        .map_err(e500)?
+        .iter()
+        .map(|list| escape_html(&list.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let segment_html = match get_issue_segment(&pool, id).await.map_err(e500)? {
+        Some(segment) => format!(
+            "<p>Segment: {} ({})</p>\n",
+            escape_html(&segment.name),
+            escape_html(&segment.condition.to_string())
+        ),
+        None => String::new(),
+    };
+    let title = escape_html(&issue.title);
+    let text = escape_html(&issue.text_content);
+    let status = issue.status;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>{title}</title>
+</head>
+<body>
+{message_html}
+<h1>{title}</h1>
+<p>Status: {status}</p>
+<p>Lists: {lists}</p>
+{segment_html}{visibility_html}
+<h2>HTML preview</h2>
+{warnings_html}<iframe sandbox src="/admin/newsletters/{id}/preview" width="800" height="400"></iframe>
+<h2>Plain text preview</h2>
+<pre>{text}</pre>
+<form action="/admin/newsletters/{id}/test" method="post">
+<label>Send a test to
+<textarea placeholder="Up to 5 addresses, separated by commas. Leave empty to use your own email." name="recipients" rows="2" cols="60"></textarea>
+</label>
+<button type="submit">Send test</button>
+</form>
+{actions_html}
+<p><a href="/admin/newsletters">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+fn schedule_form_html(id: Uuid, label: &str) -> String {
+    format!(
+        r#"<form action="/admin/newsletters/{id}/schedule" method="post">
+<label>Send at
+<input type="datetime-local" name="send_at">
+</label>
+<label>Timezone
+<input type="text" name="timezone" value="UTC" placeholder="Europe/Paris">
+</label>
+<button type="submit">{label}</button>
+</form>"#
+    )
+}
+
+/// Serves the HTML body of an issue as it will be rendered in a mail client,
+/// meant to be displayed in a sandboxed iframe.
+pub async fn newsletter_issue_preview(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(issue) = get_issue(&pool, *issue_id).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let html = match get_email_layout(&pool, issue.template_id)
+        .await
+        .map_err(e500)?
+    {
+        Some(layout) => layout
+            .html_layout
+            .replacen(CONTENT_SLOT, &issue.html_content, 1),
+        None => issue.html_content,
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .insert_header(("Content-Security-Policy", "sandbox"))
+        .body(html))
+}
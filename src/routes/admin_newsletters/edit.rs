@@ -0,0 +1,102 @@
+use crate::domain::IssueStatus;
+use crate::email_html::prepare_email_html;
+use crate::mailing_lists::get_issue_lists;
+use crate::newsletter_issues::{get_issue, update_draft};
+use crate::routes::admin_newsletters::new::{
+    IssueFormOptions, NewsletterForm, flash_messages_html, issue_form_html,
+};
+use crate::templates::get_layout;
+use crate::utils::{e500, see_other};
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub async fn edit_newsletter_form(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    flash_message: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let message_html = flash_messages_html(&flash_message)?;
+    let Some(issue) = get_issue(&pool, *issue_id).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    if issue.status != IssueStatus::Draft {
+        FlashMessage::error("Only drafts can be edited.").send();
+        return Ok(see_other(&format!("/admin/newsletters/{}", issue.id)));
+    }
+    let form = NewsletterForm {
+        title: issue.title,
+        html: issue.html_content,
+        text: issue.text_content,
+        template_id: issue
+            .template_id
+            .map(|id| id.to_string())
+            .unwrap_or_default(),
+        list_ids: get_issue_lists(&pool, issue.id)
+            .await
+            .map_err(e500)?
+            .into_iter()
+            .map(|list| list.id)
+            .collect(),
+        segment_id: issue
+            .segment_id
+            .map(|id| id.to_string())
+            .unwrap_or_default(),
+    };
+    let options = IssueFormOptions::load(&pool).await.map_err(e500)?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(issue_form_html(
+            "Edit the newsletter issue",
+            &message_html,
+            &format!("/admin/newsletters/{}", issue.id),
+            &form,
+            &options,
+            &format!("/admin/newsletters/{}", issue.id),
+        )))
+}
+
+#[tracing::instrument(name = "Update a newsletter issue", skip(body, pool))]
+pub async fn update_newsletter_issue(
+    issue_id: web::Path<Uuid>,
+    body: web::Bytes,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = issue_id.into_inner();
+    let edit = see_other(&format!("/admin/newsletters/{}/edit", issue_id));
+    let form = NewsletterForm::parse(&body)?;
+    let options = IssueFormOptions::load(&pool).await.map_err(e500)?;
+    if let Err(e) = form
+        .validate(&options)
+        .and_then(|_| form.validate_audience(&options))
+    {
+        FlashMessage::error(e).send();
+        return Ok(edit);
+    }
+    let template_id = form.template_id().map_err(e500)?;
+    if let Some(id) = template_id
+        && get_layout(&pool, id).await.map_err(e500)?.is_none()
+    {
+        FlashMessage::error("The layout does not exist.").send();
+        return Ok(edit);
+    }
+    let html = prepare_email_html(&form.html);
+    if update_draft(
+        &pool,
+        issue_id,
+        &form.title,
+        &form.text,
+        &html,
+        template_id,
+        &form.audience().map_err(e500)?,
+    )
+    .await
+    .map_err(e500)?
+    {
+        FlashMessage::info("The draft has been saved.").send();
+    } else {
+        FlashMessage::error("Only drafts can be edited.").send();
+    }
+    Ok(see_other(&format!("/admin/newsletters/{}", issue_id)))
+}
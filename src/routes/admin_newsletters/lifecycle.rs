@@ -0,0 +1,119 @@
+use crate::domain::SendAt;
+use crate::newsletter_issues::{
+    PublishOutcome, cancel_issue, delete_issue, publish_issue, schedule_issue, set_issue_visibility,
+};
+use crate::utils::{e500, see_other};
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use chrono::Utc;
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[tracing::instrument(name = "Publish a newsletter issue", skip(pool))]
+pub async fn publish_newsletter_issue(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = issue_id.into_inner();
+    match publish_issue(&pool, issue_id).await.map_err(e500)? {
+        PublishOutcome::Published => {
+            FlashMessage::info("The newsletter issue has been published!").send()
+        }
+        PublishOutcome::NotPublishable => {
+            FlashMessage::error("Only drafts can be published.").send()
+        }
+    }
+    Ok(see_other(&format!("/admin/newsletters/{}", issue_id)))
+}
+
+#[derive(Deserialize)]
+pub struct ScheduleForm {
+    send_at: String,
+    timezone: String,
+}
+
+#[tracing::instrument(name = "Schedule a newsletter issue", skip(form, pool))]
+pub async fn schedule_newsletter_issue(
+    issue_id: web::Path<Uuid>,
+    form: web::Form<ScheduleForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = issue_id.into_inner();
+    let send_at = match SendAt::parse(&form.send_at, &form.timezone) {
+        Ok(send_at) if *send_at.as_ref() > Utc::now() => send_at,
+        Ok(_) => {
+            FlashMessage::error("The sending time must be in the future.").send();
+            return Ok(see_other(&format!("/admin/newsletters/{}", issue_id)));
+        }
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other(&format!("/admin/newsletters/{}", issue_id)));
+        }
+    };
+    if schedule_issue(&pool, issue_id, send_at.into_inner())
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::info("The newsletter issue has been scheduled.").send();
+    } else {
+        FlashMessage::error("Only drafts and scheduled issues can be scheduled.").send();
+    }
+    Ok(see_other(&format!("/admin/newsletters/{}", issue_id)))
+}
+
+#[tracing::instrument(name = "Cancel a newsletter issue", skip(pool))]
+pub async fn cancel_newsletter_issue(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = issue_id.into_inner();
+    if cancel_issue(&pool, issue_id).await.map_err(e500)? {
+        FlashMessage::info("The newsletter issue has been cancelled.").send();
+    } else {
+        FlashMessage::error("Only drafts and scheduled issues can be cancelled.").send();
+    }
+    Ok(see_other(&format!("/admin/newsletters/{}", issue_id)))
+}
+
+#[tracing::instrument(name = "Delete a newsletter issue", skip(pool))]
+pub async fn delete_newsletter_issue(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = issue_id.into_inner();
+    if delete_issue(&pool, issue_id).await.map_err(e500)? {
+        FlashMessage::info("The newsletter issue has been deleted.").send();
+        Ok(see_other("/admin/newsletters"))
+    } else {
+        FlashMessage::error("Only drafts and cancelled issues can be deleted.").send();
+        Ok(see_other(&format!("/admin/newsletters/{}", issue_id)))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct VisibilityForm {
+    is_public: bool,
+}
+
+/// Private issues are left out of the public archive and feeds.
+#[tracing::instrument(name = "Set the visibility of a newsletter issue", skip(form, pool))]
+pub async fn set_newsletter_issue_visibility(
+    issue_id: web::Path<Uuid>,
+    form: web::Form<VisibilityForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = issue_id.into_inner();
+    if !set_issue_visibility(&pool, issue_id, form.is_public)
+        .await
+        .map_err(e500)?
+    {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    if form.is_public {
+        FlashMessage::info("The newsletter issue is now public.").send();
+    } else {
+        FlashMessage::info("The newsletter issue is now private.").send();
+    }
+    Ok(see_other(&format!("/admin/newsletters/{}", issue_id)))
+}
@@ -0,0 +1,47 @@
+use crate::newsletter_issues::list_issues;
+use crate::routes::admin_newsletters::new::flash_messages_html;
+use crate::utils::{e500, escape_html};
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn list_newsletter_issues(
+    pool: web::Data<PgPool>,
+    flash_message: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let message_html = flash_messages_html(&flash_message)?;
+    let issues = list_issues(&pool).await.map_err(e500)?;
+    let mut rows_html = String::new();
+    for issue in issues {
+        writeln!(
+            rows_html,
+            r#"<tr><td><a href="/admin/newsletters/{}">{}</a></td><td>{}</td><td>{}</td></tr>"#,
+            issue.id,
+            escape_html(&issue.title),
+            issue.status,
+            issue.created_at.format("%Y-%m-%d %H:%M UTC"),
+        )
+        .map_err(e500)?;
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>Newsletter issues</title>
+</head>
+<body>
+{message_html}
+<p><a href="/admin/newsletters/new">Write a new issue</a></p>
+<table>
+<tr><th>Title</th><th>Status</th><th>Created at</th></tr>
+{rows_html}
+</table>
+<p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
@@ -0,0 +1,16 @@
+pub mod detail;
+pub mod edit;
+pub mod lifecycle;
+pub mod list;
+pub mod new;
+pub mod send_test;
+
+pub use detail::{newsletter_issue, newsletter_issue_preview};
+pub use edit::{edit_newsletter_form, update_newsletter_issue};
+pub use lifecycle::{
+    cancel_newsletter_issue, delete_newsletter_issue, publish_newsletter_issue,
+    schedule_newsletter_issue, set_newsletter_issue_visibility,
+};
+pub use list::list_newsletter_issues;
+pub use new::{create_newsletter_issue, newsletter_form};
+pub use send_test::send_test_newsletter_issue;
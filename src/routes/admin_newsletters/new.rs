@@ -0,0 +1,308 @@
+use crate::attributes::{AttributeDefinition, issue_variables, list_definitions};
+use crate::email_html::prepare_email_html;
+use crate::mailing_lists::{MailingList, list_lists};
+use crate::merge_tags::validate_merge_tags;
+use crate::newsletter_issues::{Audience, insert_draft};
+use crate::segments::{Segment, list_segments};
+use crate::templates::{Layout, get_layout, list_layouts};
+use crate::utils::{e500, escape_html, see_other};
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+pub struct NewsletterForm {
+    pub title: String,
+    pub html: String,
+    pub text: String,
+    /// The id of the layout of the issue, empty for no layout.
+    #[serde(default)]
+    pub template_id: String,
+    /// The lists the issue is sent to, the default list if there are none.
+    #[serde(default)]
+    pub list_ids: Vec<Uuid>,
+    /// The id of the segment the members of the lists are narrowed to,
+    /// empty for all of them.
+    #[serde(default)]
+    pub segment_id: String,
+}
+
+impl NewsletterForm {
+    /// `web::Form` does not support the repeated `list_ids` field.
+    pub fn parse(body: &[u8]) -> Result<Self, actix_web::Error> {
+        serde_html_form::from_bytes(body).map_err(actix_web::error::ErrorBadRequest)
+    }
+
+    pub fn validate(&self, options: &IssueFormOptions) -> Result<(), String> {
+        if self.title.trim().is_empty() {
+            return Err("The title of the issue is missing.".to_string());
+        }
+        if self.html.trim().is_empty() {
+            return Err("The HTML content of the issue is missing.".to_string());
+        }
+        if self.text.trim().is_empty() {
+            return Err("The plain text content of the issue is missing.".to_string());
+        }
+        self.template_id()?;
+        validate_merge_tags(
+            &self.html,
+            &self.text,
+            &issue_variables(&options.attributes),
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    pub fn validate_audience(&self, options: &IssueFormOptions) -> Result<(), String> {
+        if !self
+            .list_ids
+            .iter()
+            .all(|id| options.lists.iter().any(|list| list.id == *id))
+        {
+            return Err("The list does not exist.".to_string());
+        }
+        if let Some(id) = self.audience()?.segment_id
+            && !options.segments.iter().any(|segment| segment.id == id)
+        {
+            return Err("The segment does not exist.".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn audience(&self) -> Result<Audience, String> {
+        let segment_id = if self.segment_id.is_empty() {
+            None
+        } else {
+            Some(
+                Uuid::parse_str(&self.segment_id)
+                    .map_err(|_| "The segment does not exist.".to_string())?,
+            )
+        };
+        Ok(Audience {
+            list_ids: self.list_ids.clone(),
+            segment_id,
+        })
+    }
+
+    pub fn template_id(&self) -> Result<Option<Uuid>, String> {
+        if self.template_id.is_empty() {
+            return Ok(None);
+        }
+        Uuid::parse_str(&self.template_id)
+            .map(Some)
+            .map_err(|_| "The layout does not exist.".to_string())
+    }
+}
+
+pub fn layout_select_html(layouts: &[Layout], selected: &str) -> String {
+    let mut options = String::from(r#"<option value="">No layout</option>"#);
+    for layout in layouts {
+        let id = layout.id.to_string();
+        let selected = if id == selected { " selected" } else { "" };
+        write!(
+            options,
+            r#"<option value="{}"{}>{}</option>"#,
+            id,
+            selected,
+            escape_html(&layout.name)
+        )
+        .unwrap();
+    }
+    format!(
+        r#"<label>Layout
+<select name="template_id">{}</select>
+</label>"#,
+        options
+    )
+}
+
+/// Without a choice, the issue goes to the default list.
+pub fn list_checkboxes_html(lists: &[MailingList], selected: &[Uuid]) -> String {
+    let mut checkboxes = String::new();
+    for list in lists {
+        let checked = if selected.contains(&list.id) || (selected.is_empty() && list.is_default) {
+            " checked"
+        } else {
+            ""
+        };
+        writeln!(
+            checkboxes,
+            r#"<label><input type="checkbox" name="list_ids" value="{}"{}> {}</label>"#,
+            list.id,
+            checked,
+            escape_html(&list.name)
+        )
+        .unwrap();
+    }
+    format!(
+        "<fieldset>
+<legend>Lists</legend>
+{}</fieldset>",
+        checkboxes
+    )
+}
+
+pub fn segment_select_html(segments: &[Segment], selected: &str) -> String {
+    let mut options = String::from(r#"<option value="">No segment</option>"#);
+    for segment in segments {
+        let id = segment.id.to_string();
+        let selected = if id == selected { " selected" } else { "" };
+        write!(
+            options,
+            r#"<option value="{}"{}>{}</option>"#,
+            id,
+            selected,
+            escape_html(&segment.name)
+        )
+        .unwrap();
+    }
+    format!(
+        r#"<label>Segment
+<select name="segment_id">{}</select>
+</label>"#,
+        options
+    )
+}
+
+/// What can be picked in the form of an issue.
+pub struct IssueFormOptions {
+    pub layouts: Vec<Layout>,
+    pub lists: Vec<MailingList>,
+    pub segments: Vec<Segment>,
+    /// Their keys can be used as merge tags.
+    pub attributes: Vec<AttributeDefinition>,
+}
+
+impl IssueFormOptions {
+    pub async fn load(pool: &PgPool) -> Result<Self, anyhow::Error> {
+        Ok(IssueFormOptions {
+            layouts: list_layouts(pool).await?,
+            lists: list_lists(pool).await?,
+            segments: list_segments(pool).await?,
+            attributes: list_definitions(pool).await?,
+        })
+    }
+}
+
+pub fn flash_messages_html(
+    flash_message: &IncomingFlashMessages,
+) -> Result<String, actix_web::Error> {
+    let mut message_html = String::new();
+    for m in flash_message.iter() {
+        writeln!(message_html, "<p><i>{}</i></p>", m.content()).map_err(e500)?;
+    }
+    Ok(message_html)
+}
+
+pub fn issue_form_html(
+    page_title: &str,
+    message_html: &str,
+    action: &str,
+    form: &NewsletterForm,
+    options: &IssueFormOptions,
+    back: &str,
+) -> String {
+    let layout_select = layout_select_html(&options.layouts, &form.template_id);
+    let list_checkboxes = list_checkboxes_html(&options.lists, &form.list_ids);
+    let segment_select = segment_select_html(&options.segments, &form.segment_id);
+    let title = escape_html(&form.title);
+    let html = escape_html(&form.html);
+    let text = escape_html(&form.text);
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>{page_title}</title>
+</head>
+<body>
+{message_html}
+<form action="{action}" method="post">
+<label>Title
+<input type="text" placeholder="Enter the issue title" name="title" value="{title}">
+</label>
+<br>
+<label>HTML content
+<textarea placeholder="Enter the content as HTML" name="html" rows="20" cols="80">{html}</textarea>
+</label>
+<br>
+<label>Plain text content
+<textarea placeholder="Enter the content as plain text" name="text" rows="20" cols="80">{text}</textarea>
+</label>
+<br>
+{layout_select}
+<br>
+{list_checkboxes}
+{segment_select}
+<br>
+<button type="submit">Save draft</button>
+</form>
+<p><a href="{back}">&lt;- Back</a></p>
+</body>
+</html>"#,
+    )
+}
+
+pub async fn newsletter_form(
+    pool: web::Data<PgPool>,
+    flash_message: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let message_html = flash_messages_html(&flash_message)?;
+    let options = IssueFormOptions::load(&pool).await.map_err(e500)?;
+    let form = NewsletterForm {
+        title: String::new(),
+        html: String::new(),
+        text: String::new(),
+        template_id: String::new(),
+        list_ids: Vec::new(),
+        segment_id: String::new(),
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(issue_form_html(
+            "New newsletter issue",
+            &message_html,
+            "/admin/newsletters",
+            &form,
+            &options,
+            "/admin/newsletters",
+        )))
+}
+
+#[tracing::instrument(name = "Create a newsletter issue draft", skip(body, pool))]
+pub async fn create_newsletter_issue(
+    body: web::Bytes,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let form = NewsletterForm::parse(&body)?;
+    let options = IssueFormOptions::load(&pool).await.map_err(e500)?;
+    if let Err(e) = form
+        .validate(&options)
+        .and_then(|_| form.validate_audience(&options))
+    {
+        FlashMessage::error(e).send();
+        return Ok(see_other("/admin/newsletters/new"));
+    }
+    let template_id = form.template_id().map_err(e500)?;
+    if let Some(id) = template_id
+        && get_layout(&pool, id).await.map_err(e500)?.is_none()
+    {
+        FlashMessage::error("The layout does not exist.").send();
+        return Ok(see_other("/admin/newsletters/new"));
+    }
+    let html = prepare_email_html(&form.html);
+    let issue_id = insert_draft(
+        pool.get_ref(),
+        &form.title,
+        &form.text,
+        &html,
+        template_id,
+        &form.audience().map_err(e500)?,
+    )
+    .await
+    .map_err(e500)?;
+    FlashMessage::info("The draft has been saved.").send();
+    Ok(see_other(&format!("/admin/newsletters/{}", issue_id)))
+}
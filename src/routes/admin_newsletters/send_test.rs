@@ -0,0 +1,142 @@
+use crate::attributes::get_recipient_attributes;
+use crate::authentication::UserId;
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::issue_rendering::{IssueRenderer, Recipient};
+use crate::mailing_lists::get_recipient_list;
+use crate::newsletter_issues::get_issue;
+use crate::templates::get_email_layout;
+use crate::utils::{e500, see_other};
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// A test can be sent to a handful of addresses, not to a list.
+const MAX_TEST_RECIPIENTS: usize = 5;
+
+#[derive(Deserialize)]
+pub struct SendTestForm {
+    #[serde(default)]
+    recipients: String,
+}
+
+#[tracing::instrument(
+    name = "Send a test of a newsletter issue",
+    skip(form, pool, email_client, renderer, user_id)
+)]
+pub async fn send_test_newsletter_issue(
+    issue_id: web::Path<Uuid>,
+    form: web::Form<SendTestForm>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    renderer: web::Data<IssueRenderer>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = issue_id.into_inner();
+    let redirect = see_other(&format!("/admin/newsletters/{}", issue_id));
+    let Some(issue) = get_issue(&pool, issue_id).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let layout = get_email_layout(&pool, issue.template_id)
+        .await
+        .map_err(e500)?;
+    let admin = sqlx::query!(
+        r#"SELECT username, email FROM users WHERE user_id = $1"#,
+        *user_id.into_inner()
+    )
+    .fetch_one(pool.as_ref())
+    .await
+    .context("Failed to fetch the current user")
+    .map_err(e500)?;
+
+    let addresses: Vec<String> = form
+        .recipients
+        .split([',', '\n'])
+        .map(|address| address.trim().to_string())
+        .filter(|address| !address.is_empty())
+        .collect();
+    let addresses = if addresses.is_empty() {
+        match admin.email {
+            Some(email) => vec![email],
+            None => {
+                FlashMessage::error(
+                    "Set your email in the notification settings or enter the addresses to send the test to.",
+                )
+                .send();
+                return Ok(redirect);
+            }
+        }
+    } else {
+        addresses
+    };
+    if addresses.len() > MAX_TEST_RECIPIENTS {
+        FlashMessage::error(format!(
+            "A test can be sent to at most {} addresses.",
+            MAX_TEST_RECIPIENTS
+        ))
+        .send();
+        return Ok(redirect);
+    }
+    let emails = match addresses
+        .into_iter()
+        .map(SubscriberEmail::parse)
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(emails) => emails,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(redirect);
+        }
+    };
+
+    for email in emails {
+        // Subscribers see their own details, whatever the case of the address
+        // typed, other addresses get the admin's.
+        let subscriber = sqlx::query!(
+            r#"SELECT email, name FROM subscriptions WHERE normalized_email = $1"#,
+            email.normalized()
+        )
+        .fetch_optional(pool.as_ref())
+        .await
+        .context("Failed to fetch the name of the recipient")
+        .map_err(e500)?;
+        let (stored_email, name) = match subscriber {
+            Some(subscriber) => (subscriber.email, subscriber.name),
+            None => (email.as_ref().to_string(), admin.username.clone()),
+        };
+        let attributes = get_recipient_attributes(&pool, &stored_email)
+            .await
+            .map_err(e500)?;
+        let list_id = get_recipient_list(&pool, issue_id, &stored_email)
+            .await
+            .map_err(e500)?;
+        let recipient = Recipient {
+            email,
+            name,
+            attributes,
+            list_id,
+        };
+        let rendered = match renderer.render(&issue, layout.as_ref(), &recipient) {
+            Ok(rendered) => rendered,
+            Err(e) => {
+                FlashMessage::error(e.to_string()).send();
+                return Ok(redirect);
+            }
+        };
+        email_client
+            .send_email(
+                &recipient.email,
+                &format!("[TEST] {}", rendered.subject),
+                &rendered.html,
+                &rendered.text,
+            )
+            .await
+            .context("Failed to send the test email")
+            .map_err(e500)?;
+    }
+    FlashMessage::info("The test email has been sent.").send();
+    Ok(redirect)
+}
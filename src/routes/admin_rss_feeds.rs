@@ -0,0 +1,149 @@
+use crate::routes::admin_newsletters::new::flash_messages_html;
+use crate::rss_feeds::{delete_feed, insert_feed, list_feeds};
+use crate::utils::{e500, escape_html, see_other};
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use reqwest::Url;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+/// A feed is polled at least once a week.
+const MAX_POLL_INTERVAL_MINUTES: i32 = 7 * 24 * 60;
+
+pub async fn list_rss_feeds(
+    pool: web::Data<PgPool>,
+    flash_message: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let message_html = flash_messages_html(&flash_message)?;
+    let feeds = list_feeds(&pool).await.map_err(e500)?;
+    let mut feeds_html = String::new();
+    for feed in feeds {
+        writeln!(
+            feeds_html,
+            r#"<tr><td>{url}</td><td>{interval} min</td><td>{mode}</td><td>{last_polled_at}</td><td>{last_error}</td>
+<td><form action="/admin/rss/{id}/delete" method="post"><button type="submit">Remove</button></form></td></tr>"#,
+            url = escape_html(&feed.url),
+            interval = feed.poll_interval_minutes,
+            mode = if feed.auto_send {
+                "Send right away"
+            } else {
+                "Create a draft"
+            },
+            last_polled_at = feed
+                .last_polled_at
+                .map(|last_polled_at| last_polled_at.format("%Y-%m-%d %H:%M UTC").to_string())
+                .unwrap_or_else(|| "Never".to_string()),
+            last_error = feed
+                .last_error
+                .map(|last_error| escape_html(&last_error))
+                .unwrap_or_default(),
+            id = feed.id,
+        )
+        .map_err(e500)?;
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>RSS feeds</title>
+</head>
+<body>
+{message_html}
+<h2>RSS feeds</h2>
+<p>The new items of these feeds are turned into newsletter issues.
+The items already in a feed when it is added are not.</p>
+<table>
+<tr><th>URL</th><th>Polling interval</th><th>New items</th><th>Last polled at</th><th>Last error</th><th></th></tr>
+{feeds_html}
+</table>
+<h2>Add a feed</h2>
+<form action="/admin/rss" method="post">
+<label>URL
+<input type="url" placeholder="https://example.com/feed.xml" name="url">
+</label>
+<br>
+<label>Polling interval (minutes)
+<input type="number" name="poll_interval_minutes" value="60" min="1" max="{MAX_POLL_INTERVAL_MINUTES}">
+</label>
+<br>
+<label>New items
+<select name="auto_send">
+<option value="false">Create a draft</option>
+<option value="true">Send right away</option>
+</select>
+</label>
+<br>
+<button type="submit">Add</button>
+</form>
+<p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[derive(Deserialize)]
+pub struct RssFeedForm {
+    url: String,
+    poll_interval_minutes: i32,
+    auto_send: bool,
+}
+
+impl RssFeedForm {
+    fn validate(&self) -> Result<Url, String> {
+        let url = Url::parse(self.url.trim())
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .ok_or_else(|| "The feed URL must be an http or https URL.".to_string())?;
+        if !(1..=MAX_POLL_INTERVAL_MINUTES).contains(&self.poll_interval_minutes) {
+            return Err(format!(
+                "The polling interval must be between 1 and {} minutes.",
+                MAX_POLL_INTERVAL_MINUTES
+            ));
+        }
+        Ok(url)
+    }
+}
+
+#[tracing::instrument(name = "Add an RSS feed", skip(form, pool))]
+pub async fn create_rss_feed(
+    form: web::Form<RssFeedForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let url = match form.validate() {
+        Ok(url) => url,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/rss"));
+        }
+    };
+    let inserted = insert_feed(
+        &pool,
+        url.as_str(),
+        form.poll_interval_minutes,
+        form.auto_send,
+    )
+    .await
+    .map_err(e500)?;
+    if inserted.is_some() {
+        FlashMessage::info("The feed has been added.").send();
+    } else {
+        FlashMessage::error("This feed is already registered.").send();
+    }
+    Ok(see_other("/admin/rss"))
+}
+
+#[tracing::instrument(name = "Remove an RSS feed", skip(pool))]
+pub async fn delete_rss_feed(
+    feed_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if delete_feed(&pool, *feed_id).await.map_err(e500)? {
+        FlashMessage::info("The feed has been removed.").send();
+    }
+    Ok(see_other("/admin/rss"))
+}
@@ -0,0 +1,130 @@
+use crate::attributes::list_definitions;
+use crate::routes::admin_newsletters::new::flash_messages_html;
+use crate::segments::{self, Condition, count_segments_members, insert_segment, list_segments};
+use crate::utils::{e500, escape_html, see_other};
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+const EXAMPLE: &str =
+    r#"{"all": [{"tag": "beta"}, {"not": {"subscribed": {"to": "2025-12-31"}}}]}"#;
+
+pub async fn list_segments_page(
+    pool: web::Data<PgPool>,
+    flash_message: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let message_html = flash_messages_html(&flash_message)?;
+    let segments = list_segments(&pool).await.map_err(e500)?;
+    let mut segments_html = String::new();
+    let members = count_segments_members(&pool, &segments)
+        .await
+        .map_err(e500)?;
+    for (segment, members) in segments.iter().zip(members) {
+        writeln!(
+            segments_html,
+            r#"<tr><td>{name}</td><td>{condition}</td><td>{members}</td><td><form action="/admin/segments/{id}/delete" method="post"><button type="submit">Delete</button></form></td></tr>"#,
+            name = escape_html(&segment.name),
+            condition = escape_html(&segment.condition.to_string()),
+            id = segment.id,
+        )
+        .map_err(e500)?;
+    }
+    let example = escape_html(EXAMPLE);
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>Segments</title>
+</head>
+<body>
+{message_html}
+<h2>Segments</h2>
+<p>An issue sent to a segment only goes to the confirmed members of its lists that are in the segment.</p>
+<table>
+<tr><th>Name</th><th>Definition</th><th>Confirmed subscribers</th><th></th></tr>
+{segments_html}
+</table>
+<h2>Create a segment</h2>
+<p>A segment is defined in JSON with <code>{{"tag": ".."}}</code>, <code>{{"subscribed": {{"from": "YYYY-MM-DD", "to": "YYYY-MM-DD"}}}}</code> (either end can be left out), <code>{{"attribute": {{"key": "..", "op": "eq", "value": ..}}}}</code> (with the ops <code>eq</code>, <code>lt</code>, <code>lte</code>, <code>gt</code>, <code>gte</code> for numbers and dates, and <code>is_set</code> without a value), combined with <code>{{"all": [..]}}</code>, <code>{{"any": [..]}}</code> and <code>{{"not": ..}}</code>.</p>
+<form action="/admin/segments" method="post">
+<label>Name
+<input type="text" placeholder="Enter the name of the segment" name="name">
+</label>
+<br>
+<label>Definition
+<textarea placeholder="{example}" name="definition" rows="6" cols="80"></textarea>
+</label>
+<br>
+<button type="submit">Create</button>
+</form>
+<p><a href="/admin/tags">Tags</a></p>
+<p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[derive(Deserialize)]
+pub struct SegmentForm {
+    name: String,
+    definition: String,
+}
+
+#[tracing::instrument(name = "Create a segment", skip(form, pool))]
+pub async fn create_segment(
+    form: web::Form<SegmentForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        FlashMessage::error("The name of the segment is missing.").send();
+        return Ok(see_other("/admin/segments"));
+    }
+    let definitions = list_definitions(&pool).await.map_err(e500)?;
+    let condition = match Condition::parse(&form.definition).and_then(|mut condition| {
+        condition.check_attributes(&definitions)?;
+        Ok(condition)
+    }) {
+        Ok(condition) => condition,
+        Err(e) => {
+            FlashMessage::error(format!(
+                "The definition of the segment is invalid: {}",
+                escape_html(&e)
+            ))
+            .send();
+            return Ok(see_other("/admin/segments"));
+        }
+    };
+    if insert_segment(&pool, name, &condition)
+        .await
+        .map_err(e500)?
+        .is_some()
+    {
+        FlashMessage::info("The segment has been created.").send();
+    } else {
+        FlashMessage::error("A segment with this name already exists.").send();
+    }
+    Ok(see_other("/admin/segments"))
+}
+
+#[tracing::instrument(name = "Remove a segment", skip(pool))]
+pub async fn delete_segment(
+    segment_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if segments::delete_segment(&pool, *segment_id)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::info("The segment has been deleted.").send();
+    } else {
+        FlashMessage::error("Segments used by an issue cannot be deleted.").send();
+    }
+    Ok(see_other("/admin/segments"))
+}
@@ -0,0 +1,368 @@
+use crate::audit::{AuditAction, record_action};
+use crate::authentication::UserId;
+use crate::domain::{NewSubscriber, SubscriberEmail, SuscriberName};
+use crate::email_client::EmailClient;
+use crate::erasure::is_erased;
+use crate::mailing_lists::get_list_by_slug;
+use crate::routes::subscriptions::{
+    generate_random_token, insert_membership, insert_suscriber, send_email, store_token,
+};
+use crate::startup::ApplicationBaseUrl;
+use crate::subscribers::{
+    confirm_pending_memberships, delete_subscriber as delete_subscriber_rows, end_memberships,
+    get_pending_lists, get_subscriber, insert_confirmed_membership, update_subscriber,
+};
+use crate::utils::{e500, escape_html, see_other};
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+fn parse_subscriber(email: &str, name: &str) -> Result<NewSubscriber, String> {
+    Ok(NewSubscriber {
+        email: SubscriberEmail::parse(email.trim().to_string())?,
+        name: SuscriberName::parse(name.trim().to_string())?,
+    })
+}
+
+fn detail_page(subscriber_id: Uuid) -> HttpResponse {
+    see_other(&format!("/admin/subscribers/{}", subscriber_id))
+}
+
+#[derive(Deserialize)]
+pub struct AddSubscriberForm {
+    email: String,
+    name: String,
+    /// The slug of the list, the default list if empty.
+    #[serde(default)]
+    list: String,
+    /// Adds the subscriber as confirmed, without the confirmation email.
+    #[serde(default)]
+    skip_confirmation: bool,
+    /// Why the double opt-in was skipped, required to skip it.
+    #[serde(default)]
+    reason: String,
+}
+
+#[tracing::instrument(
+    name = "Add a subscriber as an admin",
+    skip(form, pool, email_client, base_url, user_id),
+    fields(subscriber_email = %form.email)
+)]
+pub async fn add_subscriber(
+    form: web::Form<AddSubscriberForm>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(list) = get_list_by_slug(&pool, &form.list).await.map_err(e500)? else {
+        FlashMessage::error("The list does not exist.").send();
+        return Ok(see_other("/admin/subscribers"));
+    };
+    let reason = form.reason.trim();
+    if form.skip_confirmation && reason.is_empty() {
+        FlashMessage::error("A reason is needed to skip the confirmation.").send();
+        return Ok(see_other("/admin/subscribers"));
+    }
+    let new_subscriber = match parse_subscriber(&form.email, &form.name) {
+        Ok(new_subscriber) => new_subscriber,
+        Err(e) => {
+            FlashMessage::error(escape_html(&e)).send();
+            return Ok(see_other("/admin/subscribers"));
+        }
+    };
+    if is_erased(pool.get_ref(), new_subscriber.email.as_ref())
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::error("This person asked for their data to be erased.").send();
+        return Ok(see_other("/admin/subscribers"));
+    }
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    let (subscriber_id, _) = insert_suscriber(&mut transaction, &new_subscriber)
+        .await
+        .map_err(e500)?;
+    let (added, token, details) = if form.skip_confirmation {
+        let added = insert_confirmed_membership(&mut transaction, subscriber_id, list.id)
+            .await
+            .map_err(e500)?;
+        let details = format!(
+            "Added to {} as confirmed, the confirmation was skipped: {}",
+            list.name, reason
+        );
+        (added, None, details)
+    } else {
+        let added = insert_membership(&mut transaction, subscriber_id, list.id)
+            .await
+            .map_err(e500)?;
+        let token = generate_random_token();
+        if added {
+            store_token(&mut transaction, subscriber_id, list.id, &token)
+                .await
+                .map_err(e500)?;
+        }
+        let details = format!("Added to {}, a confirmation email was sent.", list.name);
+        (added, Some(token), details)
+    };
+    if !added {
+        FlashMessage::error("The subscriber is already confirmed on this list.").send();
+        return Ok(detail_page(subscriber_id));
+    }
+    record_action(
+        &mut transaction,
+        **user_id,
+        AuditAction::AddSubscriber,
+        Some(subscriber_id),
+        Some(new_subscriber.email.as_ref()),
+        &details,
+    )
+    .await
+    .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+    if let Some(token) = token {
+        send_email(
+            &pool,
+            &email_client,
+            new_subscriber,
+            &list,
+            base_url,
+            &token,
+        )
+        .await
+        .map_err(e500)?;
+    }
+    FlashMessage::info("The subscriber has been added.").send();
+    Ok(detail_page(subscriber_id))
+}
+
+#[derive(Deserialize)]
+pub struct EditSubscriberForm {
+    email: String,
+    name: String,
+}
+
+#[tracing::instrument(name = "Edit a subscriber", skip(form, pool, user_id))]
+pub async fn edit_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    form: web::Form<EditSubscriberForm>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let Some(subscriber) = get_subscriber(&pool, subscriber_id).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let edited = match parse_subscriber(&form.email, &form.name) {
+        Ok(edited) => edited,
+        Err(e) => {
+            FlashMessage::error(escape_html(&e)).send();
+            return Ok(detail_page(subscriber_id));
+        }
+    };
+    let mut changes = Vec::new();
+    if edited.email.as_ref() != subscriber.email {
+        changes.push(format!(
+            "email: {} -> {}",
+            subscriber.email,
+            edited.email.as_ref()
+        ));
+    }
+    if edited.name.as_ref() != subscriber.name {
+        changes.push(format!(
+            "name: {} -> {}",
+            subscriber.name,
+            edited.name.as_ref()
+        ));
+    }
+    if changes.is_empty() {
+        FlashMessage::info("Nothing has changed.").send();
+        return Ok(detail_page(subscriber_id));
+    }
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    if !update_subscriber(&mut transaction, subscriber_id, &edited)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::error("Another subscriber has this email.").send();
+        return Ok(detail_page(subscriber_id));
+    }
+    record_action(
+        &mut transaction,
+        **user_id,
+        AuditAction::EditSubscriber,
+        Some(subscriber_id),
+        Some(edited.email.as_ref()),
+        &changes.join(", "),
+    )
+    .await
+    .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+    FlashMessage::info("The subscriber has been updated.").send();
+    Ok(detail_page(subscriber_id))
+}
+
+/// Confirms the pending memberships without the confirmation link.
+#[tracing::instrument(name = "Force the confirmation of a subscriber", skip(pool, user_id))]
+pub async fn confirm_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let Some(subscriber) = get_subscriber(&pool, subscriber_id).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    let lists = confirm_pending_memberships(&mut transaction, subscriber_id)
+        .await
+        .map_err(e500)?;
+    if lists.is_empty() {
+        FlashMessage::error("There is no pending subscription to confirm.").send();
+        return Ok(detail_page(subscriber_id));
+    }
+    record_action(
+        &mut transaction,
+        **user_id,
+        AuditAction::ForceConfirm,
+        Some(subscriber_id),
+        Some(&subscriber.email),
+        &format!("Confirmed on {}", lists.join(", ")),
+    )
+    .await
+    .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+    FlashMessage::info("The subscriber has been confirmed.").send();
+    Ok(detail_page(subscriber_id))
+}
+
+#[tracing::instrument(name = "Unsubscribe a subscriber as an admin", skip(pool, user_id))]
+pub async fn unsubscribe_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let Some(subscriber) = get_subscriber(&pool, subscriber_id).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    let lists = end_memberships(&mut transaction, subscriber_id)
+        .await
+        .map_err(e500)?;
+    if lists.is_empty() {
+        FlashMessage::error("The subscriber is not on any list.").send();
+        return Ok(detail_page(subscriber_id));
+    }
+    record_action(
+        &mut transaction,
+        **user_id,
+        AuditAction::Unsubscribe,
+        Some(subscriber_id),
+        Some(&subscriber.email),
+        &format!("Unsubscribed from {}", lists.join(", ")),
+    )
+    .await
+    .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+    FlashMessage::info("The subscriber has been unsubscribed.").send();
+    Ok(detail_page(subscriber_id))
+}
+
+/// Sends a new confirmation link for each pending membership, the previous
+/// links still work.
+#[tracing::instrument(
+    name = "Resend the confirmation email",
+    skip(pool, email_client, base_url, user_id)
+)]
+pub async fn resend_confirmation(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let Some(subscriber) = get_subscriber(&pool, subscriber_id).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let lists = get_pending_lists(&pool, subscriber_id)
+        .await
+        .map_err(e500)?;
+    if lists.is_empty() {
+        FlashMessage::error("There is no pending subscription to confirm.").send();
+        return Ok(detail_page(subscriber_id));
+    }
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    let mut tokens = Vec::new();
+    for list in &lists {
+        let token = generate_random_token();
+        store_token(&mut transaction, subscriber_id, list.id, &token)
+            .await
+            .map_err(e500)?;
+        tokens.push(token);
+    }
+    let list_names: Vec<&str> = lists.iter().map(|list| list.name.as_str()).collect();
+    record_action(
+        &mut transaction,
+        **user_id,
+        AuditAction::ResendConfirmation,
+        Some(subscriber_id),
+        Some(&subscriber.email),
+        &format!("Sent for {}", list_names.join(", ")),
+    )
+    .await
+    .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+    for (list, token) in lists.iter().zip(tokens) {
+        let recipient = parse_subscriber(&subscriber.email, &subscriber.name)
+            .map_err(actix_web::error::ErrorBadRequest)?;
+        send_email(
+            &pool,
+            &email_client,
+            recipient,
+            list,
+            base_url.clone(),
+            &token,
+        )
+        .await
+        .map_err(e500)?;
+    }
+    FlashMessage::info("The confirmation email has been sent again.").send();
+    Ok(detail_page(subscriber_id))
+}
+
+/// Deletes every trace of the subscriber but the audit trail.
+#[tracing::instrument(name = "Hard-delete a subscriber", skip(pool, user_id))]
+pub async fn delete_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let Some(subscriber) = get_subscriber(&pool, subscriber_id).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    let n_tokens = delete_subscriber_rows(&mut transaction, subscriber_id)
+        .await
+        .map_err(e500)?;
+    record_action(
+        &mut transaction,
+        **user_id,
+        AuditAction::DeleteSubscriber,
+        Some(subscriber_id),
+        Some(&subscriber.email),
+        &format!("Deleted, confirmation links removed: {}", n_tokens),
+    )
+    .await
+    .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+    FlashMessage::info(format!(
+        "{} has been deleted.",
+        escape_html(&subscriber.email)
+    ))
+    .send();
+    Ok(see_other("/admin/subscribers"))
+}
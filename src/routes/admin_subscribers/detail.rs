@@ -0,0 +1,368 @@
+use crate::attributes::get_recipient_attributes;
+use crate::audit::{AuditEntry, list_entries};
+use crate::consent::{ConsentRecord, get_consent_history};
+use crate::routes::admin_audit::audit_entries_html;
+use crate::routes::admin_newsletters::new::flash_messages_html;
+use crate::subscribers::{
+    DeliveryRecord, Membership, Subscriber, TokenRecord, get_delivery_history, get_memberships,
+    get_subscriber, get_token_history,
+};
+use crate::tags::get_subscriber_tags;
+use crate::utils::{e500, escape_html};
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+/// Everything known about a subscriber.
+pub(crate) struct SubscriberDetail {
+    subscriber: Subscriber,
+    memberships: Vec<Membership>,
+    tags: Vec<String>,
+    attributes: Vec<(String, String)>,
+    tokens: Vec<TokenRecord>,
+    consents: Vec<ConsentRecord>,
+    deliveries: Vec<DeliveryRecord>,
+    history: Vec<AuditEntry>,
+}
+
+impl SubscriberDetail {
+    pub(crate) async fn load(
+        pool: &PgPool,
+        subscriber_id: Uuid,
+    ) -> Result<Option<Self>, anyhow::Error> {
+        let Some(subscriber) = get_subscriber(pool, subscriber_id).await? else {
+            return Ok(None);
+        };
+        Ok(Some(SubscriberDetail {
+            memberships: get_memberships(pool, subscriber.id).await?,
+            tags: get_subscriber_tags(pool, subscriber.id).await?,
+            attributes: get_recipient_attributes(pool, &subscriber.email).await?,
+            tokens: get_token_history(pool, subscriber.id).await?,
+            consents: get_consent_history(pool, subscriber.id).await?,
+            deliveries: get_delivery_history(pool, subscriber.id).await?,
+            history: list_entries(pool, Some(subscriber.id)).await?,
+            subscriber,
+        }))
+    }
+}
+
+fn format_time(time: Option<DateTime<Utc>>) -> String {
+    time.map(|time| time.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+pub async fn subscriber_detail(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    flash_message: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let message_html = flash_messages_html(&flash_message)?;
+    let Some(detail) = SubscriberDetail::load(&pool, *subscriber_id)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let mut lists_html = String::new();
+    for membership in &detail.memberships {
+        writeln!(
+            lists_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape_html(&membership.list_name),
+            membership.status,
+            format_time(Some(membership.subscribed_at)),
+        )
+        .map_err(e500)?;
+    }
+    let mut attributes_html = String::new();
+    for (key, value) in &detail.attributes {
+        writeln!(
+            attributes_html,
+            "<tr><td><code>{}</code></td><td>{}</td></tr>",
+            escape_html(key),
+            escape_html(value),
+        )
+        .map_err(e500)?;
+    }
+    let mut tokens_html = String::new();
+    for token in &detail.tokens {
+        writeln!(
+            tokens_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape_html(&token.list_name),
+            format_time(token.created_at),
+            format_time(token.used_at),
+        )
+        .map_err(e500)?;
+    }
+    if detail.tokens.is_empty() {
+        tokens_html.push_str("<tr><td>No confirmation link was sent.</td></tr>\n");
+    }
+    let mut consents_html = String::new();
+    for consent in &detail.consents {
+        writeln!(
+            consents_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape_html(&consent.list_name),
+            escape_html(&consent.source),
+            escape_html(&consent.text_version),
+            format_time(Some(consent.signed_up_at)),
+            escape_html(consent.signup_ip.as_deref().unwrap_or("-")),
+            escape_html(consent.signup_user_agent.as_deref().unwrap_or("-")),
+            format_time(consent.confirmed_at),
+            escape_html(consent.confirmation_ip.as_deref().unwrap_or("-")),
+            escape_html(consent.confirmation_user_agent.as_deref().unwrap_or("-")),
+        )
+        .map_err(e500)?;
+    }
+    if detail.consents.is_empty() {
+        consents_html.push_str("<tr><td>No consent was recorded.</td></tr>\n");
+    }
+    let mut deliveries_html = String::new();
+    for delivery in &detail.deliveries {
+        writeln!(
+            deliveries_html,
+            r#"<tr><td><a href="/admin/newsletters/{}">{}</a></td><td>{}</td><td>{}</td><td>{}</td></tr>"#,
+            delivery.issue_id,
+            escape_html(&delivery.issue_title),
+            delivery.outcome,
+            delivery.n_attempts,
+            format_time(Some(delivery.delivered_at)),
+        )
+        .map_err(e500)?;
+    }
+    if detail.deliveries.is_empty() {
+        deliveries_html.push_str("<tr><td>No issue was delivered.</td></tr>\n");
+    }
+    let history_html = audit_entries_html(&detail.history, false)?;
+    let subscriber = &detail.subscriber;
+    let id = subscriber.id;
+    let email = escape_html(&subscriber.email);
+    let name = escape_html(&subscriber.name);
+    let subscribed_at = format_time(Some(subscriber.subscribed_at));
+    let tags = escape_html(&detail.tags.join(", "));
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>Subscriber</title>
+</head>
+<body>
+{message_html}
+<h2>{email}</h2>
+<p>Name: {name}</p>
+<p>Subscribed at: {subscribed_at}</p>
+<p>Tags: {tags}</p>
+<h3>Lists</h3>
+<table>
+<tr><th>List</th><th>Status</th><th>Since</th></tr>
+{lists_html}</table>
+<h3>Attributes</h3>
+<table>
+{attributes_html}</table>
+<h3>Confirmation links</h3>
+<table>
+<tr><th>List</th><th>Sent at</th><th>Used at</th></tr>
+{tokens_html}</table>
+<h3>Consent</h3>
+<table>
+<tr><th>List</th><th>Source</th><th>Text version</th><th>Signed up at</th><th>IP address</th><th>User agent</th><th>Confirmed at</th><th>IP address</th><th>User agent</th></tr>
+{consents_html}</table>
+<h3>Deliveries</h3>
+<table>
+<tr><th>Issue</th><th>Outcome</th><th>Attempts</th><th>At</th></tr>
+{deliveries_html}</table>
+<h3>History</h3>
+<table>
+<tr><th>At</th><th>Admin</th><th>Action</th><th>Details</th></tr>
+{history_html}</table>
+<h3>Edit</h3>
+<form action="/admin/subscribers/{id}/edit" method="post">
+<label>Email
+<input type="email" name="email" value="{email}">
+</label>
+<label>Name
+<input type="text" name="name" value="{name}">
+</label>
+<button type="submit">Save</button>
+</form>
+<h3>Actions</h3>
+<form action="/admin/subscribers/{id}/confirm" method="post">
+<button type="submit">Confirm without the link</button>
+</form>
+<form action="/admin/subscribers/{id}/resend" method="post">
+<button type="submit">Resend the confirmation email</button>
+</form>
+<form action="/admin/subscribers/{id}/unsubscribe" method="post">
+<button type="submit">Unsubscribe from every list</button>
+</form>
+<form action="/admin/subscribers/{id}/delete" method="post">
+<button type="submit">Delete the subscriber and their data</button>
+</form>
+<p><a href="/admin/subscribers">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[derive(Serialize)]
+struct MembershipJson {
+    list: String,
+    status: &'static str,
+    subscribed_at: String,
+}
+
+#[derive(Serialize)]
+struct TokenJson {
+    list: String,
+    created_at: Option<String>,
+    used_at: Option<String>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ConsentJson {
+    list: String,
+    source: String,
+    text_version: String,
+    signup_ip: Option<String>,
+    signup_user_agent: Option<String>,
+    signed_up_at: String,
+    confirmation_ip: Option<String>,
+    confirmation_user_agent: Option<String>,
+    confirmed_at: Option<String>,
+}
+
+impl From<ConsentRecord> for ConsentJson {
+    fn from(consent: ConsentRecord) -> Self {
+        ConsentJson {
+            list: consent.list_name,
+            source: consent.source,
+            text_version: consent.text_version,
+            signup_ip: consent.signup_ip,
+            signup_user_agent: consent.signup_user_agent,
+            signed_up_at: consent.signed_up_at.to_rfc3339(),
+            confirmation_ip: consent.confirmation_ip,
+            confirmation_user_agent: consent.confirmation_user_agent,
+            confirmed_at: consent.confirmed_at.map(|time| time.to_rfc3339()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct DeliveryJson {
+    issue_id: Uuid,
+    issue_title: String,
+    outcome: String,
+    attempts: i16,
+    delivered_at: String,
+}
+
+#[derive(Serialize)]
+struct AuditEntryJson {
+    action: &'static str,
+    admin: Option<String>,
+    details: String,
+    at: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct SubscriberDetailJson {
+    id: Uuid,
+    email: String,
+    name: String,
+    subscribed_at: String,
+    lists: Vec<MembershipJson>,
+    tags: Vec<String>,
+    attributes: serde_json::Map<String, serde_json::Value>,
+    tokens: Vec<TokenJson>,
+    consents: Vec<ConsentJson>,
+    deliveries: Vec<DeliveryJson>,
+    history: Vec<AuditEntryJson>,
+}
+
+impl From<SubscriberDetail> for SubscriberDetailJson {
+    fn from(detail: SubscriberDetail) -> Self {
+        SubscriberDetailJson {
+            id: detail.subscriber.id,
+            email: detail.subscriber.email,
+            name: detail.subscriber.name,
+            subscribed_at: detail.subscriber.subscribed_at.to_rfc3339(),
+            lists: detail
+                .memberships
+                .into_iter()
+                .map(|membership| MembershipJson {
+                    list: membership.list_name,
+                    status: membership.status.as_str(),
+                    subscribed_at: membership.subscribed_at.to_rfc3339(),
+                })
+                .collect(),
+            tags: detail.tags,
+            attributes: detail
+                .attributes
+                .into_iter()
+                .map(|(key, value)| (key, serde_json::Value::String(value)))
+                .collect(),
+            tokens: detail
+                .tokens
+                .into_iter()
+                .map(|token| TokenJson {
+                    list: token.list_name,
+                    created_at: token.created_at.map(|time| time.to_rfc3339()),
+                    used_at: token.used_at.map(|time| time.to_rfc3339()),
+                })
+                .collect(),
+            consents: detail.consents.into_iter().map(ConsentJson::from).collect(),
+            deliveries: detail
+                .deliveries
+                .into_iter()
+                .map(|delivery| DeliveryJson {
+                    issue_id: delivery.issue_id,
+                    issue_title: delivery.issue_title,
+                    outcome: delivery.outcome,
+                    attempts: delivery.n_attempts,
+                    delivered_at: delivery.delivered_at.to_rfc3339(),
+                })
+                .collect(),
+            history: detail
+                .history
+                .into_iter()
+                .map(|entry| AuditEntryJson {
+                    action: entry.action.as_str(),
+                    admin: entry.username,
+                    details: entry.details,
+                    at: entry.created_at.to_rfc3339(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl SubscriberDetailJson {
+    /// The same data for the subscriber themselves, without the admins' names.
+    pub(crate) fn without_admins(mut self) -> Self {
+        for entry in &mut self.history {
+            entry.admin = None;
+        }
+        self
+    }
+}
+
+pub async fn subscriber_detail_json(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(detail) = SubscriberDetail::load(&pool, *subscriber_id)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    Ok(HttpResponse::Ok().json(SubscriberDetailJson::from(detail)))
+}
@@ -0,0 +1,109 @@
+use crate::audit::{AuditAction, record_action};
+use crate::authentication::UserId;
+use crate::routes::admin_newsletters::new::flash_messages_html;
+use crate::subscriber_duplicates::{get_unmerged_duplicate, list_duplicates, merge_subscribers};
+use crate::utils::{e500, escape_html, see_other};
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+/// The merge report of the subscribers whose emails only differed by case.
+pub async fn duplicates_page(
+    pool: web::Data<PgPool>,
+    flash_message: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let message_html = flash_messages_html(&flash_message)?;
+    let mut duplicates_html = String::new();
+    for duplicate in list_duplicates(&pool).await.map_err(e500)? {
+        let kept = match &duplicate.kept_email {
+            Some(email) => format!(
+                r#"<a href="/admin/subscribers/{}">{}</a>"#,
+                duplicate.kept_id,
+                escape_html(email)
+            ),
+            None => "Deleted".to_string(),
+        };
+        let merge = match (duplicate.merged_at, &duplicate.kept_email) {
+            (Some(merged_at), _) => format!("Merged {}", merged_at.format("%Y-%m-%d %H:%M UTC")),
+            (None, Some(_)) => format!(
+                r#"<form action="/admin/subscribers/duplicates/{}/merge" method="post"><button type="submit">Merge</button></form>"#,
+                duplicate.duplicate_id
+            ),
+            (None, None) => "-".to_string(),
+        };
+        writeln!(
+            duplicates_html,
+            r#"<tr><td><a href="/admin/subscribers/{}">{}</a></td><td>{}</td><td>{}</td><td>{}</td></tr>"#,
+            duplicate.duplicate_id,
+            escape_html(&duplicate.email),
+            kept,
+            duplicate.detected_at.format("%Y-%m-%d %H:%M UTC"),
+            merge,
+        )
+        .map_err(e500)?;
+    }
+    if duplicates_html.is_empty() {
+        duplicates_html.push_str("<tr><td>No duplicate subscriber was found.</td></tr>\n");
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>Duplicate subscribers</title>
+</head>
+<body>
+{message_html}
+<h2>Duplicate subscribers</h2>
+<p>These subscribers have the email of an older subscriber in another case. Merging one moves its lists, tags, attributes and history to the older subscriber, then deletes it.</p>
+<table>
+<tr><th>Duplicate</th><th>Merged into</th><th>Detected at</th><th></th></tr>
+{duplicates_html}</table>
+<p><a href="/admin/subscribers">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[tracing::instrument(name = "Merge a duplicate subscriber", skip(pool, user_id))]
+pub async fn merge_duplicate(
+    duplicate_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let duplicate_id = duplicate_id.into_inner();
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    let Some((email, kept_id, kept_email)) = get_unmerged_duplicate(&mut transaction, duplicate_id)
+        .await
+        .map_err(e500)?
+    else {
+        FlashMessage::error("This duplicate cannot be merged anymore.").send();
+        return Ok(see_other("/admin/subscribers/duplicates"));
+    };
+    merge_subscribers(&mut transaction, duplicate_id, kept_id)
+        .await
+        .map_err(e500)?;
+    record_action(
+        &mut transaction,
+        **user_id,
+        AuditAction::MergeSubscribers,
+        Some(kept_id),
+        Some(&kept_email),
+        &format!("Merged {} into {}", email, kept_email),
+    )
+    .await
+    .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+    FlashMessage::info(format!(
+        "{} has been merged into {}.",
+        escape_html(&email),
+        escape_html(&kept_email)
+    ))
+    .send();
+    Ok(see_other("/admin/subscribers/duplicates"))
+}
@@ -0,0 +1,329 @@
+use crate::audit::{AuditAction, record_action};
+use crate::authentication::UserId;
+use crate::routes::admin_subscribers::detail::ConsentJson;
+use crate::routes::admin_subscribers::list::{SubscriberJson, SubscribersQuery};
+use crate::subscribers::{ExportedSubscriber, SubscriberFilter, stream_subscribers};
+use crate::utils::e500;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{HttpResponse, web, web::Bytes};
+use chrono::{DateTime, Utc};
+use futures::channel::mpsc;
+use futures::{SinkExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::borrow::Cow;
+
+/// The size of the chunks sent to the client, in bytes.
+const CHUNK_SIZE: usize = 64 * 1024;
+/// The chunks encoded ahead of the client before the export waits for it.
+const BUFFERED_CHUNKS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    /// One JSON object per line.
+    Ndjson,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 2] = [ExportFormat::Csv, ExportFormat::Ndjson];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+        }
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+}
+
+impl TryFrom<&str> for ExportFormat {
+    type Error = String;
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        ExportFormat::ALL
+            .into_iter()
+            .find(|format| format.as_str() == s)
+            .ok_or_else(|| format!("{} is not an export format, use csv or ndjson.", s))
+    }
+}
+
+/// The subscribers to export, a CSV file of all of them by default.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ExportQuery {
+    /// `csv` or `ndjson`.
+    #[serde(default)]
+    pub format: String,
+    /// Empty for any status.
+    #[serde(default)]
+    pub status: String,
+    /// `YYYY-MM-DD`, the first day of the subscription date range.
+    #[serde(default)]
+    pub from: String,
+    /// `YYYY-MM-DD`, the last day of the subscription date range.
+    #[serde(default)]
+    pub to: String,
+}
+
+/// The rows are encoded as they come out of the database, so an export never
+/// holds more than a few chunks in memory.
+#[tracing::instrument(name = "Export subscribers", skip(pool, user_id))]
+pub async fn export_subscribers(
+    query: web::Query<ExportQuery>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let format = match query.format.as_str() {
+        "" => ExportFormat::default(),
+        format => ExportFormat::try_from(format).map_err(actix_web::error::ErrorBadRequest)?,
+    };
+    let filter = SubscribersQuery {
+        status: query.status.clone(),
+        from: query.from.clone(),
+        to: query.to.clone(),
+        ..Default::default()
+    }
+    .filter()
+    .map_err(actix_web::error::ErrorBadRequest)?;
+    let or_dash = |value: &str| {
+        if value.trim().is_empty() {
+            "-".to_string()
+        } else {
+            value.trim().to_string()
+        }
+    };
+    let details = format!(
+        "{} export, status: {}, from: {}, to: {}",
+        format.as_str(),
+        filter.status.map_or("any", |status| status.as_str()),
+        or_dash(&query.from),
+        or_dash(&query.to)
+    );
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    record_action(
+        &mut transaction,
+        **user_id,
+        AuditAction::ExportSubscribers,
+        None,
+        None,
+        &details,
+    )
+    .await
+    .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+
+    let (sender, receiver) = mpsc::channel(BUFFERED_CHUNKS);
+    actix_web::rt::spawn(write_export(pool.get_ref().clone(), filter, format, sender));
+    let file_name = format!(
+        "subscribers-{}.{}",
+        Utc::now().format("%Y-%m-%d"),
+        format.as_str()
+    );
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(file_name)],
+        })
+        .streaming(receiver))
+}
+
+type ChunkSender = mpsc::Sender<Result<Bytes, anyhow::Error>>;
+
+async fn write_export(
+    pool: PgPool,
+    filter: SubscriberFilter,
+    format: ExportFormat,
+    mut sender: ChunkSender,
+) {
+    if let Err(e) = encode_subscribers(&pool, &filter, format, &mut sender).await {
+        tracing::error!(error.cause_chain = ?e, "Failed to export the subscribers");
+        // The response is cut short rather than looking complete.
+        let _ = sender.send(Err(e)).await;
+    }
+}
+
+async fn encode_subscribers(
+    pool: &PgPool,
+    filter: &SubscriberFilter,
+    format: ExportFormat,
+    sender: &mut ChunkSender,
+) -> Result<(), anyhow::Error> {
+    let mut subscribers = std::pin::pin!(stream_subscribers(pool, filter));
+    let mut buffer = Vec::new();
+    if format == ExportFormat::Csv {
+        write_csv_record(&mut buffer, CSV_HEADER)?;
+    }
+    while let Some(subscriber) = subscribers.try_next().await? {
+        encode_subscriber(&mut buffer, format, subscriber)?;
+        if buffer.len() >= CHUNK_SIZE
+            && sender
+                .send(Ok(Bytes::from(std::mem::take(&mut buffer))))
+                .await
+                .is_err()
+        {
+            // The client went away.
+            return Ok(());
+        }
+    }
+    if !buffer.is_empty() {
+        let _ = sender.send(Ok(Bytes::from(buffer))).await;
+    }
+    Ok(())
+}
+
+/// The consent columns are those of the latest signup, empty if none was
+/// recorded.
+const CSV_HEADER: [&str; 14] = [
+    "id",
+    "email",
+    "name",
+    "status",
+    "subscribed_at",
+    "consent_list",
+    "consent_source",
+    "consent_text_version",
+    "signed_up_at",
+    "signup_ip",
+    "signup_user_agent",
+    "confirmed_at",
+    "confirmation_ip",
+    "confirmation_user_agent",
+];
+
+#[derive(Serialize)]
+struct ExportedSubscriberJson {
+    #[serde(flatten)]
+    subscriber: SubscriberJson,
+    /// The latest signup.
+    consent: Option<ConsentJson>,
+}
+
+fn encode_subscriber(
+    buffer: &mut Vec<u8>,
+    format: ExportFormat,
+    exported: ExportedSubscriber,
+) -> Result<(), anyhow::Error> {
+    let ExportedSubscriber {
+        subscriber,
+        consent,
+    } = exported;
+    match format {
+        ExportFormat::Csv => {
+            let time = |time: Option<DateTime<Utc>>| {
+                time.map(|time| time.to_rfc3339()).unwrap_or_default()
+            };
+            let consent_columns = match consent {
+                Some(consent) => [
+                    consent.list_name,
+                    consent.source,
+                    consent.text_version,
+                    time(Some(consent.signed_up_at)),
+                    consent.signup_ip.unwrap_or_default(),
+                    consent.signup_user_agent.unwrap_or_default(),
+                    time(consent.confirmed_at),
+                    consent.confirmation_ip.unwrap_or_default(),
+                    consent.confirmation_user_agent.unwrap_or_default(),
+                ],
+                None => Default::default(),
+            };
+            write_csv_record(
+                buffer,
+                [
+                    subscriber.id.to_string(),
+                    subscriber.email,
+                    subscriber.name,
+                    subscriber.status.to_string(),
+                    subscriber.subscribed_at.to_rfc3339(),
+                ]
+                .into_iter()
+                .chain(consent_columns),
+            )
+        }
+        ExportFormat::Ndjson => {
+            let json = ExportedSubscriberJson {
+                subscriber: SubscriberJson::from(subscriber),
+                consent: consent.map(ConsentJson::from),
+            };
+            serde_json::to_writer(&mut *buffer, &json)?;
+            buffer.push(b'\n');
+            Ok(())
+        }
+    }
+}
+
+/// Spreadsheets run a cell starting with one of these as a formula.
+const FORMULA_PREFIXES: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+/// Prefixes a cell a spreadsheet would run as a formula with `'`, so that an
+/// email or a name chosen by a subscriber is only shown as text.
+fn neutralize_formula(cell: &str) -> Cow<'_, str> {
+    if cell.starts_with(FORMULA_PREFIXES) {
+        Cow::Owned(format!("'{}", cell))
+    } else {
+        Cow::Borrowed(cell)
+    }
+}
+
+/// Every cell is neutralized, the admins open these files in spreadsheets.
+pub(super) fn write_csv_record<I>(buffer: &mut Vec<u8>, record: I) -> Result<(), anyhow::Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut writer = csv::Writer::from_writer(buffer);
+    writer.write_record(
+        record
+            .into_iter()
+            .map(|cell| neutralize_formula(cell.as_ref()).into_owned()),
+    )?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExportFormat, write_csv_record};
+    use claim::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn export_formats_round_trip() {
+        for format in ExportFormat::ALL {
+            assert_ok_eq!(ExportFormat::try_from(format.as_str()), format);
+        }
+    }
+
+    #[test]
+    fn an_unknown_export_format_is_rejected() {
+        assert_err!(ExportFormat::try_from("xlsx"));
+    }
+
+    #[test]
+    fn formulas_are_written_as_text() {
+        let mut buffer = Vec::new();
+        write_csv_record(
+            &mut buffer,
+            [
+                "=HYPERLINK(\"http://evil.example\")",
+                "+1",
+                "-1",
+                "@SUM(A1)",
+                "\tindented",
+                "\rreturn",
+                "ursula@example.com",
+                "",
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "\"'=HYPERLINK(\"\"http://evil.example\"\")\",'+1,'-1,'@SUM(A1),'\tindented,\"'\rreturn\",ursula@example.com,\n"
+        );
+    }
+}
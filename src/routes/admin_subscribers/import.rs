@@ -0,0 +1,216 @@
+use crate::audit::{AuditAction, record_action};
+use crate::authentication::UserId;
+use crate::mailing_lists::{get_list_by_slug, list_lists};
+use crate::routes::admin_newsletters::new::flash_messages_html;
+use crate::routes::admin_subscribers::export::write_csv_record;
+use crate::subscriber_imports::{ImportMode, get_import_errors, insert_import, list_imports};
+use crate::utils::{e500, escape_html, see_other};
+use actix_multipart::form::{MultipartForm, bytes::Bytes, text::Text};
+use actix_web::http::header::{ContentDisposition, ContentType, DispositionParam, DispositionType};
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+pub async fn import_form(
+    pool: web::Data<PgPool>,
+    flash_message: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let message_html = flash_messages_html(&flash_message)?;
+    let mut lists_html = String::new();
+    for list in list_lists(&pool).await.map_err(e500)? {
+        write!(
+            lists_html,
+            r#"<option value="{}">{}</option>"#,
+            escape_html(&list.slug),
+            escape_html(&list.name)
+        )
+        .map_err(e500)?;
+    }
+    let mut imports_html = String::new();
+    for import in list_imports(&pool).await.map_err(e500)? {
+        let report = if import.status == "done" {
+            format!(
+                r#"<a href="/admin/subscribers/import/{}/report">Download</a>"#,
+                import.id
+            )
+        } else {
+            "-".to_string()
+        };
+        writeln!(
+            imports_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            import.created_at.format("%Y-%m-%d %H:%M UTC"),
+            escape_html(&import.file_name),
+            escape_html(&import.list_name),
+            import.mode,
+            import.status,
+            import.n_rows,
+            import.n_imported,
+            import.n_duplicates,
+            import.n_errors,
+            report,
+        )
+        .map_err(e500)?;
+    }
+    if imports_html.is_empty() {
+        imports_html.push_str("<tr><td>No file has been imported yet.</td></tr>\n");
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>Import subscribers</title>
+</head>
+<body>
+{message_html}
+<h2>Import subscribers</h2>
+<p>The first line of the CSV file names the columns, an <code>email</code> and a <code>name</code> column are required. The existing subscribers are left untouched.</p>
+<form action="/admin/subscribers/import" method="post" enctype="multipart/form-data">
+<label>File
+<input type="file" name="file" accept=".csv,text/csv">
+</label>
+<br>
+<label>List
+<select name="list">{lists_html}</select>
+</label>
+<br>
+<label>
+<input type="radio" name="mode" value="send_confirmation" checked>
+Send the confirmation email
+</label>
+<label>
+<input type="radio" name="mode" value="confirmed">
+Import as confirmed
+</label>
+<br>
+<label>Consent attestation, required to import as confirmed
+<input type="text" placeholder="They opted in on our previous provider's form" name="consent_attestation">
+</label>
+<br>
+<button type="submit">Import</button>
+</form>
+<h2>Imports</h2>
+<table>
+<tr><th>Uploaded at</th><th>File</th><th>List</th><th>Mode</th><th>Status</th><th>Rows</th><th>Imported</th><th>Duplicates</th><th>Errors</th><th>Report</th></tr>
+{imports_html}</table>
+<p><a href="/admin/subscribers">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[derive(MultipartForm)]
+pub struct ImportForm {
+    file: Bytes,
+    /// The slug of the list.
+    list: Text<String>,
+    mode: Text<String>,
+    consent_attestation: Option<Text<String>>,
+}
+
+#[tracing::instrument(name = "Upload a subscriber import", skip(form, pool, user_id))]
+pub async fn upload_import(
+    MultipartForm(form): MultipartForm<ImportForm>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(list) = get_list_by_slug(&pool, &form.list).await.map_err(e500)? else {
+        FlashMessage::error("The list does not exist.").send();
+        return Ok(see_other("/admin/subscribers/import"));
+    };
+    let mode = match ImportMode::try_from(form.mode.into_inner()) {
+        Ok(mode) => mode,
+        Err(e) => {
+            FlashMessage::error(escape_html(&e)).send();
+            return Ok(see_other("/admin/subscribers/import"));
+        }
+    };
+    let consent_attestation = form
+        .consent_attestation
+        .map(|attestation| attestation.trim().to_string())
+        .filter(|attestation| !attestation.is_empty() && mode == ImportMode::Confirmed);
+    if mode == ImportMode::Confirmed && consent_attestation.is_none() {
+        FlashMessage::error("Importing as confirmed needs a consent attestation.").send();
+        return Ok(see_other("/admin/subscribers/import"));
+    }
+    let Ok(content) = String::from_utf8(form.file.data.to_vec()) else {
+        FlashMessage::error("The file is not UTF-8 text.").send();
+        return Ok(see_other("/admin/subscribers/import"));
+    };
+    if content.trim().is_empty() {
+        FlashMessage::error("The file is empty.").send();
+        return Ok(see_other("/admin/subscribers/import"));
+    }
+    let file_name = form
+        .file
+        .file_name
+        .unwrap_or_else(|| "subscribers.csv".to_string());
+    let details = match &consent_attestation {
+        Some(attestation) => format!(
+            "Import of {} to {} as confirmed, consent: {}",
+            file_name, list.name, attestation
+        ),
+        None => format!(
+            "Import of {} to {} with a confirmation email",
+            file_name, list.name
+        ),
+    };
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    insert_import(
+        &mut transaction,
+        **user_id,
+        &file_name,
+        list.id,
+        mode,
+        consent_attestation.as_deref(),
+        &content,
+    )
+    .await
+    .map_err(e500)?;
+    record_action(
+        &mut transaction,
+        **user_id,
+        AuditAction::ImportSubscribers,
+        None,
+        None,
+        &details,
+    )
+    .await
+    .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+    FlashMessage::info("The import has started, its report will be listed below.").send();
+    Ok(see_other("/admin/subscribers/import"))
+}
+
+/// The rows that were not imported, as CSV.
+pub async fn import_report(
+    import_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some((file_name, errors)) = get_import_errors(&pool, *import_id).await.map_err(e500)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let mut report = Vec::new();
+    write_csv_record(&mut report, ["line", "email", "error"]).map_err(e500)?;
+    for error in errors {
+        write_csv_record(
+            &mut report,
+            [error.line.to_string(), error.email, error.error],
+        )
+        .map_err(e500)?;
+    }
+    let stem = file_name.trim_end_matches(".csv");
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!("{}-report.csv", stem))],
+        })
+        .body(report))
+}
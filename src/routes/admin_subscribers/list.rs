@@ -0,0 +1,311 @@
+use crate::domain::SubscriptionStatus;
+use crate::mailing_lists::list_lists;
+use crate::routes::admin_newsletters::new::flash_messages_html;
+use crate::routes::admin_subscribers::export::ExportQuery;
+use crate::subscribers::{
+    SortField, SortOrder, SubscriberFilter, SubscriberPage, SubscriberSummary, list_subscribers,
+};
+use crate::utils::{e500, escape_html};
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+/// The filters of the subscribers, kept in the links to the next pages.
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct SubscribersQuery {
+    #[serde(default)]
+    pub search: String,
+    /// Empty for any status.
+    #[serde(default)]
+    pub status: String,
+    /// `YYYY-MM-DD`, the first day of the subscription date range.
+    #[serde(default)]
+    pub from: String,
+    /// `YYYY-MM-DD`, the last day of the subscription date range.
+    #[serde(default)]
+    pub to: String,
+    #[serde(default)]
+    pub sort: String,
+    #[serde(default)]
+    pub order: String,
+    /// The id of the last subscriber of the previous page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<Uuid>,
+}
+
+impl SubscribersQuery {
+    pub fn filter(&self) -> Result<SubscriberFilter, String> {
+        let status = match self.status.as_str() {
+            "" => None,
+            status => Some(SubscriptionStatus::try_from(status.to_string())?),
+        };
+        let from = parse_date(&self.from)?;
+        let to = parse_date(&self.to)?;
+        if let (Some(from), Some(to)) = (from, to)
+            && from > to
+        {
+            return Err("The start of the date range is after its end.".to_string());
+        }
+        Ok(SubscriberFilter {
+            search: self.search.clone(),
+            status,
+            from,
+            to,
+            sort: match self.sort.as_str() {
+                "" => SortField::default(),
+                sort => SortField::try_from(sort)?,
+            },
+            order: match self.order.as_str() {
+                "" => SortOrder::default(),
+                order => SortOrder::try_from(order)?,
+            },
+        })
+    }
+
+    /// The same filters, from the subscriber after `after`.
+    pub fn page_query_string(&self, after: Option<Uuid>) -> String {
+        serde_html_form::to_string(SubscribersQuery {
+            after,
+            ..self.clone()
+        })
+        .unwrap_or_default()
+    }
+}
+
+fn parse_date(date: &str) -> Result<Option<NaiveDate>, String> {
+    let date = date.trim();
+    if date.is_empty() {
+        return Ok(None);
+    }
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map(Some)
+        .map_err(|_| format!("{} is not a date (YYYY-MM-DD).", date))
+}
+
+async fn fetch_page(
+    pool: &PgPool,
+    query: &SubscribersQuery,
+) -> Result<SubscriberPage, actix_web::Error> {
+    let filter = query.filter().map_err(actix_web::error::ErrorBadRequest)?;
+    list_subscribers(pool, &filter, query.after)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| {
+            actix_web::error::ErrorBadRequest(
+                "The subscriber the page starts after does not exist anymore.",
+            )
+        })
+}
+
+fn select_html(name: &str, options: &[(&str, &str)], selected: &str) -> String {
+    let mut options_html = String::new();
+    for (value, label) in options {
+        let selected = if *value == selected { " selected" } else { "" };
+        write!(
+            options_html,
+            r#"<option value="{}"{}>{}</option>"#,
+            value, selected, label
+        )
+        .unwrap();
+    }
+    format!(r#"<select name="{}">{}</select>"#, name, options_html)
+}
+
+pub async fn list_subscribers_page(
+    query: web::Query<SubscribersQuery>,
+    pool: web::Data<PgPool>,
+    flash_message: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let message_html = flash_messages_html(&flash_message)?;
+    let page = fetch_page(&pool, &query).await?;
+    let mut subscribers_html = String::new();
+    for subscriber in &page.subscribers {
+        writeln!(
+            subscribers_html,
+            r#"<tr><td><a href="/admin/subscribers/{}">{}</a></td><td>{}</td><td>{}</td><td>{}</td></tr>"#,
+            subscriber.id,
+            escape_html(&subscriber.email),
+            escape_html(&subscriber.name),
+            subscriber.status,
+            subscriber.subscribed_at.format("%Y-%m-%d %H:%M UTC"),
+        )
+        .map_err(e500)?;
+    }
+    if page.subscribers.is_empty() {
+        subscribers_html.push_str("<tr><td>No subscribers match.</td></tr>\n");
+    }
+    let mut pagination_html = String::new();
+    if query.after.is_some() {
+        write!(
+            pagination_html,
+            r#"<a href="/admin/subscribers?{}">First page</a> "#,
+            escape_html(&query.page_query_string(None))
+        )
+        .map_err(e500)?;
+    }
+    if let Some(next) = page.next {
+        write!(
+            pagination_html,
+            r#"<a href="/admin/subscribers?{}">Next page -&gt;</a>"#,
+            escape_html(&query.page_query_string(Some(next)))
+        )
+        .map_err(e500)?;
+    }
+    let statuses: Vec<(&str, &str)> = std::iter::once(("", "Any status"))
+        .chain(
+            SubscriptionStatus::ALL
+                .iter()
+                .map(|status| (status.as_str(), status.as_str())),
+        )
+        .collect();
+    let status_select = select_html("status", &statuses, &query.status);
+    let sort_select = select_html(
+        "sort",
+        &[
+            ("subscribed_at", "Subscription date"),
+            ("email", "Email"),
+            ("name", "Name"),
+        ],
+        &query.sort,
+    );
+    let order_select = select_html(
+        "order",
+        &[("desc", "Descending"), ("asc", "Ascending")],
+        &query.order,
+    );
+    let lists: Vec<(String, String)> = list_lists(&pool)
+        .await
+        .map_err(e500)?
+        .into_iter()
+        .map(|list| (escape_html(&list.slug), escape_html(&list.name)))
+        .collect();
+    let lists: Vec<(&str, &str)> = lists
+        .iter()
+        .map(|(slug, name)| (slug.as_str(), name.as_str()))
+        .collect();
+    let list_select = select_html("list", &lists, "");
+    let export_query = |format: &str| {
+        escape_html(
+            &serde_html_form::to_string(ExportQuery {
+                format: format.to_string(),
+                status: query.status.clone(),
+                from: query.from.clone(),
+                to: query.to.clone(),
+            })
+            .unwrap_or_default(),
+        )
+    };
+    let csv_export = export_query("csv");
+    let ndjson_export = export_query("ndjson");
+    let search = escape_html(&query.search);
+    let from = escape_html(&query.from);
+    let to = escape_html(&query.to);
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>Subscribers</title>
+</head>
+<body>
+{message_html}
+<h2>Subscribers</h2>
+<p><a href="/admin/subscribers/import">Import a CSV file</a> - <a href="/admin/subscribers/duplicates">Duplicates</a></p>
+<form action="/admin/subscribers" method="get">
+<label>Search
+<input type="search" placeholder="Email or name" name="search" value="{search}">
+</label>
+{status_select}
+<label>Subscribed from
+<input type="date" name="from" value="{from}">
+</label>
+<label>to
+<input type="date" name="to" value="{to}">
+</label>
+<label>Sort by
+{sort_select}
+</label>
+{order_select}
+<button type="submit">Filter</button>
+</form>
+<table>
+<tr><th>Email</th><th>Name</th><th>Status</th><th>Subscribed at</th></tr>
+{subscribers_html}</table>
+<p>{pagination_html}</p>
+<p>Export the subscribers of this status and date range as <a href="/admin/subscribers/export?{csv_export}">CSV</a> or <a href="/admin/subscribers/export?{ndjson_export}">NDJSON</a>.</p>
+<h2>Add a subscriber</h2>
+<form action="/admin/subscribers" method="post">
+<label>Email
+<input type="email" name="email">
+</label>
+<label>Name
+<input type="text" name="name">
+</label>
+<label>List
+{list_select}
+</label>
+<br>
+<label>
+<input type="checkbox" name="skip_confirmation" value="true">
+Skip the confirmation email
+</label>
+<label>Reason
+<input type="text" placeholder="Signed up at the conference booth" name="reason">
+</label>
+<br>
+<button type="submit">Add</button>
+</form>
+<p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[derive(Serialize)]
+pub(super) struct SubscriberJson {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: &'static str,
+    subscribed_at: String,
+}
+
+impl From<SubscriberSummary> for SubscriberJson {
+    fn from(subscriber: SubscriberSummary) -> Self {
+        SubscriberJson {
+            id: subscriber.id,
+            email: subscriber.email,
+            name: subscriber.name,
+            status: subscriber.status.as_str(),
+            subscribed_at: subscriber.subscribed_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SubscriberPageJson {
+    subscribers: Vec<SubscriberJson>,
+    /// Passed as `after` to get the next page.
+    next: Option<Uuid>,
+}
+
+pub async fn list_subscribers_json(
+    query: web::Query<SubscribersQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let page = fetch_page(&pool, &query).await?;
+    Ok(HttpResponse::Ok().json(SubscriberPageJson {
+        subscribers: page
+            .subscribers
+            .into_iter()
+            .map(SubscriberJson::from)
+            .collect(),
+        next: page.next,
+    }))
+}
@@ -0,0 +1,16 @@
+pub mod actions;
+pub mod detail;
+pub mod duplicates;
+pub mod export;
+pub mod import;
+pub mod list;
+
+pub use actions::{
+    add_subscriber, confirm_subscriber, delete_subscriber, edit_subscriber, resend_confirmation,
+    unsubscribe_subscriber,
+};
+pub use detail::{subscriber_detail, subscriber_detail_json};
+pub use duplicates::{duplicates_page, merge_duplicate};
+pub use export::export_subscribers;
+pub use import::{import_form, import_report, upload_import};
+pub use list::{list_subscribers_json, list_subscribers_page};
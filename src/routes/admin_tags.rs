@@ -0,0 +1,119 @@
+use crate::routes::admin_newsletters::new::flash_messages_html;
+use crate::tags::{list_tags, parse_tag, tag_subscribers, unknown_emails, untag_subscribers};
+use crate::utils::{e500, escape_html, see_other};
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn list_tags_page(
+    pool: web::Data<PgPool>,
+    flash_message: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let message_html = flash_messages_html(&flash_message)?;
+    let mut tags_html = String::new();
+    for (tag, subscribers) in list_tags(&pool).await.map_err(e500)? {
+        writeln!(
+            tags_html,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            escape_html(&tag),
+            subscribers
+        )
+        .map_err(e500)?;
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>Tags</title>
+</head>
+<body>
+{message_html}
+<h2>Tags</h2>
+<table>
+<tr><th>Tag</th><th>Subscribers</th></tr>
+{tags_html}
+</table>
+<h2>Tag subscribers</h2>
+<form action="/admin/tags" method="post">
+<label>Tag
+<input type="text" placeholder="beta testers" name="tag">
+</label>
+<br>
+<label>Subscribers
+<textarea placeholder="The emails of the subscribers, separated by commas or new lines." name="emails" rows="6" cols="60"></textarea>
+</label>
+<br>
+<button type="submit" name="action" value="add">Add the tag</button>
+<button type="submit" name="action" value="remove">Remove the tag</button>
+</form>
+<p><a href="/admin/segments">Segments</a></p>
+<p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagAction {
+    Add,
+    Remove,
+}
+
+#[derive(Deserialize)]
+pub struct TagForm {
+    tag: String,
+    emails: String,
+    action: TagAction,
+}
+
+#[tracing::instrument(name = "Change the tags of subscribers", skip(form, pool))]
+pub async fn update_subscriber_tags(
+    form: web::Form<TagForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let tag = match parse_tag(&form.tag) {
+        Ok(tag) => tag,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/tags"));
+        }
+    };
+    let emails: Vec<String> = form
+        .emails
+        .split([',', '\n'])
+        .map(|email| email.trim().to_string())
+        .filter(|email| !email.is_empty())
+        .collect();
+    if emails.is_empty() {
+        FlashMessage::error("Enter the emails of the subscribers.").send();
+        return Ok(see_other("/admin/tags"));
+    }
+    let unknown = unknown_emails(&pool, &emails).await.map_err(e500)?;
+    if !unknown.is_empty() {
+        FlashMessage::error(format!(
+            "No subscriber has the email {}.",
+            escape_html(&unknown.join(", "))
+        ))
+        .send();
+    }
+    let message = match form.action {
+        TagAction::Add => {
+            let tagged = tag_subscribers(&pool, &emails, &tag).await.map_err(e500)?;
+            format!("The tag has been added to {} subscribers.", tagged)
+        }
+        TagAction::Remove => {
+            let untagged = untag_subscribers(&pool, &emails, &tag)
+                .await
+                .map_err(e500)?;
+            format!("The tag has been removed from {} subscribers.", untagged)
+        }
+    };
+    FlashMessage::info(message).send();
+    Ok(see_other("/admin/tags"))
+}
@@ -0,0 +1,192 @@
+use crate::merge_tags::MergeValues;
+use crate::routes::admin_newsletters::new::flash_messages_html;
+use crate::templates::{self, get_layout, insert_layout, validate_layout};
+use crate::utils::{e500, escape_html, see_other};
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// What the body of an email looks like in the preview of a layout.
+const SAMPLE_CONTENT: &str = "<h1>Sample title</h1>\n<p>The content of the email goes here.</p>";
+
+#[derive(Deserialize)]
+pub struct LayoutForm {
+    name: String,
+    html_layout: String,
+    text_layout: String,
+}
+
+impl LayoutForm {
+    fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("The name of the layout is missing.".to_string());
+        }
+        validate_layout(&self.html_layout, &self.text_layout)
+    }
+}
+
+fn layout_form_html(
+    page_title: &str,
+    message_html: &str,
+    action: &str,
+    form: &LayoutForm,
+    preview_html: &str,
+) -> String {
+    let name = escape_html(&form.name);
+    let html_layout = escape_html(&form.html_layout);
+    let text_layout = escape_html(&form.text_layout);
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>{page_title}</title>
+</head>
+<body>
+{message_html}
+<p>The content of the email replaces <code>{{{{ content }}}}</code>, which must appear once in each version.
+The <code>{{{{ name }}}}</code> and <code>{{{{ email }}}}</code> merge tags can be used.</p>
+<form action="{action}" method="post">
+<label>Name
+<input type="text" placeholder="Enter the name of the layout" name="name" value="{name}">
+</label>
+<br>
+<label>HTML layout
+<textarea name="html_layout" rows="20" cols="80">{html_layout}</textarea>
+</label>
+<br>
+<label>Plain text layout
+<textarea name="text_layout" rows="10" cols="80">{text_layout}</textarea>
+</label>
+<br>
+<button type="submit">Save</button>
+</form>
+{preview_html}
+<p><a href="/admin/templates">&lt;- Back</a></p>
+</body>
+</html>"#,
+    )
+}
+
+pub async fn new_layout_form(
+    flash_message: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let message_html = flash_messages_html(&flash_message)?;
+    let form = LayoutForm {
+        name: String::new(),
+        html_layout: format!("<div>\n{}\n</div>", templates::CONTENT_SLOT),
+        text_layout: templates::CONTENT_SLOT.to_string(),
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(layout_form_html(
+            "New layout",
+            &message_html,
+            "/admin/templates",
+            &form,
+            "",
+        )))
+}
+
+#[tracing::instrument(name = "Create a layout", skip(form, pool))]
+pub async fn create_layout(
+    form: web::Form<LayoutForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if let Err(e) = form.validate() {
+        FlashMessage::error(e).send();
+        return Ok(see_other("/admin/templates/new"));
+    }
+    let Some(id) = insert_layout(
+        &pool,
+        form.name.trim(),
+        &form.html_layout,
+        &form.text_layout,
+    )
+    .await
+    .map_err(e500)?
+    else {
+        FlashMessage::error("A layout with this name already exists.").send();
+        return Ok(see_other("/admin/templates/new"));
+    };
+    FlashMessage::info("The layout has been saved.").send();
+    Ok(see_other(&format!("/admin/templates/{}", id)))
+}
+
+pub async fn edit_layout_form(
+    layout_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    flash_message: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let message_html = flash_messages_html(&flash_message)?;
+    let Some(layout) = get_layout(&pool, *layout_id).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let id = layout.id;
+    let form = LayoutForm {
+        name: layout.name,
+        html_layout: layout.html_layout,
+        text_layout: layout.text_layout,
+    };
+    let preview_html = format!(
+        r#"<h2>Preview</h2>
+<iframe sandbox src="/admin/templates/{id}/preview" width="800" height="400"></iframe>"#
+    );
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(layout_form_html(
+            "Edit the layout",
+            &message_html,
+            &format!("/admin/templates/{}", id),
+            &form,
+            &preview_html,
+        )))
+}
+
+#[tracing::instrument(name = "Update a layout", skip(form, pool))]
+pub async fn update_layout(
+    layout_id: web::Path<Uuid>,
+    form: web::Form<LayoutForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let layout_id = layout_id.into_inner();
+    let redirect = see_other(&format!("/admin/templates/{}", layout_id));
+    if let Err(e) = form.validate() {
+        FlashMessage::error(e).send();
+        return Ok(redirect);
+    }
+    if templates::update_layout(
+        &pool,
+        layout_id,
+        form.name.trim(),
+        &form.html_layout,
+        &form.text_layout,
+    )
+    .await
+    .map_err(e500)?
+    {
+        FlashMessage::info("The layout has been saved.").send();
+    } else {
+        FlashMessage::error("A layout with this name already exists.").send();
+    }
+    Ok(redirect)
+}
+
+/// Serves the layout around sample content, meant to be displayed in a
+/// sandboxed iframe.
+pub async fn layout_preview(
+    layout_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(layout) = get_layout(&pool, *layout_id).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let values = MergeValues(vec![("name", "Ursula"), ("email", "ursula@example.com")]);
+    let (html, _) = layout.render(SAMPLE_CONTENT, "", &values).map_err(e500)?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .insert_header(("Content-Security-Policy", "sandbox"))
+        .body(html))
+}
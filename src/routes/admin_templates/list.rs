@@ -0,0 +1,64 @@
+use crate::routes::admin_newsletters::new::flash_messages_html;
+use crate::templates::{list_layouts, list_system_emails};
+use crate::utils::{e500, escape_html};
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn list_templates(
+    pool: web::Data<PgPool>,
+    flash_message: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let message_html = flash_messages_html(&flash_message)?;
+    let layouts = list_layouts(&pool).await.map_err(e500)?;
+    let system_emails = list_system_emails(&pool).await.map_err(e500)?;
+    let mut layouts_html = String::new();
+    for layout in layouts {
+        writeln!(
+            layouts_html,
+            r#"<tr><td><a href="/admin/templates/{}">{}</a></td><td>{}</td></tr>"#,
+            layout.id,
+            escape_html(&layout.name),
+            layout.updated_at.format("%Y-%m-%d %H:%M UTC"),
+        )
+        .map_err(e500)?;
+    }
+    let mut system_emails_html = String::new();
+    for email in system_emails {
+        writeln!(
+            system_emails_html,
+            r#"<tr><td><a href="/admin/templates/system/{}">{}</a></td><td>{}</td></tr>"#,
+            email.kind,
+            email.kind,
+            escape_html(&email.subject),
+        )
+        .map_err(e500)?;
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>Email layouts</title>
+</head>
+<body>
+{message_html}
+<h2>Layouts</h2>
+<p><a href="/admin/templates/new">Create a new layout</a></p>
+<table>
+<tr><th>Name</th><th>Updated at</th></tr>
+{layouts_html}
+</table>
+<h2>System emails</h2>
+<table>
+<tr><th>Kind</th><th>Subject</th></tr>
+{system_emails_html}
+</table>
+<p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
@@ -0,0 +1,9 @@
+pub mod layouts;
+pub mod list;
+pub mod system_emails;
+
+pub use layouts::{
+    create_layout, edit_layout_form, layout_preview, new_layout_form, update_layout,
+};
+pub use list::list_templates;
+pub use system_emails::{system_email_form, update_system_email};
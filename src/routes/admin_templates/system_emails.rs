@@ -0,0 +1,137 @@
+use crate::merge_tags::{Template, validate_merge_tags};
+use crate::routes::admin_newsletters::new::{flash_messages_html, layout_select_html};
+use crate::templates::{self, get_layout, get_system_email, list_layouts, system_email_variables};
+use crate::utils::{e500, escape_html, see_other};
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+pub struct SystemEmailForm {
+    subject: String,
+    html_body: String,
+    text_body: String,
+    /// The id of the layout of the email, empty for no layout.
+    #[serde(default)]
+    template_id: String,
+}
+
+impl SystemEmailForm {
+    fn validate(&self, kind: &str) -> Result<Option<Uuid>, String> {
+        if self.subject.trim().is_empty() {
+            return Err("The subject of the email is missing.".to_string());
+        }
+        let variables = system_email_variables(kind);
+        Template::parse(&self.subject, variables).map_err(|e| e.to_string())?;
+        validate_merge_tags(&self.html_body, &self.text_body, variables)
+            .map_err(|e| e.to_string())?;
+        if self.template_id.is_empty() {
+            return Ok(None);
+        }
+        Uuid::parse_str(&self.template_id)
+            .map(Some)
+            .map_err(|_| "The layout does not exist.".to_string())
+    }
+}
+
+pub async fn system_email_form(
+    kind: web::Path<String>,
+    pool: web::Data<PgPool>,
+    flash_message: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let message_html = flash_messages_html(&flash_message)?;
+    let Some(email) = get_system_email(&pool, &kind).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let layouts = list_layouts(&pool).await.map_err(e500)?;
+    let layout_select = layout_select_html(
+        &layouts,
+        &email
+            .template_id
+            .map(|id| id.to_string())
+            .unwrap_or_default(),
+    );
+    let variables = system_email_variables(&email.kind)
+        .iter()
+        .map(|variable| format!("<code>{{{{ {} }}}}</code>", variable))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let kind = email.kind;
+    let subject = escape_html(&email.subject);
+    let html_body = escape_html(&email.html_body);
+    let text_body = escape_html(&email.text_body);
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>{kind}</title>
+</head>
+<body>
+{message_html}
+<h1>{kind}</h1>
+<p>Merge tags: {variables}.</p>
+<form action="/admin/templates/system/{kind}" method="post">
+<label>Subject
+<input type="text" name="subject" value="{subject}">
+</label>
+<br>
+<label>HTML body
+<textarea name="html_body" rows="20" cols="80">{html_body}</textarea>
+</label>
+<br>
+<label>Plain text body
+<textarea name="text_body" rows="10" cols="80">{text_body}</textarea>
+</label>
+<br>
+{layout_select}
+<br>
+<button type="submit">Save</button>
+</form>
+<p><a href="/admin/templates">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[tracing::instrument(name = "Update a system email", skip(form, pool))]
+pub async fn update_system_email(
+    kind: web::Path<String>,
+    form: web::Form<SystemEmailForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let kind = kind.into_inner();
+    let redirect = see_other(&format!("/admin/templates/system/{}", kind));
+    let template_id = match form.validate(&kind) {
+        Ok(template_id) => template_id,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(redirect);
+        }
+    };
+    if let Some(id) = template_id
+        && get_layout(&pool, id).await.map_err(e500)?.is_none()
+    {
+        FlashMessage::error("The layout does not exist.").send();
+        return Ok(redirect);
+    }
+    if !templates::update_system_email(
+        &pool,
+        &kind,
+        form.subject.trim(),
+        &form.html_body,
+        &form.text_body,
+        template_id,
+    )
+    .await
+    .map_err(e500)?
+    {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    FlashMessage::info("The email has been saved.").send();
+    Ok(redirect)
+}
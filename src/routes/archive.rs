@@ -0,0 +1,136 @@
+use crate::attributes::list_definitions;
+use crate::domain::IssueStatus;
+use crate::issue_rendering::{render_for_archive, verify_archive_signature};
+use crate::newsletter_issues::{count_public_issues, get_issue_by_slug, list_public_issues};
+use crate::startup::HmacSecret;
+use crate::utils::{e500, escape_html};
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+const ISSUES_PER_PAGE: i64 = 10;
+
+#[derive(Deserialize)]
+pub struct ArchiveParameters {
+    page: Option<i64>,
+}
+
+pub async fn archive(
+    parameters: web::Query<ArchiveParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let page = parameters.page.unwrap_or(1).max(1);
+    // Past the last page that can be counted, there is nothing to show.
+    let Some(offset) = (page - 1).checked_mul(ISSUES_PER_PAGE) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let issues = list_public_issues(&pool, ISSUES_PER_PAGE, offset)
+        .await
+        .map_err(e500)?;
+    let total = count_public_issues(&pool).await.map_err(e500)?;
+    let mut issues_html = String::new();
+    for issue in &issues {
+        writeln!(
+            issues_html,
+            r#"<li><a href="/archive/{}">{}</a> ({})</li>"#,
+            issue.slug,
+            escape_html(&issue.title),
+            issue
+                .published_at
+                .map(|published_at| published_at.format("%Y-%m-%d").to_string())
+                .unwrap_or_default(),
+        )
+        .map_err(e500)?;
+    }
+    if issues.is_empty() {
+        issues_html.push_str("<li>No issues yet.</li>\n");
+    }
+    let mut pagination_html = String::new();
+    if page > 1 {
+        write!(
+            pagination_html,
+            r#"<a href="/archive?page={}">&lt;- Newer issues</a> "#,
+            page - 1
+        )
+        .map_err(e500)?;
+    }
+    if offset.saturating_add(ISSUES_PER_PAGE) < total {
+        write!(
+            pagination_html,
+            r#"<a href="/archive?page={}">Older issues -&gt;</a>"#,
+            page + 1
+        )
+        .map_err(e500)?;
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>Newsletter archive</title>
+</head>
+<body>
+<h1>Past issues</h1>
+<ul>
+{issues_html}</ul>
+<p>{pagination_html}</p>
+</body>
+</html>"#,
+        )))
+}
+
+#[derive(Deserialize)]
+pub struct ArchiveIssueParameters {
+    signature: Option<String>,
+}
+
+/// Sent public issues are open to everyone, the signed links of the emails
+/// also open private issues.
+#[tracing::instrument(name = "Show an archived issue", skip(parameters, pool, hmac_secret))]
+pub async fn archive_issue(
+    slug: web::Path<String>,
+    parameters: web::Query<ArchiveIssueParameters>,
+    pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(issue) = get_issue_by_slug(&pool, &slug).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let signed = parameters
+        .signature
+        .as_deref()
+        .is_some_and(|signature| verify_archive_signature(&hmac_secret, &issue.slug, signature));
+    let published =
+        issue.is_public && matches!(issue.status, IssueStatus::Sending | IssueStatus::Sent);
+    if !signed && !published {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    let attributes = list_definitions(&pool).await.map_err(e500)?;
+    let content = render_for_archive(&issue, &attributes).map_err(e500)?;
+    let title = escape_html(&issue.title);
+    let published_at = issue
+        .published_at
+        .map(|published_at| format!("<p>{}</p>", published_at.format("%Y-%m-%d")))
+        .unwrap_or_default();
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>{title}</title>
+</head>
+<body>
+<p><a href="/archive">&lt;- All issues</a></p>
+<h1>{title}</h1>
+{published_at}
+{content}
+</body>
+</html>"#,
+        )))
+}
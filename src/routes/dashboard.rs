@@ -1,7 +1,9 @@
-use crate::utils::e500;
+use crate::newsletter_issues::list_scheduled_issues;
+use crate::utils::{e500, escape_html};
 use actix_web::http::header::{ContentType, LOCATION};
 use actix_web::{HttpResponse, web};
 use sqlx::PgPool;
+use std::fmt::Write;
 use uuid::Uuid;
 
 use crate::session_state::TypedSession;
@@ -19,6 +21,7 @@ pub async fn admin_dashboard(
             .append_header((LOCATION, "/login"))
             .finish());
     };
+    let scheduled_html = scheduled_issues_html(&pool).await?;
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
         .body(format!(
@@ -32,18 +35,58 @@ pub async fn admin_dashboard(
 <p>Welcome {username}!</p>
 <p>Available actions:</p>
 <ol>
+<li><a href="/admin/newsletters">Newsletter issues</a></li>
+<li><a href="/admin/subscribers">Subscribers</a></li>
+<li><a href="/admin/audit">Audit trail</a></li>
+<li><a href="/admin/lists">Lists</a></li>
+<li><a href="/admin/segments">Segments</a></li>
+<li><a href="/admin/tags">Tags</a></li>
+<li><a href="/admin/attributes">Subscriber attributes</a></li>
+<li><a href="/admin/email-domains">Email domains</a></li>
+<li><a href="/admin/templates">Email layouts</a></li>
+<li><a href="/admin/rss">RSS feeds</a></li>
 <li><a href="/admin/change/password">Change password</a></li>
+<li><a href="/admin/settings/notifications">Login notifications</a></li>
 <li>
 <form name="logoutForm" action="/admin/logout" method="post">
 <input type="submit" value="Logout">
 </form>
 </li>
 </ol>
+{scheduled_html}
 </body>
 </html>"#,
         )))
 }
 
+/// The issues waiting to go out, each can still be rescheduled or cancelled.
+async fn scheduled_issues_html(pool: &PgPool) -> Result<String, actix_web::Error> {
+    let issues = list_scheduled_issues(pool).await.map_err(e500)?;
+    if issues.is_empty() {
+        return Ok(String::new());
+    }
+    let mut html = String::from("<p>Scheduled issues:</p>\n<ul>\n");
+    for issue in issues {
+        let send_at = issue
+            .send_at
+            .map(|send_at| send_at.format("%Y-%m-%d %H:%M UTC").to_string())
+            .unwrap_or_default();
+        writeln!(
+            html,
+            r#"<li>{send_at}: <a href="/admin/newsletters/{id}">{title}</a> (<a href="/admin/newsletters/{id}">Reschedule</a>)
+<form action="/admin/newsletters/{id}/cancel" method="post">
+<button type="submit">Cancel</button>
+</form>
+</li>"#,
+            id = issue.id,
+            title = escape_html(&issue.title),
+        )
+        .map_err(e500)?;
+    }
+    html.push_str("</ul>");
+    Ok(html)
+}
+
 #[tracing::instrument(name = "Search for username", skip(user_id, pool), fields(username=tracing::field::Empty, user_id=%user_id))]
 pub async fn get_username(user_id: Uuid, pool: &PgPool) -> Result<String, anyhow::Error> {
     let username = sqlx::query!(
@@ -0,0 +1,186 @@
+use crate::attributes::list_definitions;
+use crate::configuration::NewsletterSettings;
+use crate::issue_rendering::render_for_archive;
+use crate::newsletter_issues::{NewsletterIssue, last_sent_issue_update, list_public_issues};
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::{e500, escape_html};
+use actix_web::http::header::{
+    self, ETag, EntityTag, Header, HttpDate, IfModifiedSince, IfNoneMatch, LastModified,
+};
+use actix_web::{HttpRequest, HttpResponse, web};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::fmt::Write;
+use std::time::SystemTime;
+
+/// Feed readers only need the latest issues, the archive has the others.
+const FEED_LENGTH: i64 = 20;
+
+pub async fn rss_feed(
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<NewsletterSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let base_url = &base_url.0;
+    let issues = list_public_issues(&pool, FEED_LENGTH, 0)
+        .await
+        .map_err(e500)?;
+    let last_update = last_sent_issue_update(&pool).await.map_err(e500)?;
+    let attributes = list_definitions(&pool).await.map_err(e500)?;
+    let mut items = String::new();
+    for issue in &issues {
+        writeln!(
+            items,
+            r#"<item>
+<title>{title}</title>
+<link>{base_url}/archive/{slug}</link>
+<guid isPermaLink="false">urn:uuid:{id}</guid>
+<pubDate>{published_at}</pubDate>
+<description>{content}</description>
+</item>"#,
+            title = escape_html(&issue.title),
+            slug = issue.slug,
+            id = issue.id,
+            published_at = published_at(issue).to_rfc2822(),
+            content = escape_html(&render_for_archive(issue, &attributes).map_err(e500)?),
+        )
+        .map_err(e500)?;
+    }
+    let last_build_date = last_update
+        .map(|last_update| {
+            format!(
+                "<lastBuildDate>{}</lastBuildDate>\n",
+                last_update.to_rfc2822()
+            )
+        })
+        .unwrap_or_default();
+    let body = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom">
+<channel>
+<title>{title}</title>
+<link>{base_url}/archive</link>
+<description>{description}</description>
+<atom:link href="{base_url}/feed.rss" rel="self" type="application/rss+xml"/>
+{last_build_date}{items}</channel>
+</rss>
+"#,
+        title = escape_html(&settings.title),
+        description = escape_html(&settings.description),
+    );
+    Ok(feed_response(
+        &request,
+        body,
+        "application/rss+xml; charset=utf-8",
+        last_update,
+    ))
+}
+
+pub async fn atom_feed(
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<NewsletterSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let base_url = &base_url.0;
+    let issues = list_public_issues(&pool, FEED_LENGTH, 0)
+        .await
+        .map_err(e500)?;
+    let last_update = last_sent_issue_update(&pool).await.map_err(e500)?;
+    let attributes = list_definitions(&pool).await.map_err(e500)?;
+    let mut entries = String::new();
+    for issue in &issues {
+        writeln!(
+            entries,
+            r#"<entry>
+<title>{title}</title>
+<id>urn:uuid:{id}</id>
+<link href="{base_url}/archive/{slug}"/>
+<published>{published_at}</published>
+<updated>{updated_at}</updated>
+<content type="html">{content}</content>
+</entry>"#,
+            title = escape_html(&issue.title),
+            id = issue.id,
+            slug = issue.slug,
+            published_at = published_at(issue).to_rfc3339(),
+            updated_at = issue.updated_at.to_rfc3339(),
+            content = escape_html(&render_for_archive(issue, &attributes).map_err(e500)?),
+        )
+        .map_err(e500)?;
+    }
+    // The feed of an empty archive has to be dated anyway.
+    let updated = last_update.unwrap_or(DateTime::UNIX_EPOCH).to_rfc3339();
+    let body = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+<title>{title}</title>
+<subtitle>{description}</subtitle>
+<id>{base_url}/feed.atom</id>
+<link href="{base_url}/archive"/>
+<link rel="self" href="{base_url}/feed.atom"/>
+<updated>{updated}</updated>
+<author><name>{title}</name></author>
+{entries}</feed>
+"#,
+        title = escape_html(&settings.title),
+        description = escape_html(&settings.description),
+    );
+    Ok(feed_response(
+        &request,
+        body,
+        "application/atom+xml; charset=utf-8",
+        last_update,
+    ))
+}
+
+fn published_at(issue: &NewsletterIssue) -> DateTime<Utc> {
+    issue.published_at.unwrap_or(issue.created_at)
+}
+
+/// Feed readers poll, a `304 Not Modified` spares them the download when
+/// nothing changed since their last visit.
+fn feed_response(
+    request: &HttpRequest,
+    body: String,
+    content_type: &str,
+    last_update: Option<DateTime<Utc>>,
+) -> HttpResponse {
+    let etag = EntityTag::new_strong(hex::encode(Sha256::digest(body.as_bytes())));
+    // HTTP dates have a precision of one second.
+    let last_modified = last_update
+        .and_then(|last_update| DateTime::from_timestamp(last_update.timestamp(), 0))
+        .map(SystemTime::from);
+    if is_fresh(request, &etag, last_modified) {
+        return HttpResponse::NotModified()
+            .insert_header(ETag(etag))
+            .finish();
+    }
+    let mut response = HttpResponse::Ok();
+    response
+        .content_type(content_type)
+        .insert_header(ETag(etag));
+    if let Some(last_modified) = last_modified {
+        response.insert_header(LastModified(HttpDate::from(last_modified)));
+    }
+    response.body(body)
+}
+
+/// `If-None-Match` takes precedence over `If-Modified-Since`.
+fn is_fresh(request: &HttpRequest, etag: &EntityTag, last_modified: Option<SystemTime>) -> bool {
+    if request.headers().contains_key(header::IF_NONE_MATCH) {
+        return match IfNoneMatch::parse(request) {
+            Ok(IfNoneMatch::Any) => true,
+            Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(etag)),
+            Err(_) => false,
+        };
+    }
+    match (IfModifiedSince::parse(request), last_modified) {
+        (Ok(IfModifiedSince(since)), Some(last_modified)) => {
+            SystemTime::from(since) >= last_modified
+        }
+        _ => false,
+    }
+}
@@ -1,7 +1,110 @@
-use actix_web::{HttpResponse, http::header::ContentType};
+use crate::attributes::{AttributeDefinition, AttributeKind, list_definitions};
+use crate::bot_protection::{ChallengeVerifier, form_timestamp_fields};
+use crate::consent::{CONSENT_TEXT, CONSENT_TEXT_VERSION};
+use crate::mailing_lists::{MailingList, list_lists};
+use crate::startup::HmacSecret;
+use crate::utils::{e500, escape_html};
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use chrono::Utc;
+use sqlx::PgPool;
 
-pub async fn home() -> HttpResponse {
-    HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(include_str!("home.html"))
+pub async fn home(
+    pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+    challenge_verifier: Option<web::Data<dyn ChallengeVerifier>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let lists = list_lists(&pool).await.map_err(e500)?;
+    let attributes = list_definitions(&pool).await.map_err(e500)?;
+    let challenge_widget = challenge_verifier
+        .as_ref()
+        .map_or("", |verifier| verifier.widget_html());
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
+        include_str!("home.html")
+            .replace("{list_field}", &list_field_html(&lists))
+            .replace("{attribute_fields}", &attribute_fields_html(&attributes))
+            .replace("{consent_text}", &escape_html(CONSENT_TEXT))
+            .replace("{consent_version}", &escape_html(CONSENT_TEXT_VERSION))
+            .replace(
+                "{form_timestamp_fields}",
+                &form_timestamp_fields(&hmac_secret, Utc::now()),
+            )
+            .replace("{challenge_widget}", challenge_widget),
+    ))
+}
+
+fn attribute_fields_html(attributes: &[AttributeDefinition]) -> String {
+    attributes
+        .iter()
+        .map(|attribute| {
+            let name = escape_html(&attribute.key);
+            let required = if attribute.required { " required" } else { "" };
+            let input = match attribute.kind {
+                AttributeKind::Text => {
+                    let max_length = attribute
+                        .max_length
+                        .map(|max_length| format!(r#" maxlength="{}""#, max_length))
+                        .unwrap_or_default();
+                    format!(r#"<input type="text" name="{name}"{max_length}{required}>"#)
+                }
+                AttributeKind::Number => {
+                    let min = attribute
+                        .min_value
+                        .map(|min| format!(r#" min="{}""#, min))
+                        .unwrap_or_default();
+                    let max = attribute
+                        .max_value
+                        .map(|max| format!(r#" max="{}""#, max))
+                        .unwrap_or_default();
+                    format!(r#"<input type="number" step="any" name="{name}"{min}{max}{required}>"#)
+                }
+                AttributeKind::Date => {
+                    format!(r#"<input type="date" name="{name}"{required}>"#)
+                }
+                AttributeKind::Boolean => {
+                    format!(r#"<input type="checkbox" name="{name}" value="true"{required}>"#)
+                }
+                AttributeKind::Choice => {
+                    let mut options = String::from(r#"<option value=""></option>"#);
+                    for choice in &attribute.choices {
+                        let choice = escape_html(choice);
+                        options.push_str(&format!(r#"<option value="{choice}">{choice}</option>"#));
+                    }
+                    format!(r#"<select name="{name}"{required}>{options}</select>"#)
+                }
+            };
+            format!(
+                r#"<label>{}
+        {}
+    </label>
+    <br>
+    "#,
+                escape_html(&attribute.label),
+                input
+            )
+        })
+        .collect()
+}
+
+/// A single list needs no choice.
+fn list_field_html(lists: &[MailingList]) -> String {
+    if lists.len() < 2 {
+        return String::new();
+    }
+    let options: String = lists
+        .iter()
+        .map(|list| {
+            format!(
+                r#"<option value="{}">{}</option>"#,
+                escape_html(&list.slug),
+                escape_html(&list.name)
+            )
+        })
+        .collect();
+    format!(
+        r#"<label>List
+        <select name="list">{}</select>
+    </label>
+    <br>"#,
+        options
+    )
 }
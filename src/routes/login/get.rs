@@ -1,14 +1,27 @@
+use crate::authentication::oidc::OidcClient;
+use crate::utils::escape_html;
 use actix_web::HttpResponse;
 use actix_web::cookie::Cookie;
 use actix_web::http::header::ContentType;
+use actix_web::web;
 use actix_web_flash_messages::{IncomingFlashMessages, Level};
 use std::fmt::Write;
 
-pub async fn login_form(flash_message: IncomingFlashMessages) -> HttpResponse {
+pub async fn login_form(
+    flash_message: IncomingFlashMessages,
+    oidc_client: Option<web::Data<OidcClient>>,
+) -> HttpResponse {
     let mut error_html = String::new();
     for m in flash_message.iter().filter(|m| m.level() == Level::Error) {
         writeln!(error_html, r#"<p><i>{}</i></p>"#, m.content()).unwrap();
     }
+    let oidc_html = match oidc_client {
+        Some(client) => format!(
+            r#"<p><a href="/login/oidc">Login with {}</a></p>"#,
+            escape_html(&client.settings.provider_name)
+        ),
+        None => String::new(),
+    };
     let mut response = HttpResponse::Ok()
         .content_type(ContentType::html())
         .body(format!(
@@ -37,6 +50,8 @@ name="password"
 </label>
 <button type="submit">Login</button>
 </form>
+<p><a href="/password-reset">Forgot your password?</a></p>
+{oidc_html}
 </body></html>"#,
         ));
     response
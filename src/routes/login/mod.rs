@@ -1,4 +1,6 @@
 pub mod get;
+pub mod oidc;
 pub mod post;
 pub use get::login_form;
+pub use oidc::{oidc_callback, oidc_login};
 pub use post::login;
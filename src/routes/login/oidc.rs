@@ -0,0 +1,114 @@
+use crate::authentication::oidc::{OidcClient, find_user};
+use crate::authentication::{LoginContext, on_successful_login};
+use crate::email_client::EmailClient;
+use crate::routes::login::post::{LoginError, login_redirect};
+use crate::session_state::TypedSession;
+use crate::startup::{ApplicationBaseUrl, HmacSecret};
+use crate::utils::see_other;
+use actix_web::error::InternalError;
+use actix_web::{HttpRequest, HttpResponse, web};
+use chrono::Utc;
+use serde::Deserialize;
+use sqlx::PgPool;
+
+#[tracing::instrument(name = "Start an OIDC login", skip(oidc_client, session))]
+pub async fn oidc_login(
+    oidc_client: web::Data<OidcClient>,
+    session: TypedSession,
+) -> Result<HttpResponse, InternalError<LoginError>> {
+    let request = oidc_client
+        .authorization_request()
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+    session
+        .insert_oidc_request(&request.state, &request.code_verifier)
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+    Ok(see_other(request.url.as_str()))
+}
+
+#[derive(Deserialize)]
+pub struct CallbackParameters {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(
+    name = "OIDC login callback",
+    skip(parameters, oidc_client, pool, session, request, email_client, base_url, hmac_secret),
+    fields(user_id=tracing::field::Empty)
+)]
+pub async fn oidc_callback(
+    parameters: web::Query<CallbackParameters>,
+    oidc_client: web::Data<OidcClient>,
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+    request: HttpRequest,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, InternalError<LoginError>> {
+    let parameters = parameters.into_inner();
+    let (expected_state, code_verifier) = session
+        .take_oidc_request()
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?
+        .ok_or_else(|| {
+            login_redirect(LoginError::AuthError(anyhow::anyhow!(
+                "There is no pending OIDC login"
+            )))
+        })?;
+    if let Some(error) = parameters.error {
+        return Err(login_redirect(LoginError::AuthError(anyhow::anyhow!(
+            "The identity provider returned an error: {}",
+            error
+        ))));
+    }
+    if parameters.state.as_deref() != Some(expected_state.as_str()) {
+        return Err(login_redirect(LoginError::AuthError(anyhow::anyhow!(
+            "The OIDC state does not match"
+        ))));
+    }
+    let code = parameters.code.ok_or_else(|| {
+        login_redirect(LoginError::AuthError(anyhow::anyhow!(
+            "The identity provider did not return a code"
+        )))
+    })?;
+
+    let user_info = oidc_client
+        .exchange_code(&code, &code_verifier)
+        .await
+        .map_err(|e| login_redirect(LoginError::AuthError(e)))?;
+    let user_id = find_user(&pool, &user_info)
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?
+        .ok_or_else(|| {
+            login_redirect(LoginError::AuthError(anyhow::anyhow!(
+                "No user matches the OIDC identity"
+            )))
+        })?;
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+    session.renew();
+    session
+        .insert_user(user_id)
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+    session
+        .insert_logged_in_at(Utc::now())
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+    if let Err(e) = on_successful_login(
+        &pool,
+        &email_client,
+        &base_url,
+        &hmac_secret,
+        user_id,
+        &LoginContext::from_request(&request),
+    )
+    .await
+    {
+        tracing::warn!(
+            error.cause_chain = ?e,
+            "Failed to process the login notification"
+        );
+    }
+    Ok(see_other("/admin/dashboard"))
+}
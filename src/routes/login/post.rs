@@ -1,9 +1,15 @@
-use crate::authentication::{AuthError, Credential, validate_credential};
+use crate::authentication::{
+    AuthError, Credential, LoginContext, on_failed_login, on_successful_login, validate_credential,
+};
+use crate::configuration::LoginNotificationSettings;
+use crate::email_client::EmailClient;
 use crate::session_state::TypedSession;
+use crate::startup::{ApplicationBaseUrl, HmacSecret};
 use actix_web::error::InternalError;
 use actix_web::http::header::LOCATION;
-use actix_web::{HttpResponse, ResponseError, web};
+use actix_web::{HttpRequest, HttpResponse, ResponseError, web};
 use actix_web_flash_messages::FlashMessage;
+use chrono::Utc;
 use secrecy::Secret;
 use serde::Deserialize;
 use sqlx::PgPool;
@@ -14,16 +20,28 @@ pub struct FormData {
     password: Secret<String>,
 }
 
-#[tracing::instrument (name="Login", skip(form, pool, session), fields(username=tracing::field::Empty, user_id=tracing::field::Empty))]
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument (
+    name="Login",
+    skip(form, pool, session, request, email_client, base_url, hmac_secret, notification_settings),
+    fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
+)]
 pub async fn login(
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
     session: TypedSession,
+    request: HttpRequest,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    hmac_secret: web::Data<HmacSecret>,
+    notification_settings: web::Data<LoginNotificationSettings>,
 ) -> Result<HttpResponse, InternalError<LoginError>> {
     let credential = Credential {
         username: form.0.username,
         password: form.0.password,
     };
+    let username = credential.username.clone();
+    let context = LoginContext::from_request(&request);
     tracing::Span::current().record("username", &tracing::field::display(&credential.username));
     match validate_credential(&pool, credential).await {
         Ok(user_id) => {
@@ -32,11 +50,46 @@ pub async fn login(
             session
                 .insert_user(user_id)
                 .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+            session
+                .insert_logged_in_at(Utc::now())
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+            if let Err(e) = on_successful_login(
+                &pool,
+                &email_client,
+                &base_url,
+                &hmac_secret,
+                user_id,
+                &context,
+            )
+            .await
+            {
+                tracing::warn!(
+                    error.cause_chain = ?e,
+                    "Failed to process the login notification"
+                );
+            }
             Ok(HttpResponse::SeeOther()
                 .insert_header((LOCATION, "/admin/dashboard"))
                 .finish())
         }
         Err(e) => {
+            if matches!(e, AuthError::InvalidCredentials(_))
+                && let Err(notification_error) = on_failed_login(
+                    &pool,
+                    &email_client,
+                    &base_url,
+                    &hmac_secret,
+                    notification_settings.failed_attempts_threshold,
+                    &username,
+                    &context,
+                )
+                .await
+            {
+                tracing::warn!(
+                    error.cause_chain = ?notification_error,
+                    "Failed to process the failed login notification"
+                );
+            }
             let e: LoginError = e.into();
             FlashMessage::error(e.to_string()).send();
             let response = HttpResponse::SeeOther()
@@ -47,7 +100,7 @@ pub async fn login(
     }
 }
 
-fn login_redirect(e: LoginError) -> InternalError<LoginError> {
+pub fn login_redirect(e: LoginError) -> InternalError<LoginError> {
     FlashMessage::error(e.to_string()).send();
     let response = HttpResponse::SeeOther()
         .insert_header((LOCATION, "/login"))
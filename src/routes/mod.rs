@@ -1,19 +1,74 @@
+pub mod admin_attributes;
+pub mod admin_audit;
+pub mod admin_email_domains;
+pub mod admin_lists;
+pub mod admin_newsletters;
+pub mod admin_rss_feeds;
+pub mod admin_segments;
+pub mod admin_subscribers;
+pub mod admin_tags;
+pub mod admin_templates;
+pub mod archive;
 pub mod change_password;
 pub mod dashboard;
+pub mod feeds;
 pub mod health_check;
 pub mod home;
 pub mod log_out;
 pub mod login;
 pub mod newsletter;
+pub mod notification_settings;
+pub mod password_reset;
+pub mod privacy;
+pub mod revoke_sessions;
 pub mod subscriptions;
 pub mod subscriptions_confirm;
+pub mod unsubscribe;
 
+pub use admin_attributes::{create_attribute, list_attributes_page};
+pub use admin_audit::audit_trail_page;
+pub use admin_email_domains::{
+    delete_email_domain_rule, email_domains_page, save_email_domain_rule,
+};
+pub use admin_lists::{create_mailing_list, list_mailing_lists};
+pub use admin_newsletters::{
+    cancel_newsletter_issue, create_newsletter_issue, delete_newsletter_issue,
+    edit_newsletter_form, list_newsletter_issues, newsletter_form, newsletter_issue,
+    newsletter_issue_preview, publish_newsletter_issue, schedule_newsletter_issue,
+    send_test_newsletter_issue, set_newsletter_issue_visibility, update_newsletter_issue,
+};
+pub use admin_rss_feeds::{create_rss_feed, delete_rss_feed, list_rss_feeds};
+pub use admin_segments::{create_segment, delete_segment, list_segments_page};
+pub use admin_subscribers::{
+    add_subscriber, confirm_subscriber, delete_subscriber, duplicates_page, edit_subscriber,
+    export_subscribers, import_form, import_report, list_subscribers_json, list_subscribers_page,
+    merge_duplicate, resend_confirmation, subscriber_detail, subscriber_detail_json,
+    unsubscribe_subscriber, upload_import,
+};
+pub use admin_tags::{list_tags_page, update_subscriber_tags};
+pub use admin_templates::{
+    create_layout, edit_layout_form, layout_preview, list_templates, new_layout_form,
+    system_email_form, update_layout, update_system_email,
+};
+pub use archive::{archive, archive_issue};
 pub use change_password::{form_password, password_change};
 pub use dashboard::admin_dashboard;
+pub use feeds::{atom_feed, rss_feed};
 pub use health_check::*;
 pub use home::*;
 pub use log_out::*;
 pub use login::*;
 pub use newsletter::*;
+pub use notification_settings::{
+    notification_settings_form, update_notification_settings, verify_user_email,
+};
+pub use password_reset::{
+    password_reset_form, password_reset_request_form, request_password_reset, reset_password,
+};
+pub use privacy::{
+    download_data, erase_data, erasure_form, privacy_page, request_data, request_erasure,
+};
+pub use revoke_sessions::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use unsubscribe::*;
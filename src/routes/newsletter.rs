@@ -1,7 +1,13 @@
+use crate::attributes::{issue_variables, list_definitions};
 use crate::authentication::{AuthError, Credential, validate_credential};
-use crate::{
-    domain::SubscriberEmail, email_client::EmailClient, routes::subscriptions::error_chain_fmt,
-};
+use crate::configuration::NewsletterSettings;
+use crate::email_html::prepare_email_html;
+use crate::mailing_lists::get_list_by_slug;
+use crate::markdown::render_markdown;
+use crate::merge_tags::validate_merge_tags;
+use crate::newsletter_issues::{Audience, insert_draft, publish_issue};
+use crate::routes::subscriptions::error_chain_fmt;
+use crate::segments::get_segment_by_name;
 
 use actix_web::http::StatusCode;
 use actix_web::http::header;
@@ -18,54 +24,100 @@ use sqlx::PgPool;
 pub struct Mail {
     pub title: String,
     pub content: MailContent,
+    /// The slugs of the lists the issue is sent to, the default list if
+    /// there are none.
+    #[serde(default)]
+    pub lists: Vec<String>,
+    /// The name of the segment the members of the lists are narrowed to.
+    #[serde(default)]
+    pub segment: Option<String>,
 }
 
+/// Either markdown, from which both versions are derived, or the HTML and
+/// plain-text versions written by hand.
 #[derive(Deserialize)]
-pub struct MailContent {
-    pub html: String,
-    pub text: String,
+#[serde(untagged)]
+pub enum MailContent {
+    Markdown { markdown: String },
+    Explicit { html: String, text: String },
+}
+
+impl MailContent {
+    /// Returns the HTML and the plain-text versions of the content.
+    pub fn render(self, settings: &NewsletterSettings) -> (String, String) {
+        match self {
+            MailContent::Markdown { markdown } => {
+                let rendered = render_markdown(&markdown, &settings.markdown_layout);
+                (rendered.html, rendered.text)
+            }
+            MailContent::Explicit { html, text } => (html, text),
+        }
+    }
 }
 
 #[tracing::instrument(
     name = "Publish newsletter",
-    skip(connection, email_client, mail_to_send, request)
+    skip(connection, mail_to_send, request, settings)
 )]
 #[actix_web::post("/newsletter")]
 pub async fn newsletter(
     connection: web::Data<PgPool>,
-    email_client: web::Data<EmailClient>,
     mail_to_send: web::Json<Mail>,
     request: HttpRequest,
+    settings: web::Data<NewsletterSettings>,
 ) -> Result<HttpResponse, NewsletterError> {
     let credential = basic_auth(&request.headers())?;
 
     tracing::Span::current().record("username", &tracing::field::display(&credential.username));
     let id = validate_credential(&connection, credential).await?;
     tracing::Span::current().record("id", &tracing::field::display(&id));
-    let saved = get_confirmed_subscribers(&connection)
-        .await
-        .context("Failed to fetch the database")?;
-
-    for mail in saved {
-        match mail {
-            Ok(email) => email_client
-                .send_email(
-                    &email.email,
-                    &mail_to_send.title,
-                    &mail_to_send.content.html,
-                    &mail_to_send.content.text,
-                )
-                .await
-                .with_context(|| format!("Could not send email to {}", email.email.as_ref()))?,
-            Err(error) => {
-                tracing::warn!(
-                    error.cause_chain = ?error,
-                    "Skipping a confirmed subscriber. \
-                        Their stored contact details are invalid",
-                )
+    let Mail {
+        title,
+        content,
+        lists,
+        segment,
+    } = mail_to_send.into_inner();
+    let (html, text) = content.render(&settings);
+    let attributes = list_definitions(&connection).await?;
+    validate_merge_tags(&html, &text, &issue_variables(&attributes))
+        .map_err(|e| NewsletterError::ValidationError(e.to_string()))?;
+    let mut list_ids = Vec::with_capacity(lists.len());
+    for slug in &lists {
+        match get_list_by_slug(&connection, slug).await? {
+            Some(list) if !slug.is_empty() => list_ids.push(list.id),
+            _ => {
+                return Err(NewsletterError::ValidationError(format!(
+                    "The list `{}` does not exist.",
+                    slug
+                )));
             }
         }
     }
+    let segment_id = match segment {
+        Some(name) => match get_segment_by_name(&connection, &name).await? {
+            Some(segment) => Some(segment.id),
+            None => {
+                return Err(NewsletterError::ValidationError(format!(
+                    "The segment `{}` does not exist.",
+                    name
+                )));
+            }
+        },
+        None => None,
+    };
+    let issue_id = insert_draft(
+        connection.get_ref(),
+        &title,
+        &text,
+        &prepare_email_html(&html),
+        None,
+        &Audience {
+            list_ids,
+            segment_id,
+        },
+    )
+    .await?;
+    publish_issue(&connection, issue_id).await?;
 
     Ok(HttpResponse::Ok().finish())
 }
@@ -108,30 +160,10 @@ fn basic_auth(header: &HeaderMap) -> Result<Credential, AuthError> {
     })
 }
 
-#[tracing::instrument(name = "Get confirmed subscribers", skip(pool))]
-async fn get_confirmed_subscribers(
-    pool: &PgPool,
-) -> Result<Vec<Result<ConfirmedSubscriber, anyhow::Error>>, anyhow::Error> {
-    let confirmed_subscribers = sqlx::query!(
-        r#"
-SELECT email
-FROM subscriptions
-WHERE status_subscription = 'confirmed'
-"#,
-    )
-    .fetch_all(pool)
-    .await?
-    .into_iter()
-    .map(|r| match SubscriberEmail::parse(r.email) {
-        Ok(email) => Ok(ConfirmedSubscriber { email }),
-        Err(error) => Err(anyhow::anyhow!(error)),
-    })
-    .collect();
-    Ok(confirmed_subscribers)
-}
-
 #[derive(thiserror::Error)]
 pub enum NewsletterError {
+    #[error("{0}")]
+    ValidationError(String),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
     #[error("Authentification failed")]
@@ -146,12 +178,14 @@ impl std::fmt::Debug for NewsletterError {
 impl ResponseError for NewsletterError {
     fn status_code(&self) -> actix_web::http::StatusCode {
         match self {
+            NewsletterError::ValidationError(_) => StatusCode::BAD_REQUEST,
             NewsletterError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             NewsletterError::AuthError(_) => StatusCode::UNAUTHORIZED,
         }
     }
     fn error_response(&self) -> HttpResponse {
         match self {
+            NewsletterError::ValidationError(_) => HttpResponse::new(StatusCode::BAD_REQUEST),
             NewsletterError::UnexpectedError(_) => {
                 HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR)
             }
@@ -169,11 +203,6 @@ impl ResponseError for NewsletterError {
     }
 }
 
-#[derive(sqlx::FromRow)]
-pub struct ConfirmedSubscriber {
-    email: SubscriberEmail,
-}
-
 impl From<AuthError> for NewsletterError {
     fn from(value: AuthError) -> Self {
         match value {
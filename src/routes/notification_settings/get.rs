@@ -0,0 +1,66 @@
+use crate::authentication::UserId;
+use crate::utils::{e500, escape_html};
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn notification_settings_form(
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    flash_message: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let mut message_html = String::new();
+    for m in flash_message.iter() {
+        writeln!(message_html, "<p><i>{}</i></p>", m.content()).map_err(e500)?;
+    }
+    let settings = sqlx::query!(
+        r#"SELECT email, email_verified_at, login_notifications FROM users WHERE user_id = $1"#,
+        *user_id
+    )
+    .fetch_one(pool.as_ref())
+    .await
+    .context("Failed to fetch the notification settings")
+    .map_err(e500)?;
+    let email = escape_html(settings.email.as_deref().unwrap_or_default());
+    let verified = match (&settings.email, settings.email_verified_at) {
+        (None, _) => "",
+        (Some(_), Some(_)) => "<p>Your email is verified.</p>",
+        (Some(_), None) => {
+            "<p>Your email is not verified yet, follow the link we have emailed you.</p>"
+        }
+    };
+    let checked = if settings.login_notifications {
+        "checked"
+    } else {
+        ""
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>Login notifications</title>
+</head>
+<body>
+{message_html}
+{verified}
+<form action="/admin/settings/notifications" method="post">
+<label>Email
+<input type="email" placeholder="Enter your email" name="email" value="{email}">
+</label>
+<label>
+<input type="checkbox" name="login_notifications" value="on" {checked}>
+Email me on login from a new device and after repeated failed attempts
+</label>
+<button type="submit">Save</button>
+</form>
+<p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
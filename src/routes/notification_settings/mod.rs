@@ -0,0 +1,5 @@
+pub mod get;
+pub mod post;
+
+pub use get::notification_settings_form;
+pub use post::{update_notification_settings, verify_user_email};
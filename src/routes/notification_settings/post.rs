@@ -0,0 +1,134 @@
+use crate::authentication::UserId;
+use crate::authentication::notifications::{send_email_verification, verify_email_payload};
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::signature::verify_signed_query;
+use crate::startup::{ApplicationBaseUrl, HmacSecret};
+use crate::utils::{e500, see_other};
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::Utc;
+use serde::Deserialize;
+use sqlx::PgPool;
+
+#[derive(Deserialize)]
+pub struct NotificationSettingsForm {
+    email: String,
+    login_notifications: Option<String>,
+}
+
+#[tracing::instrument(
+    name = "Update notification settings",
+    skip(form, user_id, pool, email_client, base_url, hmac_secret)
+)]
+pub async fn update_notification_settings(
+    form: web::Form<NotificationSettingsForm>,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let form = form.into_inner();
+    let login_notifications = form.login_notifications.is_some();
+    let email = if form.email.trim().is_empty() {
+        None
+    } else {
+        match SubscriberEmail::parse(form.email) {
+            Ok(email) => Some(email),
+            Err(e) => {
+                FlashMessage::error(e).send();
+                return Ok(see_other("/admin/settings/notifications"));
+            }
+        }
+    };
+    if login_notifications && email.is_none() {
+        FlashMessage::error("An email is required to receive login notifications.").send();
+        return Ok(see_other("/admin/settings/notifications"));
+    }
+    // A new address has to be verified again.
+    let saved = sqlx::query!(
+        r#"UPDATE users SET email = $1, login_notifications = $2,
+            email_verified_at = CASE WHEN lower(email) = lower($1) THEN email_verified_at END
+        WHERE user_id = $3
+            AND NOT EXISTS (
+                SELECT 1 FROM users WHERE lower(email) = lower($1) AND user_id <> $3
+            )
+        RETURNING email_verified_at IS NOT NULL AS "verified!""#,
+        email.as_ref().map(|e| e.as_ref()),
+        login_notifications,
+        *user_id
+    )
+    .fetch_optional(pool.as_ref())
+    .await
+    .context("Failed to update the notification settings")
+    .map_err(e500)?;
+    let Some(saved) = saved else {
+        FlashMessage::error("This email is used by another user.").send();
+        return Ok(see_other("/admin/settings/notifications"));
+    };
+    match email {
+        Some(email) if !saved.verified => {
+            send_email_verification(&email_client, &base_url, &hmac_secret, *user_id, &email)
+                .await
+                .map_err(e500)?;
+            FlashMessage::info(
+                "Your notification settings have been saved. \
+                Follow the link we have emailed you to verify your email.",
+            )
+            .send();
+        }
+        _ => FlashMessage::info("Your notification settings have been saved.").send(),
+    }
+    Ok(see_other("/admin/settings/notifications"))
+}
+
+#[derive(Deserialize)]
+pub struct VerifyEmailParameters {
+    expires: i64,
+    signature: String,
+}
+
+/// Behind the login, a mail scanner following the link verifies nothing.
+#[tracing::instrument(
+    name = "Verify the email of a user",
+    skip(parameters, user_id, pool, hmac_secret)
+)]
+pub async fn verify_user_email(
+    parameters: web::Query<VerifyEmailParameters>,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let user = sqlx::query!(r#"SELECT email FROM users WHERE user_id = $1"#, *user_id)
+        .fetch_one(pool.as_ref())
+        .await
+        .context("Failed to fetch the email of the user")
+        .map_err(e500)?;
+    let valid = user.email.as_deref().is_some_and(|email| {
+        verify_signed_query(
+            &hmac_secret,
+            &verify_email_payload(*user_id, email),
+            parameters.expires,
+            &parameters.signature,
+        )
+    });
+    if !valid {
+        FlashMessage::error("The verification link is invalid or has expired.").send();
+        return Ok(see_other("/admin/settings/notifications"));
+    }
+    sqlx::query!(
+        r#"UPDATE users SET email_verified_at = $1 WHERE user_id = $2"#,
+        Utc::now(),
+        *user_id
+    )
+    .execute(pool.as_ref())
+    .await
+    .context("Failed to verify the email of the user")
+    .map_err(e500)?;
+    FlashMessage::info("Your email is verified.").send();
+    Ok(see_other("/admin/settings/notifications"))
+}
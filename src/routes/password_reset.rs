@@ -0,0 +1,261 @@
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::merge_tags::MergeValues;
+use crate::signature::{signed_query, verify_signed_query};
+use crate::startup::{ApplicationBaseUrl, HmacSecret};
+use crate::templates::{PASSWORD_RESET, render_system_email};
+use crate::utils::{e500, escape_html};
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use argon2::password_hash::{SaltString, rand_core::OsRng};
+use argon2::{Argon2, PasswordHasher};
+use chrono::Utc;
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// How long the link sent by email works.
+const RESET_LINK_VALIDITY_HOURS: i64 = 1;
+
+/// The link is bound to the current password hash, so it stops working once
+/// the password is changed.
+fn password_reset_payload(user_id: Uuid, password_hash: &str) -> String {
+    format!("password_reset:{user_id}:{password_hash}")
+}
+
+fn password_reset_html(title: &str, body: &str) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>{title}</title>
+</head>
+<body>
+{body}
+</body>
+</html>"#
+        ))
+}
+
+pub async fn password_reset_request_form() -> HttpResponse {
+    password_reset_html(
+        "Reset your password",
+        r#"<p>We will email a link to choose a new password to the verified email of your account.</p>
+<form action="/password-reset" method="post">
+<label>Username
+<input type="text" placeholder="Enter Username" name="username">
+</label>
+<button type="submit">Send me the link</button>
+</form>
+<p><a href="/login">&lt;- Back</a></p>"#,
+    )
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetRequestForm {
+    username: String,
+}
+
+#[tracing::instrument(
+    name = "Request a password reset",
+    skip(form, pool, email_client, base_url, hmac_secret),
+    fields(username=%form.username)
+)]
+pub async fn request_password_reset(
+    form: web::Form<PasswordResetRequestForm>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    send_reset_link(
+        &form.username,
+        &pool,
+        &email_client,
+        &base_url,
+        &hmac_secret,
+    )
+    .await
+    .map_err(e500)?;
+    // The same page whether the user is known or not.
+    Ok(password_reset_html(
+        "Check your inbox",
+        "<p>If this account has a verified email, we have sent it a link to reset the password.</p>",
+    ))
+}
+
+async fn send_reset_link(
+    username: &str,
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &ApplicationBaseUrl,
+    hmac_secret: &HmacSecret,
+) -> Result<(), anyhow::Error> {
+    // Only a verified address, an admin cannot set another one to take over
+    // the account.
+    let Some(user) = sqlx::query!(
+        r#"SELECT user_id, username, password_hash, email AS "email!" FROM users
+        WHERE username = $1 AND email IS NOT NULL AND email_verified_at IS NOT NULL"#,
+        username
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look for the user")?
+    else {
+        return Ok(());
+    };
+    let email = SubscriberEmail::parse(user.email).map_err(anyhow::Error::msg)?;
+    let expires_at = Utc::now() + chrono::Duration::hours(RESET_LINK_VALIDITY_HOURS);
+    let reset_link = format!(
+        "{}/password-reset/confirm?user_id={}&{}",
+        base_url.0,
+        user.user_id,
+        signed_query(
+            hmac_secret,
+            &password_reset_payload(user.user_id, &user.password_hash),
+            expires_at
+        )
+    );
+    let rendered = render_system_email(
+        pool,
+        PASSWORD_RESET,
+        &MergeValues(vec![
+            ("name", &user.username),
+            ("email", email.as_ref()),
+            ("reset_link", &reset_link),
+        ]),
+    )
+    .await?;
+    email_client
+        .send_email(&email, &rendered.subject, &rendered.html, &rendered.text)
+        .await?;
+    Ok(())
+}
+
+/// The parameters of the link sent by email.
+#[derive(Deserialize)]
+pub struct ResetParameters {
+    user_id: Uuid,
+    expires: i64,
+    signature: String,
+}
+
+impl ResetParameters {
+    async fn verify(&self, pool: &PgPool, hmac_secret: &HmacSecret) -> Result<bool, anyhow::Error> {
+        let Some(user) = sqlx::query!(
+            r#"SELECT password_hash FROM users WHERE user_id = $1"#,
+            self.user_id
+        )
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch the password hash of the user")?
+        else {
+            return Ok(false);
+        };
+        Ok(verify_signed_query(
+            hmac_secret,
+            &password_reset_payload(self.user_id, &user.password_hash),
+            self.expires,
+            &self.signature,
+        ))
+    }
+
+    fn form_html(&self, error: &str) -> HttpResponse {
+        password_reset_html(
+            "Choose a new password",
+            &format!(
+                r#"{error}<form action="/password-reset/confirm" method="post">
+<input hidden type="text" name="user_id" value="{user_id}">
+<input hidden type="text" name="expires" value="{expires}">
+<input hidden type="text" name="signature" value="{signature}">
+<label>New password
+<input type="password" placeholder="Enter new password" name="password">
+</label>
+<label>Confirm new password
+<input type="password" placeholder="Type the new password again" name="confirmpassword">
+</label>
+<button type="submit">Reset my password</button>
+</form>"#,
+                user_id = self.user_id,
+                expires = self.expires,
+                signature = escape_html(&self.signature),
+            ),
+        )
+    }
+}
+
+fn invalid_link() -> HttpResponse {
+    HttpResponse::Unauthorized()
+        .content_type(ContentType::html())
+        .body("<p>The link is invalid or has expired, please ask for a new one.</p>")
+}
+
+pub async fn password_reset_form(
+    parameters: web::Query<ResetParameters>,
+    pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !parameters.verify(&pool, &hmac_secret).await.map_err(e500)? {
+        return Ok(invalid_link());
+    }
+    Ok(parameters.form_html(""))
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordForm {
+    user_id: Uuid,
+    expires: i64,
+    signature: String,
+    password: Secret<String>,
+    #[serde(rename = "confirmpassword")]
+    confirm_password: Secret<String>,
+}
+
+/// Every session of the user is revoked along the way.
+#[tracing::instrument(name = "Reset a password", skip(form, pool, hmac_secret), fields(user_id=%form.user_id))]
+pub async fn reset_password(
+    form: web::Form<ResetPasswordForm>,
+    pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let form = form.into_inner();
+    let parameters = ResetParameters {
+        user_id: form.user_id,
+        expires: form.expires,
+        signature: form.signature,
+    };
+    if !parameters.verify(&pool, &hmac_secret).await.map_err(e500)? {
+        return Ok(invalid_link());
+    }
+    if form.password.expose_secret() != form.confirm_password.expose_secret() {
+        return Ok(parameters.form_html(
+            "<p><i>You entered two different new passwords - the field values must match.</i></p>\n",
+        ));
+    }
+    let salt = SaltString::generate(&mut OsRng);
+    let password_hash = Argon2::default()
+        .hash_password(form.password.expose_secret().as_bytes(), &salt)
+        .expect("Could not hash properly")
+        .to_string();
+    sqlx::query!(
+        r#"UPDATE users SET password_hash = $1, sessions_revoked_at = $2, failed_login_attempts = 0
+        WHERE user_id = $3"#,
+        password_hash,
+        Utc::now(),
+        parameters.user_id
+    )
+    .execute(pool.as_ref())
+    .await
+    .context("Failed to reset the password")
+    .map_err(e500)?;
+    Ok(password_reset_html(
+        "Password reset",
+        r#"<p>Your password has been reset, every session of your account has been logged out.</p>
+<p><a href="/login">Login</a></p>"#,
+    ))
+}
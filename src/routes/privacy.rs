@@ -0,0 +1,314 @@
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::erasure::erase_subscriber;
+use crate::merge_tags::MergeValues;
+use crate::routes::admin_subscribers::detail::{SubscriberDetail, SubscriberDetailJson};
+use crate::signature::{signed_query, verify_signed_query};
+use crate::startup::{ApplicationBaseUrl, HmacSecret};
+use crate::subscribers::get_subscribers_by_email;
+use crate::templates::{DATA_REQUEST, ERASURE_REQUEST, render_system_email};
+use crate::utils::{e500, escape_html};
+use actix_web::http::header::{ContentDisposition, ContentType, DispositionParam, DispositionType};
+use actix_web::{HttpResponse, web};
+use chrono::Utc;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+/// How long the links sent by email work.
+const PRIVACY_LINK_VALIDITY_HOURS: i64 = 24;
+
+/// What a subscriber can ask about their data, each is confirmed by a link
+/// sent to their address.
+#[derive(Debug, Clone, Copy)]
+enum PrivacyRequest {
+    Data,
+    Erasure,
+}
+
+impl PrivacyRequest {
+    fn system_email(&self) -> &'static str {
+        match self {
+            PrivacyRequest::Data => DATA_REQUEST,
+            PrivacyRequest::Erasure => ERASURE_REQUEST,
+        }
+    }
+
+    fn path(&self) -> &'static str {
+        match self {
+            PrivacyRequest::Data => "/privacy/data",
+            PrivacyRequest::Erasure => "/privacy/erasure",
+        }
+    }
+
+    fn link_variable(&self) -> &'static str {
+        match self {
+            PrivacyRequest::Data => "download_link",
+            PrivacyRequest::Erasure => "erasure_link",
+        }
+    }
+
+    fn payload(&self, email: &str) -> String {
+        match self {
+            PrivacyRequest::Data => format!("data_request:{email}"),
+            PrivacyRequest::Erasure => format!("erasure_request:{email}"),
+        }
+    }
+}
+
+fn privacy_html(title: &str, body: &str) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>{title}</title>
+</head>
+<body>
+{body}
+</body>
+</html>"#
+        ))
+}
+
+pub async fn privacy_page() -> HttpResponse {
+    privacy_html(
+        "Your data",
+        r#"<h2>Download your data</h2>
+<p>We will email you a link to download everything we hold about you.</p>
+<form action="/privacy/data-request" method="post">
+<label>Email
+<input type="email" name="email">
+</label>
+<button type="submit">Send me the link</button>
+</form>
+<h2>Erase your data</h2>
+<p>We will email you a link to confirm. Once erased, you will not receive our newsletters anymore and we cannot add you back.</p>
+<form action="/privacy/erasure-request" method="post">
+<label>Email
+<input type="email" name="email">
+</label>
+<button type="submit">Send me the link</button>
+</form>"#,
+    )
+}
+
+#[derive(Deserialize)]
+pub struct PrivacyRequestForm {
+    email: String,
+}
+
+/// The same page whether the address is known or not.
+fn request_sent_page() -> HttpResponse {
+    privacy_html(
+        "Check your inbox",
+        "<p>If we hold data about this address, we have sent it an email with a link.</p>",
+    )
+}
+
+#[tracing::instrument(
+    name = "Request one's data",
+    skip(form, pool, email_client, base_url, hmac_secret)
+)]
+pub async fn request_data(
+    form: web::Form<PrivacyRequestForm>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = SubscriberEmail::parse(form.0.email).map_err(actix_web::error::ErrorBadRequest)?;
+    send_request_link(
+        PrivacyRequest::Data,
+        &email,
+        &pool,
+        &email_client,
+        &base_url,
+        &hmac_secret,
+    )
+    .await
+    .map_err(e500)?;
+    Ok(request_sent_page())
+}
+
+#[tracing::instrument(
+    name = "Request an erasure",
+    skip(form, pool, email_client, base_url, hmac_secret)
+)]
+pub async fn request_erasure(
+    form: web::Form<PrivacyRequestForm>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = SubscriberEmail::parse(form.0.email).map_err(actix_web::error::ErrorBadRequest)?;
+    send_request_link(
+        PrivacyRequest::Erasure,
+        &email,
+        &pool,
+        &email_client,
+        &base_url,
+        &hmac_secret,
+    )
+    .await
+    .map_err(e500)?;
+    Ok(request_sent_page())
+}
+
+async fn send_request_link(
+    request: PrivacyRequest,
+    email: &SubscriberEmail,
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &ApplicationBaseUrl,
+    hmac_secret: &HmacSecret,
+) -> Result<(), anyhow::Error> {
+    let Some(subscriber) = get_subscribers_by_email(pool, email)
+        .await?
+        .into_iter()
+        .next()
+    else {
+        return Ok(());
+    };
+    let expires_at = Utc::now() + chrono::Duration::hours(PRIVACY_LINK_VALIDITY_HOURS);
+    let url = Url::parse_with_params(
+        &format!("{}{}", base_url.0, request.path()),
+        &[("email", email.as_ref())],
+    )?;
+    let link = format!(
+        "{}&{}",
+        url,
+        signed_query(hmac_secret, &request.payload(email.as_ref()), expires_at)
+    );
+    let rendered = render_system_email(
+        pool,
+        request.system_email(),
+        &MergeValues(vec![
+            ("name", &subscriber.name),
+            ("email", email.as_ref()),
+            (request.link_variable(), &link),
+        ]),
+    )
+    .await?;
+    email_client
+        .send_email(email, &rendered.subject, &rendered.html, &rendered.text)
+        .await?;
+    Ok(())
+}
+
+/// The parameters of a link sent by email.
+#[derive(Deserialize)]
+pub struct SignedEmail {
+    email: String,
+    expires: i64,
+    signature: String,
+}
+
+impl SignedEmail {
+    fn verify(&self, request: PrivacyRequest, hmac_secret: &HmacSecret) -> bool {
+        verify_signed_query(
+            hmac_secret,
+            &request.payload(&self.email),
+            self.expires,
+            &self.signature,
+        )
+    }
+}
+
+#[derive(Serialize)]
+struct DownloadedData {
+    #[serde(flatten)]
+    subscriber: SubscriberDetailJson,
+    /// The other subscribers with the address, waiting for a merge.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    duplicates: Vec<SubscriberDetailJson>,
+}
+
+/// Everything held about the subscriber, as a JSON file.
+#[tracing::instrument(name = "Download one's data", skip(parameters, pool, hmac_secret))]
+pub async fn download_data(
+    parameters: web::Query<SignedEmail>,
+    pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !parameters.verify(PrivacyRequest::Data, &hmac_secret) {
+        return Ok(HttpResponse::Unauthorized().finish());
+    }
+    let email =
+        SubscriberEmail::parse(parameters.0.email).map_err(actix_web::error::ErrorBadRequest)?;
+    let mut details = Vec::new();
+    for subscriber in get_subscribers_by_email(&pool, &email)
+        .await
+        .map_err(e500)?
+    {
+        if let Some(detail) = SubscriberDetail::load(&pool, subscriber.id)
+            .await
+            .map_err(e500)?
+        {
+            details.push(SubscriberDetailJson::from(detail).without_admins());
+        }
+    }
+    let mut details = details.into_iter();
+    let Some(subscriber) = details.next() else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    Ok(HttpResponse::Ok()
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("my-data.json".to_string())],
+        })
+        .json(DownloadedData {
+            subscriber,
+            duplicates: details.collect(),
+        }))
+}
+
+/// The link only shows a confirmation, so that a mail scanner following it
+/// does not erase anything.
+pub async fn erasure_form(
+    parameters: web::Query<SignedEmail>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> HttpResponse {
+    if !parameters.verify(PrivacyRequest::Erasure, &hmac_secret) {
+        return HttpResponse::Unauthorized().finish();
+    }
+    privacy_html(
+        "Erase your data",
+        &format!(
+            r#"<p>Erase everything we hold about {email}? This cannot be undone.</p>
+<form action="/privacy/erasure" method="post">
+<input hidden type="text" name="email" value="{email}">
+<input hidden type="text" name="expires" value="{expires}">
+<input hidden type="text" name="signature" value="{signature}">
+<button type="submit">Erase my data</button>
+</form>"#,
+            email = escape_html(&parameters.email),
+            expires = parameters.expires,
+            signature = escape_html(&parameters.signature),
+        ),
+    )
+}
+
+#[tracing::instrument(name = "Erase one's data", skip(form, pool, hmac_secret))]
+pub async fn erase_data(
+    form: web::Form<SignedEmail>,
+    pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !form.verify(PrivacyRequest::Erasure, &hmac_secret) {
+        return Ok(HttpResponse::Unauthorized().finish());
+    }
+    let email = SubscriberEmail::parse(form.0.email).map_err(actix_web::error::ErrorBadRequest)?;
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    erase_subscriber(&mut transaction, &email)
+        .await
+        .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+    Ok(privacy_html(
+        "Data erased",
+        "<p>Your data has been erased, you will not receive our newsletters anymore.</p>",
+    ))
+}
@@ -0,0 +1,96 @@
+use crate::authentication::notifications::revoke_sessions_payload;
+use crate::signature::verify_signed_query;
+use crate::startup::HmacSecret;
+use crate::utils::{e500, escape_html};
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
+use chrono::Utc;
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+pub struct RevokeParameters {
+    user_id: Uuid,
+    expires: i64,
+    signature: String,
+}
+
+impl RevokeParameters {
+    fn verify(&self, hmac_secret: &HmacSecret) -> bool {
+        verify_signed_query(
+            hmac_secret,
+            &revoke_sessions_payload(self.user_id),
+            self.expires,
+            &self.signature,
+        )
+    }
+}
+
+fn revoke_html(title: &str, body: &str) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>{title}</title>
+</head>
+<body>
+{body}
+</body>
+</html>"#
+        ))
+}
+
+/// The link only shows a confirmation, so that a mail scanner following it
+/// does not log anyone out.
+pub async fn revoke_sessions_form(
+    parameters: web::Query<RevokeParameters>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> HttpResponse {
+    if !parameters.verify(&hmac_secret) {
+        return HttpResponse::Unauthorized().finish();
+    }
+    revoke_html(
+        "Revoke your sessions",
+        &format!(
+            r#"<p>Log out every device logged in to your account?</p>
+<form action="/sessions/revoke" method="post">
+<input hidden type="text" name="user_id" value="{user_id}">
+<input hidden type="text" name="expires" value="{expires}">
+<input hidden type="text" name="signature" value="{signature}">
+<button type="submit">Revoke all my sessions</button>
+</form>"#,
+            user_id = parameters.user_id,
+            expires = parameters.expires,
+            signature = escape_html(&parameters.signature),
+        ),
+    )
+}
+
+#[tracing::instrument(name = "Revoke all the sessions of a user", skip(form, pool, hmac_secret), fields(user_id=%form.user_id))]
+pub async fn revoke_sessions(
+    form: web::Form<RevokeParameters>,
+    pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !form.verify(&hmac_secret) {
+        return Ok(HttpResponse::Unauthorized().finish());
+    }
+    sqlx::query!(
+        r#"UPDATE users SET sessions_revoked_at = $1 WHERE user_id = $2"#,
+        Utc::now(),
+        form.user_id
+    )
+    .execute(pool.as_ref())
+    .await
+    .map_err(e500)?;
+    Ok(revoke_html(
+        "Sessions revoked",
+        r#"<p>All your sessions have been revoked.</p>
+<p>Log in again and consider changing your password.</p>
+<p><a href="/login">Login</a></p>"#,
+    ))
+}
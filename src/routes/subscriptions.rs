@@ -1,9 +1,18 @@
+use std::collections::HashMap;
 use std::fmt::Display;
 
+use crate::attributes::{list_definitions, parse_submitted_values, store_attributes};
+use crate::bot_protection::{ChallengeVerifier, check_fill_time, check_rate_limits};
+use crate::configuration::SignupSettings;
+use crate::consent::{ConsentContext, parse_consent, record_signup};
 use crate::domain::{NewSubscriber, SubscriberEmail, SuscriberName};
 use crate::email_client::EmailClient;
-use crate::startup::ApplicationBaseUrl;
-use actix_web::{HttpResponse, post, web};
+use crate::email_domains::load_domain_policy;
+use crate::mailing_lists::{MailingList, get_list_by_slug};
+use crate::merge_tags::MergeValues;
+use crate::startup::{ApplicationBaseUrl, HmacSecret};
+use crate::templates::{SUBSCRIPTION_CONFIRMATION, render_system_email};
+use actix_web::{HttpRequest, HttpResponse, post, web};
 use anyhow::Context;
 use chrono::Utc;
 use rand::distr::Alphanumeric;
@@ -17,13 +26,47 @@ use uuid::Uuid;
 pub struct SubscriptionForm {
     email: String,
     name: String,
+    /// The slug of the list, the default list if empty.
+    #[serde(default)]
+    list: String,
+    /// The version of the consent text shown by the form.
+    #[serde(default)]
+    consent_version: String,
+    /// The form or the site the signup comes from.
+    #[serde(default)]
+    source: String,
+    /// The honeypot, hidden from people.
+    #[serde(default)]
+    website: String,
+    /// When the form was shown, signed.
+    #[serde(default)]
+    form_rendered_at: String,
+    #[serde(default)]
+    form_signature: String,
+    /// Sent by the widget of the challenge, if there is one.
+    #[serde(default)]
+    challenge_response: String,
+    /// The values of the attributes, each field is named after the key.
+    #[serde(flatten)]
+    attributes: HashMap<String, String>,
 }
+#[allow(clippy::too_many_arguments)]
 #[tracing::instrument(
     name = "Adding a new subscriber",
-    skip(form, connection, email_client, base_url),
+    skip(
+        form,
+        connection,
+        email_client,
+        base_url,
+        signup_settings,
+        hmac_secret,
+        challenge_verifier,
+        request
+    ),
     fields(
         subscriber_email = %form.email,
-        subcriber_name = %form.name
+        subcriber_name = %form.name,
+        list = %form.list
 )
 )]
 #[post("/subscription")]
@@ -32,80 +75,197 @@ async fn subscribe(
     connection: web::Data<PgPool>,
     email_client: web::Data<EmailClient>,
     base_url: web::Data<ApplicationBaseUrl>,
+    signup_settings: web::Data<SignupSettings>,
+    hmac_secret: web::Data<HmacSecret>,
+    challenge_verifier: Option<web::Data<dyn ChallengeVerifier>>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, SubscribeError> {
+    if !form.website.is_empty() {
+        // A success for the bot, so that it does not try something else.
+        tracing::info!("The honeypot field is filled, the signup is ignored");
+        return Ok(HttpResponse::Ok().finish());
+    }
+    check_fill_time(
+        &hmac_secret,
+        &form.form_rendered_at,
+        &form.form_signature,
+        signup_settings.min_fill_seconds,
+        Utc::now(),
+    )
+    .map_err(SubscribeError::ValidationError)?;
+    let context = ConsentContext::from_request(&request);
+    if let Some(verifier) = &challenge_verifier
+        && !verifier
+            .verify(&form.challenge_response, context.ip_address.as_deref())
+            .await?
+    {
+        return Err(SubscribeError::ValidationError(
+            "The challenge was not passed, please try again.".to_string(),
+        ));
+    }
+    let list = get_list_by_slug(&connection, &form.list)
+        .await?
+        .ok_or_else(|| SubscribeError::ValidationError("The list does not exist.".to_string()))?;
+    let definitions = list_definitions(&connection).await?;
+    let attributes = parse_submitted_values(&definitions, &form.attributes)
+        .map_err(SubscribeError::ValidationError)?;
+    let (consent_version, source) = parse_consent(&form.consent_version, &form.source)
+        .map_err(SubscribeError::ValidationError)?;
     let new_subscriber: NewSubscriber =
         form.0.try_into().map_err(SubscribeError::ValidationError)?;
+    load_domain_policy(&connection, signup_settings.allowlist_only)
+        .await?
+        .check(&new_subscriber.email)
+        .map_err(SubscribeError::ValidationError)?;
+    if let Some(e) = check_rate_limits(
+        &connection,
+        &signup_settings,
+        context.ip_address.as_deref(),
+        new_subscriber.email.domain(),
+    )
+    .await?
+    {
+        return Err(SubscribeError::RateLimited(e));
+    }
     let mut transaction = connection
         .begin()
         .await
         .context("Failed to acquire a connection from the pool")?;
-    let subscriber_id = insert_suscriber(&mut transaction, &new_subscriber)
+    let (subscriber_id, inserted) = insert_suscriber(&mut transaction, &new_subscriber)
         .await
         .context("Failed to insert the user into database")?;
+    // Like the name, the attributes are those of the first signup: anyone can
+    // submit the form with the email of someone else.
+    if inserted {
+        store_attributes(&mut transaction, subscriber_id, &attributes).await?;
+    }
+    if !insert_membership(&mut transaction, subscriber_id, list.id)
+        .await
+        .context("Failed to add the subscriber to the list")?
+    {
+        // Already a confirmed member, there is nothing to confirm.
+        return Ok(HttpResponse::Ok().finish());
+    }
+    record_signup(
+        &mut transaction,
+        subscriber_id,
+        list.id,
+        &consent_version,
+        &source,
+        &context,
+    )
+    .await?;
     let token = generate_random_token();
-    store_token(&mut transaction, subscriber_id, &token)
+    store_token(&mut transaction, subscriber_id, list.id, &token)
         .await
         .context("Failed to store the token into the database")?;
     transaction
         .commit()
         .await
         .context("Failed to commit the transaction into the database")?;
-    send_email(&email_client, new_subscriber, base_url, &token)
-        .await
-        .context("Failed to send confirmation email")?;
+    send_email(
+        &connection,
+        &email_client,
+        new_subscriber,
+        &list,
+        base_url,
+        &token,
+    )
+    .await
+    .context("Failed to send confirmation email")?;
     Ok(HttpResponse::Ok().finish())
 }
 
+/// Returns the id of the subscriber, who may already be on another list, maybe
+/// with another case, and `true` if they were not subscribed yet.
 #[tracing::instrument(name = "Start subscription querry", skip(transaction, newsubscriber))]
 pub async fn insert_suscriber(
     transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
     newsubscriber: &NewSubscriber,
-) -> Result<Uuid, sqlx::Error> {
-    let id = Uuid::new_v4();
-    sqlx::query!(
-        r#"INSERT INTO subscriptions (id, email, name, subscribed_at, status_subscription)
-                    VALUES($1, $2, $3, $4, 'pending_confirmation')"#,
-        id,
+) -> Result<(Uuid, bool), sqlx::Error> {
+    // The no-op update lets `RETURNING` give the id of an existing subscriber,
+    // only a row this statement inserted has no `xmax`.
+    let subscriber = sqlx::query!(
+        r#"INSERT INTO subscriptions (id, email, name, subscribed_at, normalized_email)
+                    VALUES($1, $2, $3, $4, $5)
+                    ON CONFLICT (normalized_email)
+                    DO UPDATE SET normalized_email = EXCLUDED.normalized_email
+                    RETURNING id, xmax = 0 AS "inserted!""#,
+        Uuid::new_v4(),
         newsubscriber.email.as_ref(),
         newsubscriber.name.as_ref(),
-        Utc::now()
+        Utc::now(),
+        newsubscriber.email.normalized()
     )
-    .execute(&mut **transaction)
+    .fetch_one(&mut **transaction)
     .await
     .map_err(|e| {
         tracing::error!("Could not subscribe bacause {}", e);
         e
     })?;
-    Ok(id)
+    Ok((subscriber.id, subscriber.inserted))
+}
+
+/// Put the membership of the list back to `pending_confirmation`. Returns
+/// `false` if it is already confirmed.
+#[tracing::instrument(name = "Add a subscriber to a list", skip(transaction))]
+pub async fn insert_membership(
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    subscriber_id: Uuid,
+    list_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"INSERT INTO list_memberships (list_id, subscriber_id, status, subscribed_at)
+        VALUES ($1, $2, 'pending_confirmation', $3)
+        ON CONFLICT (list_id, subscriber_id) DO UPDATE
+        SET status = 'pending_confirmation', subscribed_at = EXCLUDED.subscribed_at
+        WHERE list_memberships.status <> 'confirmed'"#,
+        list_id,
+        subscriber_id,
+        Utc::now()
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(result.rows_affected() == 1)
 }
 
-#[tracing::instrument(name = "Send an email", skip(email_client, newsubscriber, base_url))]
+#[tracing::instrument(
+    name = "Send an email",
+    skip(pool, email_client, newsubscriber, list, base_url)
+)]
 pub async fn send_email(
+    pool: &PgPool,
     email_client: &EmailClient,
     newsubscriber: NewSubscriber,
+    list: &MailingList,
     base_url: web::Data<ApplicationBaseUrl>,
     token: &str,
-) -> Result<(), reqwest::Error> {
+) -> Result<(), anyhow::Error> {
     let confirmation_link = format!(
         "{}/subscription/confirm?subscription_token={}",
         base_url.get_ref().0,
         token
     );
+    let email = render_system_email(
+        pool,
+        SUBSCRIPTION_CONFIRMATION,
+        &MergeValues(vec![
+            ("name", newsubscriber.name.as_ref()),
+            ("email", newsubscriber.email.as_ref()),
+            ("confirmation_link", &confirmation_link),
+            ("list_name", &list.name),
+        ]),
+    )
+    .await?;
     email_client
         .send_email(
             &newsubscriber.email,
-            "Welcome!",
-            &format!(
-                "Welcome to our newsletter!<br />\
-                    Click <a href=\"{}\">here</a> to confirm your subscription.",
-                confirmation_link
-            ),
-            &format!(
-                "Welcome to our newsletter!\nVisit {} to confirm your subscription.",
-                confirmation_link
-            ),
+            &email.subject,
+            &email.html,
+            &email.text,
         )
-        .await
+        .await?;
+    Ok(())
 }
 
 impl TryFrom<SubscriptionForm> for NewSubscriber {
@@ -126,17 +286,21 @@ pub fn generate_random_token() -> String {
         .collect()
 }
 
+/// The token confirms the membership of one list.
 #[tracing::instrument(name = "Store token for subscription", skip(transaction, id, token))]
 pub async fn store_token(
     transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
     id: Uuid,
+    list_id: Uuid,
     token: &str,
 ) -> Result<(), StoreTokenError> {
     sqlx::query!(
-        r#"INSERT INTO subscriptions_tokens (subscriptions_tokens ,subscriptions_id)
-    VALUES ($1, $2)"#,
+        r#"INSERT INTO subscriptions_tokens (subscriptions_tokens ,subscriptions_id, list_id, created_at)
+    VALUES ($1, $2, $3, $4)"#,
         token,
-        id
+        id,
+        list_id,
+        Utc::now()
     )
     .execute(&mut **transaction)
     .await
@@ -185,6 +349,7 @@ impl actix_web::ResponseError for SubscribeError {
     fn status_code(&self) -> actix_web::http::StatusCode {
         match self {
             SubscribeError::ValidationError(_) => actix_web::http::StatusCode::BAD_REQUEST,
+            SubscribeError::RateLimited(_) => actix_web::http::StatusCode::TOO_MANY_REQUESTS,
             SubscribeError::UnexpectedError(_) => {
                 actix_web::http::StatusCode::INTERNAL_SERVER_ERROR
             }
@@ -196,6 +361,8 @@ impl actix_web::ResponseError for SubscribeError {
 pub enum SubscribeError {
     #[error("{0}")]
     ValidationError(String),
+    #[error("{0}")]
+    RateLimited(String),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
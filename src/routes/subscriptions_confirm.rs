@@ -1,6 +1,8 @@
+use crate::consent::{ConsentContext, record_confirmation};
 use crate::routes::subscriptions::error_chain_fmt;
-use actix_web::{HttpResponse, ResponseError, web};
+use actix_web::{HttpRequest, HttpResponse, ResponseError, web};
 use anyhow::Context;
+use chrono::Utc;
 use serde::Deserialize;
 use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
@@ -9,11 +11,12 @@ pub struct Parameters {
     subscription_token: String,
 }
 
-#[tracing::instrument(name = "Confirm subscription", skip(parameters, pool))]
+#[tracing::instrument(name = "Confirm subscription", skip(parameters, pool, request))]
 #[actix_web::get("/subscription/confirm")]
 pub async fn confirm(
     parameters: web::Query<Parameters>,
     pool: web::Data<PgPool>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, ConfirmationError> {
     let mut transaction = pool
         .begin()
@@ -24,10 +27,20 @@ pub async fn confirm(
         .context("Failed to get subscriber id")?;
     match id {
         None => return Ok(HttpResponse::Unauthorized().finish()),
-        Some(subscriber_id) => {
-            confirm_token(subscriber_id, &mut transaction)
+        Some((subscriber_id, list_id)) => {
+            confirm_token(subscriber_id, list_id, &mut transaction)
                 .await
                 .context("Failed to confirm the token")?;
+            record_token_use(&parameters.subscription_token, &mut transaction)
+                .await
+                .context("Failed to record the use of the token")?;
+            record_confirmation(
+                &mut transaction,
+                subscriber_id,
+                list_id,
+                &ConsentContext::from_request(&request),
+            )
+            .await?;
         }
     }
     transaction
@@ -40,12 +53,14 @@ pub async fn confirm(
 #[tracing::instrument(name = "Confirm the id", skip(id, transaction))]
 pub async fn confirm_token(
     id: Uuid,
+    list_id: Uuid,
     transaction: &mut Transaction<'_, Postgres>,
 ) -> Result<(), sqlx::Error> {
     sqlx::query!(
-        r#"UPDATE subscriptions  SET status_subscription = 'confirmed'
-        WHERE id=$1"#,
-        id
+        r#"UPDATE list_memberships SET status = 'confirmed'
+        WHERE subscriber_id = $1 AND list_id = $2"#,
+        id,
+        list_id
     )
     .execute(&mut **transaction)
     .await
@@ -56,13 +71,31 @@ pub async fn confirm_token(
     Ok(())
 }
 
+/// Keeps the time of the first use for the token history.
+#[tracing::instrument(name = "Record the use of a token", skip(token, transaction))]
+pub async fn record_token_use(
+    token: &str,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE subscriptions_tokens SET used_at = $1
+        WHERE subscriptions_tokens = $2 AND used_at IS NULL"#,
+        Utc::now(),
+        token
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+/// The subscriber and the list the token confirms.
 #[tracing::instrument(name = "Confirm the id", skip(token, transaction))]
 pub async fn get_subscriber_id_from_token(
     token: &str,
     transaction: &mut Transaction<'_, Postgres>,
-) -> Result<Option<Uuid>, sqlx::Error> {
+) -> Result<Option<(Uuid, Uuid)>, sqlx::Error> {
     let saved = sqlx::query!(
-        r#"SELECT subscriptions_id FROM subscriptions_tokens
+        r#"SELECT subscriptions_id, list_id FROM subscriptions_tokens
     WHERE subscriptions_tokens = $1"#,
         token
     )
@@ -72,7 +105,7 @@ pub async fn get_subscriber_id_from_token(
         tracing::error!("Failed to execute query: {:?}", e);
         e
     })?;
-    Ok(saved.map(|r| r.subscriptions_id))
+    Ok(saved.map(|r| (r.subscriptions_id, r.list_id)))
 }
 
 #[derive(thiserror::Error)]
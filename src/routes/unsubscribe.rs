@@ -0,0 +1,131 @@
+use crate::domain::SubscriberEmail;
+use crate::issue_rendering::verify_unsubscribe_signature;
+use crate::mailing_lists::get_list;
+use crate::startup::HmacSecret;
+use crate::utils::{e500, escape_html};
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// The parameters of the link of an issue. The signature does not expire: the
+/// link has to keep working in the old issues a reader still has, and it can
+/// only unsubscribe the address it was sent to.
+#[derive(Deserialize)]
+pub struct UnsubscribeParameters {
+    email: String,
+    /// The list the issue was sent through, the links without one leave
+    /// every list.
+    list: Option<Uuid>,
+    signature: String,
+    /// Set by the checkbox of the confirmation to leave every list anyway.
+    all: Option<String>,
+}
+
+impl UnsubscribeParameters {
+    fn verify(&self, hmac_secret: &HmacSecret) -> bool {
+        verify_unsubscribe_signature(hmac_secret, &self.email, self.list, &self.signature)
+    }
+}
+
+fn unsubscribe_html(title: &str, body: &str) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta http-equiv="content-type" content="text/html; charset=utf-8">
+<title>{title}</title>
+</head>
+<body>
+{body}
+</body>
+</html>"#
+        ))
+}
+
+/// The link only shows a confirmation, so that a mail scanner following it
+/// does not unsubscribe anyone.
+pub async fn unsubscribe_form(
+    parameters: web::Query<UnsubscribeParameters>,
+    pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !parameters.verify(&hmac_secret) {
+        return Ok(HttpResponse::Unauthorized().finish());
+    }
+    let email = escape_html(&parameters.email);
+    let list = match parameters.list {
+        Some(list_id) => get_list(&pool, list_id).await.map_err(e500)?,
+        None => None,
+    };
+    let (question, list_fields) = match list {
+        Some(list) => (
+            format!("Stop sending {} to {email}?", escape_html(&list.name)),
+            format!(
+                r#"<input hidden type="text" name="list" value="{}">
+<label><input type="checkbox" name="all" value="on"> Unsubscribe from all our lists</label>
+"#,
+                list.id
+            ),
+        ),
+        None => (
+            format!("Stop sending our newsletters to {email}?"),
+            String::new(),
+        ),
+    };
+    Ok(unsubscribe_html(
+        "Unsubscribe",
+        &format!(
+            r#"<p>{question}</p>
+<form action="/unsubscribe" method="post">
+<input hidden type="text" name="email" value="{email}">
+<input hidden type="text" name="signature" value="{signature}">
+{list_fields}<button type="submit">Unsubscribe</button>
+</form>"#,
+            signature = escape_html(&parameters.signature),
+        ),
+    ))
+}
+
+#[tracing::instrument(name = "Unsubscribe", skip(form, pool, hmac_secret), fields(subscriber_email=%form.email))]
+pub async fn unsubscribe(
+    form: web::Form<UnsubscribeParameters>,
+    pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !form.verify(&hmac_secret) {
+        return Ok(HttpResponse::Unauthorized().finish());
+    }
+    let form = form.into_inner();
+    let list_id = form.list.filter(|_| form.all.is_none());
+    let email = SubscriberEmail::parse(form.email).map_err(actix_web::error::ErrorBadRequest)?;
+    // The link names the address it was sent to: an unmerged duplicate leaves
+    // its own lists. The normalized email only finds the subscriber a merge
+    // kept in its place.
+    sqlx::query!(
+        r#"UPDATE list_memberships SET status = 'unsubscribed'
+        WHERE subscriber_id = COALESCE(
+                (SELECT id FROM subscriptions WHERE email = $1),
+                (SELECT id FROM subscriptions WHERE normalized_email = $2)
+            )
+            AND ($3::uuid IS NULL OR list_id = $3)"#,
+        email.as_ref(),
+        email.normalized(),
+        list_id
+    )
+    .execute(pool.as_ref())
+    .await
+    .map_err(e500)?;
+    Ok(unsubscribe_html(
+        "Unsubscribed",
+        match list_id {
+            Some(_) => "<p>You have been unsubscribed, you will not receive this list anymore.</p>",
+            None => {
+                "<p>You have been unsubscribed, you will not receive our newsletters anymore.</p>"
+            }
+        },
+    ))
+}
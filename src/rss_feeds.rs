@@ -0,0 +1,160 @@
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+/// A feed whose new items are turned into newsletter issues.
+pub struct RssFeed {
+    pub id: Uuid,
+    pub url: String,
+    pub poll_interval_minutes: i32,
+    /// New items are sent right away instead of being saved as drafts.
+    pub auto_send: bool,
+    pub next_poll_at: DateTime<Utc>,
+    /// When the feed was last fetched successfully.
+    pub last_polled_at: Option<DateTime<Utc>>,
+    /// Why the last poll failed, if it did.
+    pub last_error: Option<String>,
+}
+
+/// Returns `None` if the feed is already registered.
+#[tracing::instrument(name = "Insert an RSS feed", skip(pool))]
+pub async fn insert_feed(
+    pool: &PgPool,
+    url: &str,
+    poll_interval_minutes: i32,
+    auto_send: bool,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let feed = sqlx::query!(
+        r#"INSERT INTO rss_feeds
+            (rss_feed_id, url, poll_interval_minutes, auto_send, next_poll_at, created_at)
+        VALUES ($1, $2, $3, $4, $5, $5)
+        ON CONFLICT (url) DO NOTHING
+        RETURNING rss_feed_id"#,
+        Uuid::new_v4(),
+        url,
+        poll_interval_minutes,
+        auto_send,
+        Utc::now()
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to insert the RSS feed")?;
+    Ok(feed.map(|f| f.rss_feed_id))
+}
+
+pub async fn list_feeds(pool: &PgPool) -> Result<Vec<RssFeed>, anyhow::Error> {
+    let feeds = sqlx::query_as!(
+        RssFeed,
+        r#"SELECT rss_feed_id AS id, url, poll_interval_minutes, auto_send, next_poll_at,
+            last_polled_at, last_error
+        FROM rss_feeds
+        ORDER BY created_at"#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the RSS feeds")?;
+    Ok(feeds)
+}
+
+#[tracing::instrument(name = "Delete an RSS feed", skip(pool))]
+pub async fn delete_feed(pool: &PgPool, id: Uuid) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(r#"DELETE FROM rss_feeds WHERE rss_feed_id = $1"#, id)
+        .execute(pool)
+        .await
+        .context("Failed to delete the RSS feed")?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Lock a feed that is due for polling. Concurrent pollers skip it until the
+/// transaction ends.
+pub async fn dequeue_due_feed(
+    pool: &PgPool,
+) -> Result<Option<(Transaction<'static, Postgres>, RssFeed)>, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a connection from the pool")?;
+    let feed = sqlx::query_as!(
+        RssFeed,
+        r#"SELECT rss_feed_id AS id, url, poll_interval_minutes, auto_send, next_poll_at,
+            last_polled_at, last_error
+        FROM rss_feeds
+        WHERE next_poll_at <= now()
+        ORDER BY next_poll_at
+        FOR UPDATE
+        SKIP LOCKED
+        LIMIT 1"#
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to fetch a due RSS feed")?;
+    Ok(feed.map(|feed| (transaction, feed)))
+}
+
+/// Remember an item of the feed. Returns `false` if it was already seen.
+pub async fn record_item(
+    transaction: &mut Transaction<'_, Postgres>,
+    feed_id: Uuid,
+    guid: &str,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"INSERT INTO rss_feed_items (rss_feed_id, guid, seen_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT DO NOTHING"#,
+        feed_id,
+        guid,
+        Utc::now()
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to record the RSS feed item")?;
+    Ok(result.rows_affected() == 1)
+}
+
+pub async fn set_item_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    feed_id: Uuid,
+    guid: &str,
+    issue_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"UPDATE rss_feed_items SET newsletter_issue_id = $3
+        WHERE rss_feed_id = $1 AND guid = $2"#,
+        feed_id,
+        guid,
+        issue_id
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to link the RSS feed item to its newsletter issue")?;
+    Ok(())
+}
+
+/// Schedule the next poll of the feed and release it.
+pub async fn finish_poll(
+    mut transaction: Transaction<'static, Postgres>,
+    feed: &RssFeed,
+    error: Option<&str>,
+) -> Result<(), anyhow::Error> {
+    let now = Utc::now();
+    sqlx::query!(
+        r#"UPDATE rss_feeds
+        SET last_polled_at = CASE WHEN $4::TEXT IS NULL THEN $2 ELSE last_polled_at END,
+            next_poll_at = $3,
+            last_error = $4
+        WHERE rss_feed_id = $1"#,
+        feed.id,
+        now,
+        now + Duration::minutes(feed.poll_interval_minutes.into()),
+        error
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to schedule the next poll of the RSS feed")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the transaction into the database")?;
+    Ok(())
+}
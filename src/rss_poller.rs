@@ -0,0 +1,254 @@
+use crate::email_html::{PreparedHtml, prepare_email_html};
+use crate::issue_delivery_worker::ExecutionOutcome;
+use crate::newsletter_issues::{Audience, insert_draft, publish_draft};
+use crate::rss_feeds::{RssFeed, dequeue_due_feed, finish_poll, record_item, set_item_issue};
+use crate::utils::escape_html;
+use anyhow::Context;
+use feed_rs::model::Entry;
+use scraper::Html;
+use sqlx::{Connection, PgPool, Postgres, Transaction};
+use std::time::Duration;
+use tracing::{Span, field::display};
+
+pub async fn run_rss_poller_until_stopped(pool: PgPool, http_client: reqwest::Client) {
+    loop {
+        match try_poll_due_feed(&pool, &http_client).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                actix_web::rt::time::sleep(Duration::from_secs(10)).await;
+            }
+            Err(_) => {
+                actix_web::rt::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+}
+
+/// Fetch one feed that is due and turn its new items into issues. The items
+/// found on the first poll are only remembered: registering a feed must not
+/// send its whole history.
+#[tracing::instrument(skip_all, fields(rss_feed_id=tracing::field::Empty), err)]
+pub async fn try_poll_due_feed(
+    pool: &PgPool,
+    http_client: &reqwest::Client,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let Some((mut transaction, feed)) = dequeue_due_feed(pool).await? else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    Span::current().record("rss_feed_id", display(feed.id));
+    let items = match fetch_items(http_client, &feed.url).await {
+        Ok(items) => items,
+        Err(e) => {
+            tracing::warn!(error.cause_chain = ?e, "Failed to fetch the RSS feed");
+            finish_poll(transaction, &feed, Some(&format!("{:#}", e))).await?;
+            return Ok(ExecutionOutcome::TaskCompleted);
+        }
+    };
+    // In a savepoint: if an item cannot be saved, none of them is and they are
+    // tried again on the next poll. The feed is not due again before then.
+    let mut savepoint = transaction
+        .begin()
+        .await
+        .context("Failed to create a savepoint")?;
+    match save_items(&mut savepoint, &feed, items).await {
+        Ok(()) => {
+            savepoint
+                .commit()
+                .await
+                .context("Failed to release the savepoint")?;
+            finish_poll(transaction, &feed, None).await?;
+        }
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, "Failed to save the items of the RSS feed");
+            savepoint
+                .rollback()
+                .await
+                .context("Failed to roll back to the savepoint")?;
+            finish_poll(transaction, &feed, Some(&format!("{:#}", e))).await?;
+        }
+    }
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+async fn save_items(
+    transaction: &mut Transaction<'_, Postgres>,
+    feed: &RssFeed,
+    items: Vec<FeedItem>,
+) -> Result<(), anyhow::Error> {
+    let first_poll = feed.last_polled_at.is_none();
+    for item in items {
+        if record_item(transaction, feed.id, &item.guid).await? && !first_poll {
+            create_issue(transaction, feed, &item).await?;
+        }
+    }
+    Ok(())
+}
+
+/// The issue is created in the transaction that records the item, so an
+/// item is never turned into two issues.
+async fn create_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    feed: &RssFeed,
+    item: &FeedItem,
+) -> Result<(), anyhow::Error> {
+    let issue_id = insert_draft(
+        &mut **transaction,
+        &item.title,
+        &item.text,
+        &item.html,
+        None,
+        &Audience::default(),
+    )
+    .await?;
+    if feed.auto_send {
+        publish_draft(transaction, issue_id).await?;
+    }
+    set_item_issue(transaction, feed.id, &item.guid, issue_id).await?;
+    tracing::info!(%issue_id, auto_send = feed.auto_send, "Created a newsletter issue from an RSS feed item");
+    Ok(())
+}
+
+struct FeedItem {
+    guid: String,
+    title: String,
+    text: String,
+    html: PreparedHtml,
+}
+
+async fn fetch_items(
+    http_client: &reqwest::Client,
+    url: &str,
+) -> Result<Vec<FeedItem>, anyhow::Error> {
+    let body = http_client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    parse_items(&body)
+}
+
+/// The items of an RSS or Atom feed, the oldest first.
+fn parse_items(body: &[u8]) -> Result<Vec<FeedItem>, anyhow::Error> {
+    let mut entries = feed_rs::parser::parse(body)?.entries;
+    // Feeds usually list their newest items first, undated items keep that
+    // order once reversed.
+    entries.reverse();
+    entries.sort_by_key(|entry| entry.published.or(entry.updated));
+    Ok(entries.into_iter().map(FeedItem::from).collect())
+}
+
+impl From<Entry> for FeedItem {
+    fn from(entry: Entry) -> Self {
+        let title = entry
+            .title
+            .map(|title| title.content)
+            .unwrap_or_else(|| "New post".to_string());
+        let body = match (
+            entry.content.and_then(|content| content.body),
+            entry.summary,
+        ) {
+            (Some(body), _) => body,
+            (None, Some(summary)) if summary.content_type.essence().to_string() == "text/plain" => {
+                format!("<p>{}</p>", escape_html(&summary.content))
+            }
+            (None, Some(summary)) => summary.content,
+            (None, None) => String::new(),
+        };
+        let link = entry.links.into_iter().next().map(|link| link.href);
+        let mut html = prepare_email_html(&body);
+        let mut text = Html::parse_fragment(&html.html)
+            .root_element()
+            .text()
+            .collect::<String>()
+            .trim()
+            .to_string();
+        if let Some(link) = link {
+            html.html.push_str(&format!(
+                "\n<p><a href=\"{}\">Read more</a></p>",
+                escape_html(&link)
+            ));
+            text.push_str(&format!("\n\nRead more: {}", link));
+        }
+        html.html = escape_merge_tags(&html.html, "&#123;&#123;");
+        FeedItem {
+            guid: entry.id,
+            title,
+            text: escape_merge_tags(&text, "{ {"),
+            html,
+        }
+    }
+}
+
+/// The content of the feed is not ours: what looks like a merge tag in it
+/// must not be rendered, nor break the rendering of the issue.
+fn escape_merge_tags(s: &str, replacement: &str) -> String {
+    s.replace("{{", replacement)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_items;
+
+    const FEED: &str = r#"<?xml version="1.0"?>
+<rss version="2.0">
+<channel>
+<title>Blog</title>
+<link>https://blog.example.com</link>
+<description>Our blog</description>
+<item>
+<title>Second post</title>
+<link>https://blog.example.com/2</link>
+<guid>post-2</guid>
+<pubDate>Tue, 20 Oct 2026 08:00:00 GMT</pubDate>
+<description>&lt;p&gt;Hello {{ name }}&lt;/p&gt;&lt;script&gt;alert(1)&lt;/script&gt;</description>
+</item>
+<item>
+<title>First post</title>
+<link>https://blog.example.com/1</link>
+<guid>post-1</guid>
+<pubDate>Mon, 19 Oct 2026 08:00:00 GMT</pubDate>
+<description>&lt;p&gt;The first post&lt;/p&gt;</description>
+</item>
+</channel>
+</rss>"#;
+
+    #[test]
+    fn items_are_listed_oldest_first() {
+        let items = parse_items(FEED.as_bytes()).unwrap();
+        let guids: Vec<_> = items.iter().map(|item| item.guid.as_str()).collect();
+        assert_eq!(guids, ["post-1", "post-2"]);
+        assert_eq!(items[0].title, "First post");
+    }
+
+    #[test]
+    fn items_link_to_the_post() {
+        let item = parse_items(FEED.as_bytes()).unwrap().remove(0);
+        assert_eq!(
+            item.html.html,
+            "<p>The first post</p>\n<p><a href=\"https://blog.example.com/1\">Read more</a></p>"
+        );
+        assert_eq!(
+            item.text,
+            "The first post\n\nRead more: https://blog.example.com/1"
+        );
+    }
+
+    #[test]
+    fn the_content_of_items_is_sanitized_and_its_merge_tags_escaped() {
+        let item = parse_items(FEED.as_bytes()).unwrap().remove(1);
+        assert!(!item.html.html.contains("<script>"));
+        assert!(
+            item.html
+                .html
+                .starts_with("<p>Hello &#123;&#123; name }}</p>")
+        );
+        assert!(item.text.starts_with("Hello { { name }}"));
+    }
+
+    #[test]
+    fn an_invalid_feed_is_rejected() {
+        assert!(parse_items(b"<html>Not a feed</html>").is_err());
+    }
+}
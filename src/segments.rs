@@ -0,0 +1,712 @@
+use crate::attributes::AttributeDefinition;
+use crate::tags::parse_tag;
+use anyhow::Context;
+use chrono::{NaiveDate, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgPool, Postgres, QueryBuilder, Row};
+use uuid::Uuid;
+
+/// Which subscribers belong to a segment, saved as JSON, e.g.
+/// `{"all": [{"tag": "beta"}, {"not": {"subscribed": {"from": "2026-01-01"}}}]}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    Tag(String),
+    /// Both ends are included, dates are in UTC.
+    Subscribed(DateRange),
+    Attribute(AttributeCondition),
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+    Not(Box<Condition>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DateRange {
+    #[serde(default, with = "date_format", skip_serializing_if = "Option::is_none")]
+    pub from: Option<NaiveDate>,
+    #[serde(default, with = "date_format", skip_serializing_if = "Option::is_none")]
+    pub to: Option<NaiveDate>,
+}
+
+/// Compares the value of an attribute, numbers and dates by their order,
+/// e.g. `{"attribute": {"key": "age", "op": "gte", "value": 18}}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AttributeCondition {
+    pub key: String,
+    pub op: AttributeOp,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_scalar",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttributeOp {
+    Eq,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    /// The subscriber has a value, `value` is left out.
+    IsSet,
+}
+
+impl AttributeOp {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AttributeOp::Eq => "=",
+            AttributeOp::Lt => "<",
+            AttributeOp::Lte => "<=",
+            AttributeOp::Gt => ">",
+            AttributeOp::Gte => ">=",
+            AttributeOp::IsSet => "is set",
+        }
+    }
+}
+
+/// Values can be written as JSON strings, numbers or booleans.
+fn deserialize_scalar<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Option<String>, D::Error> {
+    use serde::de::Error;
+    match Option::<serde_json::Value>::deserialize(d)? {
+        None => Ok(None),
+        Some(serde_json::Value::String(s)) => Ok(Some(s)),
+        Some(serde_json::Value::Number(n)) => Ok(Some(n.to_string())),
+        Some(serde_json::Value::Bool(b)) => Ok(Some(b.to_string())),
+        Some(_) => Err(D::Error::custom(
+            "the value of an attribute must be a string, a number or a boolean",
+        )),
+    }
+}
+
+impl Condition {
+    pub fn parse(definition: &str) -> Result<Condition, String> {
+        let mut condition: Condition =
+            serde_json::from_str(definition).map_err(|e| e.to_string())?;
+        condition.validate()?;
+        Ok(condition)
+    }
+
+    fn validate(&mut self) -> Result<(), String> {
+        match self {
+            Condition::Tag(tag) => *tag = parse_tag(tag)?,
+            Condition::Subscribed(DateRange { from, to }) => match (*from, *to) {
+                (None, None) => {
+                    return Err("A date range needs a start or an end.".to_string());
+                }
+                (Some(from), Some(to)) if from > to => {
+                    return Err("A date range cannot end before it starts.".to_string());
+                }
+                _ => {}
+            },
+            Condition::Attribute(AttributeCondition { op, value, .. }) => match (op, value) {
+                (AttributeOp::IsSet, Some(_)) => {
+                    return Err("`is_set` does not take a value.".to_string());
+                }
+                (AttributeOp::IsSet, None) | (_, Some(_)) => {}
+                (op, None) => {
+                    return Err(format!("`{}` needs a value.", op.as_str()));
+                }
+            },
+            Condition::All(conditions) | Condition::Any(conditions) => {
+                if conditions.is_empty() {
+                    return Err("`all` and `any` need at least one condition.".to_string());
+                }
+                for condition in conditions {
+                    condition.validate()?;
+                }
+            }
+            Condition::Not(condition) => condition.validate()?,
+        }
+        Ok(())
+    }
+
+    /// Checks the attributes against their definitions, and puts the values
+    /// in the canonical form of the stored ones.
+    pub fn check_attributes(&mut self, definitions: &[AttributeDefinition]) -> Result<(), String> {
+        match self {
+            Condition::Tag(_) | Condition::Subscribed(_) => {}
+            Condition::Attribute(AttributeCondition { key, op, value }) => {
+                let definition = definitions
+                    .iter()
+                    .find(|definition| definition.key == *key)
+                    .ok_or_else(|| format!("The attribute `{}` does not exist.", key))?;
+                if !matches!(op, AttributeOp::Eq | AttributeOp::IsSet)
+                    && !definition.kind.is_ordered()
+                {
+                    return Err(format!(
+                        "`{}` only applies to numbers and dates.",
+                        op.as_str()
+                    ));
+                }
+                if let Some(value) = value {
+                    // A required field left empty does not matter in a segment.
+                    let lenient = AttributeDefinition {
+                        required: false,
+                        ..definition.clone()
+                    };
+                    *value = lenient
+                        .parse_value(value)?
+                        .ok_or_else(|| format!("`{}` needs a value.", op.as_str()))?;
+                }
+            }
+            Condition::All(conditions) | Condition::Any(conditions) => {
+                for condition in conditions {
+                    condition.check_attributes(definitions)?;
+                }
+            }
+            Condition::Not(condition) => condition.check_attributes(definitions)?,
+        }
+        Ok(())
+    }
+
+    /// Appends the condition as a predicate on the subscriber `s`.
+    fn push_sql(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        match self {
+            Condition::Tag(tag) => {
+                query
+                    .push(
+                        "EXISTS (SELECT 1 FROM subscriber_tags t \
+                        WHERE t.subscriber_id = s.id AND t.tag = ",
+                    )
+                    .push_bind(tag.clone())
+                    .push(")");
+            }
+            Condition::Subscribed(DateRange { from, to }) => {
+                let start_of_day = |day: NaiveDate| day.and_time(NaiveTime::MIN).and_utc();
+                query.push("(TRUE");
+                if let Some(from) = from {
+                    query
+                        .push(" AND s.subscribed_at >= ")
+                        .push_bind(start_of_day(*from));
+                }
+                if let Some(next_day) = to.and_then(|to| to.succ_opt()) {
+                    query
+                        .push(" AND s.subscribed_at < ")
+                        .push_bind(start_of_day(next_day));
+                }
+                query.push(")");
+            }
+            Condition::Attribute(AttributeCondition { key, op, value }) => {
+                query
+                    .push(
+                        "EXISTS (SELECT 1 FROM subscriber_attributes a \
+                        WHERE a.subscriber_id = s.id AND a.key = ",
+                    )
+                    .push_bind(key.clone());
+                if let Some(value) = value {
+                    let op = op.as_str();
+                    if is_canonical_number(value) {
+                        // Numbers compare as numbers, the other values of the
+                        // attribute as text.
+                        query
+                            .push(format!(
+                                " AND CASE WHEN a.value ~ '{NUMBER_PATTERN}' \
+                                THEN a.value::numeric {op} "
+                            ))
+                            .push_bind(value.clone())
+                            .push(format!("::numeric ELSE a.value COLLATE \"C\" {op} "))
+                            .push_bind(value.clone())
+                            .push(" END");
+                    } else {
+                        // Dates (`YYYY-MM-DD`) compare as text in the order of
+                        // the calendar.
+                        query
+                            .push(format!(" AND a.value COLLATE \"C\" {op} "))
+                            .push_bind(value.clone());
+                    }
+                }
+                query.push(")");
+            }
+            Condition::All(conditions) => push_joined(query, conditions, " AND "),
+            Condition::Any(conditions) => push_joined(query, conditions, " OR "),
+            Condition::Not(condition) => {
+                query.push("NOT (");
+                condition.push_sql(query);
+                query.push(")");
+            }
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("A condition can always be serialized")
+    }
+}
+
+impl std::fmt::Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Condition::Tag(tag) => write!(f, "tagged \"{}\"", tag),
+            Condition::Subscribed(DateRange { from, to }) => {
+                write!(f, "subscribed")?;
+                if let Some(from) = from {
+                    write!(f, " from {}", from)?;
+                }
+                if let Some(to) = to {
+                    write!(f, " until {}", to)?;
+                }
+                Ok(())
+            }
+            Condition::Attribute(AttributeCondition { key, op, value }) => {
+                write!(f, "{} {}", key, op.as_str())?;
+                if let Some(value) = value {
+                    write!(f, " \"{}\"", value)?;
+                }
+                Ok(())
+            }
+            Condition::All(conditions) => write_joined(f, conditions, " AND "),
+            Condition::Any(conditions) => write_joined(f, conditions, " OR "),
+            Condition::Not(condition) => write!(f, "NOT {}", condition),
+        }
+    }
+}
+
+/// How the stored numbers are written, without an exponent.
+const NUMBER_PATTERN: &str = r"^-?[0-9]+(\.[0-9]+)?$";
+
+fn is_canonical_number(value: &str) -> bool {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    let (integer, fraction) = digits.split_once('.').unwrap_or((digits, "0"));
+    [integer, fraction]
+        .iter()
+        .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))
+}
+
+fn push_joined(query: &mut QueryBuilder<'_, Postgres>, conditions: &[Condition], separator: &str) {
+    query.push("(");
+    for (i, condition) in conditions.iter().enumerate() {
+        if i > 0 {
+            query.push(separator);
+        }
+        condition.push_sql(query);
+    }
+    query.push(")");
+}
+
+fn write_joined(
+    f: &mut std::fmt::Formatter<'_>,
+    conditions: &[Condition],
+    separator: &str,
+) -> std::fmt::Result {
+    if let [condition] = conditions {
+        return write!(f, "{}", condition);
+    }
+    write!(f, "(")?;
+    for (i, condition) in conditions.iter().enumerate() {
+        if i > 0 {
+            write!(f, "{}", separator)?;
+        }
+        write!(f, "{}", condition)?;
+    }
+    write!(f, ")")
+}
+
+/// chrono is built without serde, dates are written as `YYYY-MM-DD`.
+mod date_format {
+    use chrono::NaiveDate;
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    const FORMAT: &str = "%Y-%m-%d";
+
+    pub fn serialize<S: Serializer>(date: &Option<NaiveDate>, s: S) -> Result<S::Ok, S::Error> {
+        match date {
+            Some(date) => s.serialize_some(&date.format(FORMAT).to_string()),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<NaiveDate>, D::Error> {
+        Option::<String>::deserialize(d)?
+            .map(|date| {
+                NaiveDate::parse_from_str(&date, FORMAT).map_err(|_| {
+                    D::Error::custom(format!("{} is not a date, expected YYYY-MM-DD", date))
+                })
+            })
+            .transpose()
+    }
+}
+
+pub struct Segment {
+    pub id: Uuid,
+    pub name: String,
+    pub condition: Condition,
+}
+
+struct SegmentRow {
+    segment_id: Uuid,
+    name: String,
+    definition: String,
+}
+
+impl TryFrom<SegmentRow> for Segment {
+    type Error = anyhow::Error;
+    fn try_from(r: SegmentRow) -> Result<Self, Self::Error> {
+        Ok(Segment {
+            id: r.segment_id,
+            condition: Condition::parse(&r.definition).map_err(|e| {
+                anyhow::anyhow!("The definition of the segment {} is invalid: {}", r.name, e)
+            })?,
+            name: r.name,
+        })
+    }
+}
+
+pub async fn list_segments(pool: &PgPool) -> Result<Vec<Segment>, anyhow::Error> {
+    sqlx::query_as!(
+        SegmentRow,
+        r#"SELECT segment_id, name, definition FROM segments ORDER BY name"#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the segments")?
+    .into_iter()
+    .map(Segment::try_from)
+    .collect()
+}
+
+#[tracing::instrument(name = "Get a segment by name", skip(pool))]
+pub async fn get_segment_by_name(
+    pool: &PgPool,
+    name: &str,
+) -> Result<Option<Segment>, anyhow::Error> {
+    sqlx::query_as!(
+        SegmentRow,
+        r#"SELECT segment_id, name, definition FROM segments WHERE name = $1"#,
+        name
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the segment")?
+    .map(Segment::try_from)
+    .transpose()
+}
+
+#[tracing::instrument(name = "Get the segment of an issue", skip(pool))]
+pub async fn get_issue_segment(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<Option<Segment>, anyhow::Error> {
+    sqlx::query_as!(
+        SegmentRow,
+        r#"SELECT s.segment_id, s.name, s.definition
+        FROM segments s
+        JOIN newsletter_issues i ON i.segment_id = s.segment_id
+        WHERE i.newsletter_issue_id = $1"#,
+        issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the segment of the newsletter issue")?
+    .map(Segment::try_from)
+    .transpose()
+}
+
+/// Returns `None` if the name is taken by another segment.
+#[tracing::instrument(name = "Insert a segment", skip(pool, condition))]
+pub async fn insert_segment(
+    pool: &PgPool,
+    name: &str,
+    condition: &Condition,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let segment = sqlx::query!(
+        r#"INSERT INTO segments (segment_id, name, definition, created_at)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT DO NOTHING
+        RETURNING segment_id"#,
+        Uuid::new_v4(),
+        name,
+        condition.to_json(),
+        Utc::now()
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to insert the segment")?;
+    Ok(segment.map(|s| s.segment_id))
+}
+
+/// A segment some issue was sent to, or will be, is kept. Returns `false`
+/// if the segment is in use or does not exist.
+#[tracing::instrument(name = "Delete a segment", skip(pool))]
+pub async fn delete_segment(pool: &PgPool, id: Uuid) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"DELETE FROM segments
+        WHERE segment_id = $1
+            AND NOT EXISTS (SELECT 1 FROM newsletter_issues WHERE segment_id = $1)"#,
+        id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to delete the segment")?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Appends the `FROM` and `WHERE` clauses selecting the confirmed
+/// subscribers `s` of the lists of an issue, or of any list if there is no
+/// issue, each counted once.
+fn push_confirmed_subscribers(query: &mut QueryBuilder<'_, Postgres>, issue_id: Option<Uuid>) {
+    query
+        .push(
+            " FROM subscriptions s
+        WHERE EXISTS (
+            SELECT 1 FROM list_memberships m
+            WHERE m.subscriber_id = s.id
+                AND m.status = 'confirmed'
+                AND (",
+        )
+        .push_bind(issue_id)
+        .push(
+            "::uuid IS NULL OR m.list_id IN (
+                    SELECT list_id FROM newsletter_issue_lists WHERE newsletter_issue_id = ",
+        )
+        .push_bind(issue_id)
+        .push("))\n        )");
+}
+
+/// The condition of the segment of an issue, if it has one.
+async fn get_issue_condition(
+    connection: &mut PgConnection,
+    issue_id: Uuid,
+) -> Result<Option<Condition>, anyhow::Error> {
+    let definition = sqlx::query!(
+        r#"SELECT s.definition
+        FROM segments s
+        JOIN newsletter_issues i ON i.segment_id = s.segment_id
+        WHERE i.newsletter_issue_id = $1"#,
+        issue_id
+    )
+    .fetch_optional(&mut *connection)
+    .await
+    .context("Failed to fetch the segment of the newsletter issue")?;
+    definition
+        .map(|r| Condition::parse(&r.definition).map_err(|e| anyhow::anyhow!(e)))
+        .transpose()
+        .context("The segment of the newsletter issue is invalid")
+}
+
+/// Restricts the subscribers to those of an issue and its segment.
+fn push_issue_recipients(
+    query: &mut QueryBuilder<'_, Postgres>,
+    issue_id: Uuid,
+    condition: Option<&Condition>,
+) {
+    push_confirmed_subscribers(query, Some(issue_id));
+    if let Some(condition) = condition {
+        query.push(" AND ");
+        condition.push_sql(query);
+    }
+}
+
+/// The emails an issue goes to: the confirmed members of its lists that
+/// are in its segment, if it has one.
+#[tracing::instrument(name = "Resolve the recipients of an issue", skip(connection))]
+pub async fn get_issue_recipients(
+    connection: &mut PgConnection,
+    issue_id: Uuid,
+) -> Result<Vec<String>, anyhow::Error> {
+    let condition = get_issue_condition(connection, issue_id).await?;
+    let mut query = QueryBuilder::new("SELECT s.email");
+    push_issue_recipients(&mut query, issue_id, condition.as_ref());
+    query
+        .build_query_scalar()
+        .fetch_all(connection)
+        .await
+        .context("Failed to fetch the recipients of the newsletter issue")
+}
+
+pub async fn count_issue_recipients(pool: &PgPool, issue_id: Uuid) -> Result<usize, anyhow::Error> {
+    let mut connection = pool
+        .acquire()
+        .await
+        .context("Failed to acquire a connection from the pool")?;
+    let condition = get_issue_condition(&mut connection, issue_id).await?;
+    let mut query = QueryBuilder::new("SELECT COUNT(*)");
+    push_issue_recipients(&mut query, issue_id, condition.as_ref());
+    let count: i64 = query
+        .build_query_scalar()
+        .fetch_one(&mut *connection)
+        .await
+        .context("Failed to count the recipients of the newsletter issue")?;
+    Ok(count as usize)
+}
+
+/// The number of subscribers confirmed on any list that are in each segment,
+/// counted in a single pass.
+pub async fn count_segments_members(
+    pool: &PgPool,
+    segments: &[Segment],
+) -> Result<Vec<usize>, anyhow::Error> {
+    if segments.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut query = QueryBuilder::new("SELECT ");
+    for (i, segment) in segments.iter().enumerate() {
+        if i > 0 {
+            query.push(", ");
+        }
+        query.push("COUNT(*) FILTER (WHERE ");
+        segment.condition.push_sql(&mut query);
+        query.push(")");
+    }
+    push_confirmed_subscribers(&mut query, None);
+    let row = query
+        .build()
+        .fetch_one(pool)
+        .await
+        .context("Failed to count the members of the segments")?;
+    (0..segments.len())
+        .map(|i| {
+            let count: i64 = row
+                .try_get(i)
+                .context("Failed to read the number of members of a segment")?;
+            Ok(count as usize)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Condition, DateRange, is_canonical_number};
+    use crate::attributes::{AttributeDefinition, AttributeKind};
+    use chrono::NaiveDate;
+    use claim::{assert_err, assert_ok_eq};
+    use sqlx::{Postgres, QueryBuilder};
+
+    fn sql(condition: &Condition) -> String {
+        let mut query = QueryBuilder::<Postgres>::new("");
+        condition.push_sql(&mut query);
+        query.into_sql()
+    }
+
+    fn attribute(key: &str, kind: AttributeKind) -> AttributeDefinition {
+        AttributeDefinition {
+            key: key.to_string(),
+            label: key.to_string(),
+            kind,
+            required: true,
+            choices: vec!["free".to_string(), "pro".to_string()],
+            min_value: None,
+            max_value: None,
+            max_length: None,
+        }
+    }
+
+    #[test]
+    fn a_definition_is_parsed_and_its_tags_normalized() {
+        assert_ok_eq!(
+            Condition::parse(
+                r#"{"all": [{"tag": " Beta "}, {"not": {"subscribed": {"to": "2025-12-31"}}}]}"#
+            ),
+            Condition::All(vec![
+                Condition::Tag("beta".to_string()),
+                Condition::Not(Box::new(Condition::Subscribed(DateRange {
+                    from: None,
+                    to: NaiveDate::from_ymd_opt(2025, 12, 31),
+                }))),
+            ])
+        );
+    }
+
+    #[test]
+    fn a_definition_survives_a_round_trip() {
+        let condition = Condition::parse(
+            r#"{"any": [{"tag": "beta"}, {"subscribed": {"from": "2026-01-01", "to": "2026-03-31"}}]}"#,
+        )
+        .unwrap();
+        assert_ok_eq!(Condition::parse(&condition.to_json()), condition);
+    }
+
+    #[test]
+    fn invalid_definitions_are_rejected() {
+        for definition in [
+            r#"{"tag": ""}"#,
+            r#"{"all": []}"#,
+            r#"{"subscribed": {}}"#,
+            r#"{"subscribed": {"from": "2026-02-01", "to": "2026-01-01"}}"#,
+            r#"{"subscribed": {"from": "yesterday"}}"#,
+            r#"{"subscribed": {"since": "2026-01-01"}}"#,
+            r#"{"country": "fr"}"#,
+        ] {
+            assert_err!(Condition::parse(definition), "{}", definition);
+        }
+    }
+
+    #[test]
+    fn conditions_are_combined_in_sql() {
+        let condition = Condition::parse(
+            r#"{"all": [{"tag": "beta"}, {"not": {"subscribed": {"from": "2026-01-01", "to": "2026-01-31"}}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            sql(&condition),
+            "(EXISTS (SELECT 1 FROM subscriber_tags t WHERE t.subscriber_id = s.id AND t.tag = $1) \
+            AND NOT ((TRUE AND s.subscribed_at >= $2 AND s.subscribed_at < $3)))"
+        );
+    }
+
+    #[test]
+    fn a_condition_is_displayed_in_words() {
+        let condition = Condition::parse(
+            r#"{"any": [{"tag": "beta"}, {"not": {"subscribed": {"from": "2026-01-01"}}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            condition.to_string(),
+            r#"(tagged "beta" OR NOT subscribed from 2026-01-01)"#
+        );
+    }
+
+    #[test]
+    fn attributes_are_compared_by_their_kind() {
+        let definitions = [
+            attribute("age", AttributeKind::Number),
+            attribute("plan", AttributeKind::Choice),
+        ];
+        let mut condition = Condition::parse(
+            r#"{"all": [{"attribute": {"key": "age", "op": "gte", "value": 9.0}},
+                {"attribute": {"key": "plan", "op": "eq", "value": "pro"}}]}"#,
+        )
+        .unwrap();
+        condition.check_attributes(&definitions).unwrap();
+        assert_eq!(condition.to_string(), r#"(age >= "9" AND plan = "pro")"#);
+        // As text, "10" would come before "9".
+        assert!(sql(&condition).contains("THEN a.value::numeric >= $2::numeric"));
+        assert!(sql(&condition).contains(r#"a.value COLLATE "C" = $5"#));
+    }
+
+    #[test]
+    fn only_canonical_numbers_compare_as_numbers() {
+        for value in ["10", "-2.5", "0"] {
+            assert!(is_canonical_number(value), "{}", value);
+        }
+        for value in ["", "-", "1.", ".5", "1e3", "2026-01-01", "pro"] {
+            assert!(!is_canonical_number(value), "{}", value);
+        }
+    }
+
+    #[test]
+    fn attributes_must_exist_and_fit_their_definition() {
+        let definitions = [
+            attribute("age", AttributeKind::Number),
+            attribute("plan", AttributeKind::Choice),
+        ];
+        for definition in [
+            r#"{"attribute": {"key": "height", "op": "eq", "value": 180}}"#,
+            r#"{"attribute": {"key": "age", "op": "eq", "value": "old"}}"#,
+            r#"{"attribute": {"key": "plan", "op": "gt", "value": "free"}}"#,
+        ] {
+            let mut condition = Condition::parse(definition).unwrap();
+            assert_err!(condition.check_attributes(&definitions), "{}", definition);
+        }
+        for definition in [
+            r#"{"attribute": {"key": "age", "op": "eq"}}"#,
+            r#"{"attribute": {"key": "age", "op": "is_set", "value": 1}}"#,
+            r#"{"attribute": {"key": "age", "op": "eq", "value": [1]}}"#,
+        ] {
+            assert_err!(Condition::parse(definition), "{}", definition);
+        }
+    }
+}
@@ -2,12 +2,17 @@ use std::future::{Ready, ready};
 
 use actix_session::{Session, SessionExt, SessionInsertError};
 use actix_web::FromRequest;
+use chrono::{DateTime, Utc};
+use secrecy::{ExposeSecret, Secret};
 use uuid::Uuid;
 
 pub struct TypedSession(Session);
 
 impl TypedSession {
     const USER_ID_KEY: &'static str = "user_id";
+    const LOGGED_IN_AT_KEY: &'static str = "logged_in_at";
+    const OIDC_STATE_KEY: &'static str = "oidc_state";
+    const OIDC_CODE_VERIFIER_KEY: &'static str = "oidc_code_verifier";
     pub fn renew(&self) {
         self.0.renew();
     }
@@ -19,6 +24,45 @@ impl TypedSession {
         self.0.get(Self::USER_ID_KEY)
     }
 
+    pub fn insert_logged_in_at(
+        &self,
+        logged_in_at: DateTime<Utc>,
+    ) -> Result<(), SessionInsertError> {
+        self.0
+            .insert(Self::LOGGED_IN_AT_KEY, logged_in_at.timestamp_millis())
+    }
+
+    pub fn get_logged_in_at(
+        &self,
+    ) -> Result<Option<DateTime<Utc>>, actix_session::SessionGetError> {
+        Ok(self
+            .0
+            .get::<i64>(Self::LOGGED_IN_AT_KEY)?
+            .and_then(DateTime::from_timestamp_millis))
+    }
+
+    pub fn insert_oidc_request(
+        &self,
+        state: &str,
+        code_verifier: &Secret<String>,
+    ) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::OIDC_STATE_KEY, state)?;
+        self.0
+            .insert(Self::OIDC_CODE_VERIFIER_KEY, code_verifier.expose_secret())
+    }
+
+    /// Returns the state and the code verifier of the pending OIDC login, they
+    /// are removed from the session so that they can only be used once.
+    pub fn take_oidc_request(
+        &self,
+    ) -> Result<Option<(String, Secret<String>)>, actix_session::SessionGetError> {
+        let state = self.0.get::<String>(Self::OIDC_STATE_KEY)?;
+        let code_verifier = self.0.get::<String>(Self::OIDC_CODE_VERIFIER_KEY)?;
+        self.0.remove(Self::OIDC_STATE_KEY);
+        self.0.remove(Self::OIDC_CODE_VERIFIER_KEY);
+        Ok(state.zip(code_verifier.map(Secret::new)))
+    }
+
     pub fn log_out(&self) {
         self.0.purge();
     }
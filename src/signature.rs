@@ -0,0 +1,90 @@
+use crate::startup::HmacSecret;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use secrecy::ExposeSecret;
+use sha2::Sha256;
+
+pub fn sign(secret: &HmacSecret, payload: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.0.expose_secret().as_bytes())
+        .expect("HMAC can take a key of any size");
+    mac.update(payload.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+pub fn verify(secret: &HmacSecret, payload: &str, signature: &str) -> bool {
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.0.expose_secret().as_bytes())
+        .expect("HMAC can take a key of any size");
+    mac.update(payload.as_bytes());
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Signs `payload` together with an expiry date, the returned query string
+/// carries both the expiry and the signature.
+pub fn signed_query(secret: &HmacSecret, payload: &str, expires_at: DateTime<Utc>) -> String {
+    let expires = expires_at.timestamp();
+    let signature = sign(secret, &format!("{payload}:{expires}"));
+    format!("expires={expires}&signature={signature}")
+}
+
+pub fn verify_signed_query(
+    secret: &HmacSecret,
+    payload: &str,
+    expires: i64,
+    signature: &str,
+) -> bool {
+    expires >= Utc::now().timestamp() && verify(secret, &format!("{payload}:{expires}"), signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secrecy::Secret;
+
+    fn secret() -> HmacSecret {
+        HmacSecret(Secret::new("a very long secret for the tests".to_string()))
+    }
+
+    #[test]
+    fn a_signature_is_accepted_for_the_same_payload() {
+        let signature = sign(&secret(), "payload");
+        assert!(verify(&secret(), "payload", &signature));
+    }
+
+    #[test]
+    fn a_signature_is_rejected_for_another_payload() {
+        let signature = sign(&secret(), "payload");
+        assert!(!verify(&secret(), "another payload", &signature));
+    }
+
+    #[test]
+    fn a_malformed_signature_is_rejected() {
+        assert!(!verify(&secret(), "payload", "not hexadecimal"));
+    }
+
+    #[test]
+    fn an_expired_query_is_rejected() {
+        let expires_at = Utc::now() - chrono::Duration::minutes(1);
+        let signature = sign(&secret(), &format!("payload:{}", expires_at.timestamp()));
+        assert!(!verify_signed_query(
+            &secret(),
+            "payload",
+            expires_at.timestamp(),
+            &signature
+        ));
+    }
+
+    #[test]
+    fn a_valid_query_is_accepted() {
+        let expires_at = Utc::now() + chrono::Duration::minutes(1);
+        let signature = sign(&secret(), &format!("payload:{}", expires_at.timestamp()));
+        assert!(verify_signed_query(
+            &secret(),
+            "payload",
+            expires_at.timestamp(),
+            &signature
+        ));
+    }
+}
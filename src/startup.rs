@@ -1,6 +1,21 @@
 use crate::{
-    authentication::reject_anonymous_user, configuration::{DatabaseSettings, Settings}, email_client, routes
+    authentication::{oidc::OidcClient, reject_anonymous_user},
+    configuration::{
+        DatabaseSettings, LoginNotificationSettings, NewsletterSettings, Settings, SignupSettings,
+    },
+    consent::TrustedProxies,
+    email_client::{self, EmailClient},
+    import_worker::run_import_worker_until_stopped,
+    issue_delivery_worker::run_worker_until_stopped,
+    issue_rendering::IssueRenderer,
+    issue_scheduler::run_scheduler_until_stopped,
+    routes,
+    rss_poller::run_rss_poller_until_stopped,
+    subscriber_duplicates::normalize_international_emails,
+    subscriber_imports::MAX_IMPORT_SIZE,
+    templates,
 };
+use actix_multipart::form::MultipartFormConfig;
 use actix_session::SessionMiddleware;
 use actix_session::storage::RedisSessionStore;
 use actix_web::{self, App, HttpServer, cookie::Key, dev::Server, web};
@@ -13,17 +28,34 @@ use tracing_actix_web::TracingLogger;
 
 pub struct ApplicationBaseUrl(pub String);
 
+#[allow(clippy::too_many_arguments)]
 pub async fn run(
     listener: TcpListener,
     connection: PgPool,
     email_client: email_client::EmailClient,
+    issue_renderer: IssueRenderer,
     base_url: String,
     hmac_secret: HmacSecret,
     redis_uri: Secret<String>,
+    login_notifications: LoginNotificationSettings,
+    newsletter_settings: NewsletterSettings,
+    signup_settings: SignupSettings,
+    trusted_proxies: TrustedProxies,
+    oidc_client: Option<OidcClient>,
 ) -> Result<Server, anyhow::Error> {
     let connection_pool = web::Data::new(connection);
     let email_client = web::Data::new(email_client);
+    let issue_renderer = web::Data::new(issue_renderer);
     let base_url = web::Data::new(ApplicationBaseUrl(base_url));
+    let login_notifications = web::Data::new(login_notifications);
+    let newsletter_settings = web::Data::new(newsletter_settings);
+    let challenge_verifier = signup_settings
+        .challenge
+        .as_ref()
+        .map(|challenge| web::Data::from(challenge.verifier()));
+    let signup_settings = web::Data::new(signup_settings);
+    let trusted_proxies = web::Data::new(trusted_proxies);
+    let oidc_client = oidc_client.map(web::Data::new);
     let secret_key = Key::from(&hmac_secret.0.expose_secret().as_bytes());
     let storage = CookieMessageStore::builder(secret_key.clone()).build();
     let message_framework = FlashMessagesFramework::builder(storage).build();
@@ -32,7 +64,7 @@ pub async fn run(
         .unwrap();
 
     let server = HttpServer::new(move || {
-        App::new()
+        let mut app = App::new()
             .wrap(SessionMiddleware::new(store.clone(), secret_key.clone()))
             .wrap(message_framework.clone())
             .wrap(TracingLogger::default())
@@ -42,22 +74,228 @@ pub async fn run(
             .service(routes::newsletter)
             .route("/login", web::get().to(routes::login_form))
             .route("/login", web::post().to(routes::login))
+            .route(
+                "/password-reset",
+                web::get().to(routes::password_reset_request_form),
+            )
+            .route(
+                "/password-reset",
+                web::post().to(routes::request_password_reset),
+            )
+            .route(
+                "/password-reset/confirm",
+                web::get().to(routes::password_reset_form),
+            )
+            .route(
+                "/password-reset/confirm",
+                web::post().to(routes::reset_password),
+            )
             .route("/", web::get().to(routes::home))
             .route("/admin/logout", web::post().to(routes::logout))
-
+            .route(
+                "/sessions/revoke",
+                web::get().to(routes::revoke_sessions_form),
+            )
+            .route("/sessions/revoke", web::post().to(routes::revoke_sessions))
+            .route("/unsubscribe", web::get().to(routes::unsubscribe_form))
+            .route("/unsubscribe", web::post().to(routes::unsubscribe))
+            .route("/privacy", web::get().to(routes::privacy_page))
+            .route(
+                "/privacy/data-request",
+                web::post().to(routes::request_data),
+            )
+            .route("/privacy/data", web::get().to(routes::download_data))
+            .route(
+                "/privacy/erasure-request",
+                web::post().to(routes::request_erasure),
+            )
+            .route("/privacy/erasure", web::get().to(routes::erasure_form))
+            .route("/privacy/erasure", web::post().to(routes::erase_data))
+            .route("/archive", web::get().to(routes::archive))
+            .route("/archive/{slug}", web::get().to(routes::archive_issue))
+            .route("/feed.rss", web::get().to(routes::rss_feed))
+            .route("/feed.atom", web::get().to(routes::atom_feed))
             .service(
-                    web::scope("/admin")
+                web::scope("/admin")
                     .wrap(from_fn(reject_anonymous_user))
                     .route("/dashboard", web::get().to(routes::admin_dashboard))
+                    .route(
+                        "/newsletters",
+                        web::get().to(routes::list_newsletter_issues),
+                    )
+                    .route(
+                        "/newsletters",
+                        web::post().to(routes::create_newsletter_issue),
+                    )
+                    .route("/newsletters/new", web::get().to(routes::newsletter_form))
+                    .route("/newsletters/{id}", web::get().to(routes::newsletter_issue))
+                    .route(
+                        "/newsletters/{id}",
+                        web::post().to(routes::update_newsletter_issue),
+                    )
+                    .route(
+                        "/newsletters/{id}/preview",
+                        web::get().to(routes::newsletter_issue_preview),
+                    )
+                    .route(
+                        "/newsletters/{id}/edit",
+                        web::get().to(routes::edit_newsletter_form),
+                    )
+                    .route(
+                        "/newsletters/{id}/publish",
+                        web::post().to(routes::publish_newsletter_issue),
+                    )
+                    .route(
+                        "/newsletters/{id}/schedule",
+                        web::post().to(routes::schedule_newsletter_issue),
+                    )
+                    .route(
+                        "/newsletters/{id}/test",
+                        web::post().to(routes::send_test_newsletter_issue),
+                    )
+                    .route(
+                        "/newsletters/{id}/visibility",
+                        web::post().to(routes::set_newsletter_issue_visibility),
+                    )
+                    .route(
+                        "/newsletters/{id}/cancel",
+                        web::post().to(routes::cancel_newsletter_issue),
+                    )
+                    .route(
+                        "/newsletters/{id}/delete",
+                        web::post().to(routes::delete_newsletter_issue),
+                    )
+                    .route("/templates", web::get().to(routes::list_templates))
+                    .route("/templates", web::post().to(routes::create_layout))
+                    .route("/templates/new", web::get().to(routes::new_layout_form))
+                    .route(
+                        "/templates/system/{kind}",
+                        web::get().to(routes::system_email_form),
+                    )
+                    .route(
+                        "/templates/system/{kind}",
+                        web::post().to(routes::update_system_email),
+                    )
+                    .route("/templates/{id}", web::get().to(routes::edit_layout_form))
+                    .route("/templates/{id}", web::post().to(routes::update_layout))
+                    .route(
+                        "/templates/{id}/preview",
+                        web::get().to(routes::layout_preview),
+                    )
+                    .route("/subscribers", web::get().to(routes::list_subscribers_page))
+                    .route("/subscribers", web::post().to(routes::add_subscriber))
+                    .route(
+                        "/subscribers/export",
+                        web::get().to(routes::export_subscribers),
+                    )
+                    .route(
+                        "/subscribers/duplicates",
+                        web::get().to(routes::duplicates_page),
+                    )
+                    .route(
+                        "/subscribers/duplicates/{id}/merge",
+                        web::post().to(routes::merge_duplicate),
+                    )
+                    .route("/subscribers/import", web::get().to(routes::import_form))
+                    .route("/subscribers/import", web::post().to(routes::upload_import))
+                    .route(
+                        "/subscribers/import/{id}/report",
+                        web::get().to(routes::import_report),
+                    )
+                    .route(
+                        "/subscribers/{id}",
+                        web::get().to(routes::subscriber_detail),
+                    )
+                    .route(
+                        "/subscribers/{id}/edit",
+                        web::post().to(routes::edit_subscriber),
+                    )
+                    .route(
+                        "/subscribers/{id}/confirm",
+                        web::post().to(routes::confirm_subscriber),
+                    )
+                    .route(
+                        "/subscribers/{id}/unsubscribe",
+                        web::post().to(routes::unsubscribe_subscriber),
+                    )
+                    .route(
+                        "/subscribers/{id}/resend",
+                        web::post().to(routes::resend_confirmation),
+                    )
+                    .route(
+                        "/subscribers/{id}/delete",
+                        web::post().to(routes::delete_subscriber),
+                    )
+                    .route(
+                        "/api/subscribers",
+                        web::get().to(routes::list_subscribers_json),
+                    )
+                    .route(
+                        "/api/subscribers/{id}",
+                        web::get().to(routes::subscriber_detail_json),
+                    )
+                    .route("/audit", web::get().to(routes::audit_trail_page))
+                    .route("/lists", web::get().to(routes::list_mailing_lists))
+                    .route("/lists", web::post().to(routes::create_mailing_list))
+                    .route("/segments", web::get().to(routes::list_segments_page))
+                    .route("/segments", web::post().to(routes::create_segment))
+                    .route(
+                        "/segments/{id}/delete",
+                        web::post().to(routes::delete_segment),
+                    )
+                    .route("/email-domains", web::get().to(routes::email_domains_page))
+                    .route(
+                        "/email-domains",
+                        web::post().to(routes::save_email_domain_rule),
+                    )
+                    .route(
+                        "/email-domains/delete",
+                        web::post().to(routes::delete_email_domain_rule),
+                    )
+                    .route("/tags", web::get().to(routes::list_tags_page))
+                    .route("/tags", web::post().to(routes::update_subscriber_tags))
+                    .route("/attributes", web::get().to(routes::list_attributes_page))
+                    .route("/attributes", web::post().to(routes::create_attribute))
+                    .route("/rss", web::get().to(routes::list_rss_feeds))
+                    .route("/rss", web::post().to(routes::create_rss_feed))
+                    .route("/rss/{id}/delete", web::post().to(routes::delete_rss_feed))
                     .route("/change/password", web::get().to(routes::form_password))
                     .route("/change/password", web::post().to(routes::password_change))
-                    .route("/logout", web::post().to(routes::logout)),
+                    .route(
+                        "/settings/notifications",
+                        web::get().to(routes::notification_settings_form),
+                    )
+                    .route(
+                        "/settings/notifications",
+                        web::post().to(routes::update_notification_settings),
+                    )
+                    .route(
+                        "/settings/notifications/verify",
+                        web::get().to(routes::verify_user_email),
                     )
-                    
+                    .route("/logout", web::post().to(routes::logout)),
+            )
             .app_data(connection_pool.clone())
             .app_data(email_client.clone())
+            .app_data(issue_renderer.clone())
             .app_data(base_url.clone())
             .app_data(web::Data::new(hmac_secret.clone()))
+            .app_data(login_notifications.clone())
+            .app_data(newsletter_settings.clone())
+            .app_data(signup_settings.clone())
+            .app_data(trusted_proxies.clone())
+            .app_data(MultipartFormConfig::default().memory_limit(MAX_IMPORT_SIZE));
+        // Without an identity provider, the OIDC paths are not found.
+        if let Some(oidc_client) = &oidc_client {
+            app = app
+                .app_data(oidc_client.clone())
+                .route("/login/oidc", web::get().to(routes::oidc_login))
+                .route("/login/oidc/callback", web::get().to(routes::oidc_callback));
+        }
+        if let Some(challenge_verifier) = &challenge_verifier {
+            app = app.app_data(challenge_verifier.clone());
+        }
+        app
     })
     .listen(listener)?
     .run();
@@ -73,6 +311,13 @@ pub fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
 pub struct Application {
     port: u16,
     server: Server,
+    connection_pool: PgPool,
+    email_client: EmailClient,
+    issue_renderer: IssueRenderer,
+    /// Fetches the RSS feeds turned into newsletter issues.
+    feed_client: reqwest::Client,
+    /// For the confirmation links sent by the import worker.
+    base_url: String,
 }
 
 impl Application {
@@ -84,34 +329,85 @@ impl Application {
         );
         let listener = std::net::TcpListener::bind(adress)?;
 
-        let sender_email = configuration
-            .email_client
-            .sender()
-            .expect("Invalid sender email address.");
-
-        let timeout = configuration.email_client.timeout();
-        let email_client = email_client::EmailClient::new(
-            configuration.email_client.base_url,
-            sender_email,
-            configuration.email_client.authorization_token,
-            timeout,
+        if !configuration
+            .newsletter
+            .markdown_layout
+            .contains(templates::CONTENT_SLOT)
+        {
+            anyhow::bail!(
+                "The markdown layout has no {} slot",
+                templates::CONTENT_SLOT
+            );
+        }
+        let email_client = configuration.email_client.client();
+        let oidc_client = configuration
+            .oidc
+            .map(|settings| OidcClient::new(settings, &configuration.application.base_url));
+        let issue_renderer = IssueRenderer::new(
+            configuration.application.base_url.clone(),
+            HmacSecret(configuration.application.hmac_secret.clone()),
         );
+        let feed_client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()?;
         let port = listener.local_addr().unwrap().port();
+        let base_url = configuration.application.base_url.clone();
         let server = run(
             listener,
-            connection_pool,
-            email_client,
+            connection_pool.clone(),
+            email_client.clone(),
+            issue_renderer.clone(),
             configuration.application.base_url,
             HmacSecret(configuration.application.hmac_secret),
             configuration.redis_uri,
+            configuration.login_notifications,
+            configuration.newsletter,
+            configuration.signup,
+            TrustedProxies(configuration.application.trusted_proxies),
+            oidc_client,
         )
         .await?;
 
-        Ok(Self { port, server })
+        Ok(Self {
+            port,
+            server,
+            connection_pool,
+            email_client,
+            issue_renderer,
+            feed_client,
+            base_url,
+        })
     }
 
+    /// Runs the server along with the background workers, the workers are
+    /// stopped when the server stops. The international emails the migration
+    /// could not normalize are normalized first.
     pub async fn run_until_stop(self) -> Result<(), std::io::Error> {
-        self.server.await
+        if let Err(e) = normalize_international_emails(&self.connection_pool).await {
+            tracing::error!(error.cause_chain = ?e, "Failed to normalize the subscriber emails");
+        }
+        let scheduler =
+            actix_web::rt::spawn(run_scheduler_until_stopped(self.connection_pool.clone()));
+        let rss_poller = actix_web::rt::spawn(run_rss_poller_until_stopped(
+            self.connection_pool.clone(),
+            self.feed_client,
+        ));
+        let import_worker = actix_web::rt::spawn(run_import_worker_until_stopped(
+            self.connection_pool.clone(),
+            self.email_client.clone(),
+            self.base_url,
+        ));
+        let worker = actix_web::rt::spawn(run_worker_until_stopped(
+            self.connection_pool,
+            self.email_client,
+            self.issue_renderer,
+        ));
+        let result = self.server.await;
+        scheduler.abort();
+        rss_poller.abort();
+        import_worker.abort();
+        worker.abort();
+        result
     }
 
     pub fn port(&self) -> u16 {
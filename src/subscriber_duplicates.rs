@@ -0,0 +1,228 @@
+//! The subscribers that only differ by the case of their email, found when the
+//! emails were normalized. An admin merges each of them into the oldest one.
+
+use crate::domain::SubscriberEmail;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+pub struct SubscriberDuplicate {
+    pub duplicate_id: Uuid,
+    pub email: String,
+    pub kept_id: Uuid,
+    /// `None` if the kept subscriber has been deleted since.
+    pub kept_email: Option<String>,
+    pub detected_at: DateTime<Utc>,
+    pub merged_at: Option<DateTime<Utc>>,
+}
+
+/// The duplicates waiting for a merge first.
+pub async fn list_duplicates(pool: &PgPool) -> Result<Vec<SubscriberDuplicate>, anyhow::Error> {
+    sqlx::query_as!(
+        SubscriberDuplicate,
+        r#"SELECT d.duplicate_id, d.email, d.kept_id, s.email AS "kept_email?",
+            d.detected_at, d.merged_at
+        FROM subscriber_duplicates d
+        LEFT JOIN subscriptions s ON s.id = d.kept_id
+        ORDER BY d.merged_at IS NOT NULL, d.normalized_email, d.email"#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the duplicate subscribers")
+}
+
+/// The duplicate and the kept subscriber, if the duplicate is not merged yet
+/// and both still exist.
+pub async fn get_unmerged_duplicate(
+    transaction: &mut Transaction<'_, Postgres>,
+    duplicate_id: Uuid,
+) -> Result<Option<(String, Uuid, String)>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT d.email, d.kept_id, s.email AS kept_email
+        FROM subscriber_duplicates d
+        JOIN subscriptions s ON s.id = d.kept_id
+        WHERE d.duplicate_id = $1 AND d.merged_at IS NULL
+            AND EXISTS (SELECT 1 FROM subscriptions WHERE id = d.duplicate_id)
+        FOR UPDATE OF d"#,
+        duplicate_id
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to fetch the duplicate subscriber")?;
+    Ok(row.map(|r| (r.email, r.kept_id, r.kept_email)))
+}
+
+/// Moves the lists, tags, attributes and history of the duplicate to the kept
+/// subscriber, then deletes the duplicate. On a list they both belong to, the
+/// latest subscription wins; the kept subscriber's tags and attributes win.
+#[tracing::instrument(name = "Merge a duplicate subscriber", skip(transaction))]
+pub async fn merge_subscribers(
+    transaction: &mut Transaction<'_, Postgres>,
+    duplicate_id: Uuid,
+    kept_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"INSERT INTO list_memberships (list_id, subscriber_id, status, subscribed_at)
+        SELECT list_id, $2, status, subscribed_at FROM list_memberships
+        WHERE subscriber_id = $1
+        ON CONFLICT (list_id, subscriber_id) DO UPDATE
+        SET status = EXCLUDED.status, subscribed_at = EXCLUDED.subscribed_at
+        WHERE list_memberships.subscribed_at < EXCLUDED.subscribed_at"#,
+        duplicate_id,
+        kept_id
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to merge the lists")?;
+    sqlx::query!(
+        r#"INSERT INTO subscriber_tags (subscriber_id, tag)
+        SELECT $2, tag FROM subscriber_tags WHERE subscriber_id = $1
+        ON CONFLICT DO NOTHING"#,
+        duplicate_id,
+        kept_id
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to merge the tags")?;
+    sqlx::query!(
+        r#"INSERT INTO subscriber_attributes (subscriber_id, key, value)
+        SELECT $2, key, value FROM subscriber_attributes WHERE subscriber_id = $1
+        ON CONFLICT DO NOTHING"#,
+        duplicate_id,
+        kept_id
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to merge the attributes")?;
+    sqlx::query!(
+        r#"UPDATE subscriptions_tokens SET subscriptions_id = $2 WHERE subscriptions_id = $1"#,
+        duplicate_id,
+        kept_id
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to move the confirmation links")?;
+    sqlx::query!(
+        r#"UPDATE consent_records SET subscriber_id = $2 WHERE subscriber_id = $1"#,
+        duplicate_id,
+        kept_id
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to move the consent records")?;
+    sqlx::query!(
+        r#"UPDATE issue_deliveries SET subscriber_id = $2 WHERE subscriber_id = $1"#,
+        duplicate_id,
+        kept_id
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to move the deliveries")?;
+    sqlx::query!(
+        r#"UPDATE audit_log SET subscriber_id = $2 WHERE subscriber_id = $1"#,
+        duplicate_id,
+        kept_id
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to move the audit trail")?;
+    sqlx::query!(r#"DELETE FROM subscriptions WHERE id = $1"#, duplicate_id)
+        .execute(&mut **transaction)
+        .await
+        .context("Failed to delete the duplicate subscriber")?;
+    sqlx::query!(
+        r#"UPDATE subscriber_duplicates SET merged_at = $2 WHERE duplicate_id = $1"#,
+        duplicate_id,
+        Utc::now()
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to mark the duplicate as merged")?;
+    Ok(())
+}
+
+/// The migration only lowercased the existing addresses: the ones with other
+/// than ASCII characters get the normalized email of the application, with
+/// their domain in punycode. A subscriber whose normalized email is already
+/// taken becomes a duplicate of the one who has it. There is nothing left to
+/// do the next times.
+#[tracing::instrument(name = "Normalize the international subscriber emails", skip(pool))]
+pub async fn normalize_international_emails(pool: &PgPool) -> Result<(), anyhow::Error> {
+    let subscribers = sqlx::query!(
+        r#"SELECT id, email, normalized_email AS "normalized_email!"
+        FROM subscriptions
+        WHERE normalized_email IS NOT NULL AND email !~ '^[ -~]*$'"#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the international subscriber emails")?;
+    for subscriber in subscribers {
+        let normalized = match SubscriberEmail::parse(subscriber.email.clone()) {
+            Ok(email) => email.normalized(),
+            Err(e) => {
+                tracing::warn!(
+                    subscriber_id = %subscriber.id,
+                    error = %e,
+                    "The email of a subscriber cannot be normalized"
+                );
+                continue;
+            }
+        };
+        if normalized == subscriber.normalized_email {
+            continue;
+        }
+        let mut transaction = pool
+            .begin()
+            .await
+            .context("Failed to acquire a connection from the pool")?;
+        let kept = sqlx::query!(
+            r#"SELECT id FROM subscriptions WHERE normalized_email = $1 AND id <> $2 FOR UPDATE"#,
+            normalized,
+            subscriber.id
+        )
+        .fetch_optional(&mut *transaction)
+        .await
+        .context("Failed to look for the normalized email")?;
+        match kept {
+            Some(kept) => {
+                sqlx::query!(
+                    r#"INSERT INTO subscriber_duplicates
+                        (duplicate_id, kept_id, normalized_email, email, detected_at)
+                    VALUES ($1, $2, $3, $4, $5)
+                    ON CONFLICT (duplicate_id) DO NOTHING"#,
+                    subscriber.id,
+                    kept.id,
+                    normalized,
+                    subscriber.email,
+                    Utc::now()
+                )
+                .execute(&mut *transaction)
+                .await
+                .context("Failed to record the duplicate subscriber")?;
+                sqlx::query!(
+                    r#"UPDATE subscriptions SET normalized_email = NULL WHERE id = $1"#,
+                    subscriber.id
+                )
+                .execute(&mut *transaction)
+                .await
+                .context("Failed to mark the subscriber as a duplicate")?;
+            }
+            None => {
+                sqlx::query!(
+                    r#"UPDATE subscriptions SET normalized_email = $2 WHERE id = $1"#,
+                    subscriber.id,
+                    normalized
+                )
+                .execute(&mut *transaction)
+                .await
+                .context("Failed to normalize the email of the subscriber")?;
+            }
+        }
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit the normalized email")?;
+    }
+    Ok(())
+}
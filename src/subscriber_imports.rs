@@ -0,0 +1,294 @@
+//! CSV files of subscribers uploaded by the admins, imported in the
+//! background by the import worker.
+
+use crate::mailing_lists::MailingList;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+/// The largest file accepted, in bytes.
+pub const MAX_IMPORT_SIZE: usize = 10 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// The subscribers consented elsewhere, they are added as confirmed.
+    Confirmed,
+    /// Each new subscriber receives the confirmation email.
+    SendConfirmation,
+}
+
+impl ImportMode {
+    pub const ALL: [ImportMode; 2] = [ImportMode::Confirmed, ImportMode::SendConfirmation];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportMode::Confirmed => "confirmed",
+            ImportMode::SendConfirmation => "send_confirmation",
+        }
+    }
+}
+
+impl TryFrom<String> for ImportMode {
+    type Error = String;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        ImportMode::ALL
+            .into_iter()
+            .find(|mode| mode.as_str() == s)
+            .ok_or_else(|| format!("{} is not a valid import mode", s))
+    }
+}
+
+impl std::fmt::Display for ImportMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An import waiting for the worker.
+pub struct PendingImport {
+    pub id: Uuid,
+    pub list: MailingList,
+    pub mode: ImportMode,
+    pub content: String,
+}
+
+pub struct ImportSummary {
+    pub id: Uuid,
+    pub file_name: String,
+    pub list_name: String,
+    pub mode: ImportMode,
+    /// `pending`, `done` or `failed`.
+    pub status: String,
+    pub n_rows: i32,
+    pub n_imported: i32,
+    pub n_duplicates: i32,
+    pub n_errors: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A row that was not imported.
+pub struct ImportError {
+    /// The line in the file, the header is line 1.
+    pub line: i32,
+    pub email: String,
+    pub error: String,
+}
+
+#[tracing::instrument(
+    name = "Insert a subscriber import",
+    skip(transaction, consent_attestation, content)
+)]
+pub async fn insert_import(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    file_name: &str,
+    list_id: Uuid,
+    mode: ImportMode,
+    consent_attestation: Option<&str>,
+    content: &str,
+) -> Result<Uuid, anyhow::Error> {
+    let import_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"INSERT INTO subscriber_imports
+            (import_id, user_id, file_name, list_id, mode, consent_attestation, status,
+            content, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, 'pending', $7, $8)"#,
+        import_id,
+        user_id,
+        file_name,
+        list_id,
+        mode.as_str(),
+        consent_attestation,
+        content,
+        Utc::now()
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to insert the import")?;
+    Ok(import_id)
+}
+
+/// The oldest pending import, locked until the transaction ends.
+pub async fn dequeue_import(
+    pool: &PgPool,
+) -> Result<Option<(Transaction<'static, Postgres>, PendingImport)>, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a connection from the pool")?;
+    let import = sqlx::query!(
+        r#"SELECT i.import_id, i.mode, i.content,
+            l.list_id, l.name, l.slug, l.description, l.is_default
+        FROM subscriber_imports i
+        JOIN lists l ON l.list_id = i.list_id
+        WHERE i.status = 'pending'
+        ORDER BY i.created_at
+        FOR UPDATE OF i
+        SKIP LOCKED
+        LIMIT 1"#
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to fetch a pending import")?;
+    let Some(import) = import else {
+        return Ok(None);
+    };
+    let import = PendingImport {
+        id: import.import_id,
+        list: MailingList {
+            id: import.list_id,
+            name: import.name,
+            slug: import.slug,
+            description: import.description,
+            is_default: import.is_default,
+        },
+        mode: ImportMode::try_from(import.mode).map_err(|e| anyhow::anyhow!(e))?,
+        content: import.content,
+    };
+    Ok(Some((transaction, import)))
+}
+
+pub async fn insert_import_error(
+    executor: impl PgExecutor<'_>,
+    import_id: Uuid,
+    error: &ImportError,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"INSERT INTO subscriber_import_errors (import_id, line, email, error)
+        VALUES ($1, $2, $3, $4)"#,
+        import_id,
+        error.line,
+        error.email,
+        error.error
+    )
+    .execute(executor)
+    .await
+    .context("Failed to record an import error")?;
+    Ok(())
+}
+
+/// The outcome of the rows, the file itself is not kept.
+pub struct ImportCounts {
+    pub n_rows: i32,
+    pub n_imported: i32,
+    pub n_duplicates: i32,
+    pub n_errors: i32,
+}
+
+pub async fn finish_import(
+    mut transaction: Transaction<'_, Postgres>,
+    import_id: Uuid,
+    counts: &ImportCounts,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"UPDATE subscriber_imports
+        SET status = 'done', content = '', n_rows = $2, n_imported = $3, n_duplicates = $4,
+            n_errors = $5, finished_at = $6
+        WHERE import_id = $1"#,
+        import_id,
+        counts.n_rows,
+        counts.n_imported,
+        counts.n_duplicates,
+        counts.n_errors,
+        Utc::now()
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to finish the import")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the import")?;
+    Ok(())
+}
+
+/// Keeps the import from being retried forever.
+pub async fn fail_import(pool: &PgPool, import_id: Uuid) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"UPDATE subscriber_imports SET status = 'failed', content = '', finished_at = $2
+        WHERE import_id = $1"#,
+        import_id,
+        Utc::now()
+    )
+    .execute(pool)
+    .await
+    .context("Failed to mark the import as failed")?;
+    Ok(())
+}
+
+/// The newest imports first.
+pub async fn list_imports(pool: &PgPool) -> Result<Vec<ImportSummary>, anyhow::Error> {
+    sqlx::query!(
+        r#"SELECT i.import_id, i.file_name, l.name AS list_name, i.mode, i.status,
+            i.n_rows, i.n_imported, i.n_duplicates, i.n_errors, i.created_at
+        FROM subscriber_imports i
+        JOIN lists l ON l.list_id = i.list_id
+        ORDER BY i.created_at DESC
+        LIMIT 50"#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the imports")?
+    .into_iter()
+    .map(|r| {
+        Ok(ImportSummary {
+            id: r.import_id,
+            file_name: r.file_name,
+            list_name: r.list_name,
+            mode: ImportMode::try_from(r.mode).map_err(|e| anyhow::anyhow!(e))?,
+            status: r.status,
+            n_rows: r.n_rows,
+            n_imported: r.n_imported,
+            n_duplicates: r.n_duplicates,
+            n_errors: r.n_errors,
+            created_at: r.created_at,
+        })
+    })
+    .collect()
+}
+
+/// The rows that were not imported, in the order of the file. `None` if the
+/// import does not exist.
+pub async fn get_import_errors(
+    pool: &PgPool,
+    import_id: Uuid,
+) -> Result<Option<(String, Vec<ImportError>)>, anyhow::Error> {
+    let Some(import) = sqlx::query!(
+        r#"SELECT file_name FROM subscriber_imports WHERE import_id = $1"#,
+        import_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the import")?
+    else {
+        return Ok(None);
+    };
+    let errors = sqlx::query_as!(
+        ImportError,
+        r#"SELECT line, email, error FROM subscriber_import_errors
+        WHERE import_id = $1
+        ORDER BY line"#,
+        import_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the import errors")?;
+    Ok(Some((import.file_name, errors)))
+}
+
+/// The normalized emails among `emails` that already belong to a subscriber.
+pub async fn existing_emails(
+    transaction: &mut Transaction<'_, Postgres>,
+    emails: &[String],
+) -> Result<Vec<String>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT normalized_email AS "normalized_email!" FROM subscriptions
+        WHERE normalized_email = ANY($1)"#,
+        emails
+    )
+    .fetch_all(&mut **transaction)
+    .await
+    .context("Failed to look for the existing subscribers")?;
+    Ok(rows.into_iter().map(|r| r.normalized_email).collect())
+}
@@ -0,0 +1,609 @@
+//! Browsing and managing the subscribers from the admin area.
+
+use crate::consent::ConsentRecord;
+use crate::domain::{NewSubscriber, SubscriberEmail, SubscriptionStatus};
+use crate::mailing_lists::MailingList;
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::{Stream, TryStreamExt};
+use sqlx::{PgPool, Postgres, QueryBuilder, Transaction};
+use uuid::Uuid;
+
+pub const SUBSCRIBERS_PER_PAGE: i64 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortField {
+    Email,
+    /// Without regard to case.
+    Name,
+    #[default]
+    SubscribedAt,
+}
+
+impl SortField {
+    pub const ALL: [SortField; 3] = [SortField::Email, SortField::Name, SortField::SubscribedAt];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortField::Email => "email",
+            SortField::Name => "name",
+            SortField::SubscribedAt => "subscribed_at",
+        }
+    }
+
+    /// The indexed expression the subscribers are ordered by, then by id.
+    fn column(&self) -> &'static str {
+        match self {
+            SortField::Email => "s.email",
+            SortField::Name => "lower(s.name)",
+            SortField::SubscribedAt => "s.subscribed_at",
+        }
+    }
+}
+
+impl TryFrom<&str> for SortField {
+    type Error = String;
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        SortField::ALL
+            .into_iter()
+            .find(|field| field.as_str() == s)
+            .ok_or_else(|| format!("Subscribers cannot be sorted by {}.", s))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        }
+    }
+
+    /// How the keys of a page compare to the cursor.
+    fn after_operator(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => ">",
+            SortOrder::Desc => "<",
+        }
+    }
+}
+
+impl TryFrom<&str> for SortOrder {
+    type Error = String;
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "asc" => Ok(SortOrder::Asc),
+            "desc" => Ok(SortOrder::Desc),
+            other => Err(format!("{} is not a sort order, use asc or desc.", other)),
+        }
+    }
+}
+
+/// Which subscribers are listed, and in which order. The newest come first
+/// by default.
+#[derive(Debug, Clone, Default)]
+pub struct SubscriberFilter {
+    /// Part of the email or of the name, without regard to case.
+    pub search: String,
+    pub status: Option<SubscriptionStatus>,
+    /// Both ends are included, dates are in UTC.
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub sort: SortField,
+    pub order: SortOrder,
+}
+
+pub struct SubscriberSummary {
+    pub id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub subscribed_at: DateTime<Utc>,
+    /// `confirmed` if they are confirmed on any list, then
+    /// `pending_confirmation` if they are waiting on any list.
+    pub status: SubscriptionStatus,
+}
+
+pub struct SubscriberPage {
+    pub subscribers: Vec<SubscriberSummary>,
+    /// The cursor of the next page, if there is one.
+    pub next: Option<Uuid>,
+}
+
+/// The sort key of the subscriber a page starts after.
+struct Cursor {
+    id: Uuid,
+    email: String,
+    name: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+impl Cursor {
+    /// Appends `(key, id)` for the sort field.
+    fn push_key(&self, field: SortField, query: &mut QueryBuilder<'_, Postgres>) {
+        query.push("(");
+        match field {
+            SortField::Email => query.push_bind(self.email.clone()),
+            SortField::Name => query.push_bind(self.name.clone()),
+            SortField::SubscribedAt => query.push_bind(self.subscribed_at),
+        };
+        query.push(", ").push_bind(self.id).push(")");
+    }
+}
+
+/// The page of subscribers after the one with the id `after`, which is the
+/// last subscriber of the previous page. Returns `None` if that subscriber
+/// does not exist anymore.
+#[tracing::instrument(name = "List subscribers", skip(pool))]
+pub async fn list_subscribers(
+    pool: &PgPool,
+    filter: &SubscriberFilter,
+    after: Option<Uuid>,
+) -> Result<Option<SubscriberPage>, anyhow::Error> {
+    let cursor = match after {
+        Some(after) => {
+            let cursor = sqlx::query_as!(
+                Cursor,
+                r#"SELECT id, email, lower(name) AS "name!", subscribed_at
+                FROM subscriptions WHERE id = $1"#,
+                after
+            )
+            .fetch_optional(pool)
+            .await
+            .context("Failed to fetch the subscriber the page starts after")?;
+            match cursor {
+                Some(cursor) => Some(cursor),
+                None => return Ok(None),
+            }
+        }
+        None => None,
+    };
+    // The order and the cursor compare the sorted column itself, then the id,
+    // so that the matching index is used.
+    let mut query = QueryBuilder::new(
+        r#"SELECT s.id, s.email, s.name, s.subscribed_at,
+            CASE
+                WHEN EXISTS (
+                    SELECT 1 FROM list_memberships m
+                    WHERE m.subscriber_id = s.id AND m.status = 'confirmed'
+                ) THEN 'confirmed'
+                WHEN EXISTS (
+                    SELECT 1 FROM list_memberships m
+                    WHERE m.subscriber_id = s.id AND m.status = 'pending_confirmation'
+                ) THEN 'pending_confirmation'
+                ELSE 'unsubscribed'
+            END AS status
+        FROM subscriptions s
+        WHERE TRUE"#,
+    );
+    let search = filter.search.trim();
+    if !search.is_empty() {
+        query
+            .push(" AND (strpos(lower(s.email), lower(")
+            .push_bind(search.to_string())
+            .push(")) > 0 OR strpos(lower(s.name), lower(")
+            .push_bind(search.to_string())
+            .push(")) > 0)");
+    }
+    if let Some(status) = filter.status {
+        let has = |membership: &str| {
+            format!(
+                "EXISTS (SELECT 1 FROM list_memberships m \
+                WHERE m.subscriber_id = s.id AND m.status = '{membership}')"
+            )
+        };
+        query.push(match status {
+            SubscriptionStatus::Confirmed => format!(" AND {}", has("confirmed")),
+            SubscriptionStatus::PendingConfirmation => format!(
+                " AND NOT {} AND {}",
+                has("confirmed"),
+                has("pending_confirmation")
+            ),
+            SubscriptionStatus::Unsubscribed => format!(
+                " AND NOT {} AND NOT {}",
+                has("confirmed"),
+                has("pending_confirmation")
+            ),
+        });
+    }
+    if let Some(from) = filter.from {
+        query
+            .push(" AND s.subscribed_at >= ")
+            .push_bind(from)
+            .push("::date::timestamp AT TIME ZONE 'UTC'");
+    }
+    if let Some(to) = filter.to {
+        query
+            .push(" AND s.subscribed_at < (")
+            .push_bind(to)
+            .push("::date + 1)::timestamp AT TIME ZONE 'UTC'");
+    }
+    let column = filter.sort.column();
+    if let Some(cursor) = cursor {
+        query.push(format!(
+            " AND ({column}, s.id) {} ",
+            filter.order.after_operator()
+        ));
+        cursor.push_key(filter.sort, &mut query);
+    }
+    let order = filter.order.as_str();
+    query
+        .push(format!(" ORDER BY {column} {order}, s.id {order} LIMIT "))
+        .push_bind(SUBSCRIBERS_PER_PAGE + 1);
+    let rows: Vec<(Uuid, String, String, DateTime<Utc>, String)> = query
+        .build_query_as()
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch the subscribers")?;
+    let mut subscribers = rows
+        .into_iter()
+        .map(|(id, email, name, subscribed_at, status)| {
+            Ok(SubscriberSummary {
+                id,
+                email,
+                name,
+                subscribed_at,
+                status: SubscriptionStatus::try_from(status).map_err(|e| anyhow::anyhow!(e))?,
+            })
+        })
+        .collect::<Result<Vec<_>, anyhow::Error>>()?;
+    let next = if subscribers.len() as i64 > SUBSCRIBERS_PER_PAGE {
+        subscribers.truncate(SUBSCRIBERS_PER_PAGE as usize);
+        subscribers.last().map(|subscriber| subscriber.id)
+    } else {
+        None
+    };
+    Ok(Some(SubscriberPage { subscribers, next }))
+}
+
+/// A subscriber in an export, with their latest signup.
+pub struct ExportedSubscriber {
+    pub subscriber: SubscriberSummary,
+    pub consent: Option<ConsentRecord>,
+}
+
+/// Every subscriber matching the filter, oldest first, read from the database
+/// as the stream is consumed. The search, sort and order are ignored.
+pub fn stream_subscribers<'a>(
+    pool: &'a PgPool,
+    filter: &SubscriberFilter,
+) -> impl Stream<Item = Result<ExportedSubscriber, anyhow::Error>> + use<'a> {
+    sqlx::query!(
+        r#"SELECT id AS "id!", email AS "email!", name AS "name!",
+            subscribed_at AS "subscribed_at!", status AS "status!",
+            c.list_name AS "consent_list?", c.text_version AS "text_version?",
+            c.source AS "source?", c.signup_ip, c.signup_user_agent,
+            c.signed_up_at AS "signed_up_at?", c.confirmation_ip, c.confirmation_user_agent,
+            c.confirmed_at
+        FROM (
+            SELECT s.id, s.email, s.name, s.subscribed_at,
+                CASE
+                    WHEN EXISTS (
+                        SELECT 1 FROM list_memberships m
+                        WHERE m.subscriber_id = s.id AND m.status = 'confirmed'
+                    ) THEN 'confirmed'
+                    WHEN EXISTS (
+                        SELECT 1 FROM list_memberships m
+                        WHERE m.subscriber_id = s.id AND m.status = 'pending_confirmation'
+                    ) THEN 'pending_confirmation'
+                    ELSE 'unsubscribed'
+                END AS status
+            FROM subscriptions s
+        ) subscribers
+        LEFT JOIN LATERAL (
+            SELECT l.name AS list_name, r.text_version, r.source, r.signup_ip,
+                r.signup_user_agent, r.signed_up_at, r.confirmation_ip,
+                r.confirmation_user_agent, r.confirmed_at
+            FROM consent_records r
+            JOIN lists l ON l.list_id = r.list_id
+            WHERE r.subscriber_id = subscribers.id
+            ORDER BY r.signed_up_at DESC
+            LIMIT 1
+        ) c ON TRUE
+        WHERE ($1::text IS NULL OR status = $1)
+            AND ($2::date IS NULL OR subscribed_at >= $2::date::timestamp AT TIME ZONE 'UTC')
+            AND ($3::date IS NULL OR subscribed_at < ($3::date + 1)::timestamp AT TIME ZONE 'UTC')
+        ORDER BY subscribed_at, id"#,
+        filter.status.map(|status| status.as_str()),
+        filter.from,
+        filter.to
+    )
+    .fetch(pool)
+    .map_err(|e| anyhow::Error::new(e).context("Failed to fetch the subscribers"))
+    .and_then(|r| async move {
+        let consent = match (r.consent_list, r.text_version, r.source, r.signed_up_at) {
+            (Some(list_name), Some(text_version), Some(source), Some(signed_up_at)) => {
+                Some(ConsentRecord {
+                    list_name,
+                    text_version,
+                    source,
+                    signup_ip: r.signup_ip,
+                    signup_user_agent: r.signup_user_agent,
+                    signed_up_at,
+                    confirmation_ip: r.confirmation_ip,
+                    confirmation_user_agent: r.confirmation_user_agent,
+                    confirmed_at: r.confirmed_at,
+                })
+            }
+            _ => None,
+        };
+        Ok(ExportedSubscriber {
+            subscriber: SubscriberSummary {
+                id: r.id,
+                email: r.email,
+                name: r.name,
+                subscribed_at: r.subscribed_at,
+                status: SubscriptionStatus::try_from(r.status).map_err(|e| anyhow::anyhow!(e))?,
+            },
+            consent,
+        })
+    })
+}
+
+pub struct Subscriber {
+    pub id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub subscribed_at: DateTime<Utc>,
+}
+
+pub async fn get_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<Subscriber>, anyhow::Error> {
+    sqlx::query_as!(
+        Subscriber,
+        r#"SELECT id, email, name, subscribed_at FROM subscriptions WHERE id = $1"#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the subscriber")
+}
+
+/// Whatever the case of the address. The subscriber who keeps it comes
+/// first, then the duplicates waiting for a merge, which have no normalized
+/// email yet.
+pub async fn get_subscribers_by_email(
+    pool: &PgPool,
+    email: &SubscriberEmail,
+) -> Result<Vec<Subscriber>, anyhow::Error> {
+    sqlx::query_as!(
+        Subscriber,
+        r#"SELECT id, email, name, subscribed_at FROM subscriptions
+        WHERE normalized_email = $1
+            OR (normalized_email IS NULL AND (
+                lower(email) = $1
+                OR id IN (SELECT duplicate_id FROM subscriber_duplicates WHERE normalized_email = $1)
+            ))
+        ORDER BY normalized_email IS NULL, subscribed_at, id"#,
+        email.normalized()
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the subscribers")
+}
+
+pub struct Membership {
+    pub list_name: String,
+    pub status: SubscriptionStatus,
+    pub subscribed_at: DateTime<Utc>,
+}
+
+pub async fn get_memberships(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<Membership>, anyhow::Error> {
+    sqlx::query!(
+        r#"SELECT l.name, m.status, m.subscribed_at
+        FROM list_memberships m
+        JOIN lists l ON l.list_id = m.list_id
+        WHERE m.subscriber_id = $1
+        ORDER BY l.name"#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the lists of the subscriber")?
+    .into_iter()
+    .map(|r| {
+        Ok(Membership {
+            list_name: r.name,
+            status: SubscriptionStatus::try_from(r.status).map_err(|e| anyhow::anyhow!(e))?,
+            subscribed_at: r.subscribed_at,
+        })
+    })
+    .collect()
+}
+
+/// A confirmation link sent to the subscriber.
+pub struct TokenRecord {
+    pub list_name: String,
+    /// Unknown for the links sent before the history was kept.
+    pub created_at: Option<DateTime<Utc>>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// The newest links first.
+pub async fn get_token_history(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<TokenRecord>, anyhow::Error> {
+    sqlx::query_as!(
+        TokenRecord,
+        r#"SELECT l.name AS list_name, t.created_at, t.used_at
+        FROM subscriptions_tokens t
+        JOIN lists l ON l.list_id = t.list_id
+        WHERE t.subscriptions_id = $1
+        ORDER BY t.created_at DESC NULLS LAST"#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the tokens of the subscriber")
+}
+
+pub struct DeliveryRecord {
+    pub issue_id: Uuid,
+    pub issue_title: String,
+    /// `sent`, `failed` or `skipped`.
+    pub outcome: String,
+    pub n_attempts: i16,
+    pub delivered_at: DateTime<Utc>,
+}
+
+/// The latest deliveries first.
+pub async fn get_delivery_history(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<DeliveryRecord>, anyhow::Error> {
+    sqlx::query_as!(
+        DeliveryRecord,
+        r#"SELECT i.newsletter_issue_id AS issue_id, i.title AS issue_title,
+            d.outcome, d.n_attempts, d.delivered_at
+        FROM issue_deliveries d
+        JOIN newsletter_issues i ON i.newsletter_issue_id = d.newsletter_issue_id
+        WHERE d.subscriber_id = $1
+        ORDER BY d.delivered_at DESC"#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the deliveries to the subscriber")
+}
+
+/// Returns `false` if another subscriber has the email.
+#[tracing::instrument(name = "Update a subscriber", skip(transaction, subscriber))]
+pub async fn update_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    subscriber: &NewSubscriber,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE subscriptions SET email = $2, name = $3, normalized_email = $4
+        WHERE id = $1
+            AND NOT EXISTS (
+                SELECT 1 FROM subscriptions
+                WHERE (email = $2 OR normalized_email = $4) AND id <> $1
+            )"#,
+        subscriber_id,
+        subscriber.email.as_ref(),
+        subscriber.name.as_ref(),
+        subscriber.email.normalized()
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to update the subscriber")?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Adds the subscriber to the list without a confirmation link. Returns
+/// `false` if they are already a confirmed member.
+#[tracing::instrument(name = "Add a confirmed member to a list", skip(transaction))]
+pub async fn insert_confirmed_membership(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    list_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"INSERT INTO list_memberships (list_id, subscriber_id, status, subscribed_at)
+        VALUES ($1, $2, 'confirmed', $3)
+        ON CONFLICT (list_id, subscriber_id) DO UPDATE
+        SET status = 'confirmed', subscribed_at = EXCLUDED.subscribed_at
+        WHERE list_memberships.status <> 'confirmed'"#,
+        list_id,
+        subscriber_id,
+        Utc::now()
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to add the subscriber to the list")?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Confirms every membership waiting on a confirmation. Returns the names of
+/// the lists.
+#[tracing::instrument(name = "Confirm the memberships of a subscriber", skip(transaction))]
+pub async fn confirm_pending_memberships(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<Vec<String>, anyhow::Error> {
+    let lists = sqlx::query!(
+        r#"UPDATE list_memberships m SET status = 'confirmed'
+        FROM lists l
+        WHERE l.list_id = m.list_id
+            AND m.subscriber_id = $1 AND m.status = 'pending_confirmation'
+        RETURNING l.name"#,
+        subscriber_id
+    )
+    .fetch_all(&mut **transaction)
+    .await
+    .context("Failed to confirm the memberships")?;
+    Ok(lists.into_iter().map(|r| r.name).collect())
+}
+
+/// Ends every membership. Returns the names of the lists.
+#[tracing::instrument(name = "Unsubscribe a subscriber", skip(transaction))]
+pub async fn end_memberships(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<Vec<String>, anyhow::Error> {
+    let lists = sqlx::query!(
+        r#"UPDATE list_memberships m SET status = 'unsubscribed'
+        FROM lists l
+        WHERE l.list_id = m.list_id
+            AND m.subscriber_id = $1 AND m.status <> 'unsubscribed'
+        RETURNING l.name"#,
+        subscriber_id
+    )
+    .fetch_all(&mut **transaction)
+    .await
+    .context("Failed to end the memberships")?;
+    Ok(lists.into_iter().map(|r| r.name).collect())
+}
+
+/// The lists on which the subscriber has not confirmed yet.
+pub async fn get_pending_lists(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<MailingList>, anyhow::Error> {
+    sqlx::query_as!(
+        MailingList,
+        r#"SELECT l.list_id AS id, l.name, l.slug, l.description, l.is_default
+        FROM lists l
+        JOIN list_memberships m ON m.list_id = l.list_id
+        WHERE m.subscriber_id = $1 AND m.status = 'pending_confirmation'
+        ORDER BY l.name"#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the pending lists of the subscriber")
+}
+
+/// Deletes the subscriber and their confirmation links, everything else goes
+/// with the subscriber. Returns the number of links.
+#[tracing::instrument(name = "Delete a subscriber", skip(transaction))]
+pub async fn delete_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<u64, anyhow::Error> {
+    let tokens = sqlx::query!(
+        r#"DELETE FROM subscriptions_tokens WHERE subscriptions_id = $1"#,
+        subscriber_id
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to delete the tokens of the subscriber")?;
+    sqlx::query!(r#"DELETE FROM subscriptions WHERE id = $1"#, subscriber_id)
+        .execute(&mut **transaction)
+        .await
+        .context("Failed to delete the subscriber")?;
+    Ok(tokens.rows_affected())
+}
@@ -0,0 +1,127 @@
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const MAX_TAG_LENGTH: usize = 64;
+
+/// Tags are compared without regard to case or surrounding spaces.
+pub fn parse_tag(tag: &str) -> Result<String, String> {
+    let tag = tag.trim().to_lowercase();
+    if tag.is_empty() {
+        return Err("A tag cannot be empty.".to_string());
+    }
+    if tag.chars().count() > MAX_TAG_LENGTH {
+        return Err(format!(
+            "A tag is at most {} characters long.",
+            MAX_TAG_LENGTH
+        ));
+    }
+    Ok(tag)
+}
+
+/// Every tag in use with its number of subscribers, by name.
+pub async fn list_tags(pool: &PgPool) -> Result<Vec<(String, i64)>, anyhow::Error> {
+    let tags = sqlx::query!(
+        r#"SELECT tag, COUNT(*) AS "count!"
+        FROM subscriber_tags
+        GROUP BY tag
+        ORDER BY tag"#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the tags")?;
+    Ok(tags.into_iter().map(|r| (r.tag, r.count)).collect())
+}
+
+/// The tags of a subscriber, by name.
+pub async fn get_subscriber_tags(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<String>, anyhow::Error> {
+    let tags = sqlx::query!(
+        r#"SELECT tag FROM subscriber_tags WHERE subscriber_id = $1 ORDER BY tag"#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the tags of the subscriber")?;
+    Ok(tags.into_iter().map(|r| r.tag).collect())
+}
+
+/// The emails that belong to no subscriber.
+pub async fn unknown_emails(
+    pool: &PgPool,
+    emails: &[String],
+) -> Result<Vec<String>, anyhow::Error> {
+    let unknown = sqlx::query!(
+        r#"SELECT email AS "email!"
+        FROM UNNEST($1::text[]) AS email
+        WHERE email NOT IN (SELECT email FROM subscriptions)"#,
+        emails
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to look for unknown subscribers")?;
+    Ok(unknown.into_iter().map(|r| r.email).collect())
+}
+
+/// Returns the number of subscribers that did not have the tag yet.
+#[tracing::instrument(name = "Tag subscribers", skip(pool, emails))]
+pub async fn tag_subscribers(
+    pool: &PgPool,
+    emails: &[String],
+    tag: &str,
+) -> Result<u64, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"INSERT INTO subscriber_tags (subscriber_id, tag)
+        SELECT id, $2 FROM subscriptions WHERE email = ANY($1)
+        ON CONFLICT DO NOTHING"#,
+        emails,
+        tag
+    )
+    .execute(pool)
+    .await
+    .context("Failed to tag the subscribers")?;
+    Ok(result.rows_affected())
+}
+
+/// Returns the number of subscribers that had the tag.
+#[tracing::instrument(name = "Untag subscribers", skip(pool, emails))]
+pub async fn untag_subscribers(
+    pool: &PgPool,
+    emails: &[String],
+    tag: &str,
+) -> Result<u64, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"DELETE FROM subscriber_tags
+        WHERE tag = $2
+            AND subscriber_id IN (SELECT id FROM subscriptions WHERE email = ANY($1))"#,
+        emails,
+        tag
+    )
+    .execute(pool)
+    .await
+    .context("Failed to untag the subscribers")?;
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_tag;
+    use claim::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn tags_are_trimmed_and_lowercased() {
+        assert_ok_eq!(parse_tag("  Beta Testers "), "beta testers".to_string());
+    }
+
+    #[test]
+    fn an_empty_tag_is_rejected() {
+        assert_err!(parse_tag("   "));
+    }
+
+    #[test]
+    fn a_long_tag_is_rejected() {
+        assert_err!(parse_tag(&"a".repeat(65)));
+    }
+}
@@ -0,0 +1,334 @@
+use crate::merge_tags::{
+    CONFIRMATION_VARIABLES, DATA_REQUEST_VARIABLES, ERASURE_REQUEST_VARIABLES, LAYOUT_VARIABLES,
+    MergeValues, PASSWORD_RESET_VARIABLES, Template, TemplateError,
+};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Where the body of an email goes in a layout.
+pub const CONTENT_SLOT: &str = "{{ content }}";
+
+/// The kind of the system email sent to confirm a subscription.
+pub const SUBSCRIPTION_CONFIRMATION: &str = "subscription_confirmation";
+/// The kind of the system email with the link to download one's data.
+pub const DATA_REQUEST: &str = "data_request";
+/// The kind of the system email with the link to confirm an erasure.
+pub const ERASURE_REQUEST: &str = "erasure_request";
+/// The kind of the system email with the link to reset the password of an
+/// admin.
+pub const PASSWORD_RESET: &str = "password_reset";
+
+/// A named layout shared by newsletter issues and system emails.
+pub struct Layout {
+    pub id: Uuid,
+    pub name: String,
+    pub html_layout: String,
+    pub text_layout: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Layout {
+    /// Put the rendered HTML and plain-text bodies of an email in the layout,
+    /// rendering the merge tags of the layout itself along the way.
+    pub fn render(
+        &self,
+        html_body: &str,
+        text_body: &str,
+        values: &MergeValues,
+    ) -> Result<(String, String), TemplateError> {
+        Ok((
+            render_around(&self.html_layout, html_body, values, true)?,
+            render_around(&self.text_layout, text_body, values, false)?,
+        ))
+    }
+}
+
+/// Put the rendered bodies in the layout, if any.
+pub fn render_in_layout(
+    layout: Option<&Layout>,
+    html_body: String,
+    text_body: String,
+    values: &MergeValues,
+) -> Result<(String, String), TemplateError> {
+    match layout {
+        Some(layout) => layout.render(&html_body, &text_body, values),
+        None => Ok((html_body, text_body)),
+    }
+}
+
+/// The body is inserted as is: it is already rendered, and a merge tag found
+/// in the value of a variable must not be interpreted.
+fn render_around(
+    layout: &str,
+    body: &str,
+    values: &MergeValues,
+    html: bool,
+) -> Result<String, TemplateError> {
+    let (before, after) = layout.split_once(CONTENT_SLOT).unwrap_or((layout, ""));
+    Ok(format!(
+        "{}{}{}",
+        Template::parse(before, LAYOUT_VARIABLES)?.render(values, html),
+        body,
+        Template::parse(after, LAYOUT_VARIABLES)?.render(values, html),
+    ))
+}
+
+pub fn validate_layout(html_layout: &str, text_layout: &str) -> Result<(), String> {
+    for layout in [html_layout, text_layout] {
+        if layout.matches(CONTENT_SLOT).count() != 1 {
+            return Err(format!(
+                "A layout must contain the {} slot exactly once.",
+                CONTENT_SLOT
+            ));
+        }
+        Template::parse(&layout.replace(CONTENT_SLOT, ""), LAYOUT_VARIABLES)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[tracing::instrument(name = "List the layouts", skip(pool))]
+pub async fn list_layouts(pool: &PgPool) -> Result<Vec<Layout>, anyhow::Error> {
+    let layouts = sqlx::query_as!(
+        Layout,
+        r#"SELECT template_id AS id, name, html_layout, text_layout, created_at, updated_at
+        FROM templates
+        ORDER BY name"#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the layouts")?;
+    Ok(layouts)
+}
+
+#[tracing::instrument(name = "Get a layout", skip(pool))]
+pub async fn get_layout(pool: &PgPool, id: Uuid) -> Result<Option<Layout>, anyhow::Error> {
+    let layout = sqlx::query_as!(
+        Layout,
+        r#"SELECT template_id AS id, name, html_layout, text_layout, created_at, updated_at
+        FROM templates
+        WHERE template_id = $1"#,
+        id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the layout")?;
+    Ok(layout)
+}
+
+/// The layout of an email, if it has one.
+pub async fn get_email_layout(
+    pool: &PgPool,
+    template_id: Option<Uuid>,
+) -> Result<Option<Layout>, anyhow::Error> {
+    match template_id {
+        Some(id) => get_layout(pool, id).await,
+        None => Ok(None),
+    }
+}
+
+/// Returns `None` if the name is already taken.
+#[tracing::instrument(name = "Insert a layout", skip(pool, html_layout, text_layout))]
+pub async fn insert_layout(
+    pool: &PgPool,
+    name: &str,
+    html_layout: &str,
+    text_layout: &str,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let layout = sqlx::query!(
+        r#"INSERT INTO templates (template_id, name, html_layout, text_layout, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $5)
+        ON CONFLICT (name) DO NOTHING
+        RETURNING template_id"#,
+        Uuid::new_v4(),
+        name,
+        html_layout,
+        text_layout,
+        Utc::now()
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to insert the layout")?;
+    Ok(layout.map(|l| l.template_id))
+}
+
+/// Returns `false` if the layout does not exist or if the name is taken by
+/// another layout.
+#[tracing::instrument(name = "Update a layout", skip(pool, html_layout, text_layout))]
+pub async fn update_layout(
+    pool: &PgPool,
+    id: Uuid,
+    name: &str,
+    html_layout: &str,
+    text_layout: &str,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE templates
+        SET name = $1, html_layout = $2, text_layout = $3, updated_at = $4
+        WHERE template_id = $5
+            AND NOT EXISTS (SELECT 1 FROM templates WHERE name = $1 AND template_id <> $5)"#,
+        name,
+        html_layout,
+        text_layout,
+        Utc::now(),
+        id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to update the layout")?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// An email sent by the application itself, editable from the admin area.
+pub struct SystemEmail {
+    pub kind: String,
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+    pub template_id: Option<Uuid>,
+}
+
+#[tracing::instrument(name = "List the system emails", skip(pool))]
+pub async fn list_system_emails(pool: &PgPool) -> Result<Vec<SystemEmail>, anyhow::Error> {
+    let emails = sqlx::query_as!(
+        SystemEmail,
+        r#"SELECT kind, subject, html_body, text_body, template_id
+        FROM system_emails
+        ORDER BY kind"#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the system emails")?;
+    Ok(emails)
+}
+
+#[tracing::instrument(name = "Get a system email", skip(pool))]
+pub async fn get_system_email(
+    pool: &PgPool,
+    kind: &str,
+) -> Result<Option<SystemEmail>, anyhow::Error> {
+    let email = sqlx::query_as!(
+        SystemEmail,
+        r#"SELECT kind, subject, html_body, text_body, template_id
+        FROM system_emails
+        WHERE kind = $1"#,
+        kind
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the system email")?;
+    Ok(email)
+}
+
+#[tracing::instrument(
+    name = "Update a system email",
+    skip(pool, subject, html_body, text_body)
+)]
+pub async fn update_system_email(
+    pool: &PgPool,
+    kind: &str,
+    subject: &str,
+    html_body: &str,
+    text_body: &str,
+    template_id: Option<Uuid>,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE system_emails
+        SET subject = $1, html_body = $2, text_body = $3, template_id = $4, updated_at = $5
+        WHERE kind = $6"#,
+        subject,
+        html_body,
+        text_body,
+        template_id,
+        Utc::now(),
+        kind
+    )
+    .execute(pool)
+    .await
+    .context("Failed to update the system email")?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// The merge tags each kind of system email can use.
+pub fn system_email_variables(kind: &str) -> &'static [&'static str] {
+    match kind {
+        SUBSCRIPTION_CONFIRMATION => CONFIRMATION_VARIABLES,
+        DATA_REQUEST => DATA_REQUEST_VARIABLES,
+        ERASURE_REQUEST => ERASURE_REQUEST_VARIABLES,
+        PASSWORD_RESET => PASSWORD_RESET_VARIABLES,
+        _ => LAYOUT_VARIABLES,
+    }
+}
+
+pub struct RenderedEmail {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+/// Render a system email, in its layout, with the given merge tag values.
+#[tracing::instrument(name = "Render a system email", skip(pool, values))]
+pub async fn render_system_email(
+    pool: &PgPool,
+    kind: &str,
+    values: &MergeValues<'_>,
+) -> Result<RenderedEmail, anyhow::Error> {
+    let email = get_system_email(pool, kind)
+        .await?
+        .with_context(|| format!("The {} system email does not exist", kind))?;
+    let layout = get_email_layout(pool, email.template_id).await?;
+    let variables = system_email_variables(kind);
+    let render = |template: &str, html: bool| -> Result<String, TemplateError> {
+        Ok(Template::parse(template, variables)?.render(values, html))
+    };
+    let (html, text) = render_in_layout(
+        layout.as_ref(),
+        render(&email.html_body, true)?,
+        render(&email.text_body, false)?,
+        values,
+    )?;
+    Ok(RenderedEmail {
+        subject: render(&email.subject, false)?,
+        html,
+        text,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate_layout;
+    use claim::{assert_err, assert_ok};
+
+    #[test]
+    fn a_layout_with_a_content_slot_is_valid() {
+        assert_ok!(validate_layout(
+            "<div>Hi {{ name }}{{ content }}</div>",
+            "{{ content }}\n--\nSent to {{ email }}"
+        ));
+    }
+
+    #[test]
+    fn a_layout_without_a_content_slot_is_rejected() {
+        assert_err!(validate_layout("<div></div>", "{{ content }}"));
+        assert_err!(validate_layout("<div>{{ content }}</div>", "content"));
+    }
+
+    #[test]
+    fn a_layout_with_two_content_slots_is_rejected() {
+        assert_err!(validate_layout(
+            "{{ content }}{{ content }}",
+            "{{ content }}"
+        ));
+    }
+
+    #[test]
+    fn a_layout_cannot_use_the_variables_of_a_single_kind_of_email() {
+        assert_err!(validate_layout(
+            "{{ content }}{{ unsubscribe_url }}",
+            "{{ content }}"
+        ));
+    }
+}
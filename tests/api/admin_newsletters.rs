@@ -0,0 +1,226 @@
+use crate::helpers::{asser_is_redirect_to, spawn_app};
+use crate::newsletter::{create_confirmed_user, create_unconfirmed_user};
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+fn newsletter_form_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "html": "<p>Newsletter body as HTML</p>",
+        "text": "Newsletter body as plain text",
+    })
+}
+
+#[actix_web::test]
+pub async fn unauthenticated_users_cannot_reach_the_composer() {
+    let app = spawn_app().await;
+    let response = app.get_newsletter_form().await;
+    asser_is_redirect_to(&response, "/login");
+
+    let response = app.post_create_newsletter(&newsletter_form_body()).await;
+    asser_is_redirect_to(&response, "/login");
+
+    let response = app.post_newsletter_action(Uuid::new_v4(), "publish").await;
+    asser_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+pub async fn the_dashboard_links_to_the_newsletter_issues() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    let html = app.get_admindashboard_html().await;
+    assert!(html.contains(r#"<a href="/admin/newsletters">"#));
+}
+
+#[actix_web::test]
+pub async fn saving_a_draft_does_not_send_anything() {
+    let app = spawn_app().await;
+    create_confirmed_user(&app).await;
+    app.user.connect(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_newsletter_draft(&newsletter_form_body()).await;
+    app.dispatch_all_pending_emails().await;
+
+    let html = app.get_newsletter_issue_html(issue_id).await;
+    assert!(html.contains("<p><i>The draft has been saved.</i></p>"));
+    assert!(html.contains("Status: draft"));
+    assert!(html.contains("<pre>Newsletter body as plain text</pre>"));
+    assert!(html.contains("This issue will be sent to 1 confirmed subscribers."));
+
+    let html = app.get_newsletter_issues_html().await;
+    assert!(html.contains("Newsletter title"));
+}
+
+#[actix_web::test]
+pub async fn an_incomplete_issue_is_rejected_with_a_message() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+
+    let body = serde_json::json!({
+        "title": "",
+        "html": "<p>Newsletter body as HTML</p>",
+        "text": "Newsletter body as plain text",
+    });
+    let response = app.post_create_newsletter(&body).await;
+    asser_is_redirect_to(&response, "/admin/newsletters/new");
+
+    let html = app.get_newsletter_form_html().await;
+    assert!(html.contains("<p><i>The title of the issue is missing.</i></p>"));
+}
+
+#[actix_web::test]
+pub async fn a_draft_can_be_edited() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    let issue_id = app.create_newsletter_draft(&newsletter_form_body()).await;
+
+    let body = serde_json::json!({
+        "title": "Updated title",
+        "html": "<p>Updated body as HTML</p>",
+        "text": "Updated body as plain text",
+    });
+    let response = app.post_update_newsletter(issue_id, &body).await;
+    asser_is_redirect_to(&response, &format!("/admin/newsletters/{}", issue_id));
+
+    let html = app.get_newsletter_issue_html(issue_id).await;
+    assert!(html.contains("Updated title"));
+    assert!(html.contains("<pre>Updated body as plain text</pre>"));
+}
+
+#[actix_web::test]
+pub async fn publishing_sends_the_issue_to_confirmed_subscribers_only() {
+    let app = spawn_app().await;
+    create_unconfirmed_user(&app).await;
+    app.user.connect(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_newsletter_draft(&newsletter_form_body()).await;
+    let response = app.post_newsletter_action(issue_id, "publish").await;
+    asser_is_redirect_to(&response, &format!("/admin/newsletters/{}", issue_id));
+    app.dispatch_all_pending_emails().await;
+
+    let html = app.get_newsletter_issue_html(issue_id).await;
+    assert!(html.contains("<p><i>The newsletter issue has been published!</i></p>"));
+    assert!(html.contains("Status: sent"));
+}
+
+#[actix_web::test]
+pub async fn publishing_sends_the_issue() {
+    let app = spawn_app().await;
+    create_confirmed_user(&app).await;
+    app.user.connect(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_newsletter_draft(&newsletter_form_body()).await;
+    app.post_newsletter_action(issue_id, "publish").await;
+    app.dispatch_all_pending_emails().await;
+
+    let html = app.get_newsletter_issue_html(issue_id).await;
+    assert!(html.contains("Status: sent"));
+}
+
+#[actix_web::test]
+pub async fn a_published_issue_cannot_be_published_again() {
+    let app = spawn_app().await;
+    create_confirmed_user(&app).await;
+    app.user.connect(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_newsletter_draft(&newsletter_form_body()).await;
+    app.post_newsletter_action(issue_id, "publish").await;
+    app.post_newsletter_action(issue_id, "publish").await;
+    app.dispatch_all_pending_emails().await;
+
+    let html = app.get_newsletter_issue_html(issue_id).await;
+    assert!(html.contains("<p><i>Only drafts can be published.</i></p>"));
+}
+
+#[actix_web::test]
+pub async fn a_cancelled_issue_cannot_be_published_but_can_be_deleted() {
+    let app = spawn_app().await;
+    create_confirmed_user(&app).await;
+    app.user.connect(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_newsletter_draft(&newsletter_form_body()).await;
+    app.post_newsletter_action(issue_id, "cancel").await;
+    app.post_newsletter_action(issue_id, "publish").await;
+    app.dispatch_all_pending_emails().await;
+    let html = app.get_newsletter_issue_html(issue_id).await;
+    assert!(html.contains("Status: cancelled"));
+
+    let response = app.post_newsletter_action(issue_id, "delete").await;
+    asser_is_redirect_to(&response, "/admin/newsletters");
+    let response = app.get_newsletter_issue(issue_id).await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[actix_web::test]
+pub async fn an_unknown_merge_tag_is_rejected_with_a_message() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "html": "<p>Hello {{ surname }}</p>",
+        "text": "Hello {{ name }}",
+    });
+    let response = app.post_create_newsletter(&body).await;
+    asser_is_redirect_to(&response, "/admin/newsletters/new");
+
+    let html = app.get_newsletter_form_html().await;
+    assert!(html.contains("Unknown merge tag variable `surname`."));
+}
+
+#[actix_web::test]
+pub async fn the_html_is_sanitized_and_its_styles_inlined() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "html": "<style>p { color: red } a:hover { color: blue }</style>\
+            <p>Newsletter body as HTML</p><script>alert(1)</script>",
+        "text": "Newsletter body as plain text",
+    });
+    let issue_id = app.create_newsletter_draft(&body).await;
+
+    let html = app.get_newsletter_issue_preview_html(issue_id).await;
+    assert_eq!(html, r#"<p style="color: red">Newsletter body as HTML</p>"#);
+
+    let html = app.get_newsletter_issue_html(issue_id).await;
+    assert!(html.contains("<li>The &lt;script&gt; element has been removed.</li>"));
+    assert!(
+        html.contains("<li>The `a:hover` selector cannot be inlined and has been removed.</li>")
+    );
+}
@@ -0,0 +1,186 @@
+use crate::helpers::{asser_is_redirect_to, spawn_app};
+use crate::newsletter::create_confirmed_user;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+fn layout_form_body() -> serde_json::Value {
+    serde_json::json!({
+        "name": "Branded",
+        "html_layout": r#"<div class="brand">Hi {{ name }}{{ content }}</div>"#,
+        "text_layout": "BRAND\n{{ content }}",
+    })
+}
+
+#[actix_web::test]
+pub async fn unauthenticated_users_cannot_manage_templates() {
+    let app = spawn_app().await;
+    let response = app.post_create_layout(&layout_form_body()).await;
+    asser_is_redirect_to(&response, "/login");
+
+    let response = app
+        .post_system_email("subscription_confirmation", &serde_json::json!({}))
+        .await;
+    asser_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+pub async fn a_layout_can_be_created_and_previewed() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    let html = app.get_admindashboard_html().await;
+    assert!(html.contains(r#"<a href="/admin/templates">"#));
+
+    let layout_id = app.create_layout(&layout_form_body()).await;
+
+    let html = app.get_templates_html().await;
+    assert!(html.contains("Branded"));
+    assert!(html.contains("subscription_confirmation"));
+
+    let response = app.get_layout_preview(layout_id).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["Content-Security-Policy"], "sandbox");
+    let preview = response.text().await.unwrap();
+    assert!(preview.starts_with(r#"<div class="brand">Hi Ursula<h1>Sample title</h1>"#));
+}
+
+#[actix_web::test]
+pub async fn a_layout_without_a_content_slot_is_rejected() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+
+    let body = serde_json::json!({
+        "name": "Broken",
+        "html_layout": "<div></div>",
+        "text_layout": "{{ content }}",
+    });
+    let response = app.post_create_layout(&body).await;
+    asser_is_redirect_to(&response, "/admin/templates/new");
+
+    let html = app.get_new_layout_form_html().await;
+    assert!(html.contains("A layout must contain the {{ content }} slot exactly once."));
+}
+
+#[actix_web::test]
+pub async fn layout_names_are_unique() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    app.create_layout(&layout_form_body()).await;
+
+    let response = app.post_create_layout(&layout_form_body()).await;
+    asser_is_redirect_to(&response, "/admin/templates/new");
+
+    let html = app.get_new_layout_form_html().await;
+    assert!(html.contains("<p><i>A layout with this name already exists.</i></p>"));
+}
+
+#[actix_web::test]
+pub async fn an_issue_is_sent_in_its_layout() {
+    let app = spawn_app().await;
+    create_confirmed_user(&app).await;
+    app.user.connect(&app).await;
+    let layout_id = app.create_layout(&layout_form_body()).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app
+        .create_newsletter_draft(&serde_json::json!({
+            "title": "Newsletter title",
+            "html": "<p>Newsletter body as HTML</p>",
+            "text": "Newsletter body as plain text",
+            "template_id": layout_id.to_string(),
+        }))
+        .await;
+    app.post_newsletter_action(issue_id, "publish").await;
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = requests.last().unwrap().body_json().unwrap();
+    let html = body["HtmlBody"].as_str().unwrap();
+    assert!(html.starts_with(r#"<div class="brand">Hi le guin<p>Newsletter body as HTML</p>"#));
+    assert!(html.ends_with("</div>"));
+    assert!(html.contains("Unsubscribe"));
+    let text = body["TextBody"].as_str().unwrap();
+    assert!(text.starts_with("BRAND\nNewsletter body as plain text"));
+}
+
+#[actix_web::test]
+pub async fn the_confirmation_email_can_be_edited() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    let layout_id = app.create_layout(&layout_form_body()).await;
+
+    let response = app
+        .post_system_email(
+            "subscription_confirmation",
+            &serde_json::json!({
+                "subject": "Confirm, {{ name }}",
+                "html_body": r#"<a href="{{ confirmation_link }}">Confirm</a>"#,
+                "text_body": "Confirm: {{ confirmation_link }}",
+                "template_id": layout_id.to_string(),
+            }),
+        )
+        .await;
+    asser_is_redirect_to(
+        &response,
+        "/admin/templates/system/subscription_confirmation",
+    );
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let email_request = &requests[0];
+    let body: serde_json::Value = email_request.body_json().unwrap();
+    assert_eq!(body["Subject"], "Confirm, le guin");
+    assert!(
+        body["HtmlBody"]
+            .as_str()
+            .unwrap()
+            .starts_with(r#"<div class="brand">Hi le guin<a href=""#)
+    );
+    assert!(
+        body["TextBody"]
+            .as_str()
+            .unwrap()
+            .starts_with("BRAND\nConfirm: ")
+    );
+    let links = app.get_confirmation_links(email_request);
+    assert_eq!(links.html.path(), "/subscription/confirm");
+}
+
+#[actix_web::test]
+pub async fn a_system_email_cannot_use_the_merge_tags_of_another_kind_of_email() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+
+    let response = app
+        .post_system_email(
+            "subscription_confirmation",
+            &serde_json::json!({
+                "subject": "Welcome!",
+                "html_body": r#"<a href="{{ unsubscribe_url }}">Confirm</a>"#,
+                "text_body": "Confirm: {{ confirmation_link }}",
+            }),
+        )
+        .await;
+    asser_is_redirect_to(
+        &response,
+        "/admin/templates/system/subscription_confirmation",
+    );
+
+    let html = app.get_system_email_html("subscription_confirmation").await;
+    assert!(html.contains("Unknown merge tag variable `unsubscribe_url`."));
+}
@@ -0,0 +1,106 @@
+use crate::helpers::{TestApp, asser_is_redirect_to, spawn_app};
+use uuid::Uuid;
+
+/// Without subscribers, a published issue is sent right away.
+pub async fn publish_issue(app: &TestApp, title: &str) -> Uuid {
+    let issue_id = app
+        .create_newsletter_draft(&serde_json::json!({
+            "title": title,
+            "html": r#"<p>Hello {{ name | default: "reader" }}</p>"#,
+            "text": "Hello",
+        }))
+        .await;
+    app.post_newsletter_action(issue_id, "publish").await;
+    issue_id
+}
+
+#[actix_web::test]
+pub async fn sent_issues_are_in_the_archive_without_personalization() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    let issue_id = publish_issue(&app, "First issue").await;
+    let slug = app.get_issue_slug(issue_id).await;
+
+    let html = app.get_archive_html(1).await;
+    assert!(html.contains(&format!(r#"<a href="/archive/{}">First issue</a>"#, slug)));
+
+    let response = app.get_archive_issue(&slug).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains("<h1>First issue</h1>"));
+    assert!(html.contains("<p>Hello reader</p>"));
+}
+
+#[actix_web::test]
+pub async fn drafts_are_not_in_the_archive() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    let issue_id = app
+        .create_newsletter_draft(&serde_json::json!({
+            "title": "Draft issue",
+            "html": "<p>Hello</p>",
+            "text": "Hello",
+        }))
+        .await;
+    let slug = app.get_issue_slug(issue_id).await;
+
+    let html = app.get_archive_html(1).await;
+    assert!(!html.contains("Draft issue"));
+    let response = app.get_archive_issue(&slug).await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[actix_web::test]
+pub async fn private_issues_are_not_in_the_archive() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    let issue_id = publish_issue(&app, "Private issue").await;
+    let slug = app.get_issue_slug(issue_id).await;
+
+    let response = app.post_newsletter_visibility(issue_id, false).await;
+    asser_is_redirect_to(&response, &format!("/admin/newsletters/{}", issue_id));
+    let html = app.get_newsletter_issue_html(issue_id).await;
+    assert!(html.contains("<p><i>The newsletter issue is now private.</i></p>"));
+
+    let html = app.get_archive_html(1).await;
+    assert!(!html.contains("Private issue"));
+    let response = app.get_archive_issue(&slug).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    app.post_newsletter_visibility(issue_id, true).await;
+    let response = app.get_archive_issue(&slug).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[actix_web::test]
+pub async fn the_archive_is_paginated() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    for i in 0..11 {
+        publish_issue(&app, &format!("Issue number {}", i)).await;
+    }
+
+    let html = app.get_archive_html(1).await;
+    assert_eq!(html.matches("<li>").count(), 10);
+    assert!(html.contains(r#"<a href="/archive?page=2">"#));
+    assert!(!html.contains(r#"<a href="/archive?page=0">"#));
+
+    let html = app.get_archive_html(2).await;
+    assert_eq!(html.matches("<li>").count(), 1);
+    assert!(html.contains(r#"<a href="/archive?page=1">"#));
+    assert!(!html.contains(r#"<a href="/archive?page=3">"#));
+}
+
+#[actix_web::test]
+pub async fn a_page_past_the_last_countable_one_is_not_found() {
+    let app = spawn_app().await;
+
+    let html = app.get_archive_html(1_000_000).await;
+    assert!(html.contains("<li>No issues yet.</li>"));
+    let response = reqwest::Client::new()
+        .get(format!("{}/archive?page={}", &app.address, i64::MAX))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
@@ -0,0 +1,259 @@
+use crate::helpers::{TestApp, asser_is_redirect_to, spawn_app};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn define_attributes(app: &TestApp) {
+    for body in [
+        serde_json::json!({ "key": "company", "label": "Company", "kind": "text", "max_length": "20" }),
+        serde_json::json!({ "key": "seats", "label": "Seats", "kind": "number", "min_value": "1" }),
+        serde_json::json!({ "key": "plan", "label": "Plan", "kind": "choice", "choices": "free, pro", "required": "true" }),
+    ] {
+        let response = app.post_create_attribute(&body).await;
+        asser_is_redirect_to(&response, "/admin/attributes");
+    }
+}
+
+/// `query` holds the attributes, already encoded.
+async fn subscribe_and_confirm_with(app: &TestApp, email: &str, query: &str) {
+    let _guard = Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let body = format!(
+        "name=le%20guin&email={}&{}",
+        email.replace('@', "%40"),
+        query
+    );
+    app.post_subscription(body)
+        .await
+        .error_for_status()
+        .unwrap();
+    let requests = app.email_server.received_requests().await.unwrap();
+    let links = app.get_confirmation_links(requests.last().unwrap());
+    reqwest::get(links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+#[actix_web::test]
+pub async fn unauthenticated_users_cannot_define_attributes() {
+    let app = spawn_app().await;
+    let response = app
+        .post_create_attribute(&serde_json::json!({
+            "key": "company",
+            "label": "Company",
+            "kind": "text",
+        }))
+        .await;
+    asser_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+pub async fn invalid_attributes_are_rejected() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    define_attributes(&app).await;
+    let html = app.get_attributes_html().await;
+    assert!(html.contains("<p><i>The attribute has been created.</i></p>"));
+    assert!(html.contains(
+        "<tr><td><code>plan</code></td><td>Plan</td><td>choice</td><td>Yes</td><td>one of free, pro</td></tr>"
+    ));
+
+    let test_cases = [
+        (
+            serde_json::json!({ "key": "name", "label": "Name", "kind": "text" }),
+            "The key `name` is reserved.",
+        ),
+        (
+            serde_json::json!({ "key": "Job Title", "label": "Job title", "kind": "text" }),
+            "The key must start with a lowercase letter",
+        ),
+        (
+            serde_json::json!({ "key": "size", "label": "Size", "kind": "choice", "choices": " , " }),
+            "A choice needs at least one option.",
+        ),
+        (
+            serde_json::json!({ "key": "age", "label": "Age", "kind": "number", "min_value": "10", "max_value": "5" }),
+            "The minimum cannot be greater than the maximum.",
+        ),
+        (
+            serde_json::json!({ "key": "company", "label": "Employer", "kind": "text" }),
+            "An attribute with this key already exists.",
+        ),
+    ];
+    for (body, message) in test_cases {
+        let response = app.post_create_attribute(&body).await;
+        asser_is_redirect_to(&response, "/admin/attributes");
+        let html = app.get_attributes_html().await;
+        assert!(html.contains(message), "Missing the message: {}", message);
+    }
+}
+
+#[actix_web::test]
+pub async fn the_subscribe_form_validates_the_attributes() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    define_attributes(&app).await;
+
+    let home = reqwest::get(format!("{}/", app.address))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(home.contains(r#"<input type="text" name="company" maxlength="20">"#));
+    assert!(home.contains(r#"<select name="plan" required>"#));
+
+    let test_cases = [
+        ("plan=enterprise", "a plan that is not a choice"),
+        ("company=Acme", "a missing required plan"),
+        ("plan=pro&seats=0", "a number under the minimum"),
+        ("plan=pro&seats=many", "a number that is not one"),
+    ];
+    for (query, description) in test_cases {
+        let response = app
+            .post_subscription(format!("name=le%20guin&email=guin%40gmail.com&{}", query))
+            .await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not reject {}",
+            description
+        );
+    }
+
+    subscribe_and_confirm_with(&app, "guin@gmail.com", "plan=pro&seats=3.0&company=Acme").await;
+    let attributes = sqlx::query!("SELECT key, value FROM subscriber_attributes ORDER BY key")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    let attributes: Vec<_> = attributes.into_iter().map(|r| (r.key, r.value)).collect();
+    assert_eq!(
+        attributes,
+        vec![
+            ("company".to_string(), "Acme".to_string()),
+            ("plan".to_string(), "pro".to_string()),
+            ("seats".to_string(), "3".to_string()),
+        ]
+    );
+}
+
+#[actix_web::test]
+pub async fn a_new_signup_of_a_known_address_keeps_its_attributes() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    define_attributes(&app).await;
+    app.create_list("Weekly digest", "weekly").await;
+    subscribe_and_confirm_with(&app, "guin@gmail.com", "plan=free").await;
+
+    // Someone else signs the address up to another list.
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_subscription(
+        "name=le%20guin&email=Guin%40gmail.com&list=weekly&plan=pro&company=Acme".to_string(),
+    )
+    .await
+    .error_for_status()
+    .unwrap();
+
+    let attributes = sqlx::query!("SELECT key, value FROM subscriber_attributes ORDER BY key")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    let attributes: Vec<_> = attributes.into_iter().map(|r| (r.key, r.value)).collect();
+    assert_eq!(attributes, vec![("plan".to_string(), "free".to_string())]);
+}
+
+#[actix_web::test]
+pub async fn attributes_are_merge_tags_and_segment_filters() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    define_attributes(&app).await;
+    subscribe_and_confirm_with(&app, "big@example.com", "plan=pro&seats=50&company=Acme").await;
+    subscribe_and_confirm_with(&app, "small@example.com", "plan=pro&seats=2").await;
+    subscribe_and_confirm_with(&app, "free@example.com", "plan=free").await;
+
+    let response = app
+        .post_create_segment(&serde_json::json!({
+            "name": "Unknown",
+            "definition": r#"{"attribute": {"key": "plan", "op": "gt", "value": "pro"}}"#,
+        }))
+        .await;
+    asser_is_redirect_to(&response, "/admin/segments");
+    let html = app.get_segments_html().await;
+    assert!(html.contains("`&gt;` only applies to numbers and dates."));
+
+    let segment_id = app
+        .create_segment(
+            "Large customers",
+            r#"{"all": [{"attribute": {"key": "plan", "op": "eq", "value": "pro"}}, {"attribute": {"key": "seats", "op": "gte", "value": 10}}]}"#,
+        )
+        .await
+        .to_string();
+    let html = app.get_segments_html().await;
+    assert!(html.contains(
+        "<tr><td>Large customers</td><td>(plan = &quot;pro&quot; AND seats &gt;= &quot;10&quot;)</td><td>1</td>"
+    ));
+    app.create_segment(
+        "Without a company",
+        r#"{"not": {"attribute": {"key": "company", "op": "is_set"}}}"#,
+    )
+    .await;
+    let html = app.get_segments_html().await;
+    assert!(html.contains("<tr><td>Without a company</td><td>NOT company is set</td><td>2</td>"));
+
+    let issue_id = app
+        .create_newsletter_draft(&[
+            ("title", "Volume pricing"),
+            (
+                "html",
+                r#"<p>Hello {{ company | default: "there" }}, {{ seats }} seats</p>"#,
+            ),
+            (
+                "text",
+                r#"Hello {{ company | default: "there" }}, {{ seats }} seats"#,
+            ),
+            ("segment_id", segment_id.as_str()),
+        ])
+        .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter_action(issue_id, "publish").await;
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = requests.last().unwrap().body_json().unwrap();
+    assert_eq!(body["To"], "big@example.com");
+    assert!(
+        body["TextBody"]
+            .as_str()
+            .unwrap()
+            .contains("Hello Acme, 50 seats")
+    );
+}
+
+#[actix_web::test]
+pub async fn unknown_merge_tags_are_still_rejected() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    define_attributes(&app).await;
+    let response = app
+        .post_create_newsletter(&[
+            ("title", "Typo"),
+            ("html", "<p>{{ compnay }}</p>"),
+            ("text", "{{ compnay }}"),
+        ])
+        .await;
+    asser_is_redirect_to(&response, "/admin/newsletters/new");
+}
@@ -0,0 +1,236 @@
+use crate::helpers::{TestApp, spawn_app, spawn_app_with};
+use chrono::Utc;
+use secrecy::Secret;
+use wiremock::matchers::{body_string_contains, method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::bot_protection::form_timestamp;
+use zero2prod::configuration::ChallengeSettings;
+
+async fn mount_email_server(app: &TestApp) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+}
+
+async fn n_subscribers(app: &TestApp) -> i64 {
+    sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n
+}
+
+#[actix_web::test]
+async fn the_home_page_form_has_a_signed_timestamp_and_a_honeypot() {
+    let app = spawn_app().await;
+    let html = reqwest::get(&app.address)
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains(r#"name="form_rendered_at""#));
+    assert!(html.contains(r#"name="form_signature""#));
+    assert!(html.contains(r#"name="website""#));
+}
+
+#[actix_web::test]
+async fn a_filled_honeypot_is_ignored_without_an_email() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscription(
+            "name=Ursula&email=ursula%40example.com&website=http%3A%2F%2Fspam.example".to_string(),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(n_subscribers(&app).await, 0);
+}
+
+#[actix_web::test]
+async fn a_form_sent_too_quickly_or_without_its_timestamp_is_rejected() {
+    let app = spawn_app().await;
+    mount_email_server(&app).await;
+    let (rendered_at, signature) = form_timestamp(&app.hmac_secret, Utc::now());
+
+    let test_cases = [
+        (
+            format!(
+                "name=Ursula&email=ursula%40example.com&form_rendered_at={}&form_signature={}",
+                rendered_at, signature
+            ),
+            "The form was sent too quickly, please try again.",
+        ),
+        (
+            format!(
+                "name=Ursula&email=ursula%40example.com&form_rendered_at={}&form_signature=00",
+                rendered_at - 60
+            ),
+            "The form has expired, please reload the page and try again.",
+        ),
+        (
+            "name=Ursula&email=ursula%40example.com&form_rendered_at=".to_string(),
+            "The form has expired, please reload the page and try again.",
+        ),
+    ];
+    for (body, error) in test_cases {
+        let response = app.post_subscription(body).await;
+        assert_eq!(response.status().as_u16(), 400);
+        assert_eq!(response.text().await.unwrap(), error);
+    }
+    assert_eq!(n_subscribers(&app).await, 0);
+}
+
+#[actix_web::test]
+async fn signups_are_rate_limited_by_address_and_by_domain() {
+    let app = spawn_app_with(|configuration| {
+        configuration.signup.max_signups_per_ip_per_hour = 3;
+        configuration.signup.max_signups_per_domain_per_hour = 2;
+    })
+    .await;
+    mount_email_server(&app).await;
+    let subscribe = |email: &str| {
+        app.post_subscription(format!("name=Ursula&email={}", email.replace('@', "%40")))
+    };
+
+    assert_eq!(subscribe("a@example.com").await.status().as_u16(), 200);
+    assert_eq!(subscribe("b@example.com").await.status().as_u16(), 200);
+    let response = subscribe("c@example.com").await;
+    assert_eq!(response.status().as_u16(), 429);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "Too many signups for addresses at example.com, please try again later."
+    );
+    let response = subscribe("d@example.org").await;
+    assert_eq!(response.status().as_u16(), 429);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "Too many signups from your address, please try again later."
+    );
+    assert_eq!(n_subscribers(&app).await, 2);
+}
+
+#[actix_web::test]
+async fn a_forged_forwarded_address_does_not_get_around_the_rate_limit() {
+    let app = spawn_app_with(|configuration| {
+        configuration.signup.max_signups_per_ip_per_hour = 2;
+        configuration.signup.max_signups_per_domain_per_hour = 0;
+    })
+    .await;
+    mount_email_server(&app).await;
+
+    let mut statuses = Vec::new();
+    for i in 1..=3 {
+        let mut body = format!("name=Ursula&email=ursula{}%40example.com", i);
+        for (name, value) in app.form_timestamp() {
+            body.push_str(&format!("&{}={}", name, value));
+        }
+        let response = reqwest::Client::new()
+            .post(format!("{}/subscription", app.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .header("X-Forwarded-For", format!("203.0.113.{}", i))
+            .header("Forwarded", format!("for=198.51.100.{}", i))
+            .body(body)
+            .send()
+            .await
+            .unwrap();
+        statuses.push(response.status().as_u16());
+    }
+    assert_eq!(statuses, [200, 200, 429]);
+    let ip_addresses = sqlx::query!("SELECT DISTINCT ip_address FROM subscription_attempts")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(ip_addresses.len(), 1);
+    assert_eq!(ip_addresses[0].ip_address.as_deref(), Some("127.0.0.1"));
+}
+
+#[actix_web::test]
+async fn the_challenge_must_be_passed() {
+    let app = spawn_app_with(|configuration| {
+        configuration.signup.challenge = Some(ChallengeSettings::Stub {
+            accepted_response: "human".to_string(),
+        })
+    })
+    .await;
+    mount_email_server(&app).await;
+
+    for body in [
+        "name=Ursula&email=ursula%40example.com",
+        "name=Ursula&email=ursula%40example.com&challenge_response=robot",
+    ] {
+        let response = app.post_subscription(body.to_string()).await;
+        assert_eq!(response.status().as_u16(), 400);
+        assert_eq!(
+            response.text().await.unwrap(),
+            "The challenge was not passed, please try again."
+        );
+    }
+    let response = app
+        .post_subscription(
+            "name=Ursula&email=ursula%40example.com&challenge_response=human".to_string(),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[actix_web::test]
+async fn the_siteverify_challenge_asks_the_provider() {
+    let provider = wiremock::MockServer::start().await;
+    let verify_url = format!("{}/siteverify", provider.uri());
+    let app = spawn_app_with(|configuration| {
+        configuration.signup.challenge = Some(ChallengeSettings::Siteverify {
+            verify_url,
+            secret: Secret::new("challenge-secret".to_string()),
+            widget_html: r#"<div class="challenge-widget"></div>"#.to_string(),
+            timeout_millisecond: 2000,
+        })
+    })
+    .await;
+    mount_email_server(&app).await;
+    Mock::given(path("/siteverify"))
+        .and(method("POST"))
+        .and(body_string_contains("secret=challenge-secret"))
+        .and(body_string_contains("response=human"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "success": true
+        })))
+        .mount(&provider)
+        .await;
+    Mock::given(path("/siteverify"))
+        .and(method("POST"))
+        .and(body_string_contains("response=robot"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "success": false
+        })))
+        .mount(&provider)
+        .await;
+
+    let html = reqwest::get(&app.address)
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains(r#"<div class="challenge-widget"></div>"#));
+    let response = app
+        .post_subscription(
+            "name=Ursula&email=ursula%40example.com&challenge_response=robot".to_string(),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let response = app
+        .post_subscription(
+            "name=Ursula&email=ursula%40example.com&challenge_response=human".to_string(),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
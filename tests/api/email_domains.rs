@@ -0,0 +1,76 @@
+use crate::helpers::{TestApp, asser_is_redirect_to, spawn_app, spawn_app_with};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn subscribe(app: &TestApp, email: &str) -> reqwest::Response {
+    let body = format!("name=Ursula&email={}", email.replace('@', "%40"));
+    app.post_subscription(body).await
+}
+
+async fn mount_email_server(app: &TestApp) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+}
+
+#[actix_web::test]
+async fn disposable_domains_cannot_subscribe() {
+    let app = spawn_app().await;
+    mount_email_server(&app).await;
+
+    for email in ["ursula@mailinator.com", "ursula@eu.YOPMAIL.com"] {
+        let response = subscribe(&app, email).await;
+        assert_eq!(response.status().as_u16(), 400, "{} was accepted", email);
+        assert!(response.text().await.unwrap().contains("cannot subscribe"));
+    }
+    let response = subscribe(&app, "ursula@example.com").await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[actix_web::test]
+async fn admins_block_and_allow_domains() {
+    let app = spawn_app().await;
+    mount_email_server(&app).await;
+    app.user.connect(&app).await;
+
+    let response = app.post_email_domain_rule("@Spam.example", "block").await;
+    asser_is_redirect_to(&response, "/admin/email-domains");
+    assert!(
+        app.get_email_domains_html()
+            .await
+            .contains("spam.example is blocked.")
+    );
+    let response = app.post_email_domain_rule("mailinator.com", "allow").await;
+    asser_is_redirect_to(&response, "/admin/email-domains");
+    app.post_email_domain_rule("not a domain", "block").await;
+    assert!(
+        app.get_email_domains_html()
+            .await
+            .contains("not a domain is not a valid domain.")
+    );
+
+    let response = subscribe(&app, "ursula@spam.example").await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "Addresses at spam.example cannot subscribe, please use another address."
+    );
+    let response = subscribe(&app, "ursula@mailinator.com").await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[actix_web::test]
+async fn the_allowlist_mode_only_accepts_the_allowed_domains() {
+    let app = spawn_app_with(|configuration| configuration.signup.allowlist_only = true).await;
+    mount_email_server(&app).await;
+    app.user.connect(&app).await;
+    app.post_email_domain_rule("example.com", "allow").await;
+
+    let response = subscribe(&app, "ursula@example.org").await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(response.text().await.unwrap().contains("approved domains"));
+    let response = subscribe(&app, "ursula@example.com").await;
+    assert_eq!(response.status().as_u16(), 200);
+}
@@ -0,0 +1,103 @@
+use crate::helpers::{asser_is_redirect_to, spawn_app};
+use crate::lists::subscribe_and_confirm;
+use crate::subscribers::insert_subscribers;
+
+#[actix_web::test]
+pub async fn unauthenticated_users_cannot_export_subscribers() {
+    let app = spawn_app().await;
+    let response = app.get_subscribers("subscribers/export", "").await;
+    asser_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+pub async fn subscribers_are_exported_as_csv_with_the_filters() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    insert_subscribers(&app, 3).await;
+    subscribe_and_confirm(&app, "ursula@example.com", "").await;
+
+    let response = app
+        .get_subscribers("subscribers/export", "format=csv&status=confirmed")
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["content-type"],
+        "text/csv; charset=utf-8"
+    );
+    assert!(
+        response.headers()["content-disposition"]
+            .to_str()
+            .unwrap()
+            .contains(".csv")
+    );
+    let csv = response.text().await.unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(
+        lines[0],
+        "id,email,name,status,subscribed_at,consent_list,consent_source,consent_text_version,\
+        signed_up_at,signup_ip,signup_user_agent,confirmed_at,confirmation_ip,\
+        confirmation_user_agent"
+    );
+    assert!(lines[1].contains(",ursula@example.com,le guin,confirmed,"));
+    assert!(lines[1].contains(",Newsletter,subscribe_form,"));
+
+    let csv = app
+        .get_subscribers("subscribers/export", "from=2026-01-02&to=2026-01-03")
+        .await
+        .text()
+        .await
+        .unwrap();
+    let emails: Vec<&str> = csv
+        .lines()
+        .skip(1)
+        .map(|line| line.split(',').nth(1).unwrap())
+        .collect();
+    assert_eq!(
+        emails,
+        ["subscriber1@example.com", "subscriber2@example.com"]
+    );
+
+    let html = app.get_audit_trail_html().await;
+    assert!(html.contains(
+        "<td>export_subscribers</td><td>-</td><td>csv export, status: confirmed, from: -, to: -</td>"
+    ));
+    assert!(html.contains(
+        "<td>export_subscribers</td><td>-</td><td>csv export, status: any, from: 2026-01-02, to: 2026-01-03</td>"
+    ));
+}
+
+#[actix_web::test]
+pub async fn subscribers_are_streamed_as_ndjson_oldest_first() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    // Enough rows to be sent in several chunks.
+    insert_subscribers(&app, 2000).await;
+
+    let response = app
+        .get_subscribers("subscribers/export", "format=ndjson")
+        .await;
+    assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+    let body = response.text().await.unwrap();
+    let subscribers: Vec<serde_json::Value> = body
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(subscribers.len(), 2000);
+    assert_eq!(subscribers[0]["email"], "subscriber1@example.com");
+    assert_eq!(subscribers[0]["status"], "unsubscribed");
+    assert!(subscribers[0]["consent"].is_null());
+    assert_eq!(subscribers[1999]["email"], "subscriber2000@example.com");
+}
+
+#[actix_web::test]
+pub async fn invalid_export_filters_are_rejected() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    for query in ["format=xlsx", "status=gone", "from=yesterday"] {
+        let response = app.get_subscribers("subscribers/export", query).await;
+        assert_eq!(response.status().as_u16(), 400, "{}", query);
+    }
+    let html = app.get_audit_trail_html().await;
+    assert!(!html.contains("export_subscribers"));
+}
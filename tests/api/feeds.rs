@@ -0,0 +1,87 @@
+use crate::archive::publish_issue;
+use crate::helpers::spawn_app;
+
+#[actix_web::test]
+pub async fn the_rss_feed_lists_the_public_issues() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    let issue_id = publish_issue(&app, "Cats & <Dogs>").await;
+    let private_id = publish_issue(&app, "Private issue").await;
+    app.post_newsletter_visibility(private_id, false).await;
+
+    let response = reqwest::get(format!("{}/feed.rss", app.address))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/rss+xml; charset=utf-8"
+    );
+    let body = response.text().await.unwrap();
+    assert!(body.contains("<title>Cats &amp; &lt;Dogs&gt;</title>"));
+    assert!(body.contains(&format!(
+        r#"<guid isPermaLink="false">urn:uuid:{}</guid>"#,
+        issue_id
+    )));
+    assert!(body.contains("<description>&lt;p&gt;Hello reader&lt;/p&gt;</description>"));
+    assert!(body.contains("<pubDate>"));
+    assert!(!body.contains("Private issue"));
+}
+
+#[actix_web::test]
+pub async fn the_atom_feed_lists_the_public_issues() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    let issue_id = publish_issue(&app, "Cats & <Dogs>").await;
+
+    let response = reqwest::get(format!("{}/feed.atom", app.address))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/atom+xml; charset=utf-8"
+    );
+    let body = response.text().await.unwrap();
+    assert!(body.contains("<title>Cats &amp; &lt;Dogs&gt;</title>"));
+    assert!(body.contains(&format!("<id>urn:uuid:{}</id>", issue_id)));
+    assert!(body.contains(r#"<content type="html">&lt;p&gt;Hello reader&lt;/p&gt;</content>"#));
+}
+
+#[actix_web::test]
+pub async fn an_unchanged_feed_is_not_sent_again() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    publish_issue(&app, "First issue").await;
+    let client = reqwest::Client::new();
+    let url = format!("{}/feed.rss", app.address);
+
+    let response = client.get(&url).send().await.unwrap();
+    let etag = response.headers()["ETag"].clone();
+    let last_modified = response.headers()["Last-Modified"].clone();
+
+    let response = client
+        .get(&url)
+        .header("If-None-Match", etag.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 304);
+    let response = client
+        .get(&url)
+        .header("If-Modified-Since", last_modified)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 304);
+
+    publish_issue(&app, "Second issue").await;
+    let response = client
+        .get(&url)
+        .header("If-None-Match", etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains("Second issue"));
+}
@@ -1,15 +1,24 @@
 use argon2::Argon2;
 use argon2::PasswordHasher;
 use argon2::password_hash::{SaltString, rand_core::OsRng};
+use chrono::Utc;
 use once_cell::sync::Lazy;
 use reqwest::Response;
 use reqwest::Url;
 use reqwest::redirect;
+use secrecy::Secret;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use uuid::Uuid;
 use wiremock::MockServer;
-use zero2prod::configuration::{DatabaseSettings, get_configuration};
-use zero2prod::startup::{Application, get_connection_pool};
+use zero2prod::bot_protection::form_timestamp;
+use zero2prod::configuration::{DatabaseSettings, OidcSettings, Settings, get_configuration};
+use zero2prod::email_client::EmailClient;
+use zero2prod::import_worker::try_execute_import;
+use zero2prod::issue_delivery_worker::{ExecutionOutcome, try_execute_task};
+use zero2prod::issue_rendering::IssueRenderer;
+use zero2prod::issue_scheduler::try_start_due_issue;
+use zero2prod::rss_poller::try_poll_due_feed;
+use zero2prod::startup::{Application, ApplicationBaseUrl, HmacSecret, get_connection_pool};
 use zero2prod::telemetry::{get_subscriber, init_subscriber};
 
 static TRACING: Lazy<()> = Lazy::new(|| {
@@ -26,14 +35,31 @@ static TRACING: Lazy<()> = Lazy::new(|| {
 });
 
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+/// `configure` changes the configuration of the application before it starts.
+pub async fn spawn_app_with(configure: impl FnOnce(&mut Settings)) -> TestApp {
     Lazy::force(&TRACING);
 
     let email_server = MockServer::start().await;
+    let oidc_server = MockServer::start().await;
 
     let mut configuration = get_configuration().expect("Failed to get config");
     configuration.database.database_name = uuid::Uuid::new_v4().to_string();
     configuration.application.port = 0;
     configuration.email_client.base_url = email_server.uri();
+    configuration.oidc = Some(OidcSettings {
+        provider_name: "Test identity provider".to_string(),
+        client_id: "zero2prod".to_string(),
+        client_secret: Secret::new("client-secret".to_string()),
+        authorization_endpoint: format!("{}/authorize", oidc_server.uri()),
+        token_endpoint: format!("{}/token", oidc_server.uri()),
+        userinfo_endpoint: format!("{}/userinfo", oidc_server.uri()),
+        scopes: "openid email".to_string(),
+        timeout_millisecond: 2000,
+    });
+    configure(&mut configuration);
     let application = Application::build(configuration.clone())
         .await
         .expect("Failed to build app");
@@ -52,9 +78,19 @@ pub async fn spawn_app() -> TestApp {
         address,
         db_pool: get_connection_pool(&configuration.database),
         email_server,
+        oidc_server,
         port: port,
+        email_client: configuration.email_client.clone().client(),
+        issue_renderer: IssueRenderer::new(
+            configuration.application.base_url.clone(),
+            HmacSecret(configuration.application.hmac_secret.clone()),
+        ),
+        base_url: actix_web::web::Data::new(ApplicationBaseUrl(
+            configuration.application.base_url.clone(),
+        )),
         user,
         api_client,
+        hmac_secret: HmacSecret(configuration.application.hmac_secret.clone()),
     };
     app.user.store(&app.db_pool).await;
     app
@@ -135,9 +171,14 @@ pub struct TestApp {
     pub address: String,
     pub db_pool: PgPool,
     pub email_server: MockServer,
+    pub oidc_server: MockServer,
     pub port: u16,
     pub user: TestUser,
     pub api_client: reqwest::Client,
+    pub email_client: EmailClient,
+    pub issue_renderer: IssueRenderer,
+    pub base_url: actix_web::web::Data<ApplicationBaseUrl>,
+    pub hmac_secret: HmacSecret,
 }
 
 impl TestApp {
@@ -195,7 +236,27 @@ impl TestApp {
             .expect("Could not send request")
     }
 
-    pub async fn post_subscription(&self, body: String) -> reqwest::Response {
+    /// The signed timestamp of a subscribe form shown a minute ago.
+    pub fn form_timestamp(&self) -> [(&'static str, String); 2] {
+        let (rendered_at, signature) =
+            form_timestamp(&self.hmac_secret, Utc::now() - chrono::Duration::minutes(1));
+        [
+            ("form_rendered_at", rendered_at.to_string()),
+            ("form_signature", signature),
+        ]
+    }
+
+    /// Sent as the subscribe form of the home page, with its signed timestamp
+    /// unless `body` has one.
+    pub async fn post_subscription(&self, mut body: String) -> reqwest::Response {
+        if !body.contains("form_rendered_at=") {
+            for (name, value) in self.form_timestamp() {
+                if !body.is_empty() {
+                    body.push('&');
+                }
+                body.push_str(&format!("{}={}", name, value));
+            }
+        }
         reqwest::Client::new()
             .post(format!("{}/subscription", &self.address))
             .body(body)
@@ -224,6 +285,685 @@ impl TestApp {
         ConfirmationLinks { plain_text, html }
     }
 
+    pub async fn get_newsletter_form(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/newsletters/new", &self.address))
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    pub async fn get_newsletter_form_html(&self) -> String {
+        self.get_newsletter_form()
+            .await
+            .text()
+            .await
+            .expect("Could not read the html content")
+    }
+
+    pub async fn post_create_newsletter<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/newsletters", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    /// Create a draft and return its id, taken from the redirection.
+    pub async fn create_newsletter_draft<Body>(&self, body: &Body) -> Uuid
+    where
+        Body: serde::Serialize,
+    {
+        let response = self.post_create_newsletter(body).await;
+        assert_eq!(response.status().as_u16(), 303);
+        let location = response
+            .headers()
+            .get("Location")
+            .unwrap()
+            .to_str()
+            .unwrap();
+        location
+            .strip_prefix("/admin/newsletters/")
+            .expect("Unexpected redirection")
+            .parse()
+            .expect("The redirection does not contain an issue id")
+    }
+
+    pub async fn get_newsletter_issues_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/newsletters", &self.address))
+            .send()
+            .await
+            .expect("Could not send the request")
+            .text()
+            .await
+            .expect("Could not read the html content")
+    }
+
+    pub async fn get_newsletter_issue(&self, issue_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/newsletters/{}", &self.address, issue_id))
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    pub async fn get_newsletter_issue_html(&self, issue_id: Uuid) -> String {
+        self.get_newsletter_issue(issue_id)
+            .await
+            .text()
+            .await
+            .expect("Could not read the html content")
+    }
+
+    pub async fn get_newsletter_issue_preview_html(&self, issue_id: Uuid) -> String {
+        self.api_client
+            .get(format!(
+                "{}/admin/newsletters/{}/preview",
+                &self.address, issue_id
+            ))
+            .send()
+            .await
+            .expect("Could not send the request")
+            .text()
+            .await
+            .expect("Could not read the html content")
+    }
+
+    pub async fn post_update_newsletter<Body>(
+        &self,
+        issue_id: Uuid,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/newsletters/{}", &self.address, issue_id))
+            .form(body)
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    /// `action` is one of `publish`, `cancel` or `delete`.
+    pub async fn post_newsletter_action(&self, issue_id: Uuid, action: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletters/{}/{}",
+                &self.address, issue_id, action
+            ))
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    pub async fn post_newsletter_visibility(
+        &self,
+        issue_id: Uuid,
+        is_public: bool,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletters/{}/visibility",
+                &self.address, issue_id
+            ))
+            .form(&serde_json::json!({ "is_public": is_public }))
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    pub async fn get_archive_html(&self, page: u32) -> String {
+        reqwest::Client::new()
+            .get(format!("{}/archive?page={}", &self.address, page))
+            .send()
+            .await
+            .expect("Could not send the request")
+            .text()
+            .await
+            .expect("Could not read the html content")
+    }
+
+    pub async fn get_archive_issue(&self, slug: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .get(format!("{}/archive/{}", &self.address, slug))
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    pub async fn get_issue_slug(&self, issue_id: Uuid) -> String {
+        sqlx::query!(
+            "SELECT slug FROM newsletter_issues WHERE newsletter_issue_id = $1",
+            issue_id
+        )
+        .fetch_one(&self.db_pool)
+        .await
+        .expect("Failed to fetch the slug of the issue")
+        .slug
+    }
+
+    pub async fn post_schedule_newsletter<Body>(
+        &self,
+        issue_id: Uuid,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletters/{}/schedule",
+                &self.address, issue_id
+            ))
+            .form(body)
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    pub async fn post_send_test_newsletter<Body>(
+        &self,
+        issue_id: Uuid,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletters/{}/test",
+                &self.address, issue_id
+            ))
+            .form(body)
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    /// Start the fan-out of every scheduled issue that is due.
+    pub async fn start_due_issues(&self) {
+        while let ExecutionOutcome::TaskCompleted =
+            try_start_due_issue(&self.db_pool).await.unwrap()
+        {}
+    }
+
+    /// Run the delivery tasks that are due until the queue is drained. The
+    /// worker of the application may hold some of them, so we also wait for
+    /// it to release them.
+    pub async fn dispatch_all_pending_emails(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue =
+                try_execute_task(&self.db_pool, &self.email_client, &self.issue_renderer)
+                    .await
+                    .unwrap()
+            {
+                break;
+            }
+        }
+        loop {
+            let pending = sqlx::query!(
+                r#"SELECT COUNT(*) AS "count!" FROM issue_delivery_queue WHERE execute_after <= now()"#
+            )
+            .fetch_one(&self.db_pool)
+            .await
+            .unwrap()
+            .count;
+            if pending == 0 {
+                break;
+            }
+            actix_web::rt::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    }
+
+    /// `mode` is `confirmed` or `send_confirmation`.
+    pub async fn post_import(
+        &self,
+        csv: &str,
+        mode: &str,
+        consent_attestation: &str,
+    ) -> reqwest::Response {
+        let file = reqwest::multipart::Part::text(csv.to_string())
+            .file_name("contacts.csv")
+            .mime_str("text/csv")
+            .unwrap();
+        let form = reqwest::multipart::Form::new()
+            .part("file", file)
+            .text("list", "newsletter")
+            .text("mode", mode.to_string())
+            .text("consent_attestation", consent_attestation.to_string());
+        self.api_client
+            .post(format!("{}/admin/subscribers/import", &self.address))
+            .multipart(form)
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    pub async fn get_import_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/subscribers/import", &self.address))
+            .send()
+            .await
+            .expect("Could not send the request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    /// Run the pending imports, waiting for the worker of the application to
+    /// finish those it holds.
+    pub async fn run_pending_imports(&self) {
+        while let ExecutionOutcome::TaskCompleted =
+            try_execute_import(&self.db_pool, &self.email_client, &self.base_url)
+                .await
+                .unwrap()
+        {}
+        loop {
+            let pending = sqlx::query!(
+                r#"SELECT COUNT(*) AS "count!" FROM subscriber_imports WHERE status = 'pending'"#
+            )
+            .fetch_one(&self.db_pool)
+            .await
+            .unwrap()
+            .count;
+            if pending == 0 {
+                break;
+            }
+            actix_web::rt::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    }
+
+    pub async fn get_lists_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/lists", &self.address))
+            .send()
+            .await
+            .expect("Could not send the request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_create_list<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/lists", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    /// Create a list and return its id.
+    pub async fn create_list(&self, name: &str, slug: &str) -> Uuid {
+        let response = self
+            .post_create_list(&serde_json::json!({ "name": name, "slug": slug }))
+            .await;
+        assert_eq!(response.status().as_u16(), 303);
+        sqlx::query!("SELECT list_id FROM lists WHERE slug = $1", slug)
+            .fetch_one(&self.db_pool)
+            .await
+            .unwrap()
+            .list_id
+    }
+
+    pub async fn get_segments_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/segments", &self.address))
+            .send()
+            .await
+            .expect("Could not send the request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_create_segment<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/segments", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    /// Create a segment and return its id.
+    pub async fn create_segment(&self, name: &str, definition: &str) -> Uuid {
+        let response = self
+            .post_create_segment(&serde_json::json!({ "name": name, "definition": definition }))
+            .await;
+        assert_eq!(response.status().as_u16(), 303);
+        sqlx::query!("SELECT segment_id FROM segments WHERE name = $1", name)
+            .fetch_one(&self.db_pool)
+            .await
+            .unwrap()
+            .segment_id
+    }
+
+    pub async fn get_tags_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/tags", &self.address))
+            .send()
+            .await
+            .expect("Could not send the request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_tags(&self, tag: &str, emails: &str, action: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/tags", &self.address))
+            .form(&serde_json::json!({ "tag": tag, "emails": emails, "action": action }))
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    /// `query` is the query string of the filters, already encoded.
+    pub async fn get_subscribers(&self, path: &str, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/{}?{}", &self.address, path, query))
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    pub async fn get_subscribers_json(&self, query: &str) -> serde_json::Value {
+        self.get_subscribers("api/subscribers", query)
+            .await
+            .error_for_status()
+            .unwrap()
+            .json()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_add_subscriber<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/subscribers", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    /// `action` is one of `edit`, `confirm`, `unsubscribe`, `resend` or `delete`.
+    pub async fn post_subscriber_action<Body>(
+        &self,
+        subscriber_id: &str,
+        action: &str,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!(
+                "{}/admin/subscribers/{}/{}",
+                &self.address, subscriber_id, action
+            ))
+            .form(body)
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    /// `request` is `data` or `erasure`.
+    pub async fn post_privacy_request(&self, request: &str, email: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/privacy/{}-request", &self.address, request))
+            .form(&[("email", email)])
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    pub async fn get_audit_trail_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/audit", &self.address))
+            .send()
+            .await
+            .expect("Could not send the request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_email_domains_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/email-domains", &self.address))
+            .send()
+            .await
+            .expect("Could not send the request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    /// `rule` is `block` or `allow`.
+    pub async fn post_email_domain_rule(&self, domain: &str, rule: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/email-domains", &self.address))
+            .form(&[("domain", domain), ("rule", rule)])
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    pub async fn get_attributes_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/attributes", &self.address))
+            .send()
+            .await
+            .expect("Could not send the request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_create_attribute<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/attributes", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    pub async fn get_rss_feeds_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/rss", &self.address))
+            .send()
+            .await
+            .expect("Could not send the request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_rss_feed<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/rss", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    /// Poll every registered feed, whatever its polling interval. The poller
+    /// of the application may hold some of them, so we also wait for it to
+    /// release them.
+    pub async fn poll_rss_feeds(&self) {
+        sqlx::query!("UPDATE rss_feeds SET next_poll_at = now()")
+            .execute(&self.db_pool)
+            .await
+            .unwrap();
+        let client = reqwest::Client::new();
+        while let ExecutionOutcome::TaskCompleted =
+            try_poll_due_feed(&self.db_pool, &client).await.unwrap()
+        {}
+        loop {
+            let due = sqlx::query!(
+                r#"SELECT COUNT(*) AS "count!" FROM rss_feeds WHERE next_poll_at <= now()"#
+            )
+            .fetch_one(&self.db_pool)
+            .await
+            .unwrap()
+            .count;
+            if due == 0 {
+                break;
+            }
+            actix_web::rt::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    }
+
+    pub async fn get_templates_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/templates", &self.address))
+            .send()
+            .await
+            .expect("Could not send the request")
+            .text()
+            .await
+            .expect("Could not read the html content")
+    }
+
+    pub async fn get_new_layout_form_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/templates/new", &self.address))
+            .send()
+            .await
+            .expect("Could not send the request")
+            .text()
+            .await
+            .expect("Could not read the html content")
+    }
+
+    pub async fn post_create_layout<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/templates", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    /// Create a layout and return its id, taken from the redirection.
+    pub async fn create_layout<Body>(&self, body: &Body) -> Uuid
+    where
+        Body: serde::Serialize,
+    {
+        let response = self.post_create_layout(body).await;
+        assert_eq!(response.status().as_u16(), 303);
+        let location = response
+            .headers()
+            .get("Location")
+            .unwrap()
+            .to_str()
+            .unwrap();
+        location
+            .strip_prefix("/admin/templates/")
+            .expect("Unexpected redirection")
+            .parse()
+            .expect("The redirection does not contain a layout id")
+    }
+
+    pub async fn get_layout_preview(&self, layout_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/templates/{}/preview",
+                &self.address, layout_id
+            ))
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    pub async fn post_system_email<Body>(&self, kind: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/templates/system/{}", &self.address, kind))
+            .form(body)
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    pub async fn get_system_email_html(&self, kind: &str) -> String {
+        self.api_client
+            .get(format!("{}/admin/templates/system/{}", &self.address, kind))
+            .send()
+            .await
+            .expect("Could not send the request")
+            .text()
+            .await
+            .expect("Could not read the html content")
+    }
+
+    pub async fn get_notification_settings_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/settings/notifications", &self.address))
+            .send()
+            .await
+            .expect("Could not send the request")
+            .text()
+            .await
+            .expect("Could not read the html content")
+    }
+
+    pub async fn post_notification_settings<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/settings/notifications", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Could not send the request")
+    }
+
+    pub fn get_text_links(&self, email_request: &wiremock::Request) -> Vec<reqwest::Url> {
+        let body: serde_json::Value = email_request
+            .body_json()
+            .expect("Failed to read the body of the mail request");
+        linkify::LinkFinder::new()
+            .links(body["TextBody"].as_str().unwrap())
+            .filter(|l| *l.kind() == linkify::LinkKind::Url)
+            .map(|l| {
+                let mut link = Url::parse(l.as_str()).expect("failed to parse the link");
+                link.set_port(Some(self.port)).unwrap();
+                link
+            })
+            .collect()
+    }
+
     pub async fn get_test_user(&self) -> (String, String) {
         (
             self.user.username.to_string(),
@@ -0,0 +1,165 @@
+use crate::helpers::{TestApp, asser_is_redirect_to, spawn_app};
+use crate::lists::subscribe_and_confirm;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+const CONTACTS: &str = "email,name,company\n\
+    ursula@example.com,Ursula Le Guin,Earthsea\n\
+    octavia@example.com,Octavia Butler,\n\
+    not-an-email,Iain Banks,\n\
+    octavia@example.com,Octavia E. Butler,\n\
+    existing@example.com,Already Here,\n";
+
+async fn membership_statuses(app: &TestApp) -> Vec<(String, String)> {
+    sqlx::query!(
+        r#"SELECT s.email, m.status
+        FROM subscriptions s
+        JOIN list_memberships m ON m.subscriber_id = s.id
+        ORDER BY s.email"#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|r| (r.email, r.status))
+    .collect()
+}
+
+async fn last_import_id(app: &TestApp) -> uuid::Uuid {
+    sqlx::query!("SELECT import_id FROM subscriber_imports ORDER BY created_at DESC LIMIT 1")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .import_id
+}
+
+#[actix_web::test]
+pub async fn unauthenticated_users_cannot_import_subscribers() {
+    let app = spawn_app().await;
+    let response = app.get_subscribers("subscribers/import", "").await;
+    asser_is_redirect_to(&response, "/login");
+    let response = app.post_import(CONTACTS, "send_confirmation", "").await;
+    asser_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+pub async fn importing_as_confirmed_needs_a_consent_attestation() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    let response = app.post_import(CONTACTS, "confirmed", " ").await;
+    asser_is_redirect_to(&response, "/admin/subscribers/import");
+    let html = app.get_import_html().await;
+    assert!(html.contains("Importing as confirmed needs a consent attestation."));
+    let imports = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscriber_imports"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(imports.count, 0);
+}
+
+#[actix_web::test]
+pub async fn rows_are_validated_deduplicated_and_reported() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    subscribe_and_confirm(&app, "existing@example.com", "").await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_import(CONTACTS, "confirmed", "They opted in on the old provider")
+        .await;
+    asser_is_redirect_to(&response, "/admin/subscribers/import");
+    app.run_pending_imports().await;
+
+    assert_eq!(
+        membership_statuses(&app).await,
+        [
+            ("existing@example.com".to_string(), "confirmed".to_string()),
+            ("octavia@example.com".to_string(), "confirmed".to_string()),
+            ("ursula@example.com".to_string(), "confirmed".to_string()),
+        ]
+    );
+    let import_id = last_import_id(&app).await;
+    let html = app.get_import_html().await;
+    assert!(html.contains(&format!(
+        r#"<td>contacts.csv</td><td>Newsletter</td><td>confirmed</td><td>done</td><td>5</td><td>2</td><td>2</td><td>1</td><td><a href="/admin/subscribers/import/{}/report">Download</a></td>"#,
+        import_id
+    )));
+
+    let response = app
+        .get_subscribers(&format!("subscribers/import/{}/report", import_id), "")
+        .await;
+    assert_eq!(
+        response.headers()["content-type"],
+        "text/csv; charset=utf-8"
+    );
+    assert!(
+        response.headers()["content-disposition"]
+            .to_str()
+            .unwrap()
+            .contains("contacts-report.csv")
+    );
+    let report = response.text().await.unwrap();
+    assert_eq!(
+        report,
+        "line,email,error\n\
+        4,not-an-email,not-an-email is not a valid subscriber email.\n\
+        5,octavia@example.com,Duplicate of line 3.\n\
+        6,existing@example.com,Already a subscriber.\n"
+    );
+
+    let html = app.get_audit_trail_html().await;
+    assert!(html.contains(
+        "<td>import_subscribers</td><td>-</td><td>Import of contacts.csv to Newsletter as confirmed, consent: They opted in on the old provider</td>"
+    ));
+}
+
+#[actix_web::test]
+pub async fn imported_subscribers_can_be_sent_the_confirmation_email() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_import(CONTACTS, "send_confirmation", "").await;
+    app.run_pending_imports().await;
+    // The worker of the application sends the emails after its commit.
+    for _ in 0..100 {
+        if app.email_server.received_requests().await.unwrap().len() == 3 {
+            break;
+        }
+        actix_web::rt::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+
+    assert_eq!(
+        membership_statuses(&app).await,
+        [
+            (
+                "existing@example.com".to_string(),
+                "pending_confirmation".to_string()
+            ),
+            (
+                "octavia@example.com".to_string(),
+                "pending_confirmation".to_string()
+            ),
+            (
+                "ursula@example.com".to_string(),
+                "pending_confirmation".to_string()
+            ),
+        ]
+    );
+    let requests = app.email_server.received_requests().await.unwrap();
+    let links = app.get_confirmation_links(&requests[0]);
+    reqwest::get(links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
@@ -0,0 +1,218 @@
+use crate::helpers::{TestApp, asser_is_redirect_to, spawn_app};
+use crate::newsletter::create_confirmed_user;
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+/// Subscribe to a list and click the link of the confirmation email.
+pub async fn subscribe_and_confirm(app: &TestApp, email: &str, list: &str) {
+    let _guard = Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let body = format!(
+        "name=le%20guin&email={}&list={}",
+        email.replace('@', "%40"),
+        list
+    );
+    app.post_subscription(body)
+        .await
+        .error_for_status()
+        .unwrap();
+    let requests = app.email_server.received_requests().await.unwrap();
+    let links = app.get_confirmation_links(requests.last().unwrap());
+    reqwest::get(links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+async fn membership_status(app: &TestApp, list_id: Uuid) -> Option<String> {
+    sqlx::query!(
+        "SELECT status FROM list_memberships WHERE list_id = $1",
+        list_id
+    )
+    .fetch_optional(&app.db_pool)
+    .await
+    .unwrap()
+    .map(|r| r.status)
+}
+
+#[actix_web::test]
+pub async fn unauthenticated_users_cannot_create_lists() {
+    let app = spawn_app().await;
+    let response = app
+        .post_create_list(&serde_json::json!({ "name": "Weekly", "slug": "weekly" }))
+        .await;
+    asser_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+pub async fn a_new_list_can_be_chosen_in_the_subscribe_form() {
+    let app = spawn_app().await;
+    let home = reqwest::get(&app.address)
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(!home.contains(r#"<select name="list">"#));
+
+    app.user.connect(&app).await;
+    let html = app.get_admindashboard_html().await;
+    assert!(html.contains(r#"<a href="/admin/lists">"#));
+    app.create_list("Weekly digest", "weekly").await;
+
+    let html = app.get_lists_html().await;
+    assert!(html.contains("<p><i>The list has been created.</i></p>"));
+    assert!(html.contains("Newsletter (default)"));
+    assert!(html.contains("Weekly digest"));
+    let home = reqwest::get(&app.address)
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(home.contains(r#"<option value="weekly">Weekly digest</option>"#));
+}
+
+#[actix_web::test]
+pub async fn invalid_lists_are_rejected() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    app.create_list("Weekly digest", "weekly").await;
+
+    let test_cases = [
+        (
+            serde_json::json!({ "name": "", "slug": "other" }),
+            "The name of the list is missing.",
+        ),
+        (
+            serde_json::json!({ "name": "Other", "slug": "Not a slug" }),
+            "The slug can only contain lowercase letters, digits and dashes.",
+        ),
+        (
+            serde_json::json!({ "name": "Other", "slug": "weekly" }),
+            "A list with this name or slug already exists.",
+        ),
+    ];
+    for (body, message) in test_cases {
+        let response = app.post_create_list(&body).await;
+        asser_is_redirect_to(&response, "/admin/lists");
+        let html = app.get_lists_html().await;
+        assert!(html.contains(message), "Missing the message: {}", message);
+    }
+}
+
+#[actix_web::test]
+pub async fn subscribing_to_an_unknown_list_is_rejected() {
+    let app = spawn_app().await;
+    let response = app
+        .post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com&list=unknown".into())
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+pub async fn each_list_is_confirmed_on_its_own() {
+    let app = spawn_app().await;
+    create_confirmed_user(&app).await;
+    app.user.connect(&app).await;
+    let list_id = app.create_list("Weekly digest", "weekly").await;
+
+    let _guard = Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com&list=weekly".into())
+        .await
+        .error_for_status()
+        .unwrap();
+    assert_eq!(
+        membership_status(&app, list_id).await.as_deref(),
+        Some("pending_confirmation")
+    );
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let links = app.get_confirmation_links(requests.last().unwrap());
+    reqwest::get(links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert_eq!(
+        membership_status(&app, list_id).await.as_deref(),
+        Some("confirmed")
+    );
+    let subscribers = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(subscribers, 1);
+}
+
+#[actix_web::test]
+pub async fn subscribing_again_to_a_confirmed_list_sends_no_email() {
+    let app = spawn_app().await;
+    create_confirmed_user(&app).await;
+
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[actix_web::test]
+pub async fn an_issue_is_sent_once_to_the_members_of_its_lists() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    let weekly_id = app.create_list("Weekly digest", "weekly").await;
+    let monthly_id = app.create_list("Monthly digest", "monthly").await;
+    subscribe_and_confirm(&app, "default@example.com", "").await;
+    subscribe_and_confirm(&app, "weekly@example.com", "weekly").await;
+    subscribe_and_confirm(&app, "both@example.com", "weekly").await;
+    subscribe_and_confirm(&app, "both@example.com", "monthly").await;
+
+    let (weekly_id, monthly_id) = (weekly_id.to_string(), monthly_id.to_string());
+    let issue_id = app
+        .create_newsletter_draft(&[
+            ("title", "Digest"),
+            ("html", "<p>Digest</p>"),
+            ("text", "Digest"),
+            ("list_ids", weekly_id.as_str()),
+            ("list_ids", monthly_id.as_str()),
+        ])
+        .await;
+    let html = app.get_newsletter_issue_html(issue_id).await;
+    assert!(html.contains("<p>Lists: Monthly digest, Weekly digest</p>"));
+    assert!(html.contains("This issue will be sent to 2 confirmed subscribers."));
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter_action(issue_id, "publish").await;
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let mut recipients: Vec<String> = requests[requests.len() - 2..]
+        .iter()
+        .map(|request| {
+            let body: serde_json::Value = request.body_json().unwrap();
+            body["To"].as_str().unwrap().to_string()
+        })
+        .collect();
+    recipients.sort();
+    assert_eq!(recipients, ["both@example.com", "weekly@example.com"]);
+}
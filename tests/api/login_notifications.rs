@@ -0,0 +1,254 @@
+use crate::helpers::{TestApp, asser_is_redirect_to, spawn_app};
+use reqwest::redirect;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn enable_login_notifications(app: &TestApp) {
+    enable_login_notifications_unverified(app).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let links = app.get_text_links(email_request);
+    app.user.connect(app).await;
+    let response = app.api_client.get(links[0].clone()).send().await.unwrap();
+    asser_is_redirect_to(&response, "/admin/settings/notifications");
+    app.user.logout(app).await;
+    app.email_server.reset().await;
+}
+
+/// Leaves the verification email in the mock server.
+async fn enable_login_notifications_unverified(app: &TestApp) {
+    app.user.connect(app).await;
+    // The email verification.
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let body = serde_json::json!({"email": "admin@example.com", "login_notifications": "on"});
+    let response = app.post_notification_settings(&body).await;
+    asser_is_redirect_to(&response, "/admin/settings/notifications");
+    app.user.logout(app).await;
+    app.email_server.verify().await;
+}
+
+async fn login_from_another_device(app: &TestApp, password: &str) -> reqwest::Response {
+    reqwest::ClientBuilder::new()
+        .cookie_store(true)
+        .redirect(redirect::Policy::none())
+        .user_agent("another browser")
+        .build()
+        .unwrap()
+        .post(format!("{}/login", &app.address))
+        .form(&serde_json::json!({"username": &app.user.username, "password": password}))
+        .send()
+        .await
+        .expect("Could not send request")
+}
+
+#[actix_web::test]
+pub async fn login_from_a_new_device_sends_a_notification() {
+    let app = spawn_app().await;
+    enable_login_notifications(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = login_from_another_device(&app, &app.user.password).await;
+    asser_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[actix_web::test]
+pub async fn login_from_a_known_device_does_not_send_a_notification() {
+    let app = spawn_app().await;
+    enable_login_notifications(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.user.connect(&app).await;
+    asser_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[actix_web::test]
+pub async fn no_notification_is_sent_when_disabled() {
+    let app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = login_from_another_device(&app, &app.user.password).await;
+    asser_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[actix_web::test]
+pub async fn no_notification_is_sent_to_an_unverified_email() {
+    let app = spawn_app().await;
+    enable_login_notifications_unverified(&app).await;
+    app.email_server.reset().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    for _ in 0..3 {
+        let response = login_from_another_device(&app, "wrong password").await;
+        asser_is_redirect_to(&response, "/login");
+    }
+    let response = login_from_another_device(&app, &app.user.password).await;
+    asser_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[actix_web::test]
+pub async fn repeated_failed_logins_send_a_notification() {
+    let app = spawn_app().await;
+    enable_login_notifications(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    for _ in 0..3 {
+        let response = login_from_another_device(&app, "wrong password").await;
+        asser_is_redirect_to(&response, "/login");
+    }
+}
+
+#[actix_web::test]
+pub async fn the_revoke_link_logs_out_every_session() {
+    let app = spawn_app().await;
+    enable_login_notifications(&app).await;
+    app.user.connect(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    login_from_another_device(&app, &app.user.password).await;
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let links = app.get_text_links(email_request);
+    assert_eq!(links.len(), 1);
+    // Following the link only asks for a confirmation.
+    let html = reqwest::get(links[0].clone())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains("Revoke all my sessions"));
+    let response = app.get_admindashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/sessions/revoke", app.address))
+        .form(&links[0].query_pairs().collect::<Vec<_>>())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.get_admindashboard().await;
+    asser_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+pub async fn a_tampered_revoke_link_is_rejected() {
+    let app = spawn_app().await;
+    let response = reqwest::get(format!(
+        "{}/sessions/revoke?user_id={}&expires=9999999999&signature=00",
+        &app.address, &app.user.user_id
+    ))
+    .await
+    .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/sessions/revoke", &app.address))
+        .form(&serde_json::json!({
+            "user_id": &app.user.user_id,
+            "expires": 9999999999i64,
+            "signature": "00",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+pub async fn a_new_email_is_verified_with_the_emailed_link() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let body = serde_json::json!({"email": "admin@example.com"});
+    app.post_notification_settings(&body).await;
+    let html = app.get_notification_settings_html().await;
+    assert!(html.contains("Your email is not verified yet"));
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let links = app.get_text_links(email_request);
+    assert_eq!(links.len(), 1);
+    let response = app.api_client.get(links[0].clone()).send().await.unwrap();
+    asser_is_redirect_to(&response, "/admin/settings/notifications");
+    let html = app.get_notification_settings_html().await;
+    assert!(html.contains("Your email is verified."));
+
+    // Saving the same address again keeps it verified.
+    let body = serde_json::json!({"email": "Admin@example.com"});
+    app.post_notification_settings(&body).await;
+    let html = app.get_notification_settings_html().await;
+    assert!(html.contains("Your email is verified."));
+}
+
+#[actix_web::test]
+pub async fn an_email_used_by_another_user_is_rejected() {
+    let app = spawn_app().await;
+    sqlx::query!(
+        "INSERT INTO users (user_id, username, password_hash, email) VALUES ($1, 'other', '', $2)",
+        uuid::Uuid::new_v4(),
+        "other@example.com"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.user.connect(&app).await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let body = serde_json::json!({"email": "Other@example.com"});
+    let response = app.post_notification_settings(&body).await;
+    asser_is_redirect_to(&response, "/admin/settings/notifications");
+    let html = app.get_notification_settings_html().await;
+    assert!(html.contains("This email is used by another user."));
+}
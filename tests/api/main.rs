@@ -1,8 +1,28 @@
+mod admin_newsletters;
+mod admin_templates;
 mod amdin_dashboard;
+mod archive;
+mod attributes;
+mod bot_protection;
 mod change_password;
+mod email_domains;
+mod exports;
+mod feeds;
 mod health_check;
 mod helpers;
+mod imports;
+mod lists;
 mod login;
+mod login_notifications;
 mod newsletter;
+mod oidc_login;
+mod password_reset;
+mod privacy;
+mod rss_feeds;
+mod scheduled_newsletters;
+mod segments;
+mod send_test_newsletter;
+mod subscribers;
 mod subscription;
 mod subscriptions_confirm;
+mod unsubscribe;
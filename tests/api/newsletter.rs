@@ -32,6 +32,7 @@ pub async fn should_not_send_mail_to_non_confirmed_user() {
         .expect("Failed to execute request.");
 
     assert_eq!(response.status().as_u16(), 200);
+    app.dispatch_all_pending_emails().await;
 }
 
 #[actix_web::test]
@@ -62,6 +63,7 @@ pub async fn send_email_to_confirmed_user() {
         .expect("Failed to execute request.");
 
     assert_eq!(response.status().as_u16(), 200);
+    app.dispatch_all_pending_emails().await;
 }
 
 pub async fn create_unconfirmed_user(app: &TestApp) -> ConfirmationLinks {
@@ -213,3 +215,110 @@ pub async fn should_refuse_invalid_password() {
         .expect("Could not send request");
     assert_eq!(401, request.status().as_u16());
 }
+
+#[actix_web::test]
+pub async fn an_unknown_merge_tag_is_rejected_with_a_400() {
+    let app = spawn_app().await;
+    let (username, password) = app.get_test_user().await;
+
+    let newsletter_request_body = serde_json::json!({
+    "title": "Newsletter title",
+    "content": {
+    "text": "Hello {{ surname }}",
+    "html": "<p>Hello {{ name }}</p>",
+    }
+    });
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletter", &app.address))
+        .basic_auth(&username, Some(&password))
+        .json(&newsletter_request_body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+pub async fn merge_tags_are_rendered_for_each_subscriber() {
+    let app = spawn_app().await;
+    let (username, password) = app.get_test_user().await;
+    create_confirmed_user(&app).await;
+
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+    "title": "Newsletter title",
+    "content": {
+    "text": "Hello {{ name | default: \"friend\" }} <{{ email }}>",
+    "html": "<p>Hello {{ name }}</p><a href=\"{{ unsubscribe_url }}\">Leave</a>",
+    }
+    });
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletter", &app.address))
+        .basic_auth(&username, Some(&password))
+        .json(&newsletter_request_body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = requests.last().unwrap().body_json().unwrap();
+    assert!(
+        body["TextBody"]
+            .as_str()
+            .unwrap()
+            .starts_with("Hello le guin <ursula_le_guin@gmail.com>")
+    );
+    let html = body["HtmlBody"].as_str().unwrap();
+    assert!(html.starts_with("<p>Hello le guin</p><a href=\"http"));
+    assert!(!html.contains("{{"));
+}
+
+#[actix_web::test]
+pub async fn a_markdown_issue_is_sent_as_html_and_plain_text() {
+    let app = spawn_app().await;
+    let (username, password) = app.get_test_user().await;
+    create_confirmed_user(&app).await;
+
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+    "title": "Newsletter title",
+    "content": {
+    "markdown": "# Hello {{ name }}\n\nRead [the post](https://example.com).<script>alert(1)</script>",
+    }
+    });
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletter", &app.address))
+        .basic_auth(&username, Some(&password))
+        .json(&newsletter_request_body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = requests.last().unwrap().body_json().unwrap();
+    let html = body["HtmlBody"].as_str().unwrap();
+    assert!(html.starts_with("<div style="));
+    assert!(html.contains("<h1>Hello le guin</h1>"));
+    assert!(!html.contains("<script>"));
+    assert!(
+        body["TextBody"]
+            .as_str()
+            .unwrap()
+            .starts_with("Hello le guin\n\nRead the post (https://example.com).")
+    );
+}
@@ -0,0 +1,205 @@
+use crate::helpers::{TestApp, asser_is_redirect_to, spawn_app, spawn_app_with};
+use reqwest::Url;
+use wiremock::matchers::{body_string_contains, header, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn start_oidc_login(app: &TestApp) -> Url {
+    let response = app
+        .api_client
+        .get(format!("{}/login/oidc", &app.address))
+        .send()
+        .await
+        .expect("Could not send the request");
+    assert_eq!(response.status().as_u16(), 303);
+    Url::parse(response.headers()["Location"].to_str().unwrap()).unwrap()
+}
+
+fn query_parameter(url: &Url, name: &str) -> String {
+    url.query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.to_string())
+        .unwrap_or_else(|| panic!("{} is missing from the url", name))
+}
+
+async fn mount_identity_provider(app: &TestApp, userinfo: serde_json::Value) {
+    Mock::given(path("/token"))
+        .and(method("POST"))
+        .and(body_string_contains("code=authorization-code"))
+        .and(body_string_contains("code_verifier="))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "access_token": "access-token",
+            "token_type": "Bearer"
+        })))
+        .mount(&app.oidc_server)
+        .await;
+    Mock::given(path("/userinfo"))
+        .and(method("GET"))
+        .and(header("Authorization", "Bearer access-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(userinfo))
+        .mount(&app.oidc_server)
+        .await;
+}
+
+async fn callback(app: &TestApp, state: &str) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}/login/oidc/callback", &app.address))
+        .query(&[("code", "authorization-code"), ("state", state)])
+        .send()
+        .await
+        .expect("Could not send the request")
+}
+
+async fn set_user_email(app: &TestApp, email: &str) {
+    sqlx::query!(
+        "UPDATE users SET email = $1, email_verified_at = now() WHERE user_id = $2",
+        email,
+        app.user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[actix_web::test]
+pub async fn the_login_page_links_to_the_identity_provider() {
+    let app = spawn_app().await;
+    let html = app.get_login_html().await;
+    assert!(html.contains(r#"<a href="/login/oidc">Login with Test identity provider</a>"#));
+}
+
+#[actix_web::test]
+pub async fn oidc_login_redirects_to_the_identity_provider_with_pkce() {
+    let app = spawn_app().await;
+    let url = start_oidc_login(&app).await;
+
+    assert_eq!(url.path(), "/authorize");
+    assert_eq!(query_parameter(&url, "response_type"), "code");
+    assert_eq!(query_parameter(&url, "client_id"), "zero2prod");
+    assert_eq!(query_parameter(&url, "code_challenge_method"), "S256");
+    assert!(!query_parameter(&url, "code_challenge").is_empty());
+    assert!(!query_parameter(&url, "state").is_empty());
+}
+
+#[actix_web::test]
+pub async fn a_verified_email_logs_in_the_matching_user() {
+    let app = spawn_app().await;
+    set_user_email(&app, "admin@example.com").await;
+    mount_identity_provider(
+        &app,
+        serde_json::json!({"sub": "idp-subject", "email": "Admin@example.com", "email_verified": true}),
+    )
+    .await;
+
+    let url = start_oidc_login(&app).await;
+    let response = callback(&app, &query_parameter(&url, "state")).await;
+
+    asser_is_redirect_to(&response, "/admin/dashboard");
+    let html_page = app.get_admindashboard_html().await;
+    assert!(html_page.contains(&format!("Welcome {}", &app.user.username)));
+}
+
+#[actix_web::test]
+pub async fn the_subject_is_remembered_after_the_first_login() {
+    let app = spawn_app().await;
+    set_user_email(&app, "admin@example.com").await;
+    mount_identity_provider(
+        &app,
+        serde_json::json!({"sub": "idp-subject", "email": "admin@example.com", "email_verified": true}),
+    )
+    .await;
+
+    let url = start_oidc_login(&app).await;
+    callback(&app, &query_parameter(&url, "state")).await;
+
+    let saved = sqlx::query!(
+        "SELECT oidc_subject FROM users WHERE user_id = $1",
+        app.user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.oidc_subject.as_deref(), Some("idp-subject"));
+}
+
+#[actix_web::test]
+pub async fn an_unverified_email_is_rejected() {
+    let app = spawn_app().await;
+    set_user_email(&app, "admin@example.com").await;
+    mount_identity_provider(
+        &app,
+        serde_json::json!({"sub": "idp-subject", "email": "admin@example.com", "email_verified": false}),
+    )
+    .await;
+
+    let url = start_oidc_login(&app).await;
+    let response = callback(&app, &query_parameter(&url, "state")).await;
+
+    asser_is_redirect_to(&response, "/login");
+    let html = app.get_login_html().await;
+    assert!(html.contains(r#"<p><i>Authentication failed</i></p>"#));
+}
+
+#[actix_web::test]
+pub async fn an_email_the_user_did_not_verify_is_not_matched() {
+    let app = spawn_app().await;
+    set_user_email(&app, "admin@example.com").await;
+    sqlx::query!(
+        "UPDATE users SET email_verified_at = NULL WHERE user_id = $1",
+        app.user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    mount_identity_provider(
+        &app,
+        serde_json::json!({"sub": "idp-subject", "email": "admin@example.com", "email_verified": true}),
+    )
+    .await;
+
+    let url = start_oidc_login(&app).await;
+    let response = callback(&app, &query_parameter(&url, "state")).await;
+
+    asser_is_redirect_to(&response, "/login");
+    let saved = sqlx::query!(
+        "SELECT oidc_subject FROM users WHERE user_id = $1",
+        app.user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.oidc_subject, None);
+}
+
+#[actix_web::test]
+pub async fn the_oidc_paths_are_not_found_without_an_identity_provider() {
+    let app = spawn_app_with(|configuration| configuration.oidc = None).await;
+
+    for path in ["/login/oidc", "/login/oidc/callback"] {
+        let response = app
+            .api_client
+            .get(format!("{}{}", &app.address, path))
+            .send()
+            .await
+            .expect("Could not send the request");
+        assert_eq!(response.status().as_u16(), 404);
+    }
+    assert!(!app.get_login_html().await.contains("/login/oidc"));
+}
+
+#[actix_web::test]
+pub async fn a_wrong_state_is_rejected() {
+    let app = spawn_app().await;
+    set_user_email(&app, "admin@example.com").await;
+    mount_identity_provider(
+        &app,
+        serde_json::json!({"sub": "idp-subject", "email": "admin@example.com", "email_verified": true}),
+    )
+    .await;
+
+    start_oidc_login(&app).await;
+    let response = callback(&app, "forged-state").await;
+
+    asser_is_redirect_to(&response, "/login");
+    let response = app.get_admindashboard().await;
+    asser_is_redirect_to(&response, "/login");
+}
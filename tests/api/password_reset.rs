@@ -0,0 +1,107 @@
+use crate::helpers::{TestApp, asser_is_redirect_to, spawn_app};
+use reqwest::Url;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn set_user_email(app: &TestApp, verified: bool) {
+    sqlx::query!(
+        "UPDATE users SET email = 'admin@example.com',
+            email_verified_at = CASE WHEN $1 THEN now() END
+        WHERE user_id = $2",
+        verified,
+        app.user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+async fn request_reset(app: &TestApp) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/password-reset", app.address))
+        .form(&serde_json::json!({"username": &app.user.username}))
+        .send()
+        .await
+        .expect("Could not send the request")
+}
+
+async fn post_new_password(app: &TestApp, link: &Url, password: &str, confirm: &str) -> String {
+    let mut form: Vec<(String, String)> = link.query_pairs().into_owned().collect();
+    form.push(("password".into(), password.into()));
+    form.push(("confirmpassword".into(), confirm.into()));
+    reqwest::Client::new()
+        .post(format!("{}/password-reset/confirm", app.address))
+        .form(&form)
+        .send()
+        .await
+        .expect("Could not send the request")
+        .text()
+        .await
+        .unwrap()
+}
+
+#[actix_web::test]
+pub async fn the_password_is_reset_with_the_emailed_link() {
+    let app = spawn_app().await;
+    set_user_email(&app, true).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = request_reset(&app).await;
+    assert!(response.text().await.unwrap().contains("Check your inbox"));
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let links = app.get_text_links(email_request);
+    assert_eq!(links.len(), 1);
+    let html = reqwest::get(links[0].clone())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains("Reset my password"));
+
+    let html = post_new_password(&app, &links[0], "new password", "another password").await;
+    assert!(html.contains("You entered two different new passwords"));
+    let html = post_new_password(&app, &links[0], "new password", "new password").await;
+    assert!(html.contains("Your password has been reset"));
+
+    let response = app
+        .post_logic(&serde_json::json!({
+            "username": &app.user.username,
+            "password": "new password"
+        }))
+        .await;
+    asser_is_redirect_to(&response, "/admin/dashboard");
+    // The link only works once.
+    let response = reqwest::get(links[0].clone()).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+pub async fn no_link_is_sent_to_an_unverified_email() {
+    let app = spawn_app().await;
+    set_user_email(&app, false).await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = request_reset(&app).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[actix_web::test]
+pub async fn the_password_reset_email_can_be_edited() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+
+    let html = app.get_system_email_html("password_reset").await;
+    assert!(html.contains("{{ reset_link }}"));
+}
@@ -0,0 +1,227 @@
+use crate::helpers::{TestApp, asser_is_redirect_to, spawn_app};
+use crate::lists::subscribe_and_confirm;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+/// Ask for a link and return it from the email.
+async fn request_link(app: &TestApp, request: &str, email: &str) -> reqwest::Url {
+    let _guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let response = app.post_privacy_request(request, email).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let requests = app.email_server.received_requests().await.unwrap();
+    // The link of the HTML body has its `&` escaped.
+    app.get_confirmation_links(requests.last().unwrap())
+        .plain_text
+}
+
+async fn count(app: &TestApp, query: &str) -> i64 {
+    sqlx::query_scalar::<_, i64>(query)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+#[actix_web::test]
+pub async fn an_unknown_address_gets_the_same_page_and_no_email() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    for request in ["data", "erasure"] {
+        let response = app
+            .post_privacy_request(request, "nobody@example.com")
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+        let html = response.text().await.unwrap();
+        assert!(html.contains("If we hold data about this address"));
+    }
+}
+
+#[actix_web::test]
+pub async fn a_subscriber_can_download_their_data_with_the_signed_link() {
+    let app = spawn_app().await;
+    subscribe_and_confirm(&app, "ursula@example.com", "").await;
+
+    let link = request_link(&app, "data", "ursula@example.com").await;
+    let response = reqwest::get(link.clone()).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(
+        response.headers()["content-disposition"]
+            .to_str()
+            .unwrap()
+            .contains("my-data.json")
+    );
+    let data: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(data["email"], "ursula@example.com");
+    assert_eq!(data["lists"][0]["status"], "confirmed");
+    assert_eq!(data["tokens"].as_array().unwrap().len(), 1);
+
+    // The link is only valid for the address it was sent to.
+    let mut other = link.clone();
+    let query: Vec<(String, String)> = link
+        .query_pairs()
+        .map(|(key, value)| {
+            let value = if key == "email" {
+                "octavia@example.com".to_string()
+            } else {
+                value.to_string()
+            };
+            (key.to_string(), value)
+        })
+        .collect();
+    other.query_pairs_mut().clear().extend_pairs(query);
+    let response = reqwest::get(other).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+pub async fn an_erased_subscriber_is_removed_and_cannot_be_added_back() {
+    let app = spawn_app().await;
+    subscribe_and_confirm(&app, "ursula@example.com", "").await;
+
+    let link = request_link(&app, "erasure", "ursula@example.com").await;
+    // Following the link only asks for a confirmation.
+    let html = reqwest::get(link.clone())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains("Erase my data"));
+    assert_eq!(count(&app, "SELECT COUNT(*) FROM subscriptions").await, 1);
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/privacy/erasure", app.address))
+        .form(&link.query_pairs().collect::<Vec<_>>())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(count(&app, "SELECT COUNT(*) FROM subscriptions").await, 0);
+    assert_eq!(
+        count(&app, "SELECT COUNT(*) FROM subscriptions_tokens").await,
+        0
+    );
+    assert_eq!(
+        count(
+            &app,
+            "SELECT COUNT(*) FROM erased_subscribers WHERE email_hash NOT LIKE '%ursula%'"
+        )
+        .await,
+        1
+    );
+
+    // Neither an admin nor an import can add them back.
+    app.user.connect(&app).await;
+    let response = app
+        .post_add_subscriber(&serde_json::json!({
+            "email": "ursula@example.com",
+            "name": "Ursula",
+            "list": "newsletter",
+            "skip_confirmation": "true",
+            "reason": "Signed up at the booth",
+        }))
+        .await;
+    asser_is_redirect_to(&response, "/admin/subscribers");
+    let html = app
+        .get_subscribers("subscribers", "")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains("This person asked for their data to be erased."));
+
+    app.post_import(
+        "email,name\nursula@example.com,Ursula\n",
+        "confirmed",
+        "They opted in on the old provider",
+    )
+    .await;
+    app.run_pending_imports().await;
+    assert_eq!(count(&app, "SELECT COUNT(*) FROM subscriptions").await, 0);
+    let report = sqlx::query!("SELECT line, error FROM subscriber_import_errors")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(report.line, 2);
+    assert_eq!(report.error, "Erased at their request.");
+}
+
+#[actix_web::test]
+pub async fn an_erasure_needs_a_valid_signature() {
+    let app = spawn_app().await;
+    subscribe_and_confirm(&app, "ursula@example.com", "").await;
+    let response = reqwest::Client::new()
+        .post(format!("{}/privacy/erasure", app.address))
+        .form(&[
+            ("email", "ursula@example.com"),
+            ("expires", "4102444800"),
+            ("signature", "00"),
+        ])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(count(&app, "SELECT COUNT(*) FROM subscriptions").await, 1);
+}
+
+/// A duplicate with another case of the address, as left by the migration of
+/// the emails subscribed before their normalization.
+pub async fn insert_unmerged_duplicate(app: &TestApp, email: &str) {
+    let duplicate_id = uuid::Uuid::new_v4();
+    sqlx::query!(
+        r#"INSERT INTO subscriptions (id, email, name, subscribed_at)
+        VALUES ($1, $2, 'Ursula', now())"#,
+        duplicate_id,
+        email
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        r#"INSERT INTO subscriber_duplicates
+            (duplicate_id, kept_id, normalized_email, email, detected_at)
+        SELECT $1, id, normalized_email, $2, now()
+        FROM subscriptions WHERE normalized_email = lower($2)"#,
+        duplicate_id,
+        email
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[actix_web::test]
+pub async fn the_unmerged_duplicates_of_an_address_are_downloaded_and_erased() {
+    let app = spawn_app().await;
+    subscribe_and_confirm(&app, "ursula@example.com", "").await;
+    insert_unmerged_duplicate(&app, "Ursula@Example.com").await;
+
+    let link = request_link(&app, "data", "ursula@example.com").await;
+    let data: serde_json::Value = reqwest::get(link).await.unwrap().json().await.unwrap();
+    assert_eq!(data["email"], "ursula@example.com");
+    assert_eq!(data["duplicates"][0]["email"], "Ursula@Example.com");
+
+    let link = request_link(&app, "erasure", "ursula@example.com").await;
+    let response = reqwest::Client::new()
+        .post(format!("{}/privacy/erasure", app.address))
+        .form(&link.query_pairs().collect::<Vec<_>>())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(count(&app, "SELECT COUNT(*) FROM subscriptions").await, 0);
+    assert_eq!(
+        count(&app, "SELECT COUNT(*) FROM subscriber_duplicates").await,
+        0
+    );
+}
@@ -0,0 +1,222 @@
+use crate::helpers::{TestApp, asser_is_redirect_to, spawn_app};
+use crate::newsletter::create_confirmed_user;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockGuard, MockServer, ResponseTemplate};
+
+/// An RSS feed listing the posts, the newest first.
+fn feed_xml(posts: &[(&str, &str)]) -> String {
+    let items: String = posts
+        .iter()
+        .map(|(guid, title)| {
+            format!(
+                "<item><title>{title}</title><link>https://blog.example.com/{guid}</link>\
+                <guid>{guid}</guid><description>&lt;p&gt;About {title}&lt;/p&gt;</description></item>"
+            )
+        })
+        .collect();
+    format!(
+        r#"<?xml version="1.0"?><rss version="2.0"><channel><title>Blog</title>
+<link>https://blog.example.com</link><description>Our blog</description>{items}</channel></rss>"#
+    )
+}
+
+async fn serve_feed(feed_server: &MockServer, posts: &[(&str, &str)]) -> MockGuard {
+    Mock::given(path("/feed.xml"))
+        .and(method("GET"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_raw(feed_xml(posts), "application/rss+xml"),
+        )
+        .mount_as_scoped(feed_server)
+        .await
+}
+
+async fn register_feed(app: &TestApp, feed_server: &MockServer, auto_send: bool) {
+    let response = app
+        .post_rss_feed(&serde_json::json!({
+            "url": format!("{}/feed.xml", feed_server.uri()),
+            "poll_interval_minutes": 60,
+            "auto_send": auto_send,
+        }))
+        .await;
+    asser_is_redirect_to(&response, "/admin/rss");
+}
+
+async fn issues(app: &TestApp) -> Vec<(String, String)> {
+    sqlx::query!("SELECT title, status FROM newsletter_issues ORDER BY created_at")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| (r.title, r.status))
+        .collect()
+}
+
+#[actix_web::test]
+pub async fn unauthenticated_users_cannot_register_feeds() {
+    let app = spawn_app().await;
+    let response = app
+        .post_rss_feed(&serde_json::json!({
+            "url": "https://blog.example.com/feed.xml",
+            "poll_interval_minutes": 60,
+            "auto_send": false,
+        }))
+        .await;
+    asser_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+pub async fn new_items_of_a_feed_are_saved_as_drafts_once() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    let html = app.get_admindashboard_html().await;
+    assert!(html.contains(r#"<a href="/admin/rss">"#));
+    let feed_server = MockServer::start().await;
+
+    let guard = serve_feed(&feed_server, &[("post-1", "First post")]).await;
+    register_feed(&app, &feed_server, false).await;
+    let html = app.get_rss_feeds_html().await;
+    assert!(html.contains("<p><i>The feed has been added.</i></p>"));
+    app.poll_rss_feeds().await;
+    // The items already in the feed are not turned into issues.
+    assert!(issues(&app).await.is_empty());
+    drop(guard);
+
+    let _guard = serve_feed(
+        &feed_server,
+        &[
+            ("post-3", "Third post"),
+            ("post-2", "Second post"),
+            ("post-1", "First post"),
+        ],
+    )
+    .await;
+    app.poll_rss_feeds().await;
+    app.poll_rss_feeds().await;
+
+    assert_eq!(
+        issues(&app).await,
+        [
+            ("Second post".to_string(), "draft".to_string()),
+            ("Third post".to_string(), "draft".to_string())
+        ]
+    );
+}
+
+#[actix_web::test]
+pub async fn new_items_of_a_feed_can_be_sent_right_away() {
+    let app = spawn_app().await;
+    create_confirmed_user(&app).await;
+    app.user.connect(&app).await;
+    let feed_server = MockServer::start().await;
+
+    let guard = serve_feed(&feed_server, &[]).await;
+    register_feed(&app, &feed_server, true).await;
+    app.poll_rss_feeds().await;
+    drop(guard);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let _guard = serve_feed(&feed_server, &[("post-1", "First post")]).await;
+    app.poll_rss_feeds().await;
+    app.poll_rss_feeds().await;
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = requests.last().unwrap().body_json().unwrap();
+    assert_eq!(body["Subject"], "First post");
+    let html = body["HtmlBody"].as_str().unwrap();
+    assert!(html.contains("<p>About First post</p>"));
+    assert!(html.contains(r#"<a href="https://blog.example.com/post-1">Read more</a>"#));
+}
+
+#[actix_web::test]
+pub async fn the_error_of_the_last_poll_is_shown() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    let feed_server = MockServer::start().await;
+    Mock::given(path("/feed.xml"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&feed_server)
+        .await;
+
+    register_feed(&app, &feed_server, false).await;
+    app.poll_rss_feeds().await;
+
+    let html = app.get_rss_feeds_html().await;
+    assert!(html.contains("500 Internal Server Error"));
+}
+
+#[actix_web::test]
+pub async fn an_item_that_cannot_be_saved_is_tried_again_on_the_next_poll() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    let feed_server = MockServer::start().await;
+    let guard = serve_feed(&feed_server, &[]).await;
+    register_feed(&app, &feed_server, false).await;
+    app.poll_rss_feeds().await;
+    drop(guard);
+
+    sqlx::query!(
+        r#"ALTER TABLE newsletter_issues
+        ADD CONSTRAINT no_broken_post CHECK (title <> 'Broken post')"#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    let _guard = serve_feed(&feed_server, &[("post-1", "Broken post")]).await;
+    // Returns once the feed is not due anymore.
+    app.poll_rss_feeds().await;
+    assert!(issues(&app).await.is_empty());
+    let html = app.get_rss_feeds_html().await;
+    assert!(html.contains("no_broken_post"));
+
+    sqlx::query!("ALTER TABLE newsletter_issues DROP CONSTRAINT no_broken_post")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.poll_rss_feeds().await;
+    assert_eq!(
+        issues(&app).await,
+        [("Broken post".to_string(), "draft".to_string())]
+    );
+}
+
+#[actix_web::test]
+pub async fn invalid_feeds_are_rejected() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    let test_cases = [
+        (
+            serde_json::json!({
+                "url": "ftp://blog.example.com/feed.xml",
+                "poll_interval_minutes": 60,
+                "auto_send": false,
+            }),
+            "The feed URL must be an http or https URL.",
+        ),
+        (
+            serde_json::json!({
+                "url": "https://blog.example.com/feed.xml",
+                "poll_interval_minutes": 0,
+                "auto_send": false,
+            }),
+            "The polling interval must be between 1 and 10080 minutes.",
+        ),
+    ];
+    for (body, message) in test_cases {
+        let response = app.post_rss_feed(&body).await;
+        asser_is_redirect_to(&response, "/admin/rss");
+        let html = app.get_rss_feeds_html().await;
+        assert!(html.contains(message), "Missing the message: {}", message);
+    }
+
+    let feed_server = MockServer::start().await;
+    register_feed(&app, &feed_server, false).await;
+    register_feed(&app, &feed_server, false).await;
+    let html = app.get_rss_feeds_html().await;
+    assert!(html.contains("<p><i>This feed is already registered.</i></p>"));
+}
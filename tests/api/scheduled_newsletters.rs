@@ -0,0 +1,182 @@
+use crate::helpers::{TestApp, asser_is_redirect_to, spawn_app};
+use crate::newsletter::create_confirmed_user;
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+fn newsletter_form_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "html": "<p>Newsletter body as HTML</p>",
+        "text": "Newsletter body as plain text",
+    })
+}
+
+fn schedule_body(send_at: chrono::DateTime<Utc>, timezone: &str) -> serde_json::Value {
+    serde_json::json!({
+        "send_at": send_at.format("%Y-%m-%dT%H:%M").to_string(),
+        "timezone": timezone,
+    })
+}
+
+/// Move the sending time of a scheduled issue to the past, as if we waited.
+async fn make_issue_due(app: &TestApp, issue_id: Uuid) {
+    sqlx::query!(
+        "UPDATE newsletter_issues SET send_at = $1 WHERE newsletter_issue_id = $2",
+        Utc::now() - Duration::minutes(1),
+        issue_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[actix_web::test]
+pub async fn a_scheduled_issue_is_not_sent_before_its_time() {
+    let app = spawn_app().await;
+    create_confirmed_user(&app).await;
+    app.user.connect(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_newsletter_draft(&newsletter_form_body()).await;
+    let response = app
+        .post_schedule_newsletter(
+            issue_id,
+            &schedule_body(Utc::now() + Duration::days(3), "UTC"),
+        )
+        .await;
+    asser_is_redirect_to(&response, &format!("/admin/newsletters/{}", issue_id));
+    app.start_due_issues().await;
+    app.dispatch_all_pending_emails().await;
+
+    let html = app.get_newsletter_issue_html(issue_id).await;
+    assert!(html.contains("<p><i>The newsletter issue has been scheduled.</i></p>"));
+    assert!(html.contains("Status: scheduled"));
+}
+
+#[actix_web::test]
+pub async fn a_due_issue_is_sent_by_the_scheduler() {
+    let app = spawn_app().await;
+    create_confirmed_user(&app).await;
+    app.user.connect(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_newsletter_draft(&newsletter_form_body()).await;
+    app.post_schedule_newsletter(
+        issue_id,
+        &schedule_body(Utc::now() + Duration::days(3), "UTC"),
+    )
+    .await;
+    make_issue_due(&app, issue_id).await;
+    app.start_due_issues().await;
+    app.dispatch_all_pending_emails().await;
+
+    let html = app.get_newsletter_issue_html(issue_id).await;
+    assert!(html.contains("Status: sent"));
+}
+
+#[actix_web::test]
+pub async fn the_timezone_is_taken_into_account() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    let issue_id = app.create_newsletter_draft(&newsletter_form_body()).await;
+
+    let body = serde_json::json!({
+        "send_at": "2099-01-05T09:00",
+        "timezone": "America/New_York",
+    });
+    app.post_schedule_newsletter(issue_id, &body).await;
+
+    let html = app.get_newsletter_issue_html(issue_id).await;
+    assert!(html.contains("Scheduled for 2099-01-05 14:00 UTC"));
+}
+
+#[actix_web::test]
+pub async fn invalid_schedules_are_rejected_with_a_message() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    let issue_id = app.create_newsletter_draft(&newsletter_form_body()).await;
+    app.get_newsletter_issue_html(issue_id).await;
+
+    let test_cases = vec![
+        (
+            schedule_body(Utc::now() - Duration::days(1), "UTC"),
+            "The sending time must be in the future.",
+        ),
+        (
+            schedule_body(Utc::now() + Duration::days(1), "Mars/Olympus_Mons"),
+            "Mars/Olympus_Mons is not a valid timezone.",
+        ),
+        (
+            serde_json::json!({"send_at": "", "timezone": "UTC"}),
+            " is not a valid date and time.",
+        ),
+    ];
+    for (body, message) in test_cases {
+        let response = app.post_schedule_newsletter(issue_id, &body).await;
+        asser_is_redirect_to(&response, &format!("/admin/newsletters/{}", issue_id));
+        let html = app.get_newsletter_issue_html(issue_id).await;
+        assert!(html.contains(message), "Missing message: {}", message);
+        assert!(html.contains("Status: draft"));
+    }
+}
+
+#[actix_web::test]
+pub async fn a_scheduled_issue_can_be_rescheduled() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    let issue_id = app.create_newsletter_draft(&newsletter_form_body()).await;
+
+    let body = serde_json::json!({"send_at": "2099-01-05T09:00", "timezone": "UTC"});
+    app.post_schedule_newsletter(issue_id, &body).await;
+    let body = serde_json::json!({"send_at": "2099-02-05T10:30", "timezone": "UTC"});
+    app.post_schedule_newsletter(issue_id, &body).await;
+
+    let html = app.get_newsletter_issue_html(issue_id).await;
+    assert!(html.contains("Scheduled for 2099-02-05 10:30 UTC"));
+}
+
+#[actix_web::test]
+pub async fn a_cancelled_scheduled_issue_is_never_sent() {
+    let app = spawn_app().await;
+    create_confirmed_user(&app).await;
+    app.user.connect(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_newsletter_draft(&newsletter_form_body()).await;
+    app.post_schedule_newsletter(
+        issue_id,
+        &schedule_body(Utc::now() + Duration::days(3), "UTC"),
+    )
+    .await;
+    let html = app.get_admindashboard_html().await;
+    assert!(html.contains(&format!(
+        r#"<form action="/admin/newsletters/{}/cancel" method="post">"#,
+        issue_id
+    )));
+
+    app.post_newsletter_action(issue_id, "cancel").await;
+    make_issue_due(&app, issue_id).await;
+    app.start_due_issues().await;
+    app.dispatch_all_pending_emails().await;
+
+    let html = app.get_newsletter_issue_html(issue_id).await;
+    assert!(html.contains("Status: cancelled"));
+}
@@ -0,0 +1,219 @@
+use crate::helpers::{TestApp, asser_is_redirect_to, spawn_app};
+use crate::lists::subscribe_and_confirm;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn set_subscription_date(app: &TestApp, email: &str, date: &str) {
+    sqlx::query!(
+        "UPDATE subscriptions SET subscribed_at = $1::text::timestamptz WHERE email = $2",
+        date,
+        email
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[actix_web::test]
+pub async fn unauthenticated_users_cannot_create_segments() {
+    let app = spawn_app().await;
+    let response = app
+        .post_create_segment(&serde_json::json!({
+            "name": "Beta testers",
+            "definition": r#"{"tag": "beta"}"#,
+        }))
+        .await;
+    asser_is_redirect_to(&response, "/login");
+    let response = app
+        .post_tags("beta", "ursula_le_guin@gmail.com", "add")
+        .await;
+    asser_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+pub async fn subscribers_can_be_tagged_and_untagged() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    let html = app.get_admindashboard_html().await;
+    assert!(html.contains(r#"<a href="/admin/tags">"#));
+    subscribe_and_confirm(&app, "first@example.com", "").await;
+    subscribe_and_confirm(&app, "second@example.com", "").await;
+
+    let response = app
+        .post_tags(
+            " Beta ",
+            "first@example.com, second@example.com\nunknown@example.com",
+            "add",
+        )
+        .await;
+    asser_is_redirect_to(&response, "/admin/tags");
+    let html = app.get_tags_html().await;
+    assert!(html.contains("<p><i>The tag has been added to 2 subscribers.</i></p>"));
+    assert!(html.contains("<p><i>No subscriber has the email unknown@example.com.</i></p>"));
+    assert!(html.contains("<tr><td>beta</td><td>2</td></tr>"));
+
+    app.post_tags("beta", "second@example.com", "remove").await;
+    let html = app.get_tags_html().await;
+    assert!(html.contains("<p><i>The tag has been removed from 1 subscribers.</i></p>"));
+    assert!(html.contains("<tr><td>beta</td><td>1</td></tr>"));
+}
+
+#[actix_web::test]
+pub async fn invalid_segments_are_rejected() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    app.create_segment("Beta testers", r#"{"tag": "beta"}"#)
+        .await;
+
+    let test_cases = [
+        (
+            serde_json::json!({ "name": " ", "definition": r#"{"tag": "beta"}"# }),
+            "The name of the segment is missing.",
+        ),
+        (
+            serde_json::json!({ "name": "Other", "definition": r#"{"all": []}"# }),
+            "The definition of the segment is invalid: `all` and `any` need at least one condition.",
+        ),
+        (
+            serde_json::json!({ "name": "Other", "definition": "tag = beta" }),
+            "The definition of the segment is invalid:",
+        ),
+        (
+            serde_json::json!({ "name": "Beta testers", "definition": r#"{"tag": "alpha"}"# }),
+            "A segment with this name already exists.",
+        ),
+    ];
+    for (body, message) in test_cases {
+        let response = app.post_create_segment(&body).await;
+        asser_is_redirect_to(&response, "/admin/segments");
+        let html = app.get_segments_html().await;
+        assert!(html.contains(message), "Missing the message: {}", message);
+    }
+}
+
+#[actix_web::test]
+pub async fn a_segment_combines_tags_and_subscription_dates() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    subscribe_and_confirm(&app, "early@example.com", "").await;
+    subscribe_and_confirm(&app, "late@example.com", "").await;
+    subscribe_and_confirm(&app, "untagged@example.com", "").await;
+    set_subscription_date(&app, "early@example.com", "2025-06-01T10:00:00Z").await;
+    set_subscription_date(&app, "untagged@example.com", "2025-06-01T10:00:00Z").await;
+    app.post_tags("beta", "early@example.com, late@example.com", "add")
+        .await;
+
+    app.create_segment(
+        "Early beta testers",
+        r#"{"all": [{"tag": "beta"}, {"subscribed": {"to": "2025-12-31"}}]}"#,
+    )
+    .await;
+    app.create_segment(
+        "Everyone else",
+        r#"{"not": {"all": [{"tag": "beta"}, {"subscribed": {"to": "2025-12-31"}}]}}"#,
+    )
+    .await;
+
+    let html = app.get_segments_html().await;
+    assert!(html.contains("<p><i>The segment has been created.</i></p>"));
+    assert!(html.contains(
+        "<tr><td>Early beta testers</td><td>(tagged &quot;beta&quot; AND subscribed until 2025-12-31)</td><td>1</td>"
+    ));
+    assert!(html.contains("<tr><td>Everyone else</td><td>NOT (tagged &quot;beta&quot; AND subscribed until 2025-12-31)</td><td>2</td>"));
+}
+
+#[actix_web::test]
+pub async fn both_ends_of_a_date_range_are_included() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    for (email, date) in [
+        ("before@example.com", "2025-12-31T23:59:59Z"),
+        ("first@example.com", "2026-01-01T00:00:00Z"),
+        ("last@example.com", "2026-01-31T23:59:59Z"),
+        ("after@example.com", "2026-02-01T00:00:00Z"),
+    ] {
+        subscribe_and_confirm(&app, email, "").await;
+        set_subscription_date(&app, email, date).await;
+    }
+
+    app.create_segment(
+        "January",
+        r#"{"subscribed": {"from": "2026-01-01", "to": "2026-01-31"}}"#,
+    )
+    .await;
+
+    let html = app.get_segments_html().await;
+    assert!(html.contains(
+        "<tr><td>January</td><td>subscribed from 2026-01-01 until 2026-01-31</td><td>2</td>"
+    ));
+}
+
+#[actix_web::test]
+pub async fn an_issue_is_only_sent_to_its_segment() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    subscribe_and_confirm(&app, "beta@example.com", "").await;
+    subscribe_and_confirm(&app, "other@example.com", "").await;
+    app.post_tags("beta", "beta@example.com", "add").await;
+    let segment_id = app
+        .create_segment("Beta testers", r#"{"tag": "beta"}"#)
+        .await
+        .to_string();
+
+    let issue_id = app
+        .create_newsletter_draft(&[
+            ("title", "Beta news"),
+            ("html", "<p>Beta news</p>"),
+            ("text", "Beta news"),
+            ("segment_id", segment_id.as_str()),
+        ])
+        .await;
+    let html = app.get_newsletter_issue_html(issue_id).await;
+    assert!(html.contains("<p>Segment: Beta testers (tagged &quot;beta&quot;)</p>"));
+    assert!(html.contains("This issue will be sent to 1 confirmed subscribers."));
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter_action(issue_id, "publish").await;
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = requests.last().unwrap().body_json().unwrap();
+    assert_eq!(body["To"], "beta@example.com");
+
+    // The segment the issue was sent to is kept.
+    app.api_client
+        .post(format!(
+            "{}/admin/segments/{}/delete",
+            app.address, segment_id
+        ))
+        .send()
+        .await
+        .unwrap();
+    let html = app.get_segments_html().await;
+    assert!(html.contains("<p><i>Segments used by an issue cannot be deleted.</i></p>"));
+}
+
+#[actix_web::test]
+pub async fn publishing_to_an_unknown_segment_is_rejected() {
+    let app = spawn_app().await;
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletter", &app.address))
+        .json(&serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Newsletter body as plain text",
+                "html": "<p>Newsletter body as HTML</p>"
+            },
+            "segment": "Unknown",
+        }))
+        .basic_auth(&app.user.username, Some(&app.user.password))
+        .send()
+        .await
+        .expect("Could not send the request");
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -0,0 +1,132 @@
+use crate::helpers::{TestApp, asser_is_redirect_to, spawn_app};
+use crate::newsletter::create_confirmed_user;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+fn newsletter_form_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "html": "<p>Newsletter body as HTML</p>",
+        "text": "Newsletter body as plain text",
+    })
+}
+
+async fn set_admin_email(app: &TestApp, email: &str) {
+    sqlx::query!(
+        "UPDATE users SET email = $1 WHERE user_id = $2",
+        email,
+        app.user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[actix_web::test]
+pub async fn a_test_is_sent_to_the_admin_only() {
+    let app = spawn_app().await;
+    create_confirmed_user(&app).await;
+    set_admin_email(&app, "admin@example.com").await;
+    app.user.connect(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_newsletter_draft(&newsletter_form_body()).await;
+    let response = app
+        .post_send_test_newsletter(issue_id, &serde_json::json!({"recipients": ""}))
+        .await;
+    asser_is_redirect_to(&response, &format!("/admin/newsletters/{}", issue_id));
+
+    // The confirmation of the subscriber went through the same server.
+    let requests = app.email_server.received_requests().await.unwrap();
+    let email_request = requests.last().unwrap();
+    let body: serde_json::Value = email_request.body_json().unwrap();
+    assert_eq!(body["To"], "admin@example.com");
+    assert_eq!(body["Subject"], "[TEST] Newsletter title");
+    assert!(body["HtmlBody"].as_str().unwrap().contains("Unsubscribe"));
+    let links = app.get_text_links(email_request);
+    assert_eq!(links.len(), 2);
+    assert_eq!(links[0].path(), "/unsubscribe");
+    // The issue is not sent yet, the signed link opens it anyway.
+    assert!(links[1].path().starts_with("/archive/newsletter-title-"));
+    let response = reqwest::get(links[1].clone()).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let html = app.get_newsletter_issue_html(issue_id).await;
+    assert!(html.contains("<p><i>The test email has been sent.</i></p>"));
+    assert!(html.contains("Status: draft"));
+}
+
+#[actix_web::test]
+pub async fn a_test_uses_the_details_of_a_subscriber() {
+    let app = spawn_app().await;
+    create_confirmed_user(&app).await;
+    app.user.connect(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_newsletter_draft(&newsletter_form_body()).await;
+    // The subscriber is found whatever the case of the address.
+    let body = serde_json::json!({
+        "recipients": "Ursula_Le_Guin@gmail.com, someone@example.com",
+    });
+    app.post_send_test_newsletter(issue_id, &body).await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let requests = &requests[requests.len() - 2..];
+    let body: serde_json::Value = requests[0].body_json().unwrap();
+    assert_eq!(body["To"], "Ursula_Le_Guin@gmail.com");
+    assert!(body["TextBody"].as_str().unwrap().contains("le guin"));
+    let body: serde_json::Value = requests[1].body_json().unwrap();
+    assert_eq!(body["To"], "someone@example.com");
+    assert!(
+        body["TextBody"]
+            .as_str()
+            .unwrap()
+            .contains(&app.user.username)
+    );
+}
+
+#[actix_web::test]
+pub async fn invalid_test_recipients_are_rejected_with_a_message() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_newsletter_draft(&newsletter_form_body()).await;
+    app.get_newsletter_issue_html(issue_id).await;
+    let test_cases = vec![
+        (
+            "",
+            "Set your email in the notification settings or enter the addresses to send the test to.",
+        ),
+        (
+            "a@example.com,b@example.com,c@example.com,d@example.com,e@example.com,f@example.com",
+            "A test can be sent to at most 5 addresses.",
+        ),
+        ("not-an-email", "not-an-email"),
+    ];
+    for (recipients, message) in test_cases {
+        let response = app
+            .post_send_test_newsletter(issue_id, &serde_json::json!({ "recipients": recipients }))
+            .await;
+        asser_is_redirect_to(&response, &format!("/admin/newsletters/{}", issue_id));
+        let html = app.get_newsletter_issue_html(issue_id).await;
+        assert!(html.contains(message), "Missing message: {}", message);
+    }
+}
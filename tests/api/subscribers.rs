@@ -0,0 +1,603 @@
+use crate::helpers::{TestApp, asser_is_redirect_to, spawn_app};
+use crate::lists::subscribe_and_confirm;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::subscriber_duplicates::normalize_international_emails;
+
+/// Subscribers named `subscriber{i}@example.com`, subscribed one day apart,
+/// with no list.
+pub async fn insert_subscribers(app: &TestApp, count: i32) {
+    sqlx::query!(
+        r#"INSERT INTO subscriptions (id, email, name, subscribed_at, normalized_email)
+        SELECT gen_random_uuid(), 'subscriber' || i || '@example.com', 'Subscriber ' || i,
+            '2026-01-01'::timestamptz + i * interval '1 day', 'subscriber' || i || '@example.com'
+        FROM generate_series(1, $1) AS i"#,
+        count
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+fn emails(page: &serde_json::Value) -> Vec<&str> {
+    page["subscribers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|subscriber| subscriber["email"].as_str().unwrap())
+        .collect()
+}
+
+#[actix_web::test]
+pub async fn unauthenticated_users_cannot_browse_subscribers() {
+    let app = spawn_app().await;
+    for path in ["subscribers", "api/subscribers"] {
+        let response = app.get_subscribers(path, "").await;
+        asser_is_redirect_to(&response, "/login");
+    }
+}
+
+#[actix_web::test]
+pub async fn subscribers_are_paginated_with_a_cursor() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    insert_subscribers(&app, 55).await;
+
+    let first = app.get_subscribers_json("sort=email&order=asc").await;
+    assert_eq!(first["subscribers"].as_array().unwrap().len(), 50);
+    let next = first["next"].as_str().unwrap();
+    let second = app
+        .get_subscribers_json(&format!("sort=email&order=asc&after={}", next))
+        .await;
+    assert_eq!(second["subscribers"].as_array().unwrap().len(), 5);
+    assert!(second["next"].is_null());
+    let mut all: Vec<&str> = emails(&first).into_iter().chain(emails(&second)).collect();
+    all.sort();
+    all.dedup();
+    assert_eq!(all.len(), 55);
+
+    // The newest come first by default.
+    let newest = app.get_subscribers_json("").await;
+    assert_eq!(emails(&newest)[0], "subscriber55@example.com");
+}
+
+#[actix_web::test]
+pub async fn every_sort_is_paginated_without_gaps() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    insert_subscribers(&app, 55).await;
+    // Ties on the sorted column are broken by the id.
+    sqlx::query!("UPDATE subscriptions SET name = 'Twin' WHERE email LIKE 'subscriber1%'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    for sort in ["email", "name", "subscribed_at"] {
+        for order in ["asc", "desc"] {
+            let query = format!("sort={}&order={}", sort, order);
+            let first = app.get_subscribers_json(&query).await;
+            let next = first["next"].as_str().unwrap();
+            let second = app
+                .get_subscribers_json(&format!("{}&after={}", query, next))
+                .await;
+            assert!(second["next"].is_null());
+            let mut all: Vec<&str> = emails(&first).into_iter().chain(emails(&second)).collect();
+            assert_eq!(all.len(), 55, "{}", query);
+            all.sort();
+            all.dedup();
+            assert_eq!(all.len(), 55, "{}", query);
+        }
+    }
+}
+
+#[actix_web::test]
+pub async fn a_cursor_on_a_deleted_subscriber_is_rejected() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    insert_subscribers(&app, 3).await;
+
+    let response = app
+        .get_subscribers(
+            "api/subscribers",
+            &format!("after={}", uuid::Uuid::new_v4()),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "The subscriber the page starts after does not exist anymore."
+    );
+}
+
+#[actix_web::test]
+pub async fn subscribers_can_be_searched_and_filtered() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    insert_subscribers(&app, 12).await;
+    subscribe_and_confirm(&app, "ursula@example.com", "").await;
+
+    let page = app.get_subscribers_json("search=SUBSCRIBER1").await;
+    assert_eq!(
+        emails(&page),
+        [
+            "subscriber12@example.com",
+            "subscriber11@example.com",
+            "subscriber10@example.com",
+            "subscriber1@example.com"
+        ]
+    );
+
+    let page = app
+        .get_subscribers_json("from=2026-01-03&to=2026-01-04&order=asc")
+        .await;
+    assert_eq!(
+        emails(&page),
+        ["subscriber2@example.com", "subscriber3@example.com"]
+    );
+
+    let page = app.get_subscribers_json("status=confirmed").await;
+    assert_eq!(emails(&page), ["ursula@example.com"]);
+    assert_eq!(page["subscribers"][0]["status"], "confirmed");
+
+    let html = app
+        .get_subscribers("subscribers", "search=ursula")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains("ursula@example.com</a></td><td>le guin</td><td>confirmed</td>"));
+    assert!(!html.contains("subscriber1@example.com"));
+
+    for query in [
+        "status=pending",
+        "from=yesterday",
+        "from=2026-02-01&to=2026-01-01",
+        "sort=age",
+    ] {
+        let response = app.get_subscribers("api/subscribers", query).await;
+        assert_eq!(response.status().as_u16(), 400, "{} was accepted", query);
+    }
+}
+
+#[actix_web::test]
+pub async fn the_detail_shows_the_tokens_and_the_deliveries() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    subscribe_and_confirm(&app, "ursula@example.com", "").await;
+    let page = app.get_subscribers_json("").await;
+    let id = page["subscribers"][0]["id"].as_str().unwrap().to_string();
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let issue_id = app
+        .create_newsletter_draft(&[
+            ("title", "First issue"),
+            ("html", "<p>Hello</p>"),
+            ("text", "Hello"),
+        ])
+        .await;
+    app.post_newsletter_action(issue_id, "publish").await;
+    app.dispatch_all_pending_emails().await;
+
+    let detail: serde_json::Value = app
+        .get_subscribers(&format!("api/subscribers/{}", id), "")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(detail["email"], "ursula@example.com");
+    assert_eq!(detail["lists"][0]["status"], "confirmed");
+    assert!(detail["tokens"][0]["created_at"].is_string());
+    assert!(detail["tokens"][0]["used_at"].is_string());
+    assert_eq!(detail["deliveries"][0]["issue_title"], "First issue");
+    assert_eq!(detail["deliveries"][0]["outcome"], "sent");
+    assert_eq!(detail["deliveries"][0]["attempts"], 1);
+
+    let html = app
+        .get_subscribers(&format!("subscribers/{}", id), "")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains("<h2>ursula@example.com</h2>"));
+    assert!(html.contains(&format!(
+        r#"<a href="/admin/newsletters/{}">First issue</a></td><td>sent</td><td>1</td>"#,
+        issue_id
+    )));
+}
+
+async fn subscriber_id(app: &TestApp, email: &str) -> String {
+    sqlx::query!("SELECT id FROM subscriptions WHERE email = $1", email)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id
+        .to_string()
+}
+
+async fn detail_json(app: &TestApp, subscriber_id: &str) -> serde_json::Value {
+    app.get_subscribers(&format!("api/subscribers/{}", subscriber_id), "")
+        .await
+        .json()
+        .await
+        .unwrap()
+}
+
+#[actix_web::test]
+pub async fn unauthenticated_users_cannot_manage_subscribers() {
+    let app = spawn_app().await;
+    let id = uuid::Uuid::new_v4().to_string();
+    for action in ["edit", "confirm", "unsubscribe", "resend", "delete"] {
+        let response = app
+            .post_subscriber_action(&id, action, &serde_json::json!({}))
+            .await;
+        asser_is_redirect_to(&response, "/login");
+    }
+    // The body is left unread, so the connection is not reused after it.
+    let response = app
+        .post_add_subscriber(
+            &serde_json::json!({ "email": "ursula@example.com", "name": "le guin" }),
+        )
+        .await;
+    asser_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+pub async fn skipping_the_double_opt_in_needs_a_reason() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_add_subscriber(&serde_json::json!({
+            "email": "ursula@example.com",
+            "name": "le guin",
+            "skip_confirmation": "true",
+        }))
+        .await;
+    asser_is_redirect_to(&response, "/admin/subscribers");
+    let html = app
+        .get_subscribers("subscribers", "")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains("A reason is needed to skip the confirmation."));
+
+    let response = app
+        .post_add_subscriber(&serde_json::json!({
+            "email": "ursula@example.com",
+            "name": "le guin",
+            "skip_confirmation": "true",
+            "reason": "Signed up on paper",
+        }))
+        .await;
+    let id = subscriber_id(&app, "ursula@example.com").await;
+    asser_is_redirect_to(&response, &format!("/admin/subscribers/{}", id));
+    let detail = detail_json(&app, &id).await;
+    assert_eq!(detail["lists"][0]["status"], "confirmed");
+    assert_eq!(detail["history"][0]["action"], "add_subscriber");
+    assert!(
+        detail["history"][0]["details"]
+            .as_str()
+            .unwrap()
+            .ends_with("the confirmation was skipped: Signed up on paper")
+    );
+}
+
+#[actix_web::test]
+pub async fn admins_can_confirm_resend_and_unsubscribe() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_add_subscriber(
+        &serde_json::json!({ "email": "ursula@example.com", "name": "le guin" }),
+    )
+    .await;
+    let id = subscriber_id(&app, "ursula@example.com").await;
+    assert_eq!(
+        detail_json(&app, &id).await["lists"][0]["status"],
+        "pending_confirmation"
+    );
+
+    app.post_subscriber_action(&id, "resend", &serde_json::json!({}))
+        .await;
+    let requests = app.email_server.received_requests().await.unwrap();
+    let first = app.get_confirmation_links(&requests[0]);
+    let second = app.get_confirmation_links(&requests[1]);
+    assert_ne!(first.html, second.html);
+
+    let response = app
+        .post_subscriber_action(&id, "confirm", &serde_json::json!({}))
+        .await;
+    asser_is_redirect_to(&response, &format!("/admin/subscribers/{}", id));
+    assert_eq!(
+        detail_json(&app, &id).await["lists"][0]["status"],
+        "confirmed"
+    );
+    // Nothing is left to confirm or to send.
+    app.post_subscriber_action(&id, "resend", &serde_json::json!({}))
+        .await;
+    let html = app
+        .get_subscribers(&format!("subscribers/{}", id), "")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains("There is no pending subscription to confirm."));
+
+    app.post_subscriber_action(&id, "unsubscribe", &serde_json::json!({}))
+        .await;
+    let detail = detail_json(&app, &id).await;
+    assert_eq!(detail["lists"][0]["status"], "unsubscribed");
+    let actions: Vec<&str> = detail["history"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["action"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        actions,
+        [
+            "unsubscribe",
+            "force_confirm",
+            "resend_confirmation",
+            "add_subscriber"
+        ]
+    );
+    assert!(detail["history"][0]["admin"].is_string());
+}
+
+#[actix_web::test]
+pub async fn admins_can_edit_a_subscriber() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    subscribe_and_confirm(&app, "ursula@example.com", "").await;
+    subscribe_and_confirm(&app, "octavia@example.com", "").await;
+    let id = subscriber_id(&app, "ursula@example.com").await;
+
+    let test_cases = [
+        ("octavia@example.com", "Another subscriber has this email."),
+        (
+            "not-an-email",
+            "not-an-email is not a valid subscriber email.",
+        ),
+    ];
+    for (email, message) in test_cases {
+        let response = app
+            .post_subscriber_action(
+                &id,
+                "edit",
+                &serde_json::json!({ "email": email, "name": "le guin" }),
+            )
+            .await;
+        asser_is_redirect_to(&response, &format!("/admin/subscribers/{}", id));
+        let html = app
+            .get_subscribers(&format!("subscribers/{}", id), "")
+            .await
+            .text()
+            .await
+            .unwrap();
+        assert!(html.contains(message), "Missing the message: {}", message);
+    }
+
+    app.post_subscriber_action(
+        &id,
+        "edit",
+        &serde_json::json!({ "email": "ursula@earthsea.org", "name": "Ursula K. Le Guin" }),
+    )
+    .await;
+    let detail = detail_json(&app, &id).await;
+    assert_eq!(detail["email"], "ursula@earthsea.org");
+    assert_eq!(detail["name"], "Ursula K. Le Guin");
+    assert_eq!(
+        detail["history"][0]["details"],
+        "email: ursula@example.com -> ursula@earthsea.org, name: le guin -> Ursula K. Le Guin"
+    );
+}
+
+#[actix_web::test]
+pub async fn deleting_a_subscriber_removes_their_tokens_but_keeps_the_audit_trail() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    subscribe_and_confirm(&app, "ursula@example.com", "").await;
+    let id = subscriber_id(&app, "ursula@example.com").await;
+
+    let response = app
+        .post_subscriber_action(&id, "delete", &serde_json::json!({}))
+        .await;
+    asser_is_redirect_to(&response, "/admin/subscribers");
+    let html = app
+        .get_subscribers("subscribers", "")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains("ursula@example.com has been deleted."));
+
+    let subscribers = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscribers.count, 0);
+    let tokens = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions_tokens"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(tokens.count, 0);
+    let response = app
+        .get_subscribers(&format!("api/subscribers/{}", id), "")
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let html = app.get_audit_trail_html().await;
+    assert!(html.contains(
+        "<td>delete_subscriber</td><td>ursula@example.com</td><td>Deleted, confirmation links removed: 1</td>"
+    ));
+}
+
+#[actix_web::test]
+pub async fn duplicates_are_merged_into_the_oldest_subscriber() {
+    let app = spawn_app().await;
+    app.user.connect(&app).await;
+    let list_id = app.create_list("Weekly", "weekly").await;
+    subscribe_and_confirm(&app, "ursula@example.com", "").await;
+    let kept_id = subscriber_id(&app, "ursula@example.com").await;
+    // As left by the migration of the emails subscribed before their
+    // normalization.
+    let duplicate_id = uuid::Uuid::new_v4();
+    sqlx::query!(
+        r#"INSERT INTO subscriptions (id, email, name, subscribed_at)
+        VALUES ($1, 'Ursula@example.com', 'Ursula', now())"#,
+        duplicate_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        r#"INSERT INTO list_memberships (list_id, subscriber_id, status, subscribed_at)
+        VALUES ($1, $2, 'confirmed', now())"#,
+        list_id,
+        duplicate_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        "INSERT INTO subscriber_tags (subscriber_id, tag) VALUES ($1, 'vip')",
+        duplicate_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        r#"INSERT INTO subscriber_duplicates
+            (duplicate_id, kept_id, normalized_email, email, detected_at)
+        VALUES ($1, $2, 'ursula@example.com', 'Ursula@example.com', now())"#,
+        duplicate_id,
+        kept_id.parse::<uuid::Uuid>().unwrap()
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let page = app
+        .get_subscribers("subscribers/duplicates", "")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("Ursula@example.com"));
+    assert!(page.contains(&format!(
+        "/admin/subscribers/duplicates/{}/merge",
+        duplicate_id
+    )));
+
+    let merge = format!("{}/merge", duplicate_id);
+    let response = app
+        .post_subscriber_action("duplicates", &merge, &Vec::<(&str, &str)>::new())
+        .await;
+    asser_is_redirect_to(&response, "/admin/subscribers/duplicates");
+    let page = app
+        .get_subscribers("subscribers/duplicates", "")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("Ursula@example.com has been merged into ursula@example.com."));
+
+    let detail = detail_json(&app, &kept_id).await;
+    assert_eq!(detail["tags"], serde_json::json!(["vip"]));
+    assert_eq!(detail["lists"].as_array().unwrap().len(), 2);
+    let remaining = sqlx::query!("SELECT COUNT(*) AS \"n!\" FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining.n, 1);
+    assert!(
+        app.get_audit_trail_html()
+            .await
+            .contains("Merged Ursula@example.com into ursula@example.com")
+    );
+
+    // A merged duplicate is gone.
+    app.post_subscriber_action("duplicates", &merge, &Vec::<(&str, &str)>::new())
+        .await;
+    let page = app
+        .get_subscribers("subscribers/duplicates", "")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("This duplicate cannot be merged anymore."));
+}
+
+#[actix_web::test]
+pub async fn international_emails_are_normalized_like_new_signups() {
+    let app = spawn_app().await;
+    // A signup through the form, then the addresses as the migration left
+    // them, only lowercased.
+    sqlx::query!(
+        r#"INSERT INTO subscriptions (id, email, name, subscribed_at, normalized_email)
+        VALUES
+            (gen_random_uuid(), 'ursula@xn--bcher-kva.example', 'Ursula', now(),
+                'ursula@xn--bcher-kva.example'),
+            (gen_random_uuid(), 'Ursula@Bücher.example', 'Ursula', now(),
+                'ursula@bücher.example'),
+            (gen_random_uuid(), 'le.guin@bücher.example', 'Le Guin', now(),
+                'le.guin@bücher.example')"#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    normalize_international_emails(&app.db_pool).await.unwrap();
+
+    let subscribers: Vec<_> = sqlx::query!(
+        r#"SELECT email, normalized_email FROM subscriptions ORDER BY email COLLATE "C""#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|r| (r.email, r.normalized_email))
+    .collect();
+    assert_eq!(
+        subscribers,
+        vec![
+            ("Ursula@Bücher.example".to_string(), None),
+            (
+                "le.guin@bücher.example".to_string(),
+                Some("le.guin@xn--bcher-kva.example".to_string())
+            ),
+            (
+                "ursula@xn--bcher-kva.example".to_string(),
+                Some("ursula@xn--bcher-kva.example".to_string())
+            ),
+        ]
+    );
+    let duplicate = sqlx::query!(
+        r#"SELECT d.email, d.normalized_email, s.email AS kept_email
+        FROM subscriber_duplicates d JOIN subscriptions s ON s.id = d.kept_id"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(duplicate.email, "Ursula@Bücher.example");
+    assert_eq!(duplicate.normalized_email, "ursula@xn--bcher-kva.example");
+    assert_eq!(duplicate.kept_email, "ursula@xn--bcher-kva.example");
+}
@@ -69,13 +69,15 @@ async fn subscribe_return_a_200_is_ok_and_saves_data() {
     assert_eq!(200, response.status().as_u16());
     assert_eq!(Some(0), response.content_length());
 
-    let saved = sqlx::query!("SELECT email, name, status_subscription FROM subscriptions",)
-        .fetch_one(&app.db_pool.clone())
-        .await
-        .expect("Should have been able to fetch email and name from subscription");
+    let saved = sqlx::query!(
+        "SELECT email, name, status FROM subscriptions JOIN list_memberships ON subscriber_id = id",
+    )
+    .fetch_one(&app.db_pool.clone())
+    .await
+    .expect("Should have been able to fetch email and name from subscription");
     assert_eq!(saved.email, "ursula_le_guin@gmail.com");
     assert_eq!(saved.name, "le guin");
-    assert_eq!(saved.status_subscription, "pending_confirmation");
+    assert_eq!(saved.status, "pending_confirmation");
 }
 
 #[actix_web::test]
@@ -127,3 +129,31 @@ pub async fn suscribe_fail_if_error_in_database() {
     let response = app.post_subscription(body.to_string()).await;
     assert_eq!(response.status().as_u16(), 500);
 }
+
+#[actix_web::test]
+async fn subscribing_in_another_case_keeps_one_subscriber() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    for email in ["Bob%40Example.com", "%20bob%40example.COM%20"] {
+        let response = app
+            .post_subscription(format!("name=Bob&email={}", email))
+            .await;
+        assert_eq!(200, response.status().as_u16());
+    }
+
+    let saved = sqlx::query!("SELECT email, normalized_email FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].email, "Bob@example.com");
+    assert_eq!(
+        saved[0].normalized_email.as_deref(),
+        Some("bob@example.com")
+    );
+}
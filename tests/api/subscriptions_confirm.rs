@@ -60,10 +60,88 @@ pub async fn clicking_on_confirmation_link_should_switch_status() {
         .await
         .expect("Failed to send request");
 
-    let saved = sqlx::query!("SELECT status_subscription FROM subscriptions",)
+    let saved = sqlx::query!("SELECT status FROM list_memberships",)
         .fetch_one(&app.db_pool.clone())
         .await
         .expect("Should have been able to fetch email and name from subscription");
 
-    assert_eq!(saved.status_subscription, "confirmed");
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[actix_web::test]
+pub async fn the_signup_and_the_confirmation_are_recorded_as_consent() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let client = reqwest::Client::builder()
+        .user_agent("consent-test/1.0")
+        .build()
+        .unwrap();
+    let mut form: Vec<(&str, String)> = vec![
+        ("name", "le guin".to_string()),
+        ("email", "ursula_le_guin@gmail.com".to_string()),
+        ("consent_version", "v7".to_string()),
+        ("source", "footer".to_string()),
+    ];
+    form.extend(app.form_timestamp());
+    client
+        .post(format!("{}/subscription", &app.address))
+        .form(&form)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let consent = sqlx::query!("SELECT source, text_version, signup_ip, signup_user_agent, confirmed_at FROM consent_records")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(consent.source, "footer");
+    assert_eq!(consent.text_version, "v7");
+    assert_eq!(consent.signup_ip.as_deref(), Some("127.0.0.1"));
+    assert_eq!(
+        consent.signup_user_agent.as_deref(),
+        Some("consent-test/1.0")
+    );
+    assert!(consent.confirmed_at.is_none());
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let links = app.get_confirmation_links(email_request);
+    client
+        .get(links.html)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let consent = sqlx::query!(
+        "SELECT confirmation_ip, confirmation_user_agent, confirmed_at FROM consent_records"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(consent.confirmation_ip.as_deref(), Some("127.0.0.1"));
+    assert_eq!(
+        consent.confirmation_user_agent.as_deref(),
+        Some("consent-test/1.0")
+    );
+    assert!(consent.confirmed_at.is_some());
+
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    app.user.connect(&app).await;
+    let html = app
+        .get_subscribers(&format!("subscribers/{}", subscriber_id), "")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains("<td>Newsletter</td><td>footer</td><td>v7</td>"));
+    assert!(html.contains("consent-test/1.0"));
 }
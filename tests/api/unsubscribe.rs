@@ -0,0 +1,281 @@
+use crate::helpers::{TestApp, spawn_app};
+use crate::lists::subscribe_and_confirm;
+use crate::newsletter::create_confirmed_user;
+use crate::privacy::insert_unmerged_duplicate;
+use reqwest::Url;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+fn newsletter_form_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "html": "<p>Newsletter body as HTML</p>",
+        "text": "Newsletter body as plain text",
+    })
+}
+
+#[actix_web::test]
+pub async fn the_unsubscribe_link_of_an_issue_stops_the_next_ones() {
+    let app = spawn_app().await;
+    create_confirmed_user(&app).await;
+    app.user.connect(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_newsletter_draft(&newsletter_form_body()).await;
+    app.post_newsletter_action(issue_id, "publish").await;
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let links = app.get_text_links(requests.last().unwrap());
+    assert_eq!(links.len(), 2);
+    assert_eq!(links[0].path(), "/unsubscribe");
+    // Following the link only asks for a confirmation.
+    let html = reqwest::get(links[0].clone())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains(r#"<button type="submit">Unsubscribe</button>"#));
+    let status = sqlx::query!("SELECT status FROM list_memberships")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status;
+    assert_eq!(status, "confirmed");
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/unsubscribe", app.address))
+        .form(&links[0].query_pairs().collect::<Vec<_>>())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let status = sqlx::query!("SELECT status FROM list_memberships")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status;
+    assert_eq!(status, "unsubscribed");
+
+    let issue_id = app.create_newsletter_draft(&newsletter_form_body()).await;
+    app.post_newsletter_action(issue_id, "publish").await;
+    app.dispatch_all_pending_emails().await;
+}
+
+#[actix_web::test]
+pub async fn a_tampered_unsubscribe_link_is_rejected() {
+    let app = spawn_app().await;
+    create_confirmed_user(&app).await;
+
+    let parameters = [("email", "ursula_le_guin@gmail.com"), ("signature", "00ff")];
+    let response = reqwest::Client::new()
+        .get(format!("{}/unsubscribe", app.address))
+        .query(&parameters)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    let response = reqwest::Client::new()
+        .post(format!("{}/unsubscribe", app.address))
+        .form(&parameters)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+
+    let status = sqlx::query!("SELECT status FROM list_memberships")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status;
+    assert_eq!(status, "confirmed");
+}
+
+/// Subscribes `both@example.com` to two lists and sends it an issue of the
+/// weekly one, returns its unsubscribe link.
+async fn unsubscribe_link_of_a_weekly_issue(app: &TestApp) -> Url {
+    app.user.connect(app).await;
+    let weekly_id = app.create_list("Weekly digest", "weekly").await.to_string();
+    app.create_list("Monthly digest", "monthly").await;
+    subscribe_and_confirm(app, "both@example.com", "weekly").await;
+    subscribe_and_confirm(app, "both@example.com", "monthly").await;
+
+    let _guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let issue_id = app
+        .create_newsletter_draft(&[
+            ("title", "Digest"),
+            ("html", "<p>Digest</p>"),
+            ("text", "Digest"),
+            ("list_ids", weekly_id.as_str()),
+        ])
+        .await;
+    app.post_newsletter_action(issue_id, "publish").await;
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    app.get_text_links(requests.last().unwrap())[0].clone()
+}
+
+async fn membership_statuses(app: &TestApp) -> Vec<(String, String)> {
+    sqlx::query!(
+        r#"SELECT l.slug, m.status
+        FROM list_memberships m JOIN lists l ON l.list_id = m.list_id
+        ORDER BY l.slug"#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|r| (r.slug, r.status))
+    .collect()
+}
+
+#[actix_web::test]
+pub async fn the_unsubscribe_link_leaves_the_list_of_the_issue_only() {
+    let app = spawn_app().await;
+    let link = unsubscribe_link_of_a_weekly_issue(&app).await;
+
+    let html = reqwest::get(link.clone())
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains("Stop sending Weekly digest to both@example.com?"));
+    assert!(html.contains("Unsubscribe from all our lists"));
+    let response = reqwest::Client::new()
+        .post(format!("{}/unsubscribe", app.address))
+        .form(&link.query_pairs().collect::<Vec<_>>())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(
+        membership_statuses(&app).await,
+        vec![
+            ("monthly".to_string(), "confirmed".to_string()),
+            ("weekly".to_string(), "unsubscribed".to_string()),
+        ]
+    );
+}
+
+#[actix_web::test]
+pub async fn the_unsubscribe_link_can_leave_every_list() {
+    let app = spawn_app().await;
+    let link = unsubscribe_link_of_a_weekly_issue(&app).await;
+
+    let mut parameters: Vec<(String, String)> = link.query_pairs().into_owned().collect();
+    parameters.push(("all".to_string(), "on".to_string()));
+    let response = reqwest::Client::new()
+        .post(format!("{}/unsubscribe", app.address))
+        .form(&parameters)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(
+        membership_statuses(&app).await,
+        vec![
+            ("monthly".to_string(), "unsubscribed".to_string()),
+            ("weekly".to_string(), "unsubscribed".to_string()),
+        ]
+    );
+}
+
+#[actix_web::test]
+pub async fn the_list_of_an_unsubscribe_link_cannot_be_changed() {
+    let app = spawn_app().await;
+    let link = unsubscribe_link_of_a_weekly_issue(&app).await;
+
+    let parameters: Vec<(String, String)> = link
+        .query_pairs()
+        .into_owned()
+        .filter(|(key, _)| key != "list")
+        .collect();
+    let response = reqwest::Client::new()
+        .post(format!("{}/unsubscribe", app.address))
+        .form(&parameters)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+pub async fn an_unmerged_duplicate_unsubscribes_itself_only() {
+    let app = spawn_app().await;
+    subscribe_and_confirm(&app, "ursula@example.com", "").await;
+    insert_unmerged_duplicate(&app, "Ursula@example.com").await;
+    sqlx::query!(
+        r#"INSERT INTO list_memberships (list_id, subscriber_id, status, subscribed_at)
+        SELECT (SELECT list_id FROM lists WHERE is_default), id, 'confirmed', now()
+        FROM subscriptions WHERE email = 'Ursula@example.com'"#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.user.connect(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    let issue_id = app.create_newsletter_draft(&newsletter_form_body()).await;
+    app.post_newsletter_action(issue_id, "publish").await;
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let duplicate_request = requests
+        .iter()
+        .find(|request| {
+            let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            body["To"] == "Ursula@example.com"
+        })
+        .unwrap();
+    let link = app.get_text_links(duplicate_request)[0].clone();
+    let response = reqwest::Client::new()
+        .post(format!("{}/unsubscribe", app.address))
+        .form(&link.query_pairs().collect::<Vec<_>>())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let statuses: Vec<(String, String)> = sqlx::query!(
+        r#"SELECT s.email, m.status
+        FROM list_memberships m JOIN subscriptions s ON s.id = m.subscriber_id
+        ORDER BY s.email COLLATE "C""#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|r| (r.email, r.status))
+    .collect();
+    assert_eq!(
+        statuses,
+        vec![
+            ("Ursula@example.com".to_string(), "unsubscribed".to_string()),
+            ("ursula@example.com".to_string(), "confirmed".to_string()),
+        ]
+    );
+}